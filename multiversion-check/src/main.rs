@@ -0,0 +1,143 @@
+//! Validates lists of targets, using the same parsing as the `multiversion` macro.
+
+use multiversion_codegen::{targets, Target};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use std::{path::Path, process::ExitCode};
+use syn::{parse::Parser, LitStr};
+
+const USAGE: &str = "usage: multiversion-check [--env VAR] [--crate DIR] [TARGETS]...";
+
+// A list of targets to check.
+enum List {
+    // A list of targets, as accepted by `targets(...)`, or a preset name.
+    Targets(String),
+    // The name of an environment variable containing a list of targets.
+    Env(String),
+    // The directory containing `multiversion.toml`.
+    Crate(String),
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Vec<List>, String> {
+    let mut lists = Vec::new();
+    while let Some(arg) = args.next() {
+        let mut value = |option: &str| {
+            args.next()
+                .ok_or_else(|| format!("expected a value for `{option}`\n{USAGE}"))
+        };
+        lists.push(match arg.as_str() {
+            "--env" => List::Env(value("--env")?),
+            "--crate" => List::Crate(value("--crate")?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ => List::Targets(arg),
+        });
+    }
+    if lists.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    Ok(lists)
+}
+
+// Returns the targets in the list, in priority order.
+fn check(list: &List) -> syn::Result<Vec<Target>> {
+    let span = Span::call_site();
+    let targets = match list {
+        List::Targets(targets) => {
+            let name = targets.trim().trim_matches('"');
+            if name == "crate" {
+                return Err(syn::Error::new(
+                    span,
+                    "use `--crate DIR` to check the crate configuration",
+                ));
+            }
+            match targets::preset_targets(&LitStr::new(name, span)) {
+                Ok(targets) => targets,
+                // Unquoted arguments can only be presets.
+                Err(err) if !targets.contains('"') => return Err(err),
+                Err(_) => {
+                    let tokens: TokenStream = targets
+                        .parse()
+                        .map_err(|_| syn::Error::new(span, "expected a list of targets"))?;
+                    targets::parse_targets.parse2(tokens)?
+                }
+            }
+        }
+        List::Env(var) => targets::env_targets(&LitStr::new(var, span))?,
+        List::Crate(dir) => targets::crate_targets_in(Path::new(dir), &LitStr::new("crate", span))?,
+    };
+    targets::require_features(&targets, span)?;
+    Ok(targets)
+}
+
+fn main() -> ExitCode {
+    let lists = match parse_args(std::env::args().skip(1)) {
+        Ok(lists) => lists,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut status = ExitCode::SUCCESS;
+    for list in &lists {
+        match check(list) {
+            Ok(targets) => {
+                let targets = targets
+                    .iter()
+                    .map(|target| quote!(#target).to_string())
+                    .collect::<Vec<_>>();
+                println!("{}", targets.join(", "));
+            }
+            Err(err) => {
+                eprintln!("error: {err}");
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod check_test {
+    use super::*;
+
+    fn check_str(targets: &str) -> syn::Result<String> {
+        check(&List::Targets(targets.to_string())).map(|targets| {
+            targets
+                .iter()
+                .map(|target| quote!(#target).to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    }
+
+    #[test]
+    fn targets() {
+        // Targets are sorted by priority, and implied features are included.
+        let targets = check_str(r#""x86_64+sse4.2", "x86_64+avx2" priority = 1"#).unwrap();
+        assert!(targets.starts_with(r#""x86_64+avx+avx2+"#));
+        assert!(targets.ends_with(r#"+sse4.2+ssse3""#));
+        assert!(check_str("simd").unwrap().contains("aarch64+neon"));
+        assert!(check_str(r#""x86_64+avx3""#).is_err());
+        assert!(check_str(r#""x86_64""#).is_err());
+        assert!(check_str("crate").is_err());
+    }
+
+    #[test]
+    fn crate_config() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../multiversion");
+        let targets = check(&List::Crate(dir.to_string_lossy().into_owned())).unwrap();
+        assert!(!targets.is_empty());
+        assert!(check(&List::Crate("missing".to_string())).is_err());
+    }
+
+    #[test]
+    fn args() {
+        let args = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+        assert!(args(&[]).is_err());
+        assert!(args(&["--env"]).is_err());
+        assert!(matches!(
+            args(&["--env", "VAR", "simd"]).unwrap().as_slice(),
+            [List::Env(var), List::Targets(targets)] if var == "VAR" && targets == "simd"
+        ));
+    }
+}
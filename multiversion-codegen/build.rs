@@ -0,0 +1,48 @@
+fn main() {
+    // retpolines are not yet recognized by rust as a regular target feature.
+    // We can't detect them with `cfg(target_feature = "retpoline")`, but we can detect them in
+    // rustflags, since they shouldn't be the default for any target.
+    let rustflags = std::env::var("CARGO_ENCODED_RUSTFLAGS").unwrap();
+    let retpolines_enabled = rustflags.split('\x1f').any(|flag| {
+        let features = flag
+            .strip_prefix("target-feature=")
+            .or(flag.strip_prefix("-Ctarget-feature="));
+        if let Some(features) = features {
+            features
+                .split(',')
+                .any(|feature| feature.starts_with("+retpoline"))
+        } else {
+            false
+        }
+    });
+
+    if retpolines_enabled {
+        println!("cargo::rustc-cfg=retpoline")
+    }
+    println!("cargo::rustc-check-cfg=cfg(retpoline)");
+
+    // Static dispatch can be forced for an entire build, such as for deployments to a known CPU.
+    // This isn't a cargo feature, since it would change the dispatchers of every crate in the
+    // build.
+    let force_static = std::env::var("MULTIVERSION_FORCE_STATIC")
+        .map(|value| !value.is_empty() && value != "0")
+        .unwrap_or(false);
+    if force_static {
+        println!("cargo::rustc-cfg=force_static");
+    }
+    println!("cargo::rustc-check-cfg=cfg(force_static)");
+    println!("cargo::rerun-if-env-changed=MULTIVERSION_FORCE_STATIC");
+
+    // Generated code can be annotated with comments for reading with `cargo expand`.  Like
+    // `MULTIVERSION_FORCE_STATIC`, this is an environment variable rather than a cargo feature,
+    // since it's only useful while reviewing the expansion.
+    let readable = std::env::var("MULTIVERSION_READABLE")
+        .map(|value| !value.is_empty() && value != "0")
+        .unwrap_or(false);
+    if readable {
+        println!("cargo::rustc-cfg=readable");
+    }
+    println!("cargo::rustc-check-cfg=cfg(readable)");
+    println!("cargo::rerun-if-env-changed=MULTIVERSION_READABLE");
+    println!("cargo::rerun-if-changed=build.rs");
+}
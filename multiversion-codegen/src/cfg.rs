@@ -0,0 +1,125 @@
+use crate::target::Target;
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_quote,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    token::Comma,
+    Error, Expr, ExprLit, Lit, LitStr, Meta, Result,
+};
+
+fn transform_recursive(features: &[&str], input: Meta) -> Result<Meta> {
+    match input {
+        Meta::NameValue(nv) => {
+            if nv.path == parse_quote!(target_feature) {
+                if let Expr::Lit(lit) = &nv.value {
+                    if let Lit::Str(lit) = &lit.lit {
+                        if features.contains(&lit.value().as_str()) {
+                            return Ok(parse_quote! { all() });
+                        }
+                    }
+                }
+            }
+            Ok(Meta::NameValue(nv))
+        }
+        Meta::List(mut list) => {
+            let mut metas = list.parse_args_with(Punctuated::<Meta, Comma>::parse_terminated)?;
+            for meta in metas.iter_mut() {
+                *meta = transform_recursive(features, meta.clone())?;
+            }
+            list.tokens = metas.into_token_stream();
+            Ok(Meta::List(list))
+        }
+        input => Ok(input),
+    }
+}
+
+pub(crate) fn transform(mut input: Punctuated<Meta, Comma>) -> Result<Meta> {
+    if input.len() != 2 {
+        return Err(Error::new(
+            Span::call_site(),
+            "expected `target_features = \"...\"` and a configuration predicate",
+        ));
+    }
+
+    let features = input[0].require_name_value()?;
+    if !features.path.is_ident("target_features") {
+        return Err(Error::new(
+            features.path.span(),
+            "expected `target_features`",
+        ));
+    }
+    let features = match &features.value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(features),
+            ..
+        }) => features.value(),
+        value => {
+            return Err(Error::new(
+                value.span(),
+                "expected a comma-separated list of features",
+            ))
+        }
+    };
+    let features = features.split(',').collect::<Vec<&str>>();
+
+    transform_recursive(&features, input.pop().unwrap().into_value())
+}
+
+// The arguments of `target_cfg_for`: a target, followed by a configuration predicate.
+struct TargetCfgFor {
+    target: Target,
+    predicate: Meta,
+}
+
+impl Parse for TargetCfgFor {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let target = Target::parse(&input.parse::<LitStr>()?)?;
+        input.parse::<Comma>()?;
+        let predicate = input.parse()?;
+        input.parse::<Option<Comma>>()?;
+        Ok(Self { target, predicate })
+    }
+}
+
+// Applies `cfg` to an item, with the features of a target considered enabled.
+pub(crate) fn target_cfg_for(attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
+    let TargetCfgFor { target, predicate } = syn::parse2(attr)?;
+    let features = target
+        .features()
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    let predicate = transform_recursive(&features, predicate)?;
+    Ok(quote! {
+        #[cfg(#predicate)]
+        #item
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use quote::quote;
+
+    #[test]
+    fn target_cfg_for() {
+        let tokens = super::target_cfg_for(
+            quote! { "x86_64+avx2", all(target_arch = "x86_64", target_feature = "avx2", not(target_feature = "avx512f")) },
+            quote! { const AVX2: bool = true; },
+        )
+        .unwrap();
+        assert_eq!(
+            tokens.to_string(),
+            quote! {
+                #[cfg(all(target_arch = "x86_64", all(), not(target_feature = "avx512f")))]
+                const AVX2: bool = true;
+            }
+            .to_string()
+        );
+
+        let err = super::target_cfg_for(quote! { "x86_64+avx9" , all() }, quote! {}).unwrap_err();
+        assert!(err.to_string().contains("avx9"), "{err}");
+    }
+}
@@ -0,0 +1,277 @@
+use crate::default_targets::is_path;
+use crate::dispatcher::unsafe_fn_safe_block;
+use crate::multiversion::{split_options, take_fallback};
+use crate::target::Target;
+use crate::targets::{parse_targets, preset_targets};
+use crate::util;
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{
+    parenthesized, parse::Parser, parse_quote, token, Attribute, Error, Ident, Item, ItemFn,
+    ItemMod, LitStr, Result, Signature, Visibility,
+};
+
+const CLONE_MODULE: &str = "__multiversion_clone";
+
+// Parses the `targets` option, which must be a list of targets or a preset, since the clones are
+// created before the options are passed to the `multiversion` attribute.
+fn clone_targets(attr: TokenStream) -> Result<Vec<Target>> {
+    let mut targets = None;
+    for option in split_options(attr) {
+        let option = option.into_iter().collect::<TokenStream>();
+        let parser = syn::meta::parser(|meta| {
+            if !meta.path.is_ident("targets") {
+                // Other options are only used by the `multiversion` attribute.
+                let _ = meta.input.parse::<TokenStream>();
+                return Ok(());
+            }
+            if meta.input.peek(token::Paren) {
+                let content;
+                parenthesized!(content in meta.input);
+                targets = Some(parse_targets(&content)?);
+            } else if let Ok(preset) = meta.value()?.parse::<LitStr>() {
+                targets = Some(preset_targets(&preset)?);
+            } else {
+                return Err(meta.error(
+                    "`clone_module` requires `targets` to be a list of targets or a preset",
+                ));
+            }
+            Ok(())
+        });
+        parser.parse2(option)?;
+    }
+    let targets = targets.ok_or_else(|| Error::new(Span::call_site(), "expected `targets`"))?;
+    for target in &targets {
+        if target.is_tuned() {
+            return Err(Error::new(
+                Span::call_site(),
+                "`clone_module` doesn't support tuned targets",
+            ));
+        }
+    }
+    Ok(targets)
+}
+
+// The name of the version of a function with target features, in a clone of the module.
+fn featured_name(ident: &Ident) -> Ident {
+    Ident::new(&format!("__multiversion_{ident}"), ident.span())
+}
+
+// Creates the module used by `selected_target!` and `selected_token!` for the given features.
+fn helpers(features: &[String]) -> TokenStream {
+    quote! {
+        #[doc(hidden)]
+        #[allow(unused)]
+        mod __multiversion {
+            pub const FEATURES: multiversion::target::Target =
+                (multiversion::target_features::CURRENT_TARGET)#(.with_feature_str(#features))*;
+
+            #[derive(Copy, Clone, Debug)]
+            pub struct Token;
+
+            impl multiversion::target::TargetToken for Token {
+                const TARGET: multiversion::target::Target = FEATURES;
+            }
+        }
+    }
+}
+
+// Creates the clone of the module's functions for a target.
+//
+// Each function is compiled with the target's features, and is called by a safe wrapper with the
+// original name, so calls between functions in the clone stay in the clone.  The wrappers are
+// private to the clone, and are only called by the versions, which are only called once the
+// target's features are detected.
+fn clone_fns(index: usize, target: &Target, fns: &[&ItemFn]) -> TokenStream {
+    let module = Ident::new(&format!("{CLONE_MODULE}_{index}"), Span::call_site());
+    let target_arch = target.target_arch();
+    let helpers = helpers(target.features());
+    let fns = fns.iter().map(|f| {
+        let attrs = f
+            .attrs
+            .iter()
+            .filter(|attr| {
+                ["cfg", "allow", "warn", "deny", "forbid"]
+                    .iter()
+                    .any(|name| attr.path().is_ident(name))
+            })
+            .cloned()
+            .collect::<Vec<Attribute>>();
+        let featured_ident = featured_name(&f.sig.ident);
+        let mut featured = unsafe_fn_safe_block(ItemFn {
+            attrs: attrs.clone(),
+            vis: parse_quote! { pub(super) },
+            sig: Signature {
+                ident: featured_ident.clone(),
+                unsafety: parse_quote! { unsafe },
+                ..f.sig.clone()
+            },
+            block: f.block.clone(),
+        });
+        featured.attrs.extend(target.fn_attrs());
+
+        let (sig, _) = util::normalize_signature(&f.sig);
+        let call = util::forward_call(quote! { #featured_ident }, &sig);
+        quote! {
+            #(#attrs)*
+            #[inline(always)]
+            #[allow(dead_code)]
+            #sig {
+                unsafe { #call }
+            }
+
+            #featured
+        }
+    });
+    quote! {
+        #target_arch
+        #[doc(hidden)]
+        mod #module {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #helpers
+            #(#fns)*
+        }
+    }
+}
+
+// Replaces the body of a public function with a call to the clone for the selected target.
+fn front_end(attr: &TokenStream, targets: &[Target], f: &mut ItemFn) {
+    let (sig, _) = util::normalize_signature(&f.sig);
+    let featured = featured_name(&f.sig.ident);
+    let arms = targets.iter().enumerate().map(|(index, target)| {
+        let module = Ident::new(&format!("{CLONE_MODULE}_{index}"), Span::call_site());
+        let spec = format!("{}+{}", target.arch(), target.features().join("+"));
+        let call = util::forward_call(quote! { #module::#featured }, &sig);
+        quote! { #spec => unsafe { #call }, }
+    });
+    let bind_patterns = util::bind_patterns(&f.sig);
+    let stmts = &f.block.stmts;
+    f.attrs
+        .push(parse_quote! { #[multiversion::multiversion(#attr)] });
+    f.block = parse_quote! {
+        {
+            multiversion::target::match_target! {
+                #(#arms)*
+                _ => {
+                    #bind_patterns
+                    #(#stmts)*
+                }
+            }
+        }
+    };
+    f.sig = sig;
+}
+
+pub(crate) fn make_clone_module(attr: TokenStream, mut module: ItemMod) -> Result<TokenStream> {
+    let targets = clone_targets(attr.clone())?;
+    let items = match module.content.as_mut() {
+        Some((_, items)) => items,
+        None => {
+            return Err(Error::new_spanned(
+                &module,
+                "`clone_module` can only be applied to inline modules",
+            ))
+        }
+    };
+
+    let fns = items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Fn(f) => Some(f),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if let Some(attr) = fns
+        .iter()
+        .flat_map(|f| f.attrs.iter())
+        .find(|attr| is_path(attr.path(), "multiversion"))
+    {
+        return Err(Error::new_spanned(
+            attr,
+            "functions in a cloned module are already multiversioned",
+        ));
+    }
+    // Every function is cloned, so there's no default version to replace with a fallback.
+    for f in &fns {
+        if let Some(fallback) = take_fallback(&mut (*f).clone())? {
+            return Err(Error::new_spanned(
+                fallback,
+                "`#[fallback]` can't be used in a cloned module",
+            ));
+        }
+    }
+    let clones = targets
+        .iter()
+        .enumerate()
+        .map(|(index, target)| clone_fns(index, target, &fns))
+        .collect::<Vec<_>>();
+
+    // Only public functions dispatch, and other functions are only called with the features
+    // enabled at compile time, or by the clones.
+    for item in items.iter_mut() {
+        if let Item::Fn(f) = item {
+            if !matches!(f.vis, Visibility::Inherited) {
+                front_end(&attr, &targets, f);
+            }
+        }
+    }
+    // Functions that aren't cloned use the features enabled at compile time.
+    items.push(Item::Verbatim(helpers(&[])));
+    for clone in clones {
+        items.push(Item::Verbatim(clone));
+    }
+    Ok(module.into_token_stream())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clone_module() {
+        let module = parse_quote! {
+            mod kernels {
+                fn helper(x: f32) -> f32 { x * 2. }
+                pub fn double((x, y): (f32, f32)) -> f32 { helper(x) + helper(y) }
+            }
+        };
+        let tokens = make_clone_module(
+            quote! { targets("x86_64+avx2", "aarch64+neon"), dispatcher = "direct" },
+            module,
+        )
+        .unwrap()
+        .to_string();
+        assert!(tokens.contains("mod __multiversion_clone_0"));
+        assert!(tokens.contains("mod __multiversion_clone_1"));
+        assert!(tokens.contains(
+            &quote! { __multiversion_clone_1::__multiversion_double(__multiversion_arg_0) }
+                .to_string()
+        ));
+        assert!(tokens.contains(
+            &quote! { fn helper(x: f32) -> f32 { unsafe { __multiversion_helper(x) } } }
+                .to_string()
+        ));
+
+        let tuned = make_clone_module(
+            quote! { targets("x86_64+avx2~znver3") },
+            parse_quote! { mod kernels {} },
+        );
+        assert!(tuned.is_err());
+
+        let fallback = make_clone_module(
+            quote! { targets("x86_64+avx2") },
+            parse_quote! {
+                mod kernels {
+                    pub fn f() -> u32 {
+                        #[fallback]
+                        { 1 }
+                        2
+                    }
+                }
+            },
+        );
+        assert!(fallback.is_err());
+    }
+}
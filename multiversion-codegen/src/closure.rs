@@ -0,0 +1,115 @@
+use proc_macro2::{TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    token, Error, ExprClosure, Result,
+};
+
+// Returns `true` if a closure expression follows.
+fn closure_follows(input: ParseStream<'_>) -> bool {
+    input.peek(token::Or)
+        || input.peek(token::OrOr)
+        || input.peek(token::Move)
+        || input.peek(token::Async)
+        || input.peek(token::Static)
+        || input.peek(token::For)
+}
+
+// The arguments of `multiversion_closure!`: the options of the `multiversion` attribute, followed
+// by a closure.
+pub(crate) struct Closure {
+    options: TokenStream,
+    closure: ExprClosure,
+}
+
+impl Parse for Closure {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // Options may contain arbitrary expressions, so the closure is only recognized at the
+        // start of an option.
+        let mut options = TokenStream::new();
+        let mut option_start = true;
+        while !(option_start && closure_follows(input)) {
+            if input.is_empty() {
+                return Err(input.error("expected a closure"));
+            }
+            let token: TokenTree = input.parse()?;
+            option_start = matches!(&token, TokenTree::Punct(p) if p.as_char() == ',');
+            options.extend(Some(token));
+        }
+        let closure: ExprClosure = input.parse()?;
+        input.parse::<Option<token::Comma>>()?;
+        if let Some(asyncness) = &closure.asyncness {
+            return Err(Error::new_spanned(
+                asyncness,
+                "async closures can't be multiversioned",
+            ));
+        }
+        if let Some(movability) = &closure.movability {
+            return Err(Error::new_spanned(
+                movability,
+                "coroutines can't be multiversioned",
+            ));
+        }
+        Ok(Self { options, closure })
+    }
+}
+
+impl ToTokens for Closure {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let Self { options, closure } = self;
+        let ExprClosure {
+            attrs,
+            lifetimes,
+            constness,
+            capture,
+            inputs,
+            output,
+            body,
+            ..
+        } = closure;
+
+        // The body is inlined into each version of a multiversioned function, so that it's
+        // compiled once for each target.  Captures are unaffected, since the body remains in a
+        // closure.
+        quote! {
+            {
+                #[multiversion::multiversion(#options)]
+                fn __multiversion_closure<__F: FnOnce() -> __R, __R>(__f: __F) -> __R {
+                    __f()
+                }
+
+                #(#attrs)*
+                #lifetimes #constness #capture |#inputs| #output {
+                    __multiversion_closure(#[inline(always)] || #output #body)
+                }
+            }
+        }
+        .to_tokens(tokens)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_closure() {
+        let closure: Closure = syn::parse_quote! {
+            targets("x86_64+avx2"), dispatcher = "indirect", move |x: u32, y| x | y
+        };
+        assert_eq!(
+            closure.options.to_string(),
+            quote! { targets("x86_64+avx2"), dispatcher = "indirect", }.to_string()
+        );
+        assert_eq!(closure.closure.inputs.len(), 2);
+
+        let closure: Closure = syn::parse_quote! { targets = "simd", || -> u8 { 1 }, };
+        assert!(closure
+            .into_token_stream()
+            .to_string()
+            .contains("__multiversion_closure (# [inline (always)] || -> u8 { 1 })"));
+
+        assert!(syn::parse2::<Closure>(quote! { targets = "simd" }).is_err());
+        assert!(syn::parse2::<Closure>(quote! { targets = "simd", async || 1 }).is_err());
+    }
+}
@@ -0,0 +1,347 @@
+//! Reads the crate-level `multiversion.toml` configuration file.
+//!
+//! Only a small subset of TOML is supported: tables, comments, bare or quoted keys, and string,
+//! array-of-string, or integer values.  Strings are basic strings, with the escapes of TOML.  The
+//! same format is used by priority models.
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use std::path::PathBuf;
+use syn::{Error, Result};
+
+pub(crate) const FILE_NAME: &str = "multiversion.toml";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Value {
+    String(String),
+    Array(Vec<String>),
+    Integer(i64),
+}
+
+// A key, in a table if the key follows a table header.
+pub(crate) type Entry = (Option<String>, String, Value);
+
+pub(crate) struct Config {
+    path: PathBuf,
+    entries: Vec<Entry>,
+}
+
+impl Config {
+    /// Reads the configuration file from the root of the crate being compiled.
+    pub(crate) fn read(span: Span) -> Result<Self> {
+        Self::read_in(span, &manifest_dir(span)?)
+    }
+
+    /// Reads a file in the same format, relative to the root of the crate being compiled.
+    pub(crate) fn read_file(span: Span, name: &str) -> Result<Self> {
+        Self::read_path(span, manifest_dir(span)?.join(name))
+    }
+
+    /// Reads the configuration file from a directory.
+    pub(crate) fn read_in(span: Span, dir: &std::path::Path) -> Result<Self> {
+        Self::read_path(span, dir.join(FILE_NAME))
+    }
+
+    fn read_path(span: Span, path: PathBuf) -> Result<Self> {
+        let text = std::fs::read_to_string(&path).map_err(|err| {
+            Error::new(span, format!("couldn't read `{}`: {err}", path.display()))
+        })?;
+        let entries = parse_tables(&text)
+            .map_err(|err| Error::new(span, format!("error in `{}`: {err}", path.display())))?;
+        Ok(Self { path, entries })
+    }
+
+    /// Returns the value of a key, where keys in tables are written as `table.key`.
+    pub(crate) fn get(&self, key: &str) -> Option<&Value> {
+        self.entries
+            .iter()
+            .find(|(table, k, _)| match table {
+                Some(table) => {
+                    key.strip_prefix(table.as_str())
+                        .and_then(|k| k.strip_prefix('.'))
+                        == Some(k.as_str())
+                }
+                None => k == key,
+            })
+            .map(|(_, _, value)| value)
+    }
+
+    /// Returns the entries of the file, in order.
+    pub(crate) fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Returns the path of the file.
+    pub(crate) fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    /// Returns an item that causes the crate to be recompiled when the file changes.
+    pub(crate) fn track(&self) -> TokenStream {
+        let path = self.path.to_string_lossy();
+        quote! {
+            const _: &[u8] = include_bytes!(#path);
+        }
+    }
+}
+
+// Returns the root directory of the crate being compiled.
+fn manifest_dir(span: Span) -> Result<PathBuf> {
+    std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .ok_or_else(|| Error::new(span, "couldn't determine the crate root directory"))
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error<T>(&self, msg: &str) -> std::result::Result<T, String> {
+        Err(format!("line {}: {msg}", self.line))
+    }
+
+    // Skips whitespace and comments, optionally including newlines.
+    fn skip(&mut self, newlines: bool) {
+        while let Some(&c) = self.chars.peek() {
+            if c == '#' {
+                while matches!(self.chars.peek(), Some(&c) if c != '\n') {
+                    self.chars.next();
+                }
+            } else if c == '\n' && newlines {
+                self.line += 1;
+                self.chars.next();
+            } else if c.is_whitespace() && c != '\n' {
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> std::result::Result<(), String> {
+        if self.chars.next() == Some(expected) {
+            Ok(())
+        } else {
+            self.error(&format!("expected `{expected}`"))
+        }
+    }
+
+    fn key(&mut self) -> std::result::Result<String, String> {
+        if self.chars.peek() == Some(&'"') {
+            return self.string();
+        }
+        let mut key = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' {
+                key.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        if key.is_empty() {
+            self.error("expected a key")
+        } else {
+            Ok(key)
+        }
+    }
+
+    fn string(&mut self) -> std::result::Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => s.push(self.escape()?),
+                Some('\n') | None => return self.error("unterminated string"),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    // Parses the escape sequence following a backslash in a string.
+    fn escape(&mut self) -> std::result::Result<char, String> {
+        let digits = match self.chars.next() {
+            Some('b') => return Ok('\u{8}'),
+            Some('t') => return Ok('\t'),
+            Some('n') => return Ok('\n'),
+            Some('f') => return Ok('\u{c}'),
+            Some('r') => return Ok('\r'),
+            Some('"') => return Ok('"'),
+            Some('\\') => return Ok('\\'),
+            Some('u') => 4,
+            Some('U') => 8,
+            _ => return self.error("invalid escape sequence"),
+        };
+        let code = (0..digits)
+            .map(|_| self.chars.next().and_then(|c| c.to_digit(16)))
+            .try_fold(0, |code, digit| Some(code << 4 | digit?));
+        match code.and_then(char::from_u32) {
+            Some(c) => Ok(c),
+            None => self.error("invalid unicode escape"),
+        }
+    }
+
+    fn value(&mut self) -> std::result::Result<Value, String> {
+        match self.chars.peek() {
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('[') => {
+                self.chars.next();
+                let mut values = Vec::new();
+                loop {
+                    self.skip(true);
+                    if self.chars.peek() == Some(&']') {
+                        self.chars.next();
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.string()?);
+                    self.skip(true);
+                    match self.chars.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Value::Array(values)),
+                        _ => return self.error("expected `,` or `]`"),
+                    }
+                }
+            }
+            Some(&c) if c == '-' || c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if c == '-' || c == '_' || c.is_ascii_digit() {
+                        self.chars.next();
+                        if c != '_' {
+                            digits.push(c);
+                        }
+                    } else {
+                        break;
+                    }
+                }
+                match digits.parse() {
+                    Ok(value) => Ok(Value::Integer(value)),
+                    Err(_) => self.error("invalid integer"),
+                }
+            }
+            _ => self.error("expected a string, array of strings, or integer"),
+        }
+    }
+}
+
+#[cfg(test)]
+fn parse(text: &str) -> std::result::Result<Vec<(String, Value)>, String> {
+    Ok(parse_tables(text)?
+        .into_iter()
+        .map(|(table, key, value)| match table {
+            Some(table) => (format!("{table}.{key}"), value),
+            None => (key, value),
+        })
+        .collect())
+}
+
+pub(crate) fn parse_tables(text: &str) -> std::result::Result<Vec<Entry>, String> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+        line: 1,
+    };
+    let mut table: Option<String> = None;
+    let mut entries: Vec<Entry> = Vec::new();
+    loop {
+        parser.skip(true);
+        match parser.chars.peek() {
+            None => return Ok(entries),
+            Some('[') => {
+                parser.chars.next();
+                parser.skip(false);
+                table = Some(parser.key()?);
+                parser.skip(false);
+                parser.expect(']')?;
+            }
+            Some(_) => {
+                let key = parser.key()?;
+                if entries.iter().any(|(t, k, _)| *t == table && *k == key) {
+                    return parser.error(&format!("duplicate key `{key}`"));
+                }
+                parser.skip(false);
+                parser.expect('=')?;
+                parser.skip(false);
+                let value = parser.value()?;
+                entries.push((table.clone(), key, value));
+            }
+        }
+        parser.skip(false);
+        match parser.chars.next() {
+            None | Some('\n') => parser.line += 1,
+            _ => return parser.error("expected a new line"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config = r#"
+            # the default targets
+            targets = [
+                "x86_64+avx2", # comment
+                "aarch64+neon",
+            ]
+
+            [table]
+            key = "value"
+            "escaped\"key" = "tab\there \u00e9\U0001F600\\"
+        "#;
+        assert_eq!(
+            parse(config).unwrap(),
+            vec![
+                (
+                    "targets".to_string(),
+                    Value::Array(vec!["x86_64+avx2".to_string(), "aarch64+neon".to_string()])
+                ),
+                ("table.key".to_string(), Value::String("value".to_string())),
+                (
+                    "table.escaped\"key".to_string(),
+                    Value::String("tab\there \u{e9}\u{1f600}\\".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_values() {
+        let config = r#"
+            [amd.0x19]
+            "x86_64+avx2" = 10
+            "x86_64+sse4.2" = -1_000
+        "#;
+        assert_eq!(
+            parse_tables(config).unwrap(),
+            vec![
+                (
+                    Some("amd.0x19".to_string()),
+                    "x86_64+avx2".to_string(),
+                    Value::Integer(10)
+                ),
+                (
+                    Some("amd.0x19".to_string()),
+                    "x86_64+sse4.2".to_string(),
+                    Value::Integer(-1000)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        parse("targets = [\"x86_64+avx2\"").unwrap_err();
+        parse("targets = \"x86_64+avx2").unwrap_err();
+        parse("targets = x").unwrap_err();
+        parse("targets = []\ntargets = []").unwrap_err();
+        parse("targets = [] extra").unwrap_err();
+        parse("targets = \"\\x\"").unwrap_err();
+        parse("targets = \"\\u12\"").unwrap_err();
+        parse("targets = \"\\uD800\"").unwrap_err();
+    }
+}
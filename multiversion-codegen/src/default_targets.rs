@@ -0,0 +1,98 @@
+use crate::presets;
+use proc_macro2::{TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    parse_quote, visit_mut::VisitMut, Attribute, Error, ImplItemFn, ItemFn, ItemMod, LitStr, Meta,
+    Path, Result,
+};
+
+pub(crate) fn is_path(path: &Path, name: &str) -> bool {
+    let segments = path
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect::<Vec<_>>();
+    match segments.as_slice() {
+        [last] => last == name,
+        [krate, last] => krate == "multiversion" && last == name,
+        _ => false,
+    }
+}
+
+struct DefaultTargets {
+    targets: TokenStream,
+}
+
+impl DefaultTargets {
+    fn apply(&self, attrs: &mut [Attribute]) {
+        for attr in attrs.iter_mut() {
+            if !is_path(attr.path(), "multiversion") {
+                continue;
+            }
+            let targets = &self.targets;
+            match &mut attr.meta {
+                Meta::Path(path) => {
+                    attr.meta = parse_quote! { #path(#targets) };
+                }
+                Meta::List(list) => {
+                    let specified = list.tokens.clone().into_iter().any(
+                        |token| matches!(token, TokenTree::Ident(ident) if ident == "targets"),
+                    );
+                    if !specified {
+                        let tokens = &list.tokens;
+                        list.tokens = quote! { #targets, #tokens };
+                    }
+                }
+                Meta::NameValue(_) => {}
+            }
+        }
+    }
+}
+
+impl VisitMut for DefaultTargets {
+    fn visit_item_fn_mut(&mut self, f: &mut ItemFn) {
+        self.apply(&mut f.attrs);
+        syn::visit_mut::visit_item_fn_mut(self, f);
+    }
+
+    fn visit_impl_item_fn_mut(&mut self, f: &mut ImplItemFn) {
+        self.apply(&mut f.attrs);
+        syn::visit_mut::visit_impl_item_fn_mut(self, f);
+    }
+
+    fn visit_item_mod_mut(&mut self, m: &mut ItemMod) {
+        // Nested modules may specify their own default targets
+        if !m
+            .attrs
+            .iter()
+            .any(|attr| is_path(attr.path(), "default_targets"))
+        {
+            syn::visit_mut::visit_item_mod_mut(self, m);
+        }
+    }
+}
+
+pub(crate) fn make_default_targets(attr: TokenStream, mut module: ItemMod) -> Result<TokenStream> {
+    if module.content.is_none() {
+        return Err(Error::new_spanned(
+            &module,
+            "default targets can only be applied to inline modules",
+        ));
+    }
+
+    // A single preset, such as "simd", is passed as `targets = "simd"`.  Anything else is passed
+    // as `targets(...)`.
+    let targets = match syn::parse2::<LitStr>(attr.clone()) {
+        Ok(s) if s.value() == "crate" || presets::preset(&s.value()).is_some() => {
+            quote! { targets = #s }
+        }
+        _ => quote! { targets(#attr) },
+    };
+
+    let mut visitor = DefaultTargets { targets };
+    // Visit the contents directly, since the module itself carries this attribute.
+    for item in module.content.as_mut().unwrap().1.iter_mut() {
+        visitor.visit_item_mut(item);
+    }
+    Ok(module.into_token_stream())
+}
@@ -0,0 +1,3068 @@
+use crate::{model::PriorityModel, target::Target, util};
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use std::collections::{HashMap, HashSet};
+use syn::{
+    parse_quote, Attribute, Block, Error, Expr, FnArg, Ident, ItemFn, Lifetime, Path, Result,
+    ReturnType, Signature, TypeBareFn, Visibility,
+};
+
+fn baseline_fn_name(ident: &Ident, baseline: &Target, names: VersionNames) -> Ident {
+    let name = match names {
+        VersionNames::Features => format!("{}_{}_baseline_version", ident, baseline.arch()),
+        VersionNames::Short => format!("{}_{}_baseline", ident, baseline.arch()),
+    };
+    Ident::new(&name, ident.span())
+}
+
+fn feature_fn_name(ident: &Ident, target: Option<&Target>, names: VersionNames) -> Ident {
+    if let Some(target) = target {
+        if target.has_features_specified() {
+            let name = match names {
+                VersionNames::Features => format!("{}_{}_version", ident, target.features_string()),
+                VersionNames::Short => format!("{}_{}", ident, target.short_features_string()),
+            };
+            return Ident::new(&name, ident.span());
+        }
+    }
+
+    // If this is a default fn, it doesn't have a dedicated static dispatcher
+    let name = match names {
+        VersionNames::Features => format!("{ident}_default_version"),
+        VersionNames::Short => format!("{ident}_default"),
+    };
+    Ident::new(&name, ident.span())
+}
+
+// Returns the target as a target specification string, such as `x86_64+avx2+fma`.
+fn target_spec(target: &Target) -> String {
+    let mut spec = target.arch().to_string();
+    for feature in target.features() {
+        spec.push('+');
+        spec.push_str(feature);
+    }
+    if let Some(tune) = target.tune() {
+        spec.push('~');
+        spec.push_str(tune);
+    }
+    if target.is_narrow() {
+        spec.push_str(" (256-bit)");
+    }
+    spec
+}
+
+// Returns doc comments describing the generated code, if `readable` is set.  Tools like
+// `cargo expand` show them as comments, separating the parts of the generated code.
+fn readable_docs<'a>(readable: bool, lines: impl IntoIterator<Item = &'a str>) -> Vec<Attribute> {
+    if !readable {
+        return Vec::new();
+    }
+    lines
+        .into_iter()
+        .map(|line| {
+            let line = if line.is_empty() {
+                String::new()
+            } else {
+                format!(" {line}")
+            };
+            parse_quote! { #[doc = #line] }
+        })
+        .collect()
+}
+
+pub(crate) fn unsafe_fn_safe_block(f: ItemFn) -> ItemFn {
+    // The safe copy is always inlined into the version.
+    let safe_fn = ItemFn {
+        attrs: f
+            .attrs
+            .iter()
+            .filter(|attr| !attr.path().is_ident("inline"))
+            .cloned()
+            .collect(),
+        vis: Visibility::Inherited,
+        sig: Signature {
+            unsafety: None,
+            ident: Ident::new("__safe_inner", f.sig.ident.span()),
+            ..f.sig.clone()
+        },
+        ..f.clone()
+    };
+
+    let (unsafe_sig, args) = crate::util::normalize_signature(&f.sig);
+    let maybe_await = f.sig.asyncness.map(|_| crate::util::await_tokens());
+    let safe_ident = &safe_fn.sig.ident;
+    let fn_params = crate::util::fn_params(&unsafe_sig);
+    ItemFn {
+        block: parse_quote! {
+            {
+                #[inline(always)]
+                #safe_fn
+                #safe_ident::<#(#fn_params),*>(#(#args),*)#maybe_await
+            }
+        },
+        sig: unsafe_sig,
+        ..f
+    }
+}
+
+/// The method used to dispatch to the selected version, specified by `dispatcher`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DispatchMethod {
+    /// Chooses one of the other methods.
+    Default,
+    /// Selects the version at compile time.
+    Static,
+    /// Calls the selected version directly.
+    Direct,
+    /// Calls the selected version through a function pointer.
+    Indirect,
+    /// Stores the selected version in a `OnceLock`.
+    Once,
+    /// Stores the selected version in a thread-local.
+    Tls,
+    /// Selects the version with a GNU indirect function.
+    Ifunc,
+    /// Detects features on every call.
+    Inline,
+    /// Selects the version in a constructor that runs before `main`.
+    Ctor,
+}
+
+/// The module and visibility of the target clones, specified by `variants`.
+#[derive(Clone, Debug)]
+pub struct Variants {
+    /// The visibility of the clones.
+    pub vis: Visibility,
+    /// The name of the module containing the clones.
+    pub module: Ident,
+}
+
+/// The naming scheme of the versions, specified by `version_names`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VersionNames {
+    /// Names versions after every feature, including implied features, such as
+    /// `square_avx_avx2_fma_sse_sse2_sse3_sse41_sse42_ssse3_version`.
+    Features,
+    /// Names versions after the features that aren't implied by other features, such as
+    /// `square_avx2_fma`.
+    Short,
+}
+
+// The role of a function in a dispatch group, created by the `group` attribute.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) enum GroupRole {
+    // A function in the group, which dispatches with the group's selected target.
+    Member,
+    // The function that selects the group's target.
+    Selector,
+}
+
+// The name of the function that selects the target of a dispatch group.
+const GROUP_SELECTOR: &str = "__multiversion_group_select";
+
+// The helpers that can be used in the body of a multiversioned function.
+const HELPERS: &[&str] = &[
+    "selected_target",
+    "selected_token",
+    "inherit_target",
+    "target_cfg",
+    "target_cfg_attr",
+    "target_cfg_f",
+    "match_target",
+];
+
+// Macros of the standard library prelude, which never expand to helpers.  Macros invoked by a path
+// to another crate, such as `std::ptr::addr_of!`, are already excluded.
+const STD_MACROS: &[&str] = &[
+    "assert",
+    "assert_eq",
+    "assert_ne",
+    "cfg",
+    "column",
+    "compile_error",
+    "concat",
+    "dbg",
+    "debug_assert",
+    "debug_assert_eq",
+    "debug_assert_ne",
+    "env",
+    "eprint",
+    "eprintln",
+    "file",
+    "format",
+    "format_args",
+    "include",
+    "include_bytes",
+    "include_str",
+    "is_aarch64_feature_detected",
+    "is_x86_feature_detected",
+    "line",
+    "matches",
+    "module_path",
+    "option_env",
+    "panic",
+    "print",
+    "println",
+    "stringify",
+    "thread_local",
+    "todo",
+    "unimplemented",
+    "unreachable",
+    "vec",
+    "write",
+    "writeln",
+];
+
+/// Generates a multiversioned function.
+///
+/// The fields correspond to the options of the `multiversion` macro.  Additional fields may be
+/// added in minor releases, so dispatchers are created with [`Dispatcher::new`].  The generated
+/// function is produced by the [`ToTokens`] implementation.
+#[non_exhaustive]
+pub struct Dispatcher {
+    /// The dispatch method, specified by `dispatcher`.
+    pub dispatcher: DispatchMethod,
+    /// Inner attributes of the function, which are applied to every version.
+    pub inner_attrs: Vec<Attribute>,
+    /// The targets to compile the function for, in priority order.
+    pub targets: Vec<Target>,
+    /// The targets of the default version, specified by `baseline`.
+    pub baselines: Vec<Target>,
+    /// Specified by `resolve`.
+    pub resolve: bool,
+    /// Specified by `fn_ptr`.
+    pub fn_ptr: bool,
+    /// Specified by `pinned`.
+    pub pinned: bool,
+    /// Specified by `trampoline`.
+    pub trampoline: bool,
+    /// Specified by `helpers`, or `None` to only generate the helpers used by the function.
+    pub helpers: Option<bool>,
+    /// Specified by `boxed_future`.
+    pub boxed_future: bool,
+    /// Specified by `force_target`.
+    pub force_target: bool,
+    /// Specified by `all_cores`.
+    pub all_cores: bool,
+    /// Specified by `elision`.
+    pub elision: bool,
+    /// Specified by `min_stack`.
+    pub min_stack: Option<usize>,
+    /// Specified by `variants`.
+    pub variants: Option<Variants>,
+    /// Specified by `version_names`.
+    pub version_names: VersionNames,
+    pub(crate) group: Option<GroupRole>,
+    /// Specified by `detector`.
+    pub detector: Option<Path>,
+    /// Specified by `priority_model`.
+    pub priority_model: Option<PriorityModel>,
+    /// The body of the default and baseline versions, marked `#[fallback]` in the function, or
+    /// `None` to use the body of the function.
+    pub fallback: Option<Block>,
+    /// Describes the generated code with doc comments.  Defaults to whether
+    /// `MULTIVERSION_READABLE` is set when the macros are compiled.
+    pub readable: bool,
+    /// The function to multiversion.
+    pub func: ItemFn,
+}
+
+impl Dispatcher {
+    /// Creates a dispatcher for a function, with the default options.
+    pub fn new(func: ItemFn, targets: Vec<Target>) -> Self {
+        Self {
+            dispatcher: DispatchMethod::Default,
+            inner_attrs: Vec::new(),
+            targets,
+            baselines: Vec::new(),
+            resolve: false,
+            fn_ptr: false,
+            pinned: false,
+            trampoline: false,
+            helpers: None,
+            boxed_future: false,
+            force_target: false,
+            all_cores: false,
+            elision: true,
+            min_stack: None,
+            variants: None,
+            version_names: VersionNames::Features,
+            group: None,
+            detector: None,
+            priority_model: None,
+            fallback: None,
+            readable: cfg!(readable),
+            func,
+        }
+    }
+
+    // The name of the version for a target, or the default version.
+    fn version_name(&self, target: Option<&Target>) -> Ident {
+        let target = target.map(|target| self.version_target(target));
+        feature_fn_name(&self.func.sig.ident, target, self.version_names)
+    }
+
+    // Returns the target whose version is called for the target.  The 256-bit variant of an
+    // AVX-512 target calls the version of another target with the same features, if there is one,
+    // rather than compiling the same version again.
+    fn version_target<'a>(&'a self, target: &'a Target) -> &'a Target {
+        if !target.is_narrow() {
+            return target;
+        }
+        self.targets
+            .iter()
+            .filter(|other| other.same_version(target))
+            .min_by_key(|other| other.is_narrow())
+            .unwrap_or(target)
+    }
+
+    // Returns the targets that have their own version.
+    fn version_targets(&self) -> impl Iterator<Item = &Target> {
+        self.targets
+            .iter()
+            .filter(move |target| std::ptr::eq(self.version_target(target), *target))
+    }
+
+    // Create functions for each target
+    fn feature_fns(&self) -> Result<Vec<ItemFn>> {
+        // Only the helpers used by the function are generated, unless specified otherwise.  Other
+        // macros of this crate, such as those defined with `macro_rules!`, may expand to helpers,
+        // so every helper is generated if the function invokes a macro that isn't a helper, a
+        // standard macro, or a macro of another crate.
+        let (used_idents, opaque_macros) = match self.helpers {
+            Some(_) => (HashSet::new(), false),
+            None => {
+                let mut tokens = self.func.block.to_token_stream();
+                tokens.extend(self.fallback.to_token_stream());
+                let opaque_macros = util::macro_invocations(tokens.clone()).iter().any(|name| {
+                    !HELPERS.contains(&name.as_str()) && !STD_MACROS.contains(&name.as_str())
+                });
+                (util::idents(tokens), opaque_macros)
+            }
+        };
+        let used = |helper: &str| {
+            self.helpers
+                .unwrap_or_else(|| opaque_macros || used_idents.contains(helper))
+        };
+        let use_token = used("selected_token");
+        let use_features = use_token || used("selected_target");
+
+        let make_block = |target: Option<&Target>, block: &Block| {
+            let features = target.map(|t| t.features()).unwrap_or(&[]);
+            let features_init = quote! {
+                (multiversion::target_features::CURRENT_TARGET)#(.with_feature_str(#features))*
+            };
+            let feature_attrs = if let Some(target) = target {
+                target.target_feature()
+            } else {
+                Vec::new()
+            };
+            let features = if let Some(target) = target {
+                let s = target
+                    .features()
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>();
+                s.join(",")
+            } else {
+                String::new()
+            };
+
+            let mut helpers = Vec::new();
+            if use_features {
+                helpers.push(quote! {
+                    pub const FEATURES: multiversion::target::Target = #features_init;
+                });
+            }
+            if use_token {
+                helpers.push(quote! {
+                    #[derive(Copy, Clone, Debug)]
+                    pub struct Token;
+
+                    impl multiversion::target::TargetToken for Token {
+                        const TARGET: multiversion::target::Target = FEATURES;
+                    }
+                });
+            }
+            let macros = [
+                (
+                    "inherit_target",
+                    quote! { { $f:item } => { #(#feature_attrs)* $f } },
+                ),
+                (
+                    "target_cfg",
+                    quote! { { [$cfg:meta] $($attached:tt)* } => { #[multiversion::target::target_cfg_impl(target_features = #features, $cfg)] $($attached)* }; },
+                ),
+                (
+                    "target_cfg_attr",
+                    quote! { { [$cfg:meta, $attr:meta] $($attached:tt)* } => { #[multiversion::target::target_cfg_attr_impl(target_features = #features, $cfg, $attr)] $($attached)* }; },
+                ),
+                (
+                    "target_cfg_f",
+                    quote! { { $cfg:meta } => { multiversion::target::target_cfg_f_impl!(target_features = #features, $cfg) }; },
+                ),
+                (
+                    "match_target",
+                    quote! { { $($arms:tt)* } => { multiversion::target::match_target_impl!{ #features $($arms)* } } },
+                ),
+            ];
+            for (name, rules) in macros {
+                if used(name) {
+                    let name = Ident::new(name, Span::call_site());
+                    helpers.push(quote! {
+                        macro_rules! #name {
+                            #rules
+                        }
+                        pub(crate) use #name;
+                    });
+                }
+            }
+
+            let mut prologue = self.stack_checks(target);
+            prologue.extend(self.count_call(target));
+            if helpers.is_empty() && prologue.is_empty() {
+                return Box::new(block.clone());
+            }
+            let helpers = (!helpers.is_empty()).then(|| {
+                // Unused helpers are only generated when requested with `helpers = true`.
+                let allow_unused =
+                    (self.helpers.is_some() || opaque_macros).then(|| quote! { #[allow(unused)] });
+                quote! {
+                    #[doc(hidden)] // https://github.com/rust-lang/rust/issues/111415
+                    #allow_unused
+                    pub mod __multiversion {
+                        #(#helpers)*
+                    }
+                }
+            });
+            // The statements are spliced, rather than nesting the block, since a block passed to
+            // a `macro_rules!` macro would trigger the `unused_braces` lint.
+            let stmts = &block.stmts;
+            parse_quote! {
+                {
+                    #prologue
+                    #helpers
+                    #(#stmts)*
+                }
+            }
+        };
+
+        let method = self.is_method();
+        let make_clone = |ident: Ident, target: &Target, block: &Block| {
+            // Methods can't be nested, so the safe copy is another associated function, which is
+            // inlined into the version.
+            if method {
+                let safe_ident = Ident::new(&format!("{ident}_safe"), ident.span());
+                let (mut normalized, args) = util::normalize_signature(&self.func.sig);
+                if let Some(FnArg::Receiver(receiver)) = normalized.inputs.first_mut() {
+                    if receiver.reference.is_none() {
+                        receiver.mutability = None;
+                    }
+                }
+                let fn_params = util::fn_params(&normalized);
+                let maybe_await = normalized.asyncness.map(|_| util::await_tokens());
+                let target_arch = target.target_arch();
+                let safe = ItemFn {
+                    attrs: vec![parse_quote! { #[inline(always)] }, target_arch],
+                    vis: Visibility::Inherited,
+                    sig: Signature {
+                        ident: safe_ident.clone(),
+                        unsafety: None,
+                        ..self.func.sig.clone()
+                    },
+                    block: make_block(Some(target), block),
+                };
+                let mut f = ItemFn {
+                    attrs: self.inner_attrs.clone(),
+                    vis: Visibility::Inherited,
+                    sig: Signature {
+                        ident,
+                        unsafety: parse_quote! { unsafe },
+                        ..normalized
+                    },
+                    block: parse_quote! {
+                        {
+                            Self::#safe_ident::<#(#fn_params),*>(#(#args),*)#maybe_await
+                        }
+                    },
+                };
+                f.attrs.extend(target.fn_attrs());
+                return vec![f, safe];
+            }
+
+            // This function will always be unsafe, regardless of the safety of the multiversioned
+            // function.
+            //
+            // This could accidentally allow unsafe operations to end up in functions that appear
+            // safe, but the deny lint should catch it.
+            //
+            // For now, nest a safe copy in the unsafe version. This is imperfect, but sound.
+            //
+            // When target_feature 1.1 is available, this function can instead use the original
+            // function safety.
+            let mut f = unsafe_fn_safe_block(ItemFn {
+                attrs: self.inner_attrs.clone(),
+                vis: Visibility::Inherited,
+                sig: Signature {
+                    ident,
+                    unsafety: parse_quote! { unsafe },
+                    ..self.func.sig.clone()
+                },
+                block: make_block(Some(target), block),
+            });
+            f.attrs.extend(target.fn_attrs());
+            vec![f]
+        };
+
+        let ident = &self.func.sig.ident;
+        let mut fns = Vec::new();
+        for target in self.version_targets() {
+            let mut f = make_clone(self.version_name(Some(target)), target, &self.func.block);
+            f[0].attrs.extend(readable_docs(
+                self.readable,
+                [format!("The version of `{ident}` for `{}`.", target_spec(target)).as_str()],
+            ));
+            fns.extend(f);
+        }
+
+        // Create default fn
+        let mut attrs = self.inner_attrs.clone();
+        if !attrs.iter().any(|attr| attr.path().is_ident("inline")) {
+            attrs.push(parse_quote! { #[inline(always)] });
+        }
+        attrs.extend(readable_docs(
+            self.readable,
+            [
+                format!("The version of `{ident}` with the features enabled at compile time.")
+                    .as_str(),
+            ],
+        ));
+
+        // The fallback body is only compiled without the features of the targets.
+        let fallback = self.fallback.as_ref().unwrap_or(&self.func.block);
+
+        // The default fn calls the baseline version on architectures with a baseline, and the
+        // lowest priority target on architectures whose enabled features already support it,
+        // rather than compiling the function again.
+        let allow_unreachable = self.allow_unreachable();
+        let mut calls = Vec::new();
+        let mut conditions = Vec::new();
+        for baseline in &self.baselines {
+            let ident = baseline_fn_name(&self.func.sig.ident, baseline, self.version_names);
+            let arch = baseline.arch();
+            let call = self.call_fn(&ident);
+            calls.push(quote! {
+                #[cfg(target_arch = #arch)]
+                #allow_unreachable
+                {
+                    return #call
+                }
+            });
+            conditions.push(quote! { target_arch = #arch });
+            let mut f = make_clone(ident, baseline, fallback);
+            f[0].attrs.extend(readable_docs(
+                self.readable,
+                [format!(
+                    "The version of `{}` for the `{}` baseline, `{}`.",
+                    self.func.sig.ident,
+                    arch,
+                    target_spec(baseline)
+                )
+                .as_str()],
+            ));
+            fns.extend(f);
+        }
+        for target in self.folded_targets() {
+            let predicate = target.cfg_predicate();
+            let features = target.features();
+            let condition = quote! { all(#predicate #(, target_feature = #features)*) };
+            let call = self.call_target_fn(Some(target));
+            calls.push(quote! {
+                #[cfg(#condition)]
+                #allow_unreachable
+                {
+                    return #call
+                }
+            });
+            conditions.push(condition);
+        }
+        // The versions of methods are associated functions, which shouldn't be visible outside the
+        // impl.
+        let default_vis = if method {
+            Visibility::Inherited
+        } else {
+            self.func.vis.clone()
+        };
+        if calls.is_empty() {
+            fns.push(ItemFn {
+                attrs,
+                vis: default_vis,
+                sig: Signature {
+                    ident: self.version_name(None),
+                    ..self.func.sig.clone()
+                },
+                block: make_block(None, fallback),
+            });
+        } else {
+            // The statements are spliced to avoid the `unused_braces` lint, like in `make_block`.
+            let default_stmts = make_block(None, fallback).stmts;
+            let bind_patterns = util::bind_patterns(&self.func.sig);
+            let (normalized_signature, _) = util::normalize_signature(&self.func.sig);
+            fns.push(ItemFn {
+                attrs,
+                vis: default_vis,
+                sig: Signature {
+                    ident: self.version_name(None),
+                    ..normalized_signature
+                },
+                block: parse_quote! {
+                    {
+                        #(#calls)*
+                        #[cfg(not(any(#(#conditions),*)))]
+                        {
+                            #bind_patterns
+                            #(#default_stmts)*
+                        }
+                    }
+                },
+            });
+        }
+
+        Ok(fns)
+    }
+
+    // Returns the lowest priority target of each architecture without a baseline.  If the
+    // target's features are enabled at compile time, the default fn calls it.
+    fn folded_targets(&self) -> Vec<&Target> {
+        let mut folded: Vec<&Target> = Vec::new();
+        for target in self.ordered_targets() {
+            if self
+                .baselines
+                .iter()
+                .any(|baseline| baseline.arch() == target.arch())
+            {
+                continue;
+            }
+            match folded.iter_mut().find(|t| t.arch() == target.arch()) {
+                Some(lowest) => *lowest = target,
+                None => folded.push(target),
+            }
+        }
+        folded
+    }
+
+    // Dispatchers return the result of calling a version, which is unreachable if the function
+    // never returns.  The lint is only allowed on the dispatching statements, since the clones
+    // nested in the dispatcher contain the user's body.
+    fn allow_unreachable(&self) -> Option<Attribute> {
+        util::returns_never(&self.func.sig)
+            .then(|| parse_quote! { #[allow(unreachable_code, clippy::diverging_sub_expression)] })
+    }
+
+    fn call_target_fn(&self, target: Option<&Target>) -> Expr {
+        self.call_fn(&self.version_name(target))
+    }
+
+    fn call_fn(&self, function: &Ident) -> Expr {
+        let fn_params = util::fn_params(&self.func.sig);
+        let (_, argument_names) = util::normalize_signature(&self.func.sig);
+        let maybe_await = self.func.sig.asyncness.map(|_| util::await_tokens());
+        let function = if self.is_method() {
+            quote! { Self::#function }
+        } else {
+            quote! { #function }
+        };
+        parse_quote! {
+            unsafe { #function::<#(#fn_params),*>(#(#argument_names),*)#maybe_await }
+        }
+    }
+
+    // Checks the remaining stack, if `min_stack` is specified, and records the frame size, if the
+    // `stack_usage` feature is enabled.
+    fn stack_checks(&self, target: Option<&Target>) -> TokenStream {
+        if self.is_const() {
+            return TokenStream::new();
+        }
+        let function = self.func.sig.ident.to_string();
+        let path = quote! { core::concat!(core::module_path!(), "::", #function) };
+        let target = target.map_or_else(|| quote! { "default" }, ToTokens::to_token_stream);
+        let mut tokens = TokenStream::new();
+        if let Some(min_stack) = self.min_stack {
+            tokens.extend(quote! {
+                multiversion::stack::__check_remaining(#min_stack, #path, #target);
+            });
+        }
+        if cfg!(feature = "stack_usage") {
+            tokens.extend(quote! {
+                multiversion::stack::__record_frame(#path, #target);
+            });
+        }
+        tokens
+    }
+
+    // Counts the call to the version, if the `stats` feature is enabled.
+    fn count_call(&self, target: Option<&Target>) -> TokenStream {
+        if !cfg!(feature = "stats") || self.is_const() {
+            return TokenStream::new();
+        }
+        let function = self.func.sig.ident.to_string();
+        let target = target.map_or_else(|| quote! { "default" }, ToTokens::to_token_stream);
+        quote! {
+            {
+                static __MULTIVERSION_CALLS: multiversion::stats::__Counter = multiversion::stats::__Counter::new(
+                    core::concat!(core::module_path!(), "::", #function),
+                    #target,
+                );
+                __MULTIVERSION_CALLS.increment();
+            }
+        }
+    }
+
+    // Records the stack address of the dispatcher, if the `stack_usage` feature is enabled, so
+    // that the frame size of the version it calls can be estimated.
+    fn with_stack_dispatch(f: ItemFn) -> ItemFn {
+        if !cfg!(feature = "stack_usage") || f.sig.constness.is_some() {
+            return f;
+        }
+        let block = &f.block;
+        ItemFn {
+            block: parse_quote! {
+                {
+                    multiversion::stack::__dispatch();
+                    #block
+                }
+            },
+            ..f
+        }
+    }
+
+    // Calls the hook registered with `on_select`, records the selected target, if the
+    // `track_selection` feature is enabled, and logs it, if the `log` or `tracing` features are
+    // enabled.
+    fn record_selection(&self, target: Option<&Target>) -> TokenStream {
+        let target = target.map_or_else(|| quote! { "default" }, ToTokens::to_token_stream);
+        self.record_selection_of(target)
+    }
+
+    // Like `record_selection`, but records a target string expression.
+    fn record_selection_of(&self, target: TokenStream) -> TokenStream {
+        let function = self.func.sig.ident.to_string();
+        // Groups are recorded once, by the path of the group.
+        let path = if self.group == Some(GroupRole::Selector) {
+            quote! { core::module_path!() }
+        } else {
+            quote! { core::concat!(core::module_path!(), "::", #function) }
+        };
+        let mut tokens = quote! {
+            multiversion::__selected(#path, #target);
+        };
+        if cfg!(feature = "track_selection") {
+            tokens.extend(quote! {
+                multiversion::report::__record(#path, #target);
+            });
+        }
+        if cfg!(any(feature = "log", feature = "tracing")) {
+            tokens.extend(quote! {
+                multiversion::logging::__log_selection(#path, #target);
+            });
+        }
+        tokens
+    }
+
+    fn static_dispatcher_fn(&self) -> Block {
+        let return_if_detected = self.targets.iter().filter_map(|target| {
+            if target.has_features_specified() {
+                let target_arch = target.target_arch();
+                let features_enabled = target.features_enabled();
+                let call = self.call_target_fn(Some(target));
+                Some(quote! {
+                    #target_arch
+                    {
+                        if #features_enabled {
+                            return #call
+                        }
+                    }
+                })
+            } else {
+                None
+            }
+        });
+        let call_default = self.call_target_fn(None);
+        let test_override = self.static_test_override();
+        parse_quote! {
+            {
+                #test_override
+                #(#return_if_detected)*
+                #call_default
+            }
+        }
+    }
+
+    // In tests, the static dispatcher selects the versions supported by the target passed to
+    // `with_static_target`, if they're also detected at runtime.
+    fn static_test_override(&self) -> Option<TokenStream> {
+        if !self.runtime_detection() || self.is_const() {
+            return None;
+        }
+        let return_if_supported = self.targets.iter().filter_map(|target| {
+            if !target.has_features_specified() {
+                return None;
+            }
+            let target_arch = target.target_arch();
+            let features = target.features();
+            let detected = self.target_detected(target);
+            let call = self.call_target_fn(Some(target));
+            Some(quote! {
+                #target_arch
+                {
+                    if true #(&& multiversion::target::__supports_feature(&__target, #features))* && #detected {
+                        return #call
+                    }
+                }
+            })
+        });
+        let call_default = self.call_target_fn(None);
+        Some(quote! {
+            #[cfg(test)]
+            {
+                if let Some(__target) = multiversion::target::__static_target_override() {
+                    let _ = __target;
+                    #(#return_if_supported)*
+                    return #call_default;
+                }
+            }
+        })
+    }
+
+    fn inline_dispatcher_fn(&self) -> Result<Block> {
+        if !self.runtime_detection() {
+            return Err(Error::new(
+                Span::call_site(),
+                "inline function dispatch only available with the `std` cargo feature or a `detector`",
+            ));
+        }
+        let return_if_detected = self.ordered_targets().into_iter().map(|target| {
+            let target_arch = target.target_arch();
+            let features_detected = self.target_detected(target);
+            let call = self.call_target_fn(Some(target));
+            quote! {
+                #target_arch
+                {
+                    if #features_detected {
+                        return #call
+                    }
+                }
+            }
+        });
+        let call_default = self.call_target_fn(None);
+        Ok(parse_quote! {
+            {
+                #(#return_if_detected)*
+                #call_default
+            }
+        })
+    }
+
+    // Returns an expression that is `true` if the target's features are enabled at compile time,
+    // or detected at runtime.  Features that are already enabled aren't detected again, unless
+    // elision is disabled.
+    fn target_detected(&self, target: &Target) -> TokenStream {
+        let features_enabled = self.features_enabled(target);
+        let features_detected =
+            target.features_detected_with(self.detector.as_ref(), self.all_cores);
+        quote! { ((#features_enabled) || (#features_detected)) }
+    }
+
+    // Returns an expression that is `true` if the target's features are enabled at compile time.
+    // With elision disabled, features are always detected at runtime instead.
+    fn features_enabled(&self, target: &Target) -> TokenStream {
+        if self.elision {
+            target.features_enabled()
+        } else {
+            quote! { false }
+        }
+    }
+
+    // Checks that the function can be dispatched with a function pointer, and returns a
+    // `__get_fn` function that selects the target.
+    //
+    // If `fn_ty` is provided, `__get_fn` returns that function pointer type.  Otherwise, it
+    // returns a type-erased pointer, which supports signatures with lifetimes that can't be
+    // bound by a function pointer type.
+    //
+    // With `resolver`, `__get_fn` is called by an indirect function resolver (see
+    // `select_index_fn`).
+    fn fn_pointer_selector(
+        &self,
+        resolver: bool,
+        fn_ty: Option<&TypeBareFn>,
+    ) -> Result<TokenStream> {
+        self.fn_pointer_selector_with(resolver, fn_ty, &|function| function)
+    }
+
+    // Like `fn_pointer_selector`, but selects the functions named by `name` rather than the
+    // target clones.
+    fn fn_pointer_selector_with(
+        &self,
+        resolver: bool,
+        fn_ty: Option<&TypeBareFn>,
+        name: &dyn Fn(Ident) -> Ident,
+    ) -> Result<TokenStream> {
+        let return_fn = |function: Ident| {
+            let function = name(function);
+            match fn_ty {
+                Some(_) => quote! { #function },
+                None => quote! { #function as *mut () },
+            }
+        };
+        let return_ty = match fn_ty {
+            Some(fn_ty) => quote! { #fn_ty },
+            None => quote! { *mut () },
+        };
+
+        let default_fn = return_fn(self.version_name(None));
+        let arms = self
+            .ordered_targets()
+            .into_iter()
+            .enumerate()
+            .map(|(index, target)| {
+                let index = index + 1; // 0 is default features
+                let target_arch = target.target_arch();
+                let function = return_fn(self.version_name(Some(target)));
+                quote! {
+                    #target_arch
+                    #index => #function,
+                }
+            });
+        // With `resolve`, the selection is shared with the resolve function.
+        let (select_fn, select) = if self.resolve && !resolver {
+            (TokenStream::new(), self.detect_fn_name())
+        } else {
+            let select = Ident::new("__select", Span::call_site());
+            (self.select_index_fn(&select, resolver), select)
+        };
+        Ok(quote! {
+            fn __get_fn() -> #return_ty {
+                #select_fn
+                match #select() {
+                    #(#arms)*
+                    _ => #default_fn,
+                }
+            };
+        })
+    }
+
+    // Creates a function that returns the index of the first target whose features are detected
+    // (indexed from 1), or 0 for the default function.
+    //
+    // The targets of each architecture are stored in a table, rather than a chain of conditions,
+    // which keeps the function small for long lists of targets.
+    //
+    // With `resolver`, the function is called by an indirect function resolver, while the dynamic
+    // loader processes relocations.  The selection isn't recorded, and features are detected
+    // with `cpuid` or `getauxval` only.
+    fn select_index_fn(&self, ident: &Ident, resolver: bool) -> TokenStream {
+        let ordered_targets = self.ordered_targets();
+        let mut tables: Vec<(&str, Vec<TokenStream>)> = Vec::new();
+        for (index, target) in ordered_targets.iter().enumerate() {
+            let index = index + 1; // 0 is default features
+            let features = target.features();
+            let vendor = target.vendor().unwrap_or("");
+            let enabled = self.features_enabled(target);
+            // Targets with `cfg` predicates are only in the table if they're compiled.
+            let cfg = target.has_cfgs().then(|| target.target_arch());
+            let narrow = target.is_narrow();
+            let entry =
+                quote! { #cfg (#index, #target, &[#(#features),*], #vendor, #narrow, #enabled) };
+            match tables.iter_mut().find(|(arch, _)| *arch == target.arch()) {
+                Some((_, entries)) => entries.push(entry),
+                None => tables.push((target.arch(), vec![entry])),
+            }
+        }
+        let archs = tables.iter().map(|(arch, _)| arch);
+        let tables = tables.iter().map(|(arch, entries)| {
+            quote! {
+                #[cfg(target_arch = #arch)]
+                const __TABLE: (&str, &[__Entry]) = (#arch, &[#(#entries),*]);
+            }
+        });
+
+        // The detector is called once, rather than for each target.
+        let (arch, detect, detected) = match &self.detector {
+            Some(detector) => (
+                quote! { _ },
+                quote! { let __detected: multiversion::target::Target = #detector(); },
+                quote! {
+                    features
+                        .iter()
+                        .all(|feature| multiversion::target::__supports_feature(&__detected, feature))
+                },
+            ),
+            None => {
+                let is_feature_detected = if resolver {
+                    quote! { multiversion::detect::__is_ifunc_feature_detected }
+                } else {
+                    crate::target::feature_detector(self.all_cores)
+                };
+                (
+                    quote! { arch },
+                    TokenStream::new(),
+                    quote! { features.iter().all(|feature| #is_feature_detected(arch, feature)) },
+                )
+            }
+        };
+        let (vendor, check_vendor) = if ordered_targets
+            .iter()
+            .any(|target| target.vendor().is_some())
+        {
+            (
+                quote! { vendor },
+                quote! { && (vendor.is_empty() || multiversion::cpu::__is_vendor(vendor)) },
+            )
+        } else {
+            (quote! { _ }, TokenStream::new())
+        };
+        let (narrow, check_narrow) = if ordered_targets.iter().any(|target| target.is_narrow()) {
+            (
+                quote! { narrow },
+                quote! { && (!narrow || multiversion::cpu::__prefers_256_bit()) },
+            )
+        } else {
+            (quote! { _ }, TokenStream::new())
+        };
+        let (target, record_target, record_default) = if !resolver {
+            (
+                quote! { target },
+                self.record_selection_of(quote! { target }),
+                self.record_selection(None),
+            )
+        } else {
+            (quote! { _ }, TokenStream::new(), TokenStream::new())
+        };
+        let select = match &self.priority_model {
+            // The detected target with the highest score is selected.
+            Some(model) => {
+                let scores = model.scores(&ordered_targets);
+                quote! {
+                    let __scores: &[i32] = match #scores {
+                        Some(__scores) => __scores,
+                        // The CPU isn't in the model, so every target has the same score.
+                        None => &[],
+                    };
+                    let mut __best: Option<(i32, usize, &str)> = None;
+                    for &(index, target, features, #vendor, #narrow, enabled) in targets {
+                        if enabled || (#detected #check_vendor #check_narrow) {
+                            let __score = match __scores.get(index - 1) {
+                                Some(&__score) => __score,
+                                None => 0,
+                            };
+                            // Targets with equal scores keep their priority.
+                            match __best {
+                                Some((__best_score, _, _)) if __best_score >= __score => {}
+                                _ => __best = Some((__score, index, target)),
+                            }
+                        }
+                    }
+                    if let Some((_, index, target)) = __best {
+                        let _ = target;
+                        #record_target
+                        return index;
+                    }
+                    #record_default
+                    0
+                }
+            }
+            None => quote! {
+                for &(index, #target, features, #vendor, #narrow, enabled) in targets {
+                    if enabled || (#detected #check_vendor #check_narrow) {
+                        #record_target
+                        return index;
+                    }
+                }
+                #record_default
+                0
+            },
+        };
+        quote! {
+            fn #ident() -> usize {
+                // The index, target, features, and CPU vendor of each target, whether it's a
+                // 256-bit variant, and whether its features are enabled at compile time, so they
+                // don't need to be detected.
+                type __Entry = (usize, &'static str, &'static [&'static str], &'static str, bool, bool);
+                #(#tables)*
+                #[cfg(not(any(#(target_arch = #archs),*)))]
+                const __TABLE: (&str, &[__Entry]) = ("", &[]);
+
+                let (#arch, targets) = __TABLE;
+                #detect
+                #select
+            }
+        }
+    }
+
+    // Checks that the function can be dispatched with a function pointer.
+    fn check_fn_pointer(&self, method: &str) -> Result<()> {
+        self.check_fn_pointer_with(method, false)
+    }
+
+    // Like `check_fn_pointer`, but optionally allows const generic parameters.
+    fn check_fn_pointer_with(&self, method: &str, const_generics: bool) -> Result<()> {
+        if !self.runtime_detection() {
+            return Err(Error::new(
+                Span::call_site(),
+                format!("{method} function dispatch only available with the `std` cargo feature or a `detector`"),
+            ));
+        }
+        if !util::fn_params(&self.func.sig).is_empty() && (!const_generics || !self.const_generic())
+        {
+            return Err(Error::new(
+                Span::call_site(),
+                format!("{method} function dispatch does not support type generic or const generic parameters"),
+            ));
+        }
+        if self.func.sig.asyncness.is_some() {
+            return Err(Error::new(
+                Span::call_site(),
+                format!("{method} function dispatch does not support async functions"),
+            ));
+        }
+        if util::impl_trait_present(&self.func.sig) {
+            return Err(Error::new(
+                Span::call_site(),
+                format!("{method} function dispatch does not support impl trait"),
+            ));
+        }
+        Ok(())
+    }
+
+    fn boxed_future_name(function: Ident) -> Ident {
+        Ident::new(&format!("{function}_boxed"), function.span())
+    }
+
+    // Creates an indirect dispatcher for an async function, by calling wrappers of the target
+    // clones that box their futures.
+    fn boxed_future_dispatcher_fn(&self) -> Result<Block> {
+        if !cfg!(feature = "std") {
+            return Err(Error::new(
+                Span::call_site(),
+                "`boxed_future` is only available with the `std` cargo feature",
+            ));
+        }
+        if !util::fn_params(&self.func.sig).is_empty() || util::impl_trait_present(&self.func.sig) {
+            return Err(Error::new(
+                Span::call_site(),
+                "`boxed_future` does not support type generic or const generic parameters, or impl trait",
+            ));
+        }
+        if let Some(receiver) = self.func.sig.receiver() {
+            return Err(Error::new(
+                receiver.self_token.span,
+                "`boxed_future` does not support associated functions",
+            ));
+        }
+
+        // Every lifetime in the arguments must outlive the boxed future.
+        let (normalized_signature, argument_names) = util::normalize_signature(&self.func.sig);
+        let mut wrapper_signature = util::name_elided_lifetimes(&normalized_signature);
+        let future_lifetime = Lifetime::new("'__mv_future", Span::call_site());
+        let outlives = wrapper_signature
+            .generics
+            .lifetimes()
+            .map(|param| {
+                let lifetime = &param.lifetime;
+                quote! { #lifetime: #future_lifetime }
+            })
+            .collect::<Vec<_>>();
+        wrapper_signature
+            .generics
+            .params
+            .push(parse_quote! { #future_lifetime });
+        let where_clause = wrapper_signature.generics.make_where_clause();
+        for predicate in outlives {
+            where_clause.predicates.push(parse_quote! { #predicate });
+        }
+        let output = match &self.func.sig.output {
+            ReturnType::Default => quote! { () },
+            ReturnType::Type(_, ty) => quote! { #ty },
+        };
+        wrapper_signature.asyncness = None;
+        wrapper_signature.unsafety = None;
+        wrapper_signature.output = parse_quote! {
+            -> core::pin::Pin<std::boxed::Box<dyn core::future::Future<Output = #output> + Send + #future_lifetime>>
+        };
+
+        let wrappers = self
+            .version_targets()
+            .map(Some)
+            .chain(Some(None))
+            .map(|target| {
+                let function = self.version_name(target);
+                let target_arch = target.map(Target::target_arch);
+                let wrapper_signature = Signature {
+                    ident: Self::boxed_future_name(function.clone()),
+                    ..wrapper_signature.clone()
+                };
+                quote! {
+                    #target_arch
+                    #wrapper_signature {
+                        std::boxed::Box::pin(unsafe { #function(#(#argument_names),*) })
+                    }
+                }
+            });
+        let feature_detection =
+            self.fn_pointer_selector_with(false, None, &Self::boxed_future_name)?;
+        let default_wrapper = Self::boxed_future_name(self.version_name(None));
+        let arg_placeholders = argument_names.iter().map(|_| quote! { _ });
+        Ok(parse_quote! {
+            {
+                use core::sync::atomic::{AtomicPtr, Ordering};
+                #(#wrappers)*
+                #feature_detection
+                static __DISPATCHED_FN: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+                let mut __current_ptr = __DISPATCHED_FN.load(Ordering::Relaxed);
+                if __current_ptr.is_null() {
+                    __current_ptr = __get_fn();
+                    __DISPATCHED_FN.store(__current_ptr, Ordering::Relaxed);
+                }
+                // The function pointer type is inferred from the default wrapper, with the
+                // lifetimes of this call.
+                let mut __current_fn: fn(#(#arg_placeholders),*) -> _ = #default_wrapper;
+                // Safety: the pointer is one of the wrappers, which all have the same signature.
+                #[allow(clippy::undocumented_unsafe_blocks)]
+                unsafe {
+                    __current_fn = core::mem::transmute(__current_ptr);
+                }
+                __current_fn(#(#argument_names),*).await
+            }
+        })
+    }
+
+    fn indirect_dispatcher_fn(&self) -> Result<Block> {
+        if self.boxed_future && self.func.sig.asyncness.is_some() {
+            return self.boxed_future_dispatcher_fn();
+        }
+        if self.const_generic() {
+            return self.const_generic_dispatcher_fn();
+        }
+        self.check_fn_pointer("indirect")?;
+        self.indirect_dispatcher_with(TokenStream::new())
+    }
+
+    // Returns true if the function has const generic parameters, but no type generic parameters.
+    fn const_generic(&self) -> bool {
+        let generics = &self.func.sig.generics;
+        generics.const_params().next().is_some() && generics.type_params().next().is_none()
+    }
+
+    // Creates the `indirect` dispatcher for a function with const generic parameters.
+    //
+    // Statics can't depend on generic parameters, so the index of the selected version is shared
+    // by every instantiation, and each instantiation has its own table of function pointers.  The
+    // last entry of the table is the resolver, which is called until a version is selected.
+    fn const_generic_dispatcher_fn(&self) -> Result<Block> {
+        self.check_fn_pointer_with("indirect", true)?;
+
+        let mut generics = self.func.sig.generics.clone();
+        for param in generics.const_params_mut() {
+            param.eq_token = None;
+            param.default = None;
+        }
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let lifetimes = generics.lifetimes().map(|param| &param.lifetime);
+        let fn_params = util::fn_params(&self.func.sig);
+
+        let fn_ty = util::fn_type_in_scope(&Signature {
+            unsafety: parse_quote! { unsafe },
+            ..self.func.sig.clone()
+        })?;
+        let (normalized_signature, argument_names) = util::normalize_signature(&self.func.sig);
+        let resolver_signature = Signature {
+            ident: Ident::new("__resolver_fn", Span::call_site()),
+            ..normalized_signature
+        };
+
+        let default_fn = self.version_name(None);
+        let ordered_targets = self.ordered_targets();
+        let target_fns = ordered_targets.iter().map(|target| {
+            let target_arch = target.target_arch();
+            let function = self.version_name(Some(target));
+            quote! {
+                match () {
+                    #target_arch
+                    () => #function::<#(#fn_params),*> as #fn_ty,
+                    _ => #default_fn::<#(#fn_params),*> as #fn_ty,
+                }
+            }
+        });
+        let resolver_index = ordered_targets.len() + 1;
+        let table_len = ordered_targets.len() + 2;
+        let detect_index = self.detect_index();
+        let register = register_resolver(quote! {
+            || __SELECTED.store(__detect_index(), core::sync::atomic::Ordering::Relaxed)
+        });
+        Ok(parse_quote! {
+            {
+                use core::sync::atomic::{AtomicUsize, Ordering};
+                #detect_index
+                struct __Versions #impl_generics (core::marker::PhantomData<(#(&#lifetimes (),)*)>) #where_clause;
+                impl #impl_generics __Versions #ty_generics #where_clause {
+                    #[allow(unreachable_patterns)]
+                    const FNS: [#fn_ty; #table_len] = [
+                        #default_fn::<#(#fn_params),*> as #fn_ty,
+                        #(#target_fns,)*
+                        __resolver_fn::<#(#fn_params),*> as #fn_ty,
+                    ];
+                }
+                #[cold]
+                #resolver_signature {
+                    let __index = __detect_index();
+                    __SELECTED.store(__index, Ordering::Relaxed);
+                    unsafe { __Versions::<#(#fn_params),*>::FNS[__index](#(#argument_names),*) }
+                }
+                static __SELECTED: AtomicUsize = AtomicUsize::new(#resolver_index);
+                #register
+                let __index = __SELECTED.load(Ordering::Relaxed);
+                // Safety: the index is either a detected version or the resolver, which are all in
+                // the table.
+                #[allow(clippy::undocumented_unsafe_blocks)]
+                unsafe {
+                    let __current_fn = *__Versions::<#(#fn_params),*>::FNS.get_unchecked(__index);
+                    __current_fn(#(#argument_names),*)
+                }
+            }
+        })
+    }
+
+    fn ctor_dispatcher_fn(&self) -> Result<Block> {
+        self.check_fn_pointer("ctor")?;
+
+        // The selected function is stored by a constructor that runs before `main`.  On platforms
+        // without constructors, or if the function is called by another constructor first, the
+        // function is selected by the first call, like the `indirect` dispatcher.
+        let feature_detection = self.fn_pointer_selector(false, None)?;
+        let link_section = |section: &str| {
+            let span = Span::mixed_site();
+            quote::quote_spanned! { span=> link_section = #section }
+        };
+        let init_array = link_section(".init_array");
+        let mod_init_func = link_section("__DATA,__mod_init_func");
+        let crt = link_section(".CRT$XCU");
+        self.indirect_dispatcher_with(quote! {
+            #[used]
+            #[cfg_attr(
+                any(
+                    target_os = "linux",
+                    target_os = "android",
+                    target_os = "freebsd",
+                    target_os = "netbsd",
+                    target_os = "openbsd",
+                    target_os = "dragonfly",
+                    target_os = "illumos",
+                    target_os = "solaris",
+                ),
+                #init_array
+            )]
+            #[cfg_attr(target_vendor = "apple", #mod_init_func)]
+            #[cfg_attr(windows, #crt)]
+            static __CTOR: extern "C" fn() = {
+                extern "C" fn __ctor() {
+                    #feature_detection
+                    __DISPATCHED_FN.store(__get_fn(), Ordering::Relaxed);
+                }
+                __ctor
+            };
+        })
+    }
+
+    // Creates the `indirect` dispatcher, with additional items in its body.
+    fn indirect_dispatcher_with(&self, items: TokenStream) -> Result<Block> {
+        let feature_detection = self.fn_pointer_selector(false, None)?;
+
+        // The pointer is type-erased, and converted to a function pointer that uses the lifetimes
+        // of the function, rather than a higher-ranked function pointer.  This supports lifetimes
+        // that can't be higher-ranked, such as those produced by `async_trait`.
+        let fn_ty = util::fn_type_in_scope(&Signature {
+            unsafety: parse_quote! { unsafe },
+            ..self.func.sig.clone()
+        })?;
+        let (normalized_signature, argument_names) = util::normalize_signature(&self.func.sig);
+        let resolver_signature = Signature {
+            ident: Ident::new("__resolver_fn", Span::call_site()),
+            ..normalized_signature
+        };
+        let register = register_resolver(quote! {
+            || __DISPATCHED_FN.store(__get_fn(), core::sync::atomic::Ordering::Relaxed)
+        });
+        Ok(parse_quote! {
+            {
+                use core::sync::atomic::{AtomicPtr, Ordering};
+                #feature_detection
+                #[cold]
+                #resolver_signature {
+                    let __current_ptr = __get_fn();
+                    __DISPATCHED_FN.store(__current_ptr, Ordering::Relaxed);
+                    // Safety: the pointer is a fn pointer, so we can transmute it back to its
+                    // original representation.
+                    #[allow(clippy::undocumented_unsafe_blocks)]
+                    unsafe {
+                        let __current_fn = core::mem::transmute::<*mut (), #fn_ty>(__current_ptr);
+                        __current_fn(#(#argument_names),*)
+                    }
+                }
+                static __DISPATCHED_FN: AtomicPtr<()> = AtomicPtr::new(__resolver_fn as *mut ());
+                #register
+                #items
+                let __current_ptr = __DISPATCHED_FN.load(Ordering::Relaxed);
+                // Safety: the pointer is a fn pointer, so we can transmute it back to its original
+                // representation.
+                #[allow(clippy::undocumented_unsafe_blocks)]
+                unsafe {
+                    let __current_fn = core::mem::transmute::<*mut (), #fn_ty>(__current_ptr);
+                    __current_fn(#(#argument_names),*)
+                }
+            }
+        })
+    }
+
+    fn once_dispatcher_fn(&self) -> Result<Block> {
+        if !cfg!(feature = "std") {
+            return Err(Error::new(
+                Span::call_site(),
+                "once function dispatch only available with the `std` cargo feature",
+            ));
+        }
+        self.check_fn_pointer("once")?;
+        let fn_ty = util::fn_type_from_signature(&Signature {
+            unsafety: parse_quote! { unsafe },
+            ..self.func.sig.clone()
+        })?;
+        let feature_detection = self.fn_pointer_selector(false, Some(&fn_ty))?;
+        let (_, argument_names) = util::normalize_signature(&self.func.sig);
+        let register = register_resolver(quote! { || { __DISPATCHED_FN.get_or_init(__get_fn); } });
+        Ok(parse_quote! {
+            {
+                #feature_detection
+                static __DISPATCHED_FN: std::sync::OnceLock<#fn_ty> = std::sync::OnceLock::new();
+                #register
+                let __current_fn = *__DISPATCHED_FN.get_or_init(__get_fn);
+                unsafe { __current_fn(#(#argument_names),*) }
+            }
+        })
+    }
+
+    fn tls_dispatcher_fn(&self) -> Result<Block> {
+        if !cfg!(feature = "std") {
+            return Err(Error::new(
+                Span::call_site(),
+                "tls function dispatch only available with the `std` cargo feature",
+            ));
+        }
+        self.check_fn_pointer("tls")?;
+        let fn_ty = util::fn_type_from_signature(&Signature {
+            unsafety: parse_quote! { unsafe },
+            ..self.func.sig.clone()
+        })?;
+        let feature_detection = self.fn_pointer_selector(false, Some(&fn_ty))?;
+        let (_, argument_names) = util::normalize_signature(&self.func.sig);
+        Ok(parse_quote! {
+            {
+                #feature_detection
+                std::thread_local! {
+                    static __DISPATCHED_FN: core::cell::Cell<Option<#fn_ty>> = const {
+                        core::cell::Cell::new(None)
+                    };
+                }
+                // Each thread selects the version once.  If the thread-local was already
+                // destroyed, such as in the destructor of another thread-local, the version is
+                // selected again.
+                let __current_fn = match __DISPATCHED_FN.try_with(|__selected| match __selected.get() {
+                    Some(__f) => __f,
+                    None => {
+                        let __f = __get_fn();
+                        __selected.set(Some(__f));
+                        __f
+                    }
+                }) {
+                    Ok(__f) => __f,
+                    Err(_) => __get_fn(),
+                };
+                unsafe { __current_fn(#(#argument_names),*) }
+            }
+        })
+    }
+
+    fn ifunc_dispatcher_fn(&self) -> Result<Block> {
+        // The resolver runs while the dynamic loader processes relocations, before the program
+        // is initialized, so selections can't be recorded.
+        self.check_fn_pointer("ifunc")?;
+        let feature_detection = self.fn_pointer_selector(true, None)?;
+        let (normalized_signature, argument_names) = util::normalize_signature(&self.func.sig);
+
+        // The symbol is global, so it must be unique across all linked crates.  It's derived from
+        // the crate and the location of the function, so it's the same in every build.  The span
+        // includes the macro expansion, which distinguishes functions generated by the same
+        // macro.
+        let symbol = {
+            use std::hash::{Hash, Hasher};
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            for var in ["CARGO_CRATE_NAME", "CARGO_PKG_NAME", "CARGO_PKG_VERSION"] {
+                std::env::var(var).ok().hash(&mut hasher);
+            }
+            format!("{:?}", self.func.sig.ident.span()).hash(&mut hasher);
+            self.func.to_token_stream().to_string().hash(&mut hasher);
+            format!(
+                "__multiversion_ifunc_{}_{:016x}",
+                self.func.sig.ident,
+                hasher.finish()
+            )
+        };
+        let type_directive = format!(".type {symbol}, %gnu_indirect_function");
+        let globl_directive = format!(".globl {symbol}");
+        let hidden_directive = format!(".hidden {symbol}");
+        let resolver_symbol = format!("{symbol}_resolver");
+        // The resolver is also hidden, so the indirect function can't be resolved by a resolver
+        // in another shared library, which may be unloaded.
+        let hidden_resolver_directive = format!(".hidden {resolver_symbol}");
+        let set_directive = format!(".set {symbol}, {resolver_symbol}");
+
+        let ifunc_signature = Signature {
+            constness: None,
+            asyncness: None,
+            unsafety: None,
+            abi: None,
+            ident: Ident::new("__ifunc", Span::call_site()),
+            ..normalized_signature
+        };
+        let indirect = self.indirect_dispatcher_fn()?;
+        Ok(parse_quote! {
+            {
+                #[cfg(any(
+                    all(
+                        any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"),
+                        target_os = "linux",
+                        target_env = "gnu",
+                    ),
+                    all(
+                        any(target_arch = "x86", target_arch = "x86_64"),
+                        target_os = "freebsd",
+                    ),
+                ))]
+                {
+                    #feature_detection
+                    #[export_name = #resolver_symbol]
+                    extern "C" fn __resolver() -> *const () {
+                        __get_fn()
+                    }
+                    mod __ifunc {
+                        core::arch::global_asm!(
+                            #globl_directive,
+                            #hidden_directive,
+                            #hidden_resolver_directive,
+                            #type_directive,
+                            #set_directive,
+                        );
+                    }
+                    extern "Rust" {
+                        #[link_name = #symbol]
+                        #ifunc_signature;
+                    }
+                    return unsafe { __ifunc(#(#argument_names),*) };
+                }
+                #[cfg(not(any(
+                    all(
+                        any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"),
+                        target_os = "linux",
+                        target_env = "gnu",
+                    ),
+                    all(
+                        any(target_arch = "x86", target_arch = "x86_64"),
+                        target_os = "freebsd",
+                    ),
+                )))]
+                #indirect
+            }
+        })
+    }
+
+    // Returns true if features can be detected at runtime.
+    fn runtime_detection(&self) -> bool {
+        cfg!(feature = "std") || self.detector.is_some()
+    }
+
+    // Targets that are dispatched at runtime, indexed from 1 (0 is the default function).
+    fn ordered_targets(&self) -> Vec<&Target> {
+        self.targets
+            .iter()
+            .filter(|target| target.has_features_specified())
+            .collect()
+    }
+
+    fn detect_fn_name(&self) -> Ident {
+        Ident::new(
+            &format!("__multiversion_{}_detect", self.func.sig.ident),
+            self.func.sig.ident.span(),
+        )
+    }
+
+    // Creates a function that returns the index of the selected target, detecting it only once.
+    fn detect_index_fn(&self, ident: &Ident) -> TokenStream {
+        let select_fn = self.select_index_fn(&Ident::new("__select", Span::call_site()), false);
+        quote! {
+            #[inline(always)]
+            fn #ident() -> usize {
+                // The index is stored in a byte, so that targets without pointer atomics can
+                // dispatch at runtime.  Racing threads detect the same index.
+                use core::sync::atomic::{AtomicU8, Ordering};
+                static SELECTED: AtomicU8 = AtomicU8::new(u8::MAX);
+
+                #[cold]
+                #[inline(never)]
+                fn __detect() -> usize {
+                    #select_fn
+                    let selected = __select();
+                    SELECTED.store(selected as u8, Ordering::Relaxed);
+                    selected
+                }
+
+                // After the first call, this is a single load and a predictable branch.
+                let selected = SELECTED.load(Ordering::Relaxed);
+                if selected == u8::MAX {
+                    __detect()
+                } else {
+                    selected as usize
+                }
+            }
+        }
+    }
+
+    // Creates the `__detect_index` function, which returns the index of the selected target.
+    fn detect_index(&self) -> TokenStream {
+        // In a group, the detection is shared with every function in the group.  With `resolve`,
+        // the detection is shared with the resolve function.
+        if self.group.is_some() {
+            let selector = Ident::new(GROUP_SELECTOR, Span::call_site());
+            quote! {
+                use #selector as __detect_index;
+            }
+        } else if self.resolve {
+            let detect_fn = self.detect_fn_name();
+            quote! {
+                use #detect_fn as __detect_index;
+            }
+        } else {
+            self.detect_index_fn(&Ident::new("__detect_index", Span::call_site()))
+        }
+    }
+
+    fn direct_dispatcher_fn(&self) -> Result<Block> {
+        if !self.runtime_detection() {
+            return Err(Error::new(
+                Span::call_site(),
+                "direct function dispatch only available with the `std` cargo feature or a `detector`",
+            ));
+        }
+
+        let ordered_targets = self.ordered_targets();
+        let detect_index = self.detect_index();
+        // Shared detection is registered with the resolve function or group.
+        let register = if self.resolve || self.group.is_some() {
+            TokenStream::new()
+        } else {
+            register_resolver(quote! { || { __detect_index(); } })
+        };
+
+        if let Some(table) = self.versions_table() {
+            let (_, argument_names) = util::normalize_signature(&self.func.sig);
+            return Ok(parse_quote! {
+                {
+                    #detect_index
+                    #register
+                    #table
+                    // Safety: the index is a detected version, which are all in the table.
+                    #[allow(clippy::undocumented_unsafe_blocks)]
+                    unsafe {
+                        let __current_fn = *__VERSIONS.get_unchecked(__detect_index());
+                        __current_fn(#(#argument_names),*)
+                    }
+                }
+            });
+        }
+
+        // Otherwise, the versions can't be converted to function pointers, so the selected
+        // version is matched.
+        let match_arm = ordered_targets.iter().enumerate().map(|(index, target)| {
+            let index = index + 1; // 0 is default features
+            let target_arch = target.target_arch();
+            let arm = self.call_target_fn(Some(target));
+            quote! {
+                #target_arch
+                #index => #arm,
+            }
+        });
+        let call_default = self.call_target_fn(None);
+        Ok(parse_quote! {
+            {
+                #detect_index
+                #register
+                let __selected = __detect_index();
+                match __selected {
+                    #(#match_arm)*
+                    0 => #call_default,
+                    _ => unsafe { core::hint::unreachable_unchecked() },
+                }
+            }
+        })
+    }
+
+    // Creates a constant table named `__VERSIONS` of function pointers to the versions, indexed
+    // like `select_index_fn`, if the versions can be converted to function pointers.  Entries for
+    // targets that aren't compiled, such as targets of other architectures, are the default
+    // version.
+    //
+    // Constants can't use the generic parameters of the function, so generic functions don't have
+    // a table.  With retpolines, indirect calls are avoided.
+    fn versions_table(&self) -> Option<TokenStream> {
+        if cfg!(retpoline)
+            || self.is_method()
+            || self.func.sig.asyncness.is_some()
+            || util::impl_trait_present(&self.func.sig)
+            || !util::fn_params(&self.func.sig).is_empty()
+        {
+            return None;
+        }
+        let fn_ty = util::fn_type_from_signature(&Signature {
+            unsafety: parse_quote! { unsafe },
+            ..self.func.sig.clone()
+        })
+        .ok()?;
+
+        let default_fn = self.version_name(None);
+        let ordered_targets = self.ordered_targets();
+        let target_fns = ordered_targets.iter().map(|target| {
+            let target_arch = target.target_arch();
+            let function = self.version_name(Some(target));
+            quote! {
+                match () {
+                    #target_arch
+                    () => #function as #fn_ty,
+                    _ => #default_fn as #fn_ty,
+                }
+            }
+        });
+        let table_len = ordered_targets.len() + 1;
+        Some(quote! {
+            #[allow(unreachable_patterns)]
+            const __VERSIONS: [#fn_ty; #table_len] = [
+                #default_fn as #fn_ty,
+                #(#target_fns,)*
+            ];
+        })
+    }
+
+    // Returns true if the default dispatcher selected `indirect`, but can use `direct` dispatch
+    // on targets without pointer atomics.  Direct dispatch only stores the index of the selected
+    // version, in a byte.
+    fn pointer_atomics_optional(&self) -> bool {
+        self.dispatcher == DispatchMethod::Default && !self.boxed_future && !self.fn_ptr
+    }
+
+    fn dispatch_method(&self) -> DispatchMethod {
+        //
+        // Determine which dispatcher to use.
+        //
+        // If the dispatcher is unspecified, decide on the following criteria:
+        // * If the std feature is not enabled and there is no custom detector, dispatch
+        //   statically, since we can't do CPU feature detection.
+        // * If the function has type generic parameters, is async, or has impl Trait, use direct
+        //   dispatch, since we can't take a function pointer.  Functions with only const generic
+        //   parameters use a table of function pointers for each instantiation.
+        // * If any retpoline features are enabled use direct dispatch, since retpolines hurt
+        //   performance of indirect dispatch significantly.
+        // * Otherwise, prefer indirect dispatch, since it appears to have better performance on
+        //   average.  On machines with worse branch prediction, it may be significantly better.
+        //
+        // The `MULTIVERSION_FORCE_STATIC` environment variable overrides every dispatcher, even if
+        // specified.
+        //
+        if cfg!(force_static) {
+            return DispatchMethod::Static;
+        }
+        // Function pointers can't be stored in statics nested in methods, since they can't use
+        // `Self`.
+        if self.is_method() && self.dispatcher == DispatchMethod::Default {
+            return if self.runtime_detection() {
+                DispatchMethod::Direct
+            } else {
+                DispatchMethod::Static
+            };
+        }
+        match self.dispatcher {
+            DispatchMethod::Default => {
+                if self.runtime_detection() {
+                    if (!crate::util::fn_params(&self.func.sig).is_empty() && !self.const_generic())
+                        || (self.func.sig.asyncness.is_some() && !self.boxed_future)
+                        || util::impl_trait_present(&self.func.sig)
+                        || cfg!(retpoline)
+                    {
+                        DispatchMethod::Direct
+                    } else {
+                        DispatchMethod::Indirect
+                    }
+                } else {
+                    DispatchMethod::Static
+                }
+            }
+            method => method,
+        }
+    }
+
+    // Creates the function that eagerly selects the target, and the detection function it
+    // shares with the dispatcher.
+    fn resolve_fns(&self) -> TokenStream {
+        let ident = &self.func.sig.ident;
+        let vis = &self.func.vis;
+        let cfgs = self
+            .func
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"))
+            .collect::<Vec<_>>();
+        let resolve_fn = Ident::new(&format!("{ident}_resolve"), ident.span());
+        let doc = format!(
+            "Selects the version of [`{ident}`] to call, so that the first call doesn't perform feature detection."
+        );
+        let detect = match self.dispatch_method() {
+            DispatchMethod::Static | DispatchMethod::Inline => None,
+            _ => Some(self.detect_fn_name()),
+        };
+        let detect_fn = detect.as_ref().map(|detect| {
+            let detect_fn = self.detect_index_fn(detect);
+            quote! {
+                #(#cfgs)*
+                #[doc(hidden)]
+                #[allow(non_snake_case)]
+                #detect_fn
+            }
+        });
+        let call_detect = detect.map(|detect| quote! { #detect(); });
+        let register = register_resolver(quote! { #resolve_fn });
+        quote! {
+            #detect_fn
+
+            #(#cfgs)*
+            #[doc = #doc]
+            #vis fn #resolve_fn() {
+                #call_detect
+            }
+
+            #(#cfgs)*
+            #register
+        }
+    }
+
+    fn fn_ptr_name(&self) -> Ident {
+        let ident = &self.func.sig.ident;
+        Ident::new(&format!("{ident}_fn"), ident.span())
+    }
+
+    // Creates the function that returns the selected version as a function pointer.
+    fn fn_ptr_getter(&self) -> Result<ItemFn> {
+        self.check_fn_pointer("`fn_ptr`")?;
+        if self.dispatch_method() != DispatchMethod::Indirect {
+            return Err(Error::new(
+                Span::call_site(),
+                "`fn_ptr` can only be used with the `default`, `indirect` or `static` dispatcher",
+            ));
+        }
+
+        // The returned pointer has the safety of the original function, since the features of
+        // the selected version have already been detected.
+        let fn_ty = util::fn_type_from_signature(&self.func.sig)?;
+        let unsafe_fn_ty = util::fn_type_from_signature(&Signature {
+            unsafety: parse_quote! { unsafe },
+            ..self.func.sig.clone()
+        })?;
+        let feature_detection = self.fn_pointer_selector(false, Some(&unsafe_fn_ty))?;
+        let default_fn = self.version_name(None);
+
+        let ident = &self.func.sig.ident;
+        let vis = &self.func.vis;
+        // The clones are nested in the getter, so lint expectations are moved to it along with
+        // `cfg`s, since the dispatcher can't fulfill them.
+        let cfgs = self
+            .func
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg") || attr.path().is_ident("expect"));
+        let doc = format!("Returns the version of [`{ident}`] selected for this CPU.");
+        let getter = self.fn_ptr_name();
+        let getter: ItemFn = parse_quote! {
+            #(#cfgs)*
+            #[doc = #doc]
+            #vis fn #getter() -> #fn_ty {}
+        };
+        self.with_feature_fns(
+            getter,
+            parse_quote! {
+                {
+                    #feature_detection
+                    use core::sync::atomic::{AtomicPtr, Ordering};
+                    static __SELECTED_FN: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+                    let mut __current_ptr = __SELECTED_FN.load(Ordering::Relaxed);
+                    if __current_ptr.is_null() {
+                        __current_ptr = __get_fn() as *mut ();
+                        __SELECTED_FN.store(__current_ptr, Ordering::Relaxed);
+                    }
+                    // Safety: the pointer is a fn pointer, and the features required by the
+                    // selected function were detected.
+                    #[allow(clippy::undocumented_unsafe_blocks)]
+                    unsafe {
+                        core::mem::transmute::<*mut (), #fn_ty>(__current_ptr)
+                    }
+                }
+            },
+            parse_quote! { #default_fn },
+        )
+    }
+
+    // Creates the function that returns the statically dispatched function as a function
+    // pointer, with `MULTIVERSION_FORCE_STATIC`.
+    fn static_fn_ptr_getter(&self) -> Result<ItemFn> {
+        self.check_fn_pointer("`fn_ptr`")?;
+        let fn_ty = util::fn_type_from_signature(&self.func.sig)?;
+        let ident = &self.func.sig.ident;
+        let vis = &self.func.vis;
+        let cfgs = self
+            .func
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"));
+        let doc = format!("Returns the version of [`{ident}`] selected for this CPU.");
+        let getter = self.fn_ptr_name();
+        Ok(parse_quote! {
+            #(#cfgs)*
+            #[doc = #doc]
+            #vis fn #getter() -> #fn_ty {
+                #ident
+            }
+        })
+    }
+
+    // Creates the dispatcher, which calls the function returned by the `fn_ptr` getter.
+    fn fn_ptr_dispatcher(&self) -> ItemFn {
+        let getter = self.fn_ptr_name();
+        let (normalized_signature, argument_names) = util::normalize_signature(&self.func.sig);
+        Self::with_stack_dispatch(ItemFn {
+            attrs: self
+                .func
+                .attrs
+                .iter()
+                .filter(|attr| !attr.path().is_ident("expect"))
+                .cloned()
+                .collect(),
+            vis: self.func.vis.clone(),
+            sig: normalized_signature,
+            block: Box::new(parse_quote! {
+                {
+                    unsafe { #getter()(#(#argument_names),*) }
+                }
+            }),
+        })
+    }
+
+    // Returns true if the versions are nested in the `pinned` function, and the dispatcher calls
+    // the callable it returns.
+    fn pinned_dispatches(&self) -> bool {
+        self.pinned
+            && !self.fn_ptr
+            && matches!(
+                self.dispatch_method(),
+                DispatchMethod::Direct | DispatchMethod::Indirect
+            )
+    }
+
+    // Creates the dispatcher, which calls the callable returned by the `pinned` function.
+    fn pinned_dispatcher(&self) -> ItemFn {
+        let ident = &self.func.sig.ident;
+        let pinned = Ident::new(&format!("{ident}_pinned"), ident.span());
+        let (normalized_signature, argument_names) = util::normalize_signature(&self.func.sig);
+        let call = quote! { #pinned()(#(#argument_names),*) };
+        let call = if util::returns_never(&self.func.sig) {
+            quote! { match #call {} }
+        } else {
+            call
+        };
+        Self::with_stack_dispatch(ItemFn {
+            attrs: self
+                .func
+                .attrs
+                .iter()
+                .filter(|attr| !attr.path().is_ident("expect"))
+                .cloned()
+                .collect(),
+            vis: self.func.vis.clone(),
+            sig: normalized_signature,
+            block: Box::new(parse_quote! { { #call } }),
+        })
+    }
+
+    // Creates the function that returns a callable with the version selected for this CPU.
+    //
+    // The callable captures the index of the selected version, so calling it only branches on
+    // the captured index, rather than loading the selection.  The versions are nested in this
+    // function, and the dispatcher calls the callable, so the versions are only compiled once.
+    fn pinned_fn(&self) -> Result<ItemFn> {
+        self.check_fn_pointer("`pinned`")?;
+        if self.func.sig.unsafety.is_some() {
+            return Err(Error::new(
+                Span::call_site(),
+                "`pinned` does not support unsafe functions, use `fn_ptr` instead",
+            ));
+        }
+        if self.force_target {
+            return Err(Error::new(
+                Span::call_site(),
+                "`pinned` can't be used with `force_target`",
+            ));
+        }
+
+        let fn_ty = util::fn_type_from_signature(&self.func.sig)?;
+        let lifetimes = &fn_ty.lifetimes;
+        let inputs = &fn_ty.inputs;
+        // The never type can't be the output of `Fn`, so the callable returns `Infallible`.
+        let output = if util::returns_never(&self.func.sig) {
+            quote! { -> core::convert::Infallible }
+        } else {
+            fn_ty.output.to_token_stream()
+        };
+        let (_, argument_names) = util::normalize_signature(&self.func.sig);
+
+        let ident = &self.func.sig.ident;
+        let vis = &self.func.vis;
+        // The clones are nested in the function, so lint levels are copied along with `cfg`s.
+        let cfgs = self.func.attrs.iter().filter(|attr| {
+            ["cfg", "allow", "warn", "deny", "forbid"]
+                .iter()
+                .any(|name| attr.path().is_ident(name))
+        });
+        let doc = format!("Returns a callable that calls the version of [`{ident}`] selected for this CPU, without dispatching again.");
+        let pinned = Ident::new(&format!("{ident}_pinned"), ident.span());
+        let pinned_fn: ItemFn = parse_quote! {
+            #(#cfgs)*
+            #[doc = #doc]
+            #vis fn #pinned() -> impl #lifetimes Fn(#inputs) #output + Copy + Send + Sync {}
+        };
+
+        // With `fn_ptr`, the callable captures the selected function pointer.
+        if self.fn_ptr {
+            let getter = self.fn_ptr_name();
+            return Ok(ItemFn {
+                block: Box::new(parse_quote! {
+                    {
+                        let __selected = #getter();
+                        move |#(#argument_names),*| __selected(#(#argument_names),*)
+                    }
+                }),
+                ..pinned_fn
+            });
+        }
+
+        // Other dispatchers don't load a selection on each call, so the callable calls the
+        // dispatcher.
+        if !self.pinned_dispatches() {
+            return Ok(ItemFn {
+                block: Box::new(parse_quote! {
+                    {
+                        move |#(#argument_names),*| #ident(#(#argument_names),*)
+                    }
+                }),
+                ..pinned_fn
+            });
+        }
+
+        let detect_index = self.detect_index();
+        let arms = self
+            .ordered_targets()
+            .into_iter()
+            .enumerate()
+            .map(|(index, target)| {
+                let index = index + 1; // 0 is default features
+                let target_arch = target.target_arch();
+                let call = self.call_target_fn(Some(target));
+                quote! {
+                    #target_arch
+                    #index => #call,
+                }
+            });
+        let call_default = self.call_target_fn(None);
+        let default_fn = self.version_name(None);
+        self.with_feature_fns(
+            pinned_fn,
+            parse_quote! {
+                {
+                    #detect_index
+                    let __selected = __detect_index();
+                    move |#(#argument_names),*| match __selected {
+                        #(#arms)*
+                        0 => #call_default,
+                        _ => unsafe { core::hint::unreachable_unchecked() },
+                    }
+                }
+            },
+            parse_quote! {
+                move |#(#argument_names),*| unsafe { #default_fn(#(#argument_names),*) }
+            },
+        )
+    }
+
+    // Creates the function that returns a C callback calling the dispatcher.
+    fn trampoline_fn(&self) -> Result<ItemFn> {
+        if !util::fn_params(&self.func.sig).is_empty()
+            || self.func.sig.asyncness.is_some()
+            || util::impl_trait_present(&self.func.sig)
+        {
+            return Err(Error::new(
+                Span::call_site(),
+                "`trampoline` does not support generic parameters, async functions, or impl trait",
+            ));
+        }
+        let ident = &self.func.sig.ident;
+        let vis = &self.func.vis;
+        let cfgs = self
+            .func
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"));
+        let doc = format!("Returns a C callback that calls [`{ident}`].");
+        let trampoline = Ident::new(&format!("{ident}_trampoline"), ident.span());
+
+        // The argument types may contain lifetime parameters, so the callback takes them as its
+        // own parameters.  The lifetimes are never constrained.
+        let lifetimes = self.func.sig.generics.lifetimes();
+        let arg_types = self
+            .func
+            .sig
+            .inputs
+            .iter()
+            .map(|arg| match arg {
+                FnArg::Receiver(rec) => Err(Error::new(
+                    rec.self_token.span,
+                    "`trampoline` does not support associated functions",
+                )),
+                FnArg::Typed(arg) => Ok(&arg.ty),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // The context of a function that never returns never receives an output.
+        let never = util::returns_never(&self.func.sig);
+        let output = match &self.func.sig.output {
+            ReturnType::Default => quote! { () },
+            ReturnType::Type(..) if never => quote! { core::convert::Infallible },
+            ReturnType::Type(_, ty) => quote! { #ty },
+        };
+        let allow_unreachable = self.allow_unreachable();
+        let (_, argument_names) = util::normalize_signature(&self.func.sig);
+        Ok(parse_quote! {
+            #(#cfgs)*
+            #[doc = #doc]
+            #vis fn #trampoline() -> multiversion::trampoline::Trampoline {
+                #allow_unreachable
+                unsafe extern "C" fn __trampoline<#(#lifetimes),*>(context: *mut core::ffi::c_void) {
+                    // Safety: the caller guarantees that the context has the argument and return
+                    // types of the function.
+                    #[allow(clippy::undocumented_unsafe_blocks)]
+                    let context = unsafe {
+                        &mut *(context as *mut multiversion::trampoline::Context<(#(#arg_types,)*), #output>)
+                    };
+                    if let Some((#(#argument_names,)*)) = context.__take_args() {
+                        #[allow(unused_unsafe)]
+                        let output = unsafe { #ident(#(#argument_names),*) };
+                        context.__set_output(output);
+                    }
+                }
+                multiversion::trampoline::Trampoline::new(__trampoline)
+            }
+        })
+    }
+
+    // Nests the target clones in `f`, and uses `block` as its body unless the best target is
+    // already known at compile time, in which case `elided` is used.
+    fn with_feature_fns(&self, f: ItemFn, block: Block, elided: Expr) -> Result<ItemFn> {
+        // If we already know that the current build target supports the best function choice, we
+        // can skip dispatching entirely.
+        //
+        // Here we check for one of two possibilities:
+        // * If the globally enabled features (the target-feature or target-cpu codegen options)
+        //   already support the highest priority function, skip dispatch entirely and call that
+        //   function.
+        // * If the current target isn't specified in the multiversioned list at all, we can skip
+        //   dispatch entirely and call the default function.
+        //
+        // In these cases, the default function is called instead.
+        let best_targets = self
+            .targets
+            .iter()
+            .rev()
+            .map(|t| (t.arch(), t))
+            .collect::<HashMap<_, _>>();
+        let mut skips = Vec::new();
+        for target in best_targets.values() {
+            // The CPU isn't known at compile time, so tuned targets and 256-bit variants always
+            // dispatch.  With a priority model, a lower priority target may be preferred on the
+            // detected CPU.
+            if target.is_tuned()
+                || target.is_narrow()
+                || !self.elision
+                || self.priority_model.is_some()
+            {
+                continue;
+            }
+            let predicate = target.cfg_predicate();
+            let feature = target.features();
+            skips.push(quote! {
+                all(#predicate, #(target_feature = #feature),*)
+            });
+        }
+        let specified_arches = best_targets.keys().collect::<Vec<_>>();
+        let allow_unreachable = self.allow_unreachable();
+        let feature_fns = match &self.variants {
+            // The clones are in the variants module, so import them instead.
+            Some(_) => self.variant_imports()?,
+            // The clones of methods are associated functions.
+            None if self.is_method() => Vec::new(),
+            None => self
+                .feature_fns()?
+                .into_iter()
+                .map(ToTokens::into_token_stream)
+                .collect::<Vec<_>>(),
+        };
+        Ok(ItemFn {
+            block: Box::new(parse_quote! {
+                {
+                    #(#feature_fns)*
+
+                    #[cfg(any(
+                        not(any(#(target_arch = #specified_arches),*)),
+                        #(#skips),*
+                    ))]
+                    #allow_unreachable
+                    { return #elided }
+
+                    #[cfg(not(any(
+                        not(any(#(target_arch = #specified_arches),*)),
+                        #(#skips),*
+                    )))]
+                    #allow_unreachable
+                    #block
+                }
+            }),
+            ..f
+        })
+    }
+
+    // Returns `true` if the function has a receiver or uses `Self`.  The versions of methods are
+    // associated functions of the same impl, rather than nested in the dispatcher.
+    fn is_method(&self) -> bool {
+        util::uses_self(&self.func)
+    }
+
+    // Returns `true` if the function is a `const fn`, which can only be dispatched at compile time.
+    fn is_const(&self) -> bool {
+        self.func.sig.constness.is_some()
+    }
+
+    // Checks that the options of a `const fn` don't require runtime dispatch.
+    fn check_const(&self) -> Result<()> {
+        if !self.is_const() {
+            return Ok(());
+        }
+        let options = [
+            (self.fn_ptr, "fn_ptr"),
+            (self.pinned, "pinned"),
+            (self.resolve, "resolve"),
+            (self.trampoline, "trampoline"),
+            (self.force_target, "force_target"),
+            (self.variants.is_some(), "variants"),
+            (self.min_stack.is_some(), "min_stack"),
+        ];
+        if let Some((_, option)) = options.iter().find(|(enabled, _)| *enabled) {
+            return Err(Error::new(
+                Span::call_site(),
+                format!("`{option}` can't be used with const functions"),
+            ));
+        }
+        if self.dispatch_method() != DispatchMethod::Static {
+            return Err(Error::new(
+                Span::call_site(),
+                "const functions can only use the `static` dispatcher",
+            ));
+        }
+        Ok(())
+    }
+
+    // Checks that the options of a method don't require items outside of the impl.
+    fn check_method(&self) -> Result<()> {
+        if !self.is_method() {
+            return Ok(());
+        }
+        let options = [
+            (self.fn_ptr, "fn_ptr"),
+            (self.pinned, "pinned"),
+            (self.resolve, "resolve"),
+            (self.trampoline, "trampoline"),
+            (self.boxed_future, "boxed_future"),
+            (self.force_target, "force_target"),
+            (self.variants.is_some(), "variants"),
+        ];
+        if let Some((_, option)) = options.iter().find(|(enabled, _)| *enabled) {
+            return Err(Error::new(
+                Span::call_site(),
+                format!("`{option}` can't be used with methods"),
+            ));
+        }
+        if !matches!(
+            self.dispatch_method(),
+            DispatchMethod::Static | DispatchMethod::Direct | DispatchMethod::Inline
+        ) {
+            return Err(Error::new(
+                Span::call_site(),
+                "methods can only use the `static`, `direct`, or `inline` dispatcher, unless \
+                 `self_type` is specified for functions without a `self` receiver",
+            ));
+        }
+        Ok(())
+    }
+
+    fn forced_fn_name(&self) -> Ident {
+        Ident::new(
+            &format!("__multiversion_{}_forced", self.func.sig.ident),
+            self.func.sig.ident.span(),
+        )
+    }
+
+    // Creates the function that forces a target, and the state it shares with the dispatcher.
+    //
+    // The state is 0 when no target is forced, 1 for the default version, and otherwise the
+    // position of the target plus 2.
+    fn force_target_fns(&self) -> Result<TokenStream> {
+        if !self.runtime_detection() {
+            return Err(Error::new(
+                Span::call_site(),
+                "`force_target` is only available with the `std` cargo feature or a `detector`",
+            ));
+        }
+        if self.fn_ptr {
+            return Err(Error::new(
+                Span::call_site(),
+                "`force_target` can't be used with `fn_ptr`",
+            ));
+        }
+        let ident = &self.func.sig.ident;
+        let vis = &self.func.vis;
+        let cfgs = self
+            .func
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"))
+            .collect::<Vec<_>>();
+        let forced_fn = self.forced_fn_name();
+        let force_fn = Ident::new(&format!("{ident}_force_target"), ident.span());
+        let doc = format!(
+            "Forces [`{ident}`] to call the version for `target`, or restores runtime selection if `None`."
+        );
+        let find_target = self
+            .ordered_targets()
+            .into_iter()
+            .enumerate()
+            .map(|(index, target)| {
+                let index = index + 2;
+                let target_arch = target.target_arch();
+                let arch = target.arch();
+                let features = target.features();
+                let tune = target.tune().unwrap_or("");
+                let features_detected = target.features_detected_with(self.detector.as_ref(), self.all_cores);
+                quote! {
+                    #target_arch
+                    {
+                        if __forced == 0 && multiversion::target::__is_target(__target, #arch, &[#(#features),*], #tune) {
+                            if !(#features_detected) {
+                                return false;
+                            }
+                            __forced = #index;
+                        }
+                    }
+                }
+            });
+        Ok(quote! {
+            #(#cfgs)*
+            #[doc(hidden)]
+            #[allow(non_snake_case)]
+            fn #forced_fn() -> &'static core::sync::atomic::AtomicUsize {
+                static FORCED: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+                &FORCED
+            }
+
+            #(#cfgs)*
+            #[doc = #doc]
+            ///
+            /// Targets are specified as in `targets`, or as `"default"` for the default version.
+            /// Returns `false`, without changing the selected version, if `target` isn't one of
+            /// the targets or its features aren't detected.
+            #vis fn #force_fn(target: Option<&str>) -> bool {
+                let mut __forced = 0;
+                if let Some(__target) = target {
+                    if __target == "default" {
+                        __forced = 1;
+                    }
+                    #(#find_target)*
+                    if __forced == 0 {
+                        return false;
+                    }
+                }
+                #forced_fn().store(__forced, core::sync::atomic::Ordering::Relaxed);
+                true
+            }
+        })
+    }
+
+    // Calls the forced target, if any.
+    fn call_forced(&self) -> TokenStream {
+        if !self.force_target {
+            return TokenStream::new();
+        }
+        let forced_fn = self.forced_fn_name();
+        let arms = self
+            .ordered_targets()
+            .into_iter()
+            .enumerate()
+            .map(|(index, target)| {
+                let index = index + 2;
+                let target_arch = target.target_arch();
+                let call = self.call_target_fn(Some(target));
+                quote! {
+                    #target_arch
+                    #index => return #call,
+                }
+            });
+        let call_default = self.call_target_fn(None);
+        quote! {
+            match #forced_fn().load(core::sync::atomic::Ordering::Relaxed) {
+                1 => return #call_default,
+                #(#arms)*
+                _ => {}
+            }
+        }
+    }
+
+    // Creates a dispatcher that returns a coroutine, which drives the coroutine returned by the
+    // selected version.  The versions return different coroutine types, so they can't be
+    // returned directly.  The inner coroutine is pinned within the outer coroutine, so the outer
+    // coroutine is immovable.
+    fn coroutine_dispatcher_fn(&self) -> Result<Block> {
+        let method = self.dispatch_method();
+        if !matches!(
+            method,
+            DispatchMethod::Static | DispatchMethod::Direct | DispatchMethod::Inline
+        ) || self.fn_ptr
+            || self.trampoline
+        {
+            return Err(Error::new(
+                Span::call_site(),
+                "functions returning coroutines can only use the `static`, `direct`, or `inline` dispatcher",
+            ));
+        }
+        if method != DispatchMethod::Static && !self.runtime_detection() {
+            return Err(Error::new(
+                Span::call_site(),
+                "runtime function dispatch only available with the `std` cargo feature or a `detector`",
+            ));
+        }
+
+        let drive = |call: Expr| {
+            quote! {
+                let mut __inner = core::pin::pin!(#call);
+                loop {
+                    match core::ops::Coroutine::resume(__inner.as_mut(), __resume) {
+                        core::ops::CoroutineState::Yielded(__yielded) => __resume = yield __yielded,
+                        core::ops::CoroutineState::Complete(__output) => return __output,
+                    }
+                }
+            }
+        };
+        let detect_index = if method == DispatchMethod::Direct {
+            self.detect_index_fn(&Ident::new("__detect_index", Span::call_site()))
+        } else {
+            TokenStream::new()
+        };
+        let select = (method == DispatchMethod::Direct)
+            .then(|| quote! { let __selected = __detect_index(); });
+        let arms = self
+            .ordered_targets()
+            .into_iter()
+            .enumerate()
+            .map(|(index, target)| {
+                let index = index + 1; // 0 is default features
+                let target_arch = target.target_arch();
+                let condition = match method {
+                    DispatchMethod::Static => target.features_enabled(),
+                    DispatchMethod::Direct => quote! { __selected == #index },
+                    _ => self.target_detected(target),
+                };
+                let drive = drive(self.call_target_fn(Some(target)));
+                quote! {
+                    #target_arch
+                    {
+                        if #condition {
+                            #drive
+                        }
+                    }
+                }
+            });
+        let drive_default = drive(self.call_target_fn(None));
+        Ok(parse_quote! {
+            {
+                #detect_index
+                #[coroutine]
+                static move |mut __resume| {
+                    #select
+                    #(#arms)*
+                    #drive_default
+                }
+            }
+        })
+    }
+
+    fn create_fn(&self) -> Result<ItemFn> {
+        self.check_method()?;
+        self.check_const()?;
+        if cfg!(feature = "nightly") && util::returns_coroutine(&self.func.sig) {
+            let (normalized_signature, _) = util::normalize_signature(&self.func.sig);
+            return self.with_feature_fns(
+                ItemFn {
+                    attrs: self.func.attrs.clone(),
+                    vis: self.func.vis.clone(),
+                    sig: normalized_signature,
+                    block: Box::new(parse_quote! { {} }),
+                },
+                self.coroutine_dispatcher_fn()?,
+                self.call_target_fn(None),
+            );
+        }
+        let block = match self.dispatch_method() {
+            DispatchMethod::Default => unreachable!(),
+            DispatchMethod::Static => self.static_dispatcher_fn(),
+            DispatchMethod::Direct => self.direct_dispatcher_fn()?,
+            DispatchMethod::Indirect if self.pointer_atomics_optional() => {
+                let indirect = self.indirect_dispatcher_fn()?;
+                let direct = self.direct_dispatcher_fn()?;
+                parse_quote! {
+                    {
+                        #[cfg(target_has_atomic = "ptr")]
+                        {
+                            return #indirect;
+                        }
+                        #[cfg(not(target_has_atomic = "ptr"))]
+                        #direct
+                    }
+                }
+            }
+            DispatchMethod::Indirect => self.indirect_dispatcher_fn()?,
+            DispatchMethod::Once => self.once_dispatcher_fn()?,
+            DispatchMethod::Tls => self.tls_dispatcher_fn()?,
+            DispatchMethod::Ctor => self.ctor_dispatcher_fn()?,
+            DispatchMethod::Ifunc => self.ifunc_dispatcher_fn()?,
+            DispatchMethod::Inline => self.inline_dispatcher_fn()?,
+        };
+        let (signature, uses) = util::dispatcher_signature(&self.func.sig);
+        let call_default = self.call_target_fn(None);
+        let call_forced = self.force_target.then(|| self.call_forced());
+        let summary = self.readable_summary();
+        let (block, elided) = (
+            parse_quote! { { #summary #uses #call_forced #block } },
+            parse_quote! { { #summary #uses #call_forced #call_default } },
+        );
+        self.with_feature_fns(
+            ItemFn {
+                attrs: self.func.attrs.clone(),
+                vis: self.func.vis.clone(),
+                sig: signature,
+                block: Box::new(parse_quote! { {} }),
+            },
+            block,
+            elided,
+        )
+        .map(Self::with_stack_dispatch)
+    }
+}
+
+impl Dispatcher {
+    // Describes the dispatcher, if `readable` is set.  The description is attached to an unnamed
+    // constant, since statements can't have doc comments.
+    fn readable_summary(&self) -> Option<TokenStream> {
+        if !self.readable {
+            return None;
+        }
+        let ident = &self.func.sig.ident;
+        let method = format!("{:?}", self.dispatch_method()).to_lowercase();
+        let mut lines = vec![
+            format!("Selects a version of `{ident}` with the `{method}` dispatcher."),
+            String::new(),
+            "Versions, from highest to lowest priority:".to_string(),
+        ];
+        for (index, target) in self.ordered_targets().into_iter().enumerate() {
+            lines.push(format!(
+                "{}. `{}`, calls `{}`",
+                index + 1,
+                target_spec(target),
+                self.version_name(Some(target))
+            ));
+        }
+        lines.push(format!("Otherwise, calls `{}`.", self.version_name(None)));
+        if self.elision {
+            lines.push(String::new());
+            lines.push(
+                "Dispatch is skipped if the highest priority version of the architecture is \
+                 supported by the features enabled at compile time."
+                    .to_string(),
+            );
+        }
+        let docs = readable_docs(self.readable, lines.iter().map(String::as_str));
+        Some(quote! {
+            #(#docs)*
+            const _: () = ();
+        })
+    }
+
+    // Imports the target clones from the variants module.  Some clones aren't called on every
+    // architecture, such as the default version on architectures with a baseline.
+    fn variant_imports(&self) -> Result<Vec<TokenStream>> {
+        let module = match &self.variants {
+            Some(variants) => &variants.module,
+            None => return Ok(Vec::new()),
+        };
+        Ok(self
+            .feature_fns()?
+            .into_iter()
+            .map(|f| {
+                let cfgs = f.attrs.iter().filter(|attr| attr.path().is_ident("cfg"));
+                let ident = &f.sig.ident;
+                quote! { #(#cfgs)* #[allow(unused_imports)] use #module::#ident; }
+            })
+            .collect())
+    }
+
+    // Creates the method of the group's `Selection` that calls the version selected for the
+    // group, without loading the selection again.
+    fn selection_method(&self) -> Result<TokenStream> {
+        let (mut signature, _) = util::normalize_signature(&self.func.sig);
+        signature.inputs.insert(0, parse_quote! { self });
+        let imports = self.variant_imports()?;
+        let arms = self
+            .ordered_targets()
+            .into_iter()
+            .enumerate()
+            .map(|(index, target)| {
+                let index = index + 1; // 0 is default features
+                let target_arch = target.target_arch();
+                let call = self.call_target_fn(Some(target));
+                quote! {
+                    #target_arch
+                    #index => #call,
+                }
+            });
+        let call_default = self.call_target_fn(None);
+        let attrs = self.func.attrs.iter().filter(|attr| {
+            [
+                "cfg",
+                "doc",
+                "allow",
+                "warn",
+                "deny",
+                "forbid",
+                "must_use",
+                "deprecated",
+            ]
+            .iter()
+            .any(|name| attr.path().is_ident(name))
+        });
+        let vis = &self.func.vis;
+        Ok(quote! {
+            impl Selection {
+                #(#attrs)*
+                #vis #signature {
+                    #(#imports)*
+                    match self.0 {
+                        #(#arms)*
+                        0 => #call_default,
+                        _ => core::unreachable!(),
+                    }
+                }
+            }
+        })
+    }
+
+    // Creates the module containing the target clones, if `variants` is specified.
+    fn variants_module(&self) -> Result<TokenStream> {
+        let variants = match &self.variants {
+            Some(variants) => variants,
+            None => return Ok(TokenStream::new()),
+        };
+        let module = &variants.module;
+        let vis = &variants.vis;
+        let fn_vis = util::nested_visibility(vis);
+        let fns = self.feature_fns()?.into_iter().map(|f| ItemFn {
+            vis: fn_vis.clone(),
+            ..f
+        });
+        // The clones aren't nested in the function, so lint levels are copied along with `cfg`s.
+        let attrs = self.func.attrs.iter().filter(|attr| {
+            ["cfg", "allow", "warn", "deny", "forbid"]
+                .iter()
+                .any(|name| attr.path().is_ident(name))
+        });
+        let doc = format!(
+            "The versions of [`{}`] for each target.",
+            self.func.sig.ident
+        );
+        Ok(quote! {
+            #(#attrs)*
+            #[doc = #doc]
+            #vis mod #module {
+                #[allow(unused_imports)]
+                use super::*;
+
+                #(#fns)*
+            }
+        })
+    }
+}
+
+impl ToTokens for Dispatcher {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        // The selector only detects the group's target.
+        if self.group == Some(GroupRole::Selector) {
+            if !self.runtime_detection() {
+                tokens.extend(
+                    Error::new(
+                        Span::call_site(),
+                        "dispatch groups are only available with the `std` cargo feature or a `detector`",
+                    )
+                    .to_compile_error(),
+                );
+                return;
+            }
+            let selector = Ident::new(GROUP_SELECTOR, Span::call_site());
+            tokens.extend(self.detect_index_fn(&selector));
+            tokens.extend(register_resolver(quote! { || { #selector(); } }));
+            return;
+        }
+        if self.group == Some(GroupRole::Member) {
+            tokens.extend(
+                self.selection_method()
+                    .unwrap_or_else(|err| err.to_compile_error()),
+            );
+        }
+        // With `fn_ptr`, the clones are nested in the getter, and the dispatcher calls it.  With
+        // static dispatch, the getter returns the dispatcher.
+        let fns = if self.fn_ptr && self.dispatch_method() == DispatchMethod::Static {
+            self.create_fn().and_then(|dispatcher| {
+                let getter = self.static_fn_ptr_getter()?;
+                Ok(quote! { #dispatcher #getter })
+            })
+        } else if self.fn_ptr {
+            self.fn_ptr_getter().map(|getter| {
+                let dispatcher = self.fn_ptr_dispatcher();
+                quote! { #getter #dispatcher }
+            })
+        } else if self.pinned_dispatches() {
+            // With `pinned`, the clones are nested in the pinned function, and the dispatcher
+            // calls the callable it returns.
+            self.check_method()
+                .and_then(|_| self.check_const())
+                .and_then(|_| self.pinned_fn())
+                .map(|pinned| {
+                    let dispatcher = self.pinned_dispatcher();
+                    quote! { #pinned #dispatcher }
+                })
+        } else {
+            self.create_fn().map(ToTokens::into_token_stream)
+        };
+        // The versions of methods are emitted next to the dispatcher, in the same impl.
+        let fns = fns.and_then(|fns| {
+            if !self.is_method() {
+                return Ok(fns);
+            }
+            let versions = self.feature_fns()?;
+            Ok(quote! { #fns #(#versions)* })
+        });
+        tokens.extend(fns.unwrap_or_else(|err| err.to_compile_error()));
+        tokens.extend(
+            self.variants_module()
+                .unwrap_or_else(|err| err.to_compile_error()),
+        );
+        if self.trampoline {
+            tokens.extend(match self.trampoline_fn() {
+                Ok(val) => val.into_token_stream(),
+                Err(err) => err.to_compile_error(),
+            });
+        }
+        if self.resolve {
+            tokens.extend(self.resolve_fns());
+        }
+        if self.pinned && !self.pinned_dispatches() {
+            tokens.extend(match self.pinned_fn() {
+                Ok(val) => val.into_token_stream(),
+                Err(err) => err.to_compile_error(),
+            });
+        }
+        if self.force_target {
+            tokens.extend(
+                self.force_target_fns()
+                    .unwrap_or_else(|err| err.to_compile_error()),
+            );
+        }
+    }
+}
+
+// Registers a resolver with `multiversion::resolve_all`, if the `registry` feature is enabled.
+// Dispatchers register their resolver in their body, next to the selection it stores.
+fn register_resolver(resolver: TokenStream) -> TokenStream {
+    if !cfg!(feature = "registry") {
+        return TokenStream::new();
+    }
+    quote! {
+        const _: () = {
+            #[multiversion::__linkme::distributed_slice(multiversion::__RESOLVERS)]
+            #[linkme(crate = multiversion::__linkme)]
+            static __RESOLVER: fn() = #resolver;
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use syn::{Item, LitStr, Stmt};
+
+    fn target(s: &str) -> Target {
+        Target::parse(&LitStr::new(s, Span::call_site())).unwrap()
+    }
+
+    fn targets(targets: &[&str]) -> Vec<Target> {
+        targets.iter().copied().map(target).collect()
+    }
+
+    fn add_fn() -> ItemFn {
+        parse_quote! {
+            fn add(x: u32, y: u32) -> u32 {
+                x + y
+            }
+        }
+    }
+
+    // Each architecture has one table of targets, rather than a condition for each target.
+    #[test]
+    fn target_tables() {
+        let targets = targets(&[
+            "x86_64+avx512f",
+            "x86_64+avx2+fma",
+            "x86_64+sse4.2",
+            "aarch64+sve",
+            "aarch64+neon",
+        ]);
+        for method in [DispatchMethod::Direct, DispatchMethod::Indirect] {
+            let mut dispatcher = Dispatcher::new(add_fn(), targets.clone());
+            dispatcher.dispatcher = method;
+            dispatcher.detector = Some(parse_quote! { detect });
+            let tokens = dispatcher.to_token_stream().to_string();
+            assert_eq!(tokens.matches("const __TABLE").count(), 3);
+            assert_eq!(tokens.matches("= detect ()").count(), 1);
+        }
+    }
+
+    // The `direct` dispatcher calls the selected version from a table of function pointers, unless
+    // the function is generic.
+    #[test]
+    fn direct_table() {
+        let targets = targets(&["x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"]);
+        for (func, table) in [
+            (add_fn(), true),
+            (parse_quote! { fn add<T: Copy>(x: T) -> T { x } }, false),
+        ] {
+            let mut dispatcher = Dispatcher::new(func, targets.clone());
+            dispatcher.dispatcher = DispatchMethod::Direct;
+            dispatcher.detector = Some(parse_quote! { detect });
+            let tokens = dispatcher.to_token_stream().to_string();
+            assert_eq!(tokens.contains("const __VERSIONS"), table);
+            assert_eq!(tokens.contains("match __selected"), !table);
+        }
+    }
+
+    // The default version calls the lowest priority target of each architecture, if its features
+    // are enabled.
+    #[test]
+    fn folded_default() {
+        let targets = targets(&["x86_64+avx2", "x86_64+sse2", "aarch64+neon", "x86+sse2"]);
+        let mut dispatcher = Dispatcher::new(add_fn(), targets);
+        dispatcher.baselines =
+            vec![Target::parse_untuned(&LitStr::new("x86+sse", Span::call_site())).unwrap()];
+        let folded = dispatcher
+            .folded_targets()
+            .iter()
+            .map(|target| target.to_token_stream().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(folded, ["\"x86_64+sse+sse2\"", "\"aarch64+neon\""]);
+        let tokens = dispatcher.to_token_stream().to_string();
+        assert!(tokens.contains(
+            "cfg (all (target_arch = \"x86_64\" , target_feature = \"sse\" , target_feature = \"sse2\"))"
+        ));
+    }
+
+    // The `direct` dispatcher is used on targets without pointer atomics.
+    #[test]
+    fn direct_without_pointer_atomics() {
+        let mut dispatcher = Dispatcher::new(add_fn(), targets(&["x86_64+avx2", "aarch64+neon"]));
+        dispatcher.dispatcher = DispatchMethod::Direct;
+        let tokens = dispatcher.to_token_stream().to_string();
+        assert!(!tokens.contains("AtomicPtr"), "found `AtomicPtr`");
+        assert!(!tokens.contains("AtomicUsize"), "found `AtomicUsize`");
+    }
+
+    // A pinned function shares its versions with the dispatcher.
+    #[test]
+    fn pinned_versions_once() {
+        for method in [DispatchMethod::Direct, DispatchMethod::Indirect] {
+            let mut dispatcher =
+                Dispatcher::new(add_fn(), targets(&["x86_64+avx2", "aarch64+neon"]));
+            dispatcher.dispatcher = method;
+            dispatcher.detector = Some(parse_quote! { detect });
+            dispatcher.pinned = true;
+            let tokens = dispatcher.to_token_stream().to_string();
+            assert_eq!(tokens.matches("fn add_neon_version").count(), 1);
+            assert_eq!(tokens.matches("= detect ()").count(), 1);
+        }
+    }
+
+    // The 256-bit variant of an AVX-512 target calls the version of a target with the same
+    // features, rather than compiling it again.
+    #[test]
+    fn narrow_versions_once() {
+        let wide = target("x86_64+avx512f");
+        let narrow = wide.narrow_avx512().unwrap();
+        let same = target(&format!("x86_64+{}", narrow.features().join("+")));
+        for method in [DispatchMethod::Direct, DispatchMethod::Indirect] {
+            let mut dispatcher =
+                Dispatcher::new(add_fn(), vec![narrow.clone(), wide.clone(), same.clone()]);
+            dispatcher.dispatcher = method;
+            dispatcher.detector = Some(parse_quote! { detect });
+            let tokens = dispatcher.to_token_stream().to_string();
+            assert!(!tokens.contains("_256bit_version"), "found 256-bit version");
+            let version = format!("fn add_{}_version", same.features_string());
+            assert_eq!(tokens.matches(&version).count(), 1);
+        }
+    }
+
+    // The dispatch of a function that never returns allows unreachable code, but the body of the
+    // function doesn't.
+    #[test]
+    fn never_lints() {
+        let func: ItemFn = parse_quote! {
+            fn abort() -> ! {
+                loop {}
+            }
+        };
+        let mut dispatcher = Dispatcher::new(func, targets(&["x86_64+avx2", "aarch64+neon"]));
+        dispatcher.detector = Some(parse_quote! { detect });
+        let f = dispatcher.create_fn().unwrap();
+        assert!(f.attrs.is_empty());
+        for stmt in &f.block.stmts {
+            if let Stmt::Item(Item::Fn(clone)) = stmt {
+                assert!(
+                    !clone.attrs.iter().any(|attr| attr.path().is_ident("allow")),
+                    "`{}` allows lints",
+                    clone.sig.ident
+                );
+            }
+        }
+        assert!(f
+            .to_token_stream()
+            .to_string()
+            .contains("allow (unreachable_code"));
+    }
+
+    // Every helper is only generated if the body invokes a macro that may expand to helpers.
+    #[test]
+    fn helper_macros() {
+        let expand = |func: ItemFn| {
+            let mut dispatcher = Dispatcher::new(func, targets(&["x86_64+avx2"]));
+            dispatcher.dispatcher = DispatchMethod::Static;
+            dispatcher.to_token_stream().to_string()
+        };
+        let tokens = expand(parse_quote! {
+            fn path(x: bool) -> bool {
+                log::debug!("{}", x);
+                if !(x) {
+                    return !(x);
+                }
+                env!("PATH").is_empty()
+            }
+        });
+        assert!(!tokens.contains("macro_rules"), "{tokens}");
+        let tokens = expand(parse_quote! {
+            fn kernel() {
+                kernel!()
+            }
+        });
+        assert!(tokens.contains("macro_rules ! target_cfg"), "{tokens}");
+    }
+
+    // The indirect function symbol is the same in every expansion, but differs between functions
+    // with the same name, and the resolver doesn't use the feature detection in `std`.
+    #[cfg(feature = "std")]
+    #[test]
+    fn ifunc_symbol() {
+        let expand = |func: ItemFn| {
+            let mut dispatcher = Dispatcher::new(func, targets(&["x86_64+avx2", "aarch64+neon"]));
+            dispatcher.dispatcher = DispatchMethod::Ifunc;
+            dispatcher.to_token_stream().to_string()
+        };
+        let symbol = |tokens: &str| {
+            let prefix = "__multiversion_ifunc_add_";
+            let start = tokens.find(prefix).unwrap() + prefix.len();
+            let hash = &tokens[start..start + 16];
+            assert!(hash.chars().all(|c| c.is_ascii_hexdigit()), "{hash}");
+            hash.to_string()
+        };
+        let tokens = expand(add_fn());
+        assert_eq!(symbol(&tokens), symbol(&expand(add_fn())));
+        assert_ne!(
+            symbol(&tokens),
+            symbol(&expand(parse_quote! {
+                fn add(x: u32, y: u32) -> u32 {
+                    x.wrapping_add(y)
+                }
+            }))
+        );
+        assert!(tokens.contains("__is_ifunc_feature_detected"));
+    }
+
+    // With `readable`, the generated code is described by doc comments.
+    #[test]
+    fn readable() {
+        for readable in [false, true] {
+            let mut dispatcher =
+                Dispatcher::new(add_fn(), targets(&["x86_64+avx2", "aarch64+neon"]));
+            dispatcher.readable = readable;
+            let tokens = dispatcher.to_token_stream().to_string();
+            assert_eq!(
+                tokens.contains("The version of `add` for `aarch64+neon`."),
+                readable
+            );
+            assert_eq!(
+                tokens.contains("2. `aarch64+neon`, calls `add_neon_version`"),
+                readable
+            );
+        }
+    }
+}
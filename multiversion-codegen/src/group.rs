@@ -0,0 +1,107 @@
+use crate::default_targets::is_path;
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::{parse_quote, Error, Item, ItemMod, Result, Visibility};
+
+pub(crate) fn make_group(attr: TokenStream, mut module: ItemMod) -> Result<TokenStream> {
+    let items = match module.content.as_mut() {
+        Some((_, items)) => items,
+        None => {
+            return Err(Error::new_spanned(
+                &module,
+                "dispatch groups can only be applied to inline modules",
+            ))
+        }
+    };
+
+    // Only the functions that aren't private are in the group, so private helpers are left alone.
+    for item in items.iter_mut() {
+        if let Item::Fn(f) = item {
+            if let Visibility::Inherited = f.vis {
+                continue;
+            }
+            if let Some(attr) = f
+                .attrs
+                .iter()
+                .find(|attr| is_path(attr.path(), "multiversion"))
+            {
+                return Err(Error::new_spanned(
+                    attr,
+                    "functions in a dispatch group are already multiversioned",
+                ));
+            }
+            f.attrs
+                .push(parse_quote! { #[multiversion::multiversion(#attr, __group(member))] });
+        }
+    }
+
+    items.push(parse_quote! {
+        #[multiversion::multiversion(#attr, __group(selector))]
+        fn __multiversion_group_select() {}
+    });
+    items.push(parse_quote! {
+        /// The target selected for the functions in this group.
+        ///
+        /// Each function in the group is also a method of `Selection`, which calls the version
+        /// of the function for the selected target.
+        #[derive(Copy, Clone, Debug)]
+        pub struct Selection(usize);
+    });
+    items.push(parse_quote! {
+        impl Selection {
+            /// Returns the target selected for the group, detecting CPU features the first time
+            /// it's called.
+            pub fn get() -> Self {
+                Self(__multiversion_group_select())
+            }
+        }
+    });
+    Ok(module.into_token_stream())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quote::quote;
+
+    #[test]
+    fn private_helpers() {
+        let module: ItemMod = parse_quote! {
+            mod kernels {
+                fn helper() {}
+                pub fn kernel() {}
+            }
+        };
+        let tokens = make_group(quote! { targets("x86_64+avx2") }, module)
+            .unwrap()
+            .to_string();
+        assert!(tokens.contains("fn helper () { }"));
+        assert!(!tokens.contains("] fn helper"));
+        assert!(tokens.contains("] pub fn kernel"));
+    }
+
+    // The selector only reports the missing detection, rather than errors in the selector.
+    #[test]
+    fn selector() {
+        let select = |role| {
+            crate::multiversion::make_multiversioned_fn(
+                quote! { targets("x86_64+avx2"), __group(#role) },
+                parse_quote! { fn __multiversion_group_select() {} },
+            )
+            .map(|tokens| tokens.to_string())
+        };
+        let tokens = select(quote! { selector }).unwrap();
+        assert_eq!(
+            tokens.contains("compile_error"),
+            !cfg!(feature = "std"),
+            "{tokens}"
+        );
+        if !cfg!(feature = "std") {
+            assert!(
+                !tokens.contains("fn __multiversion_group_select"),
+                "{tokens}"
+            );
+        }
+        assert!(select(quote! { other }).is_err());
+    }
+}
@@ -0,0 +1,603 @@
+use crate::default_targets::is_path;
+use crate::util;
+use proc_macro2::{Group, Span, TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use std::collections::{hash_map::DefaultHasher, HashSet};
+use std::hash::{Hash, Hasher};
+use syn::{
+    parse_quote, punctuated::Punctuated, token, visit_mut::VisitMut, Attribute, Block, Error, Expr,
+    FnArg, GenericArgument, GenericParam, Generics, Ident, ImplItem, Item, ItemFn, ItemImpl,
+    ItemTrait, Pat, PatIdent, PatType, PathArguments, Result, ReturnType, Signature, Stmt,
+    TraitItem, Type, TypeParamBound, WhereClause,
+};
+
+const SELF_ARG: &str = "__multiversion_self";
+// The prefix of the placeholders of items nested in a function body.
+const NESTED_ITEM: &str = "__multiversion_item";
+// The generic parameter of the implementing type, in the free functions of a trait.
+const SELF_TY: &str = "__MultiversionSelf";
+
+// Replaces `self` and `Self` in tokens with the receiver argument and the implementing type.
+struct SelfReplacer {
+    // `Self` in types and expressions, such as `Self { x }` and `Self::new()`.
+    ty: TokenStream,
+    // `Self` followed by `::`, if different from `ty`.
+    qualified: TokenStream,
+    // In trait impls, `Self` followed by `::` and the name of an item of the impl, such as
+    // `Self::Item`, which must be qualified by the trait.
+    trait_qualified: Option<(TokenStream, HashSet<String>)>,
+}
+
+impl SelfReplacer {
+    fn new(imp: &ItemImpl) -> Self {
+        let self_ty = &imp.self_ty;
+        let trait_qualified = imp.trait_.as_ref().map(|(_, path, _)| {
+            let items = imp
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    ImplItem::Fn(item) => Some(item.sig.ident.to_string()),
+                    ImplItem::Const(item) => Some(item.ident.to_string()),
+                    ImplItem::Type(item) => Some(item.ident.to_string()),
+                    _ => None,
+                })
+                .collect();
+            (quote! { <#self_ty as #path> }, items)
+        });
+        Self {
+            trait_qualified,
+            ..Self::for_type(self_ty)
+        }
+    }
+
+    // Replaces `Self` with a type, outside of a trait impl.
+    fn for_type(self_ty: &Type) -> Self {
+        let (ty, qualified) = match self_ty {
+            // Paths with generic arguments are written with a turbofish, which is valid in both
+            // types and expressions.
+            Type::Path(path) if path.qself.is_none() => {
+                let mut path = path.path.clone();
+                for segment in path.segments.iter_mut() {
+                    if let syn::PathArguments::AngleBracketed(args) = &mut segment.arguments {
+                        args.colon2_token = Some(Default::default());
+                    }
+                }
+                let ty = path.into_token_stream();
+                (ty.clone(), ty)
+            }
+            ty => (ty.into_token_stream(), quote! { <#ty> }),
+        };
+        Self {
+            ty,
+            qualified,
+            trait_qualified: None,
+        }
+    }
+
+    // Replaces `self` and `Self` in a function, except in the items nested in its body, where
+    // they refer to the nested items instead.
+    fn replace_fn(&self, mut func: ItemFn) -> Result<ItemFn> {
+        let mut take = TakeItems(Vec::new());
+        take.visit_block_mut(&mut func.block);
+        let mut func: ItemFn = syn::parse2(self.replace(func.into_token_stream()))?;
+        RestoreItems(take.0.into_iter()).visit_block_mut(&mut func.block);
+        Ok(func)
+    }
+
+    fn replace(&self, tokens: TokenStream) -> TokenStream {
+        let tokens = tokens.into_iter().collect::<Vec<_>>();
+        let mut replaced = TokenStream::new();
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Ident(ident) if ident == "Self" || ident == "self" => {
+                    let path_follows = matches!(
+                        (tokens.get(i + 1), tokens.get(i + 2)),
+                        (Some(TokenTree::Punct(a)), Some(TokenTree::Punct(b)))
+                            if a.as_char() == ':' && b.as_char() == ':'
+                    );
+                    if ident == "self" {
+                        // `self::` is a path to the current module, not the receiver.
+                        if path_follows {
+                            replaced.extend(Some(token.clone()));
+                        } else {
+                            replaced
+                                .extend(Some(TokenTree::Ident(Ident::new(SELF_ARG, ident.span()))));
+                        }
+                    } else if path_follows {
+                        let item = match tokens.get(i + 3) {
+                            Some(TokenTree::Ident(item)) => item.to_string(),
+                            _ => String::new(),
+                        };
+                        match &self.trait_qualified {
+                            Some((qualified, items)) if items.contains(&item) => {
+                                replaced.extend(qualified.clone())
+                            }
+                            _ => replaced.extend(self.qualified.clone()),
+                        }
+                    } else {
+                        replaced.extend(self.ty.clone());
+                    }
+                }
+                TokenTree::Group(group) => {
+                    let mut new = Group::new(group.delimiter(), self.replace(group.stream()));
+                    new.set_span(group.span());
+                    replaced.extend(Some(TokenTree::Group(new)));
+                }
+                token => replaced.extend(Some(token.clone())),
+            }
+        }
+        replaced
+    }
+}
+
+// Replaces the items nested in a function body with placeholders.
+struct TakeItems(Vec<Item>);
+
+impl VisitMut for TakeItems {
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        let placeholder = Ident::new(
+            &format!("{NESTED_ITEM}_{}", self.0.len()),
+            Span::call_site(),
+        );
+        self.0.push(std::mem::replace(
+            item,
+            parse_quote! { struct #placeholder; },
+        ));
+    }
+}
+
+// Replaces the placeholders of `TakeItems` with the nested items, in the same order.
+struct RestoreItems(std::vec::IntoIter<Item>);
+
+impl VisitMut for RestoreItems {
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        if matches!(item, Item::Struct(placeholder) if placeholder.ident.to_string().starts_with(NESTED_ITEM))
+        {
+            if let Some(nested) = self.0.next() {
+                *item = nested;
+            }
+        }
+    }
+}
+
+// Replaces `Self` in a function in an impl block with the implementing type, specified by
+// `self_type`, so the function can be multiversioned like a free function.
+pub(crate) fn resolve_self_type(func: ItemFn, self_ty: &Type) -> Result<ItemFn> {
+    if let Some(receiver) = func.sig.receiver() {
+        return Err(Error::new_spanned(
+            receiver,
+            "`self_type` can't be used with methods that take `self`",
+        ));
+    }
+    SelfReplacer::for_type(self_ty).replace_fn(func)
+}
+
+// If the function returns an `impl Future` by evaluating an async block, such as a desugared
+// `async fn` in a trait, returns the equivalent `async fn`.
+fn desugared_async(sig: &Signature, block: &Block) -> Option<(Signature, Block)> {
+    let bounds = match &sig.output {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::ImplTrait(ty) => &ty.bounds,
+            _ => return None,
+        },
+        ReturnType::Default => return None,
+    };
+    let future = bounds.iter().find_map(|bound| match bound {
+        TypeParamBound::Trait(bound) => bound
+            .path
+            .segments
+            .last()
+            .filter(|segment| segment.ident == "Future"),
+        _ => None,
+    })?;
+    let output = match &future.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::AssocType(assoc) if assoc.ident == "Output" => Some(&assoc.ty),
+            _ => None,
+        }),
+        _ => None,
+    };
+    let inner = match block.stmts.as_slice() {
+        [Stmt::Expr(Expr::Async(inner), None)] => &inner.block,
+        _ => return None,
+    };
+    let sig = Signature {
+        asyncness: Some(Default::default()),
+        output: match output {
+            Some(ty) => parse_quote! { -> #ty },
+            None => ReturnType::Default,
+        },
+        ..sig.clone()
+    };
+    // The braces of the async block would trigger the `unused_braces` lint in the function body.
+    let block = Block {
+        brace_token: Default::default(),
+        stmts: inner.stmts.clone(),
+    };
+    Some((sig, block))
+}
+
+// Converts a method to a free function, with the generics of the impl block or trait.
+//
+// Methods that return an `impl Future` by evaluating an async block are converted to `async fn`,
+// since `impl Trait` return types can't be multiversioned.  Calling the free function still
+// returns a future.
+fn free_fn(
+    prefix: &str,
+    generics: &Generics,
+    attrs: &[Attribute],
+    sig: &Signature,
+    block: &Block,
+    replacer: &SelfReplacer,
+) -> Result<ItemFn> {
+    let (mut sig, block) = match desugared_async(sig, block) {
+        Some((sig, block)) => (sig, block),
+        None => (sig.clone(), block.clone()),
+    };
+    let method_generics = sig.generics.clone();
+    sig.ident = Ident::new(&format!("{prefix}_{}", sig.ident), sig.ident.span());
+
+    // The receiver becomes an ordinary argument.
+    if let Some(FnArg::Receiver(receiver)) = sig.inputs.first() {
+        let mutability = if receiver.reference.is_none() {
+            receiver.mutability
+        } else {
+            None
+        };
+        let arg = FnArg::Typed(PatType {
+            attrs: receiver.attrs.clone(),
+            pat: Box::new(Pat::Ident(PatIdent {
+                attrs: Vec::new(),
+                by_ref: None,
+                mutability,
+                ident: Ident::new(SELF_ARG, receiver.self_token.span),
+                subpat: None,
+            })),
+            colon_token: Default::default(),
+            ty: receiver.ty.clone(),
+        });
+        sig.inputs[0] = arg;
+    }
+
+    // Lifetimes must precede the other generic parameters.
+    let (lifetimes, params): (Vec<GenericParam>, Vec<GenericParam>) = generics
+        .params
+        .iter()
+        .chain(method_generics.params.iter())
+        .cloned()
+        .partition(|param| matches!(param, GenericParam::Lifetime(_)));
+    sig.generics.params = lifetimes.into_iter().chain(params).collect();
+    let predicates = generics
+        .where_clause
+        .iter()
+        .chain(method_generics.where_clause.iter())
+        .flat_map(|clause| clause.predicates.iter().cloned())
+        .collect::<Punctuated<_, token::Comma>>();
+    sig.generics.where_clause = if predicates.is_empty() {
+        None
+    } else {
+        Some(WhereClause {
+            where_token: Default::default(),
+            predicates,
+        })
+    };
+
+    // The clones aren't in the impl block, so only `cfg`s and lint levels are copied.
+    let attrs = attrs.iter().filter(|attr| {
+        ["cfg", "allow", "warn", "deny", "forbid"]
+            .iter()
+            .any(|name| attr.path().is_ident(name))
+    });
+    replacer.replace_fn(parse_quote! {
+        #(#attrs)*
+        #sig #block
+    })
+}
+
+// Replaces the body of a method with a call to its free function, returning the new signature
+// and body.
+fn forward(sig: &Signature, free: &ItemFn) -> (Signature, Block) {
+    let (signature, uses) = util::dispatcher_signature(sig);
+    let args = util::arg_exprs(&signature);
+    let ident = &free.sig.ident;
+    // In traits, the implementing type is `Self`.
+    let params = util::fn_params(&free.sig).into_iter().map(|param| {
+        if param == SELF_TY {
+            quote! { Self }
+        } else {
+            param.into_token_stream()
+        }
+    });
+    let turbofish = if util::impl_trait_present(&free.sig) || params.len() == 0 {
+        None
+    } else {
+        Some(quote! { ::<#(#params),*> })
+    };
+    let mut call = quote! { #ident #turbofish(#(#args),*) };
+    if signature.asyncness.is_some() {
+        call.extend(util::await_tokens());
+    }
+    if signature.unsafety.is_some() {
+        call = quote! { unsafe { #call } };
+    }
+    let block = parse_quote! { { #uses #call } };
+    (signature, block)
+}
+
+// Returns the prefix of the free functions of an impl block or trait.
+//
+// The free functions are named after the item, so that the methods of multiple items in the same
+// scope don't conflict.  They aren't placed in a module, since items in function bodies may use
+// items that are only visible in the function.
+fn free_fn_prefix(
+    name: &str,
+    description: &str,
+    item: &impl ToTokens,
+    attr: &TokenStream,
+) -> Result<String> {
+    // Every method would be kept under the same name.
+    if let Some(option) = attr
+        .clone()
+        .into_iter()
+        .find(|token| matches!(token, TokenTree::Ident(ident) if ident == "keep_original"))
+    {
+        return Err(Error::new(
+            option.span(),
+            format!("`keep_original` can't be used on {description}"),
+        ));
+    }
+
+    let mut hasher = DefaultHasher::new();
+    item.to_token_stream().to_string().hash(&mut hasher);
+    Ok(format!("__multiversion_{name}_{:016x}", hasher.finish()))
+}
+
+pub(crate) fn make_multiversioned_impl(
+    attr: TokenStream,
+    mut imp: ItemImpl,
+) -> Result<TokenStream> {
+    let prefix = free_fn_prefix("impl", "impl blocks", &imp, &attr)?;
+    let replacer = SelfReplacer::new(&imp);
+    let generics = imp.generics.clone();
+    let mut fns = Vec::new();
+    for item in imp.items.iter_mut() {
+        if let ImplItem::Fn(method) = item {
+            if let Some(attr) = method
+                .attrs
+                .iter()
+                .find(|attr| is_path(attr.path(), "multiversion"))
+            {
+                return Err(Error::new_spanned(
+                    attr,
+                    "methods in a multiversioned impl block are already multiversioned",
+                ));
+            }
+            let free = free_fn(
+                &prefix,
+                &generics,
+                &method.attrs,
+                &method.sig,
+                &method.block,
+                &replacer,
+            )?;
+            (method.sig, method.block) = forward(&method.sig, &free);
+            fns.push(free);
+        }
+    }
+
+    Ok(quote! {
+        #imp
+
+        #(
+            #[multiversion::multiversion(#attr)]
+            #fns
+        )*
+    })
+}
+
+// Multiversions the default bodies of the methods of a trait.  The free functions are generic
+// over the implementing type, so each implementation that uses a default body has its own
+// versions.
+pub(crate) fn make_multiversioned_trait(
+    attr: TokenStream,
+    mut tr: ItemTrait,
+) -> Result<TokenStream> {
+    let prefix = free_fn_prefix("trait", "traits", &tr, &attr)?;
+    let self_ty = Ident::new(SELF_TY, Span::call_site());
+    let replacer = SelfReplacer {
+        ty: self_ty.to_token_stream(),
+        qualified: self_ty.to_token_stream(),
+        trait_qualified: None,
+    };
+    let mut generics = tr.generics.clone();
+    let trait_ident = &tr.ident;
+    let (_, trait_args, _) = tr.generics.split_for_impl();
+    generics
+        .params
+        .push(parse_quote! { #self_ty: ?Sized + #trait_ident #trait_args });
+
+    let mut fns = Vec::new();
+    for item in tr.items.iter_mut() {
+        if let TraitItem::Fn(method) = item {
+            if let Some(attr) = method
+                .attrs
+                .iter()
+                .find(|attr| is_path(attr.path(), "multiversion"))
+            {
+                return Err(Error::new_spanned(
+                    attr,
+                    "methods in a multiversioned trait are already multiversioned",
+                ));
+            }
+            // Methods without a default body are left alone.
+            if let Some(block) = &mut method.default {
+                let free = free_fn(
+                    &prefix,
+                    &generics,
+                    &method.attrs,
+                    &method.sig,
+                    block,
+                    &replacer,
+                )?;
+                (method.sig, *block) = forward(&method.sig, &free);
+                fns.push(free);
+            }
+        }
+    }
+
+    Ok(quote! {
+        #tr
+
+        #(
+            #[multiversion::multiversion(#attr)]
+            #fns
+        )*
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn replace_self() {
+        let replacer = SelfReplacer::new(&parse_quote! { impl<T> Wrapper<T> {} });
+        let replaced = replacer.replace(quote! {
+            let x: Self = Self::new(self.0, self::helper());
+            [Self { x }]
+        });
+        let expected = quote! {
+            let x: Wrapper::<T> = Wrapper::<T>::new(__multiversion_self.0, self::helper());
+            [Wrapper::<T> { x }]
+        };
+        assert_eq!(replaced.to_string(), expected.to_string());
+
+        let replacer = SelfReplacer::new(&parse_quote! { impl [u8] {} });
+        let replaced = replacer.replace(quote! { Self::len(self) });
+        assert_eq!(
+            replaced.to_string(),
+            quote! { <[u8]>::len(__multiversion_self) }.to_string()
+        );
+
+        let replacer = SelfReplacer::new(&parse_quote! {
+            impl Iterator for Counter {
+                type Item = u32;
+            }
+        });
+        let replaced = replacer.replace(quote! { Option<Self::Item>, Self::new() });
+        assert_eq!(
+            replaced.to_string(),
+            quote! { Option< <Counter as Iterator>::Item>, Counter::new() }.to_string()
+        );
+    }
+
+    // `self` and `Self` in nested items refer to the nested items.
+    #[test]
+    fn nested_items() {
+        let replacer = SelfReplacer::new(&parse_quote! { impl Wrapper {} });
+        let func = replacer
+            .replace_fn(parse_quote! {
+                fn get(__multiversion_self: &Wrapper) -> Self {
+                    struct Inner;
+                    impl Inner {
+                        fn new(&self) -> Self {
+                            Self
+                        }
+                    }
+                    let f = || {
+                        fn nested() -> Self {
+                            Self
+                        }
+                        Self::new()
+                    };
+                    f()
+                }
+            })
+            .unwrap();
+        let expected: ItemFn = parse_quote! {
+            fn get(__multiversion_self: &Wrapper) -> Wrapper {
+                struct Inner;
+                impl Inner {
+                    fn new(&self) -> Self {
+                        Self
+                    }
+                }
+                let f = || {
+                    fn nested() -> Self {
+                        Self
+                    }
+                    Wrapper::new()
+                };
+                f()
+            }
+        };
+        assert_eq!(
+            func.into_token_stream().to_string(),
+            expected.into_token_stream().to_string()
+        );
+    }
+
+    #[test]
+    fn keep_original() {
+        let imp = parse_quote! {
+            impl Samples {
+                fn sum(&self) -> f32 { 0. }
+            }
+        };
+        let err = make_multiversioned_impl(
+            quote! { targets = "simd", keep_original = "sum_scalar" },
+            imp,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`keep_original` can't be used on impl blocks"
+        );
+    }
+
+    #[test]
+    fn desugared_async() {
+        let (sig, block) = super::desugared_async(
+            &parse_quote! { fn sum(&self) -> impl Future<Output = f32> + Send + '_ },
+            &parse_quote! { { async move { self.0.iter().sum() } } },
+        )
+        .unwrap();
+        assert_eq!(
+            sig.to_token_stream().to_string(),
+            quote! { async fn sum(&self) -> f32 }.to_string()
+        );
+        assert_eq!(
+            block.to_token_stream().to_string(),
+            quote! { { self.0.iter().sum() } }.to_string()
+        );
+
+        // The future must be the only expression.
+        assert!(super::desugared_async(
+            &parse_quote! { fn wait(&self) -> impl Future<Output = ()> },
+            &parse_quote! { { let x = 1; async move { x; } } },
+        )
+        .is_none());
+        assert!(super::desugared_async(
+            &parse_quote! { fn values(&self) -> impl Iterator<Item = f32> },
+            &parse_quote! { { async move { 1. } } },
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn trait_defaults() {
+        let tr = parse_quote! {
+            trait Samples<T> {
+                fn values(&self) -> &[T];
+                fn first(&self) -> &T { &self.values()[0] }
+            }
+        };
+        let tokens = make_multiversioned_trait(quote! { targets = "simd" }, tr)
+            .unwrap()
+            .to_string();
+        assert!(tokens.contains(&quote! { fn values(&self) -> &[T]; }.to_string()));
+        assert!(tokens.contains(&quote! { __MultiversionSelf: ?Sized + Samples<T> }.to_string()));
+        assert!(tokens.contains(&quote! { ::<T, Self>(self) }.to_string()));
+        assert!(tokens.contains(&quote! { &__multiversion_self.values()[0] }.to_string()));
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{impl_block, multiversion};
+use proc_macro2::{TokenStream, TokenTree};
+use syn::{
+    parse::{Parse, ParseStream},
+    token, Error, Item, Result,
+};
+
+// The arguments of `multiversion_item!`: the options of the `multiversion` attribute, followed by
+// a semicolon and the items to multiversion.
+pub(crate) struct Items {
+    options: TokenStream,
+    items: Vec<Item>,
+}
+
+impl Parse for Items {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // Options are delimited by the first semicolon outside of a group.
+        let mut options = TokenStream::new();
+        while !input.peek(token::Semi) {
+            if input.is_empty() {
+                return Err(input.error("expected `;` after the options"));
+            }
+            options.extend(Some(input.parse::<TokenTree>()?));
+        }
+        input.parse::<token::Semi>()?;
+        let mut items = Vec::new();
+        while !input.is_empty() {
+            items.push(input.parse()?);
+        }
+        Ok(Self { options, items })
+    }
+}
+
+impl Items {
+    // Multiversions each item with the options, like the `multiversion` attribute.
+    pub(crate) fn expand(self) -> Result<TokenStream> {
+        let mut tokens = TokenStream::new();
+        for item in self.items {
+            tokens.extend(match item {
+                Item::Fn(func) => multiversion::make_multiversioned_fn(self.options.clone(), func)?,
+                Item::Impl(imp) => impl_block::make_multiversioned_impl(self.options.clone(), imp)?,
+                Item::Trait(tr) => impl_block::make_multiversioned_trait(self.options.clone(), tr)?,
+                item => {
+                    return Err(Error::new_spanned(
+                        item,
+                        "expected a function, an impl block, or a trait",
+                    ))
+                }
+            });
+        }
+        Ok(tokens)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quote::quote;
+
+    #[test]
+    fn parse_items() {
+        let items: Items = syn::parse_quote! {
+            targets("x86_64+avx2"), dispatcher = "static";
+            fn f() {}
+            pub fn g(x: u32) -> u32 { x }
+        };
+        assert_eq!(
+            items.options.to_string(),
+            quote! { targets("x86_64+avx2"), dispatcher = "static" }.to_string()
+        );
+        assert_eq!(items.items.len(), 2);
+        let tokens = items.expand().unwrap().to_string();
+        assert!(tokens.contains("fn f ()"));
+        assert!(tokens.contains("pub fn g (x : u32) -> u32"));
+
+        assert!(syn::parse2::<Items>(quote! { targets = "simd" fn f() {} }).is_err());
+        let items: Items = syn::parse_quote! { targets = "simd"; struct S; };
+        assert!(items.expand().is_err());
+    }
+}
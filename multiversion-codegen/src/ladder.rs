@@ -0,0 +1,141 @@
+use crate::target::Target;
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    token, Attribute, Error, Ident, LitStr, Result, Visibility,
+};
+
+struct Level {
+    attrs: Vec<Attribute>,
+    ident: Ident,
+    target: Target,
+    target_str: LitStr,
+}
+
+impl Parse for Level {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let ident: Ident = input.parse()?;
+        if ident == "Baseline" {
+            return Err(Error::new(
+                ident.span(),
+                "`Baseline` is reserved for the level without any targets",
+            ));
+        }
+        input.parse::<token::Eq>()?;
+        let target_str: LitStr = input.parse()?;
+        let target = Target::parse_untuned(&target_str)?;
+        if !target.has_features_specified() {
+            return Err(Error::new(
+                target_str.span(),
+                "target must have features specified",
+            ));
+        }
+        Ok(Self {
+            attrs,
+            ident,
+            target,
+            target_str,
+        })
+    }
+}
+
+pub(crate) struct Ladder {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    ident: Ident,
+    levels: Vec<Level>,
+}
+
+impl Parse for Ladder {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<token::Enum>()?;
+        let ident = input.parse()?;
+        let content;
+        braced!(content in input);
+        let levels = Punctuated::<Level, token::Comma>::parse_terminated(&content)?
+            .into_iter()
+            .collect();
+        Ok(Self {
+            attrs,
+            vis,
+            ident,
+            levels,
+        })
+    }
+}
+
+impl ToTokens for Ladder {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let Self {
+            attrs,
+            vis,
+            ident,
+            levels,
+        } = self;
+
+        // Variants are declared from lowest to highest, so that the derived ordering is ascending.
+        let variants = levels.iter().rev().map(|level| {
+            let attrs = &level.attrs;
+            let ident = &level.ident;
+            quote! { #(#attrs)* #ident }
+        });
+
+        let detect = levels.iter().map(|level| {
+            let variant = &level.ident;
+            let target_arch = level.target.target_arch();
+            let condition = if cfg!(feature = "std") {
+                level.target.features_detected()
+            } else {
+                level.target.features_enabled()
+            };
+            quote! {
+                #target_arch
+                {
+                    if #condition {
+                        return Self::#variant
+                    }
+                }
+            }
+        });
+
+        let target_strs = levels.iter().map(|level| {
+            let variant = &level.ident;
+            let target_str = &level.target_str;
+            quote! { Self::#variant => Some(#target_str), }
+        });
+
+        quote! {
+            #(#attrs)*
+            #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            #vis enum #ident {
+                /// The capability level when no other level is supported.
+                Baseline,
+                #(#variants),*
+            }
+
+            impl #ident {
+                /// Detect the highest supported capability level.
+                #vis fn detect() -> Self {
+                    #(#detect)*
+                    Self::Baseline
+                }
+
+                /// Returns the target specification string of this capability level, or `None`
+                /// for `Baseline`.
+                #vis const fn target_str(self) -> Option<&'static str> {
+                    match self {
+                        #(#target_strs)*
+                        Self::Baseline => None,
+                    }
+                }
+            }
+        }
+        .to_tokens(tokens)
+    }
+}
@@ -0,0 +1,154 @@
+//! Code generation for [`multiversion`](https://docs.rs/multiversion).
+//!
+//! This crate implements the macros of `multiversion`, so that other procedural macros can
+//! generate multiversioned functions without invoking the `multiversion` attribute:
+//! ```
+//! use multiversion_codegen::{targets, Dispatcher};
+//! use quote::ToTokens;
+//! use syn::{parse::Parser, parse_quote};
+//!
+//! let func = parse_quote! {
+//!     fn square(x: &mut [f32]) {
+//!         for v in x {
+//!             *v *= *v
+//!         }
+//!     }
+//! };
+//! let targets = targets::parse_targets
+//!     .parse_str(r#""x86_64+avx2+fma", "aarch64+neon""#)
+//!     .unwrap();
+//! let tokens = Dispatcher::new(func, targets).into_token_stream();
+//! ```
+//!
+//! # Generated code
+//! The generated code refers to the `multiversion` crate by absolute path, such as
+//! `multiversion::detect`, so crates that contain it must depend on `multiversion`.  The cargo
+//! features of this crate change the generated code, and must match the features of
+//! `multiversion`.  Since `multiversion` enables the features of this crate through
+//! `multiversion-macros`, depending on this crate with `default-features = false` and on
+//! `multiversion` with the desired features is usually sufficient.
+//!
+//! # Stability
+//! The generated code uses hidden items of `multiversion` that may change in any release, so this
+//! crate must be the same version as `multiversion`.  Depend on it with an exact version
+//! requirement, such as `multiversion-codegen = "=0.8.0"`.
+//!
+//! Otherwise, the API follows semantic versioning, like `multiversion`.
+#![warn(missing_docs)]
+
+mod cfg;
+mod clone_module;
+mod closure;
+mod config;
+mod default_targets;
+mod dispatcher;
+mod group;
+mod impl_block;
+mod item;
+mod ladder;
+mod match_target;
+mod model;
+mod multiversion;
+mod presets;
+mod table;
+mod target;
+mod target_list;
+pub mod targets;
+mod util;
+
+pub use dispatcher::{DispatchMethod, Dispatcher, Variants, VersionNames};
+pub use model::PriorityModel;
+pub use target::Target;
+
+use proc_macro2::TokenStream;
+use syn::{
+    punctuated::Punctuated, token::Comma, ItemFn, ItemImpl, ItemMod, ItemTrait, LitStr, Meta,
+    Result,
+};
+
+/// Implements the `multiversion` attribute, with the options in `attr`.
+pub fn multiversion(attr: TokenStream, func: ItemFn) -> Result<TokenStream> {
+    multiversion::make_multiversioned_fn(attr, func)
+}
+
+/// Implements the `multiversion` attribute applied to an impl block, with the options in `attr`.
+///
+/// Each method is multiversioned with the same options.
+pub fn multiversion_impl(attr: TokenStream, imp: ItemImpl) -> Result<TokenStream> {
+    impl_block::make_multiversioned_impl(attr, imp)
+}
+
+/// Implements the `multiversion` attribute applied to a trait, with the options in `attr`.
+///
+/// The default body of each method is multiversioned with the same options.
+pub fn multiversion_trait(attr: TokenStream, tr: ItemTrait) -> Result<TokenStream> {
+    impl_block::make_multiversioned_trait(attr, tr)
+}
+
+/// Implements the `multiversion_closure` macro, which multiversions a closure.
+pub fn multiversion_closure(input: TokenStream) -> Result<TokenStream> {
+    syn::parse2::<closure::Closure>(input).map(quote::ToTokens::into_token_stream)
+}
+
+/// Implements the `multiversion_item` macro, which multiversions items like the `multiversion`
+/// attribute.
+pub fn multiversion_item(input: TokenStream) -> Result<TokenStream> {
+    syn::parse2::<item::Items>(input)?.expand()
+}
+
+/// Implements the `table` macro, which declares tables of multiversioned function pointers.
+pub fn table(input: TokenStream) -> Result<TokenStream> {
+    syn::parse2::<table::Tables>(input).map(quote::ToTokens::into_token_stream)
+}
+
+/// Implements the `target` attribute.
+pub fn target(target: LitStr, func: ItemFn) -> Result<TokenStream> {
+    target::make_target_fn(target, func)
+}
+
+/// Implements the `default_targets` attribute, with the options in `attr`.
+pub fn default_targets(attr: TokenStream, module: ItemMod) -> Result<TokenStream> {
+    default_targets::make_default_targets(attr, module)
+}
+
+/// Implements the `group` attribute, with the options in `attr`.
+pub fn group(attr: TokenStream, module: ItemMod) -> Result<TokenStream> {
+    group::make_group(attr, module)
+}
+
+/// Implements the `clone_module` attribute, with the options in `attr`.
+pub fn clone_module(attr: TokenStream, module: ItemMod) -> Result<TokenStream> {
+    clone_module::make_clone_module(attr, module)
+}
+
+/// Converts the arguments of `target_cfg` to a `cfg` predicate.
+///
+/// Target predicates, such as `target = "x86_64+avx2"`, are converted to predicates of
+/// `target_arch` and `target_feature`.
+pub fn target_cfg_predicate(meta: Punctuated<Meta, Comma>) -> Result<Meta> {
+    cfg::transform(meta)
+}
+
+/// Implements the `target_cfg_for` attribute, which applies `cfg` to `item` with the features of
+/// the target in `attr` considered enabled.
+pub fn target_cfg_for(attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
+    cfg::target_cfg_for(attr, item)
+}
+
+/// Implements `match_target`, which matches the target of the function it's used in.
+///
+/// This is usually called after the target is inherited from the enclosing function, as by the
+/// `multiversion::match_target` macro.
+pub fn match_target(input: TokenStream) -> Result<TokenStream> {
+    syn::parse2::<match_target::MatchTarget>(input).map(quote::ToTokens::into_token_stream)
+}
+
+/// Implements the `capability_ladder` macro.
+pub fn capability_ladder(input: TokenStream) -> Result<TokenStream> {
+    syn::parse2::<ladder::Ladder>(input).map(quote::ToTokens::into_token_stream)
+}
+
+/// Implements the `target_list` macro.
+pub fn target_list(input: TokenStream) -> Result<TokenStream> {
+    syn::parse2::<target_list::TargetList>(input).map(quote::ToTokens::into_token_stream)
+}
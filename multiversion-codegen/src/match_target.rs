@@ -32,7 +32,7 @@ impl Parse for MatchTarget {
 
             fn parse_target(e: &ExprLit) -> Result<Target> {
                 if let Lit::Str(s) = &e.lit {
-                    Target::parse(s)
+                    Target::parse_untuned(s)
                 } else {
                     Err(Error::new(e.span(), "expected a string literal"))
                 }
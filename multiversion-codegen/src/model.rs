@@ -0,0 +1,192 @@
+//! Priority models, which score targets for particular CPUs.
+//!
+//! A model is a file in the format of `multiversion.toml`, where each table is a CPU vendor,
+//! optionally followed by a CPU family, and each key is a target with its score:
+//! ```toml
+//! # Intel CPUs
+//! [intel]
+//! "x86_64+avx512f+avx512bw" = 5
+//! "x86_64+avx2" = 10
+//!
+//! # AMD Zen 4
+//! [amd.0x19]
+//! "x86_64+avx512f+avx512bw" = 20
+//! ```
+
+use crate::config::{self, Config, Value};
+use crate::target::Target;
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::quote;
+use syn::{Error, LitStr, Result};
+
+// The vendors that may be named by a model, and the corresponding `multiversion::cpu::Vendor`.
+const VENDORS: &[(&str, &str)] = &[
+    ("intel", "Intel"),
+    ("amd", "Amd"),
+    ("hygon", "Hygon"),
+    ("centaur", "Centaur"),
+    ("zhaoxin", "Zhaoxin"),
+    ("arm", "Arm"),
+    ("apple", "Apple"),
+    ("qualcomm", "Qualcomm"),
+    ("nvidia", "Nvidia"),
+    ("ampere", "Ampere"),
+    ("fujitsu", "Fujitsu"),
+    ("hisilicon", "HiSilicon"),
+];
+
+// The scores of targets on CPUs of a vendor, and optionally of a family.
+#[derive(Clone, Debug)]
+struct Cpu {
+    vendor: &'static str,
+    family: Option<u32>,
+    scores: Vec<(Target, i32)>,
+}
+
+/// Scores of targets for particular CPUs, specified by `priority_model`.
+///
+/// On a CPU named by the model, the detected target with the highest score is selected.  Targets
+/// that aren't scored have a score of 0, and targets with equal scores keep their priority.
+#[derive(Clone, Debug)]
+pub struct PriorityModel {
+    cpus: Vec<Cpu>,
+    tracked: Option<TokenStream>,
+}
+
+impl PriorityModel {
+    /// Parses a model from the contents of a model file.
+    pub fn parse_str(text: &str) -> std::result::Result<Self, String> {
+        Self::from_entries(config::parse_tables(text)?, None)
+    }
+
+    /// Reads a model file, relative to the root of the crate being compiled.
+    pub(crate) fn read(path: &LitStr) -> Result<Self> {
+        let config = Config::read_file(path.span(), &path.value())?;
+        Self::from_entries(config.entries().to_vec(), Some(config.track())).map_err(|err| {
+            Error::new(
+                path.span(),
+                format!("error in `{}`: {err}", config.path().display()),
+            )
+        })
+    }
+
+    fn from_entries(
+        entries: Vec<config::Entry>,
+        tracked: Option<TokenStream>,
+    ) -> std::result::Result<Self, String> {
+        let mut cpus: Vec<Cpu> = Vec::new();
+        for (table, key, value) in entries {
+            let table = table.ok_or_else(|| format!("`{key}` must be in a table of a CPU"))?;
+            let (vendor, family) = match table.split_once('.') {
+                Some((vendor, family)) => (vendor, Some(parse_family(family)?)),
+                None => (table.as_str(), None),
+            };
+            let vendor = VENDORS
+                .iter()
+                .find(|(name, _)| *name == vendor)
+                .map(|(_, variant)| *variant)
+                .ok_or_else(|| format!("unknown CPU vendor `{vendor}`"))?;
+            let target = Target::parse(&LitStr::new(&key, Span::call_site()))
+                .map_err(|err| format!("invalid target `{key}`: {err}"))?;
+            let score = match value {
+                Value::Integer(score) => i32::try_from(score)
+                    .map_err(|_| format!("the score of `{key}` is out of range"))?,
+                _ => return Err(format!("the score of `{key}` must be an integer")),
+            };
+            match cpus
+                .iter_mut()
+                .find(|cpu| cpu.vendor == vendor && cpu.family == family)
+            {
+                Some(cpu) => cpu.scores.push((target, score)),
+                None => cpus.push(Cpu {
+                    vendor,
+                    family,
+                    scores: vec![(target, score)],
+                }),
+            }
+        }
+        Ok(Self { cpus, tracked })
+    }
+
+    // Returns an expression that evaluates to the scores of the targets on the current CPU, as
+    // `Option<&'static [i32]>`, in the order of `targets`.
+    pub(crate) fn scores(&self, targets: &[&Target]) -> TokenStream {
+        let cpus = self.cpus.iter().map(|cpu| {
+            let vendor = syn::Ident::new(cpu.vendor, Span::call_site());
+            let family = match cpu.family {
+                Some(family) => {
+                    let family = Literal::u32_unsuffixed(family);
+                    quote! { Some(#family) }
+                }
+                None => quote! { None },
+            };
+            let scores = targets.iter().map(|target| {
+                cpu.scores
+                    .iter()
+                    .find(|(scored, _)| same_target(scored, target))
+                    .map_or(0, |(_, score)| *score)
+            });
+            let scores = scores.map(Literal::i32_unsuffixed);
+            quote! { (multiversion::cpu::Vendor::#vendor, #family, &[#(#scores),*]) }
+        });
+        let tracked = &self.tracked;
+        quote! {
+            {
+                #tracked
+                multiversion::cpu::__model_scores(&[#(#cpus),*])
+            }
+        }
+    }
+}
+
+// Returns `true` if the targets have the same specification, ignoring attributes.
+fn same_target(a: &Target, b: &Target) -> bool {
+    a.arch() == b.arch() && a.features() == b.features() && a.tune() == b.tune()
+}
+
+fn parse_family(family: &str) -> std::result::Result<u32, String> {
+    let parsed = match family.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => family.parse(),
+    };
+    parsed.map_err(|_| format!("invalid CPU family `{family}`"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_model() {
+        let model = PriorityModel::parse_str(
+            r#"
+            [intel]
+            "x86_64+avx2" = 10
+
+            [amd.0x19]
+            "x86_64+avx512f" = 20
+            "x86_64+avx2" = -1
+            "#,
+        )
+        .unwrap();
+        assert_eq!(model.cpus.len(), 2);
+        assert_eq!(model.cpus[1].vendor, "Amd");
+        assert_eq!(model.cpus[1].family, Some(0x19));
+        assert_eq!(model.cpus[1].scores[1].1, -1);
+
+        let targets = ["x86_64+avx512f", "x86_64+sse4.2", "x86_64+avx2"]
+            .iter()
+            .map(|t| Target::parse(&LitStr::new(t, Span::call_site())).unwrap())
+            .collect::<Vec<_>>();
+        let scores = model
+            .scores(&targets.iter().collect::<Vec<_>>())
+            .to_string();
+        assert!(scores.contains("Intel , None , & [0 , 0 , 10]"));
+        assert!(scores.contains("Amd , Some (25) , & [20 , 0 , - 1]"));
+
+        PriorityModel::parse_str("\"x86_64+avx2\" = 1").unwrap_err();
+        PriorityModel::parse_str("[amd]\n\"x86_64+avx2\" = \"high\"").unwrap_err();
+        PriorityModel::parse_str("[amd.zen]\n\"x86_64+avx2\" = 1").unwrap_err();
+        PriorityModel::parse_str("[transmeta]\n\"x86_64+avx2\" = 1").unwrap_err();
+    }
+}
@@ -0,0 +1,1738 @@
+use crate::dispatcher::{DispatchMethod, Dispatcher, GroupRole, Variants, VersionNames};
+use crate::impl_block;
+use crate::model::PriorityModel;
+use crate::target::Target;
+use crate::targets::{self, env_targets, parse_target_list, parse_targets};
+use crate::util;
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream, Parser},
+    parse_quote,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    token, Attribute, Block, Error, Expr, GenericParam, Ident, Item, ItemFn, Lifetime, LitBool,
+    LitInt, LitStr, Meta, Pat, Path, ReturnType, Stmt, Type, TypeParamBound, Visibility,
+};
+
+enum Targets {
+    List(Vec<Target>),
+    Profiles(Vec<(Ident, Vec<Target>)>),
+    Macro(Path),
+}
+
+// Parses `profile = targets, ...`.
+fn parse_profiles(
+    input: ParseStream,
+    tracked: &mut Vec<TokenStream>,
+) -> syn::Result<Vec<(Ident, Vec<Target>)>> {
+    let mut profiles: Vec<(Ident, Vec<Target>)> = Vec::new();
+    while !input.is_empty() {
+        let profile: Ident = input.parse()?;
+        if profiles.iter().any(|(p, _)| *p == profile) {
+            return Err(Error::new(
+                profile.span(),
+                "can't specify a profile multiple times",
+            ));
+        }
+        // Build scripts only see the output directory, which the `test` and `bench` profiles
+        // share with the profiles they inherit from.
+        if profile == "test" || profile == "bench" {
+            return Err(Error::new(
+                profile.span(),
+                format!(
+                    "the `{}` profile can't be distinguished from `{}`",
+                    profile,
+                    if profile == "test" { "dev" } else { "release" }
+                ),
+            ));
+        }
+        input.parse::<token::Eq>()?;
+        profiles.push((profile, parse_target_list(input, tracked)?));
+        if input.is_empty() {
+            break;
+        }
+        input.parse::<token::Comma>()?;
+    }
+    Ok(profiles)
+}
+
+// Splits attribute options at top-level commas.
+pub(crate) fn split_options(attr: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut options = vec![Vec::new()];
+    for token in attr {
+        match token {
+            TokenTree::Punct(p) if p.as_char() == ',' => options.push(Vec::new()),
+            token => options.last_mut().unwrap().push(token),
+        }
+    }
+    options.retain(|option| !option.is_empty());
+    options
+}
+
+// Removes the option `name` from the options, returning its value.  The value is parsed by
+// `parser` from the tokens following the name.
+fn take_option<T>(
+    attr: TokenStream,
+    name: &str,
+    parser: impl Fn(ParseStream) -> syn::Result<T>,
+) -> syn::Result<(TokenStream, Option<T>)> {
+    let mut value = None;
+    let mut options = Vec::new();
+    for option in split_options(attr) {
+        let option = option.into_iter().collect::<TokenStream>();
+        match option.clone().into_iter().next() {
+            Some(TokenTree::Ident(ident)) if ident == name => {
+                if value.is_some() {
+                    return Err(Error::new(
+                        ident.span(),
+                        format!("can't specify `{name}` multiple times"),
+                    ));
+                }
+                value = Some(
+                    (|input: ParseStream| {
+                        input.parse::<Ident>()?;
+                        parser(input)
+                    })
+                    .parse2(option)?,
+                );
+            }
+            _ => options.push(option),
+        }
+    }
+    Ok((quote! { #(#options),* }, value))
+}
+
+// Parses the value of an `option = value` option.
+fn parse_value<T: Parse>(input: ParseStream) -> syn::Result<T> {
+    input.parse::<token::Eq>()?;
+    input.parse()
+}
+
+// Removes the `share_generic_backend` option from the options, returning its value.
+fn take_share_generic_backend(attr: TokenStream) -> syn::Result<(TokenStream, bool)> {
+    let (attr, share) = take_option(attr, "share_generic_backend", parse_value::<LitBool>)?;
+    Ok((attr, matches!(share, Some(share) if share.value)))
+}
+
+// Multiversions the function marked `#[backend]` in the body of `func`, rather than `func`
+// itself.  Nested functions can't use the generic parameters of `func`, so every instantiation
+// of `func` shares the versions of the backend.
+fn make_shared_backend(attr: TokenStream, mut func: ItemFn) -> syn::Result<TokenStream> {
+    let is_backend = |stmt: &Stmt| match stmt {
+        Stmt::Item(Item::Fn(f)) => f.attrs.iter().any(|attr| attr.path().is_ident("backend")),
+        _ => false,
+    };
+    let mut backends = func
+        .block
+        .stmts
+        .iter()
+        .enumerate()
+        .filter(|(_, stmt)| is_backend(stmt))
+        .map(|(index, _)| index);
+    let index = backends.next().ok_or_else(|| {
+        Error::new(
+            func.sig.ident.span(),
+            "`share_generic_backend` requires a nested function marked `#[backend]`",
+        )
+    })?;
+    if let Some(other) = backends.next() {
+        return Err(Error::new(
+            func.block.stmts[other].span(),
+            "only one function can be marked `#[backend]`",
+        ));
+    }
+
+    let mut backend = match &func.block.stmts[index] {
+        Stmt::Item(Item::Fn(backend)) => backend.clone(),
+        _ => unreachable!(),
+    };
+    if !util::fn_params(&backend.sig).is_empty() {
+        return Err(Error::new(
+            backend.sig.generics.span(),
+            "the backend can't have generic parameters, since it's shared by every instantiation",
+        ));
+    }
+    backend
+        .attrs
+        .retain(|attr| !attr.path().is_ident("backend"));
+    let backend = make_multiversioned_fn(attr, backend)?;
+    func.block.stmts[index] = Stmt::Item(Item::Verbatim(backend));
+    Ok(func.into_token_stream())
+}
+
+// Removes the `dispatcher_visibility` option from the options, returning its value.
+fn take_dispatcher_visibility(attr: TokenStream) -> syn::Result<(TokenStream, Option<Visibility>)> {
+    take_option(attr, "dispatcher_visibility", |input| {
+        parse_value::<LitStr>(input)?.parse()
+    })
+}
+
+// Multiversions the function as `<name>_dispatcher` with the visibility `vis`, and emits a thin
+// wrapper with the original name, visibility, and attributes that calls the dispatcher.  Items
+// generated for the dispatcher, such as `variants` modules, don't exceed its visibility.
+fn make_dispatcher_wrapper(
+    attr: TokenStream,
+    vis: Visibility,
+    mut func: ItemFn,
+) -> syn::Result<TokenStream> {
+    let ident = &func.sig.ident;
+    let ident = Ident::new(&format!("{ident}_dispatcher"), ident.span());
+    let path = if util::uses_self(&func) {
+        quote! { Self::#ident }
+    } else {
+        quote! { #ident }
+    };
+    let (sig, uses) = util::dispatcher_signature(&func.sig);
+    let mut call = util::forward_call(path, &sig);
+    if sig.unsafety.is_some() {
+        call = quote! { #[allow(unused_unsafe)] unsafe { #call } };
+    }
+
+    // Documentation and symbol names only apply to the wrapper, and `#[target_feature]` only
+    // applies to the dispatcher.  Conditional compilation and lints apply to both.
+    let (dispatcher_attrs, wrapper_attrs): (Vec<_>, Vec<_>) = func
+        .attrs
+        .drain(..)
+        .partition(|attr| attr.path().is_ident("target_feature"));
+    func.attrs = dispatcher_attrs;
+    func.attrs.extend(
+        wrapper_attrs
+            .iter()
+            .filter(|attr| {
+                ["cfg", "allow", "warn", "deny", "forbid"]
+                    .iter()
+                    .any(|name| attr.path().is_ident(name))
+            })
+            .cloned(),
+    );
+    let inline = if wrapper_attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("inline"))
+    {
+        None
+    } else {
+        Some(quote! { #[inline] })
+    };
+    let wrapper_vis = std::mem::replace(&mut func.vis, vis);
+    func.sig.ident = ident;
+    let dispatcher = make_multiversioned_fn(attr, func)?;
+    Ok(quote! {
+        #(#wrapper_attrs)*
+        #inline
+        #wrapper_vis #sig {
+            #uses
+            #call
+        }
+
+        #dispatcher
+    })
+}
+
+// Removes the `foreign_attrs` option from the options, returning the attributes.
+fn take_foreign_attrs(attr: TokenStream) -> syn::Result<(TokenStream, Vec<Attribute>)> {
+    let (attr, metas) = take_option(attr, "foreign_attrs", |input| {
+        let content;
+        parenthesized!(content in input);
+        Punctuated::<Meta, token::Comma>::parse_terminated(&content)
+    })?;
+    let attrs = metas
+        .into_iter()
+        .flatten()
+        .map(|meta| parse_quote! { #[#meta] })
+        .collect();
+    Ok((attr, attrs))
+}
+
+// Removes the `self_type` option from the options, returning its value.
+fn take_self_type(attr: TokenStream) -> syn::Result<(TokenStream, Option<Type>)> {
+    let (attr, self_ty) = take_option(attr, "self_type", |input| {
+        parse_value::<LitStr>(input)?.parse()
+    })?;
+    // These options create items next to the function, which can't refer to each other from
+    // inside the impl block.
+    if self_ty.is_some() {
+        for option in split_options(attr.clone()) {
+            if let Some(TokenTree::Ident(ident)) = option.first() {
+                if [
+                    "fn_ptr",
+                    "pinned",
+                    "resolve",
+                    "trampoline",
+                    "force_target",
+                    "variants",
+                ]
+                .iter()
+                .any(|name| ident == name)
+                {
+                    return Err(Error::new(
+                        ident.span(),
+                        format!("`{ident}` can't be used with `self_type`"),
+                    ));
+                }
+            }
+        }
+    }
+    Ok((attr, self_ty))
+}
+
+// Removes the `boxed_return` option from the options, returning whether it was present.
+fn take_boxed_return(attr: TokenStream) -> syn::Result<(TokenStream, bool)> {
+    let (attr, boxed) = take_option(attr, "boxed_return", |_| Ok(()))?;
+    Ok((attr, boxed.is_some()))
+}
+
+// Multiversions a function that returns an `impl Trait` by multiversioning a nested function that
+// returns the value as a `Box<dyn Trait>`, or a `Pin<Box<dyn Future>>` for futures.  The nested
+// function can be dispatched like any other, and the boxed value is returned as the `impl Trait`.
+fn make_boxed_return(attr: TokenStream, mut func: ItemFn) -> syn::Result<TokenStream> {
+    let bounds = match &func.sig.output {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::ImplTrait(ty) => &ty.bounds,
+            _ => {
+                return Err(Error::new(
+                    ty.span(),
+                    "`boxed_return` requires an `impl Trait` return type",
+                ))
+            }
+        },
+        ReturnType::Default => {
+            return Err(Error::new(
+                func.sig.span(),
+                "`boxed_return` requires an `impl Trait` return type",
+            ))
+        }
+    };
+    if let Some(asyncness) = &func.sig.asyncness {
+        return Err(Error::new(
+            asyncness.span(),
+            "`boxed_return` can't be used with async functions",
+        ));
+    }
+    if util::uses_self(&func) {
+        return Err(Error::new(
+            func.sig.ident.span(),
+            "`boxed_return` can't be used with methods",
+        ));
+    }
+
+    let mut traits = Vec::new();
+    let mut lifetime = None;
+    for bound in bounds {
+        match bound {
+            TypeParamBound::Trait(bound) => traits.push(bound.clone()),
+            TypeParamBound::Lifetime(bound) => lifetime = Some(bound.clone()),
+            _ => {}
+        }
+    }
+    let future = traits.iter().any(
+        |bound| matches!(bound.path.segments.last(), Some(segment) if segment.ident == "Future"),
+    );
+
+    // Without an explicit lifetime, the boxed value is bounded by the type parameters and the
+    // lifetimes named in the return type, which the `impl Trait` captures.  Elided lifetimes in
+    // the return type refer to the lifetimes of the arguments.
+    let (named, elided) = util::return_lifetimes(&func.sig);
+    let mut sig = util::name_elided_lifetimes(&func.sig);
+    // Elided lifetimes in the return type are named like the lifetime of the arguments.
+    let lifetimes = sig.generics.lifetimes().collect::<Vec<_>>();
+    if let [param] = lifetimes.as_slice() {
+        let param = param.lifetime.clone();
+        for bound in &mut traits {
+            util::fill_elided_lifetimes(bound, &param);
+        }
+    }
+    let lifetime = lifetime.unwrap_or_else(|| {
+        let lifetime = Lifetime::new("'__mv_return", Span::call_site());
+        let outlives = sig
+            .generics
+            .params
+            .iter()
+            .filter_map(|param| match param {
+                GenericParam::Lifetime(param)
+                    if elided || named.contains(&param.lifetime.ident.to_string()) =>
+                {
+                    let param = &param.lifetime;
+                    Some(quote! { #param: #lifetime })
+                }
+                GenericParam::Lifetime(_) => None,
+                GenericParam::Type(param) => {
+                    let param = &param.ident;
+                    Some(quote! { #param: #lifetime })
+                }
+                GenericParam::Const(_) => None,
+            })
+            .collect::<Vec<_>>();
+        sig.generics.params.push(parse_quote! { #lifetime });
+        let where_clause = sig.generics.make_where_clause();
+        for predicate in outlives {
+            where_clause.predicates.push(parse_quote! { #predicate });
+        }
+        lifetime
+    });
+    let (output, boxed) = if future {
+        (
+            quote! { core::pin::Pin<std::boxed::Box<dyn #(#traits)+* + #lifetime>> },
+            quote! { std::boxed::Box::pin(__multiversion_value) },
+        )
+    } else {
+        (
+            quote! { std::boxed::Box<dyn #(#traits)+* + #lifetime> },
+            quote! { std::boxed::Box::new(__multiversion_value) },
+        )
+    };
+    sig.output = parse_quote! { -> #output };
+
+    // The `#[fallback]` block is boxed like the body, and remains the fallback of the nested
+    // function.
+    let fallback = take_fallback(&mut func)?.map(|fallback| {
+        quote! {
+            #[fallback]
+            {
+                let __multiversion_value = (move || #fallback)();
+                #boxed
+            }
+        }
+    });
+    // `#[target_feature]` is a baseline of the nested function, and other attributes apply to the
+    // outer function.
+    let (inner_attrs, outer_attrs): (Vec<_>, Vec<_>) = func
+        .attrs
+        .iter()
+        .partition(|attr| attr.path().is_ident("target_feature"));
+    // The body is evaluated in a closure, so `return` expressions return the unboxed value.
+    let block = &func.block;
+    let inner = quote! {
+        #[multiversion::multiversion(#attr)]
+        #(#inner_attrs)*
+        #sig {
+            #fallback
+            let __multiversion_value = (move || #block)();
+            #boxed
+        }
+    };
+
+    let (outer_sig, uses) = util::dispatcher_signature(&func.sig);
+    let ident = &func.sig.ident;
+    let mut call = util::forward_call(quote! { #ident }, &outer_sig);
+    if outer_sig.unsafety.is_some() {
+        call = quote! { #[allow(unused_unsafe)] unsafe { #call } };
+    }
+    let vis = &func.vis;
+    Ok(quote! {
+        #(#outer_attrs)*
+        #vis #outer_sig {
+            #uses
+            #inner
+            #call
+        }
+    })
+}
+
+// Multiversions a C-variadic function by multiversioning a nested function that takes the
+// variable arguments as a `VaList`.  Variable arguments can't be forwarded to another C-variadic
+// function, but the `VaList` can be passed to any version.
+fn make_variadic(attr: TokenStream, func: ItemFn) -> syn::Result<TokenStream> {
+    let variadic = func.sig.variadic.as_ref().unwrap();
+    if !cfg!(feature = "nightly") {
+        return Err(Error::new(
+            variadic.span(),
+            "multiversioning C-variadic functions requires the `nightly` cargo feature",
+        ));
+    }
+    if util::uses_self(&func) {
+        return Err(Error::new(
+            variadic.span(),
+            "C-variadic methods can't be multiversioned",
+        ));
+    }
+
+    // Unnamed variable arguments are still passed to the nested function.
+    let va_list = match &variadic.pat {
+        Some((pat, _)) => match pat.as_ref() {
+            Pat::Ident(pat) => pat.ident.clone(),
+            _ => Ident::new("__multiversion_va_list", variadic.span()),
+        },
+        None => Ident::new("__multiversion_va_list", variadic.span()),
+    };
+    let va_pat = match &variadic.pat {
+        Some((pat, _)) => quote! { #pat },
+        None => quote! { #va_list },
+    };
+
+    // The nested function has the Rust ABI, since only the outer function is called from C.
+    let lifetime = Lifetime::new("'__mv_va_list", Span::call_site());
+    let mut sig = func.sig.clone();
+    sig.abi = None;
+    sig.variadic = None;
+    sig.generics.params.push(parse_quote! { #lifetime });
+    sig.inputs
+        .push(parse_quote! { #va_pat: core::ffi::VaList<#lifetime> });
+
+    // `#[target_feature]` is a baseline of the nested function, and other attributes apply to the
+    // outer function.
+    let (inner_attrs, outer_attrs): (Vec<_>, Vec<_>) = func
+        .attrs
+        .iter()
+        .partition(|attr| attr.path().is_ident("target_feature"));
+    let block = &func.block;
+    let inner = quote! {
+        #[multiversion::multiversion(#attr)]
+        #(#inner_attrs)*
+        #sig #block
+    };
+
+    let (mut outer_sig, uses) = util::dispatcher_signature(&func.sig);
+    if let Some(variadic) = outer_sig.variadic.as_mut() {
+        variadic.pat = Some((parse_quote! { #va_list }, Default::default()));
+    }
+    let mut call_sig = outer_sig.clone();
+    call_sig.variadic = None;
+    call_sig
+        .inputs
+        .push(parse_quote! { #va_list: core::ffi::VaList<'_> });
+    let ident = &func.sig.ident;
+    let call = util::forward_call(quote! { #ident }, &call_sig);
+    let vis = &func.vis;
+    Ok(quote! {
+        #(#outer_attrs)*
+        #vis #outer_sig {
+            #uses
+            #inner
+            #[allow(unused_unsafe)]
+            unsafe { #call }
+        }
+    })
+}
+
+// Removes the `target_feature` attributes of the function, returning the enabled features.
+//
+// The features aren't detected, so the function must be `unsafe` to call, like the function
+// written without `multiversion`.
+fn take_target_features(func: &mut ItemFn) -> syn::Result<Vec<LitStr>> {
+    let mut features = Vec::new();
+    let mut attrs = Vec::new();
+    for attr in func.attrs.drain(..) {
+        if !attr.path().is_ident("target_feature") {
+            attrs.push(attr);
+            continue;
+        }
+        if func.sig.unsafety.is_none() {
+            return Err(Error::new_spanned(
+                attr,
+                "`#[target_feature]` requires an `unsafe fn`, since the features aren't detected",
+            ));
+        }
+        attr.parse_nested_meta(|meta| {
+            if !meta.path.is_ident("enable") {
+                return Err(meta.error("expected `enable`"));
+            }
+            let enabled: LitStr = meta.value()?.parse()?;
+            for feature in enabled.value().split(',') {
+                features.push(LitStr::new(feature.trim(), enabled.span()));
+            }
+            Ok(())
+        })?;
+    }
+    func.attrs = attrs;
+    Ok(features)
+}
+
+// Removes the block marked `#[fallback]` from the body of the function, returning it.
+pub(crate) fn take_fallback(func: &mut ItemFn) -> syn::Result<Option<Block>> {
+    let is_fallback = |attr: &Attribute| attr.path().is_ident("fallback");
+    let mut fallback = None;
+    let mut stmts = Vec::new();
+    for stmt in func.block.stmts.drain(..) {
+        match stmt {
+            Stmt::Expr(Expr::Block(block), _) if block.attrs.iter().any(is_fallback) => {
+                if fallback.is_some() {
+                    return Err(Error::new(
+                        block.span(),
+                        "only one block can be marked `#[fallback]`",
+                    ));
+                }
+                if block.label.is_some() || block.attrs.len() > 1 {
+                    return Err(Error::new(
+                        block.span(),
+                        "the `#[fallback]` block can't have a label or other attributes",
+                    ));
+                }
+                fallback = Some(block.block);
+            }
+            stmt => stmts.push(stmt),
+        }
+    }
+    func.block.stmts = stmts;
+    Ok(fallback)
+}
+
+// Merges the features enabled by `#[target_feature]` into the targets and baseline of each
+// architecture that has the features, since the function already requires them.
+fn merge_enabled_features(
+    enabled: &[LitStr],
+    list: &mut [Target],
+    baselines: &mut Vec<Target>,
+) -> syn::Result<()> {
+    let span = enabled[0].span();
+    let features = enabled.iter().map(LitStr::value).collect::<Vec<_>>();
+    let mut arches = list
+        .iter()
+        .map(|t| t.arch().to_string())
+        .collect::<Vec<_>>();
+    arches.sort();
+    arches.dedup();
+    let mut merged_any = list.is_empty();
+    for arch in arches {
+        let spec = LitStr::new(&format!("{arch}+{}", features.join("+")), span);
+        let enabled = match Target::parse(&spec) {
+            Ok(enabled) => enabled,
+            // The features belong to another architecture.
+            Err(_) => continue,
+        };
+        merged_any = true;
+        match baselines
+            .iter_mut()
+            .find(|baseline| baseline.arch() == arch)
+        {
+            Some(baseline) => baseline.merge_features(&enabled),
+            None => baselines.push(enabled.clone()),
+        }
+        for target in list.iter_mut().filter(|target| target.arch() == arch) {
+            if target.features_subset_of(&enabled) {
+                return Err(Error::new(
+                    span,
+                    format!(
+                        "target `{arch}+{}` is already enabled by `#[target_feature]`",
+                        target.features().join("+")
+                    ),
+                ));
+            }
+            target.merge_features(&enabled);
+        }
+    }
+    if !merged_any {
+        return Err(Error::new(
+            span,
+            "the features enabled by `#[target_feature]` aren't available on the architecture of any target",
+        ));
+    }
+    for (i, target) in list.iter().enumerate() {
+        if list[..i].contains(target) {
+            return Err(Error::new(
+                span,
+                format!(
+                    "multiple targets become `{}+{}` with the features enabled by `#[target_feature]`",
+                    target.arch(),
+                    target.features().join("+")
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn make_multiversioned_fn(
+    attr: TokenStream,
+    mut func: ItemFn,
+) -> Result<TokenStream, syn::Error> {
+    // Foreign attributes are applied to the outermost function, after the other attributes, so
+    // they're expanded after `multiversion` regardless of the order of the attributes.  Functions
+    // that are wrapped apply them to the wrapper, and the original function never has them.
+    let (attr, foreign_attrs) = take_foreign_attrs(attr)?;
+    let with_foreign_attrs = |mut func: ItemFn| {
+        func.attrs.extend(foreign_attrs.iter().cloned());
+        func
+    };
+    let (attr, self_ty) = take_self_type(attr)?;
+    if let Some(self_ty) = &self_ty {
+        func = impl_block::resolve_self_type(func, self_ty)?;
+    }
+    if func.sig.variadic.is_some() {
+        return make_variadic(attr, with_foreign_attrs(func));
+    }
+    let (attr, share_generic_backend) = take_share_generic_backend(attr)?;
+    if share_generic_backend {
+        return make_shared_backend(attr, with_foreign_attrs(func));
+    }
+    let (attr, dispatcher_visibility) = take_dispatcher_visibility(attr)?;
+    if let Some(vis) = dispatcher_visibility {
+        return make_dispatcher_wrapper(attr, vis, with_foreign_attrs(func));
+    }
+    let (attr, boxed_return) = take_boxed_return(attr)?;
+    if boxed_return {
+        return make_boxed_return(attr, with_foreign_attrs(func));
+    }
+
+    if let ReturnType::Type(_, ty) = &func.sig.output {
+        // Coroutines are driven by a coroutine returned by the dispatcher.
+        let coroutine = cfg!(feature = "nightly") && util::returns_coroutine(&func.sig);
+        if matches!(**ty, Type::ImplTrait(_)) && !coroutine {
+            return Err(Error::new(
+                ty.span(),
+                "cannot multiversion function with `impl Trait` return type without `boxed_return`",
+            ));
+        }
+    }
+
+    // Symbol names are only applied to the dispatcher, and the versions have internal names.
+    let symbol_attrs = func
+        .attrs
+        .iter()
+        .chain(&foreign_attrs)
+        .filter(|attr| util::symbol_attr(attr).is_some())
+        .collect::<Vec<_>>();
+    if symbol_attrs.len() > 1 {
+        return Err(Error::new(
+            symbol_attrs[1].span(),
+            "only one of `no_mangle` or `export_name` can be specified",
+        ));
+    }
+
+    let mut targets: Option<Targets> = None;
+    let mut inner_attrs: Option<Vec<Attribute>> = None;
+    let mut forward_attrs: Option<(Option<Vec<Path>>, Vec<Path>)> = None;
+    let mut dispatcher: Option<DispatchMethod> = None;
+    let mut baselines: Option<Vec<Target>> = None;
+    let mut resolve = false;
+    let mut fn_ptr = false;
+    let mut pinned = false;
+    let mut elision = None;
+    let mut trampoline = false;
+    let mut helpers: Option<bool> = None;
+    let mut boxed_future = false;
+    let mut force_target = false;
+    let mut detector: Option<Path> = None;
+    let mut priority_model: Option<PriorityModel> = None;
+    let mut avx512_auto: Option<bool> = None;
+    let mut all_cores: Option<bool> = None;
+    let mut min_stack: Option<usize> = None;
+    let mut variants: Option<Variants> = None;
+    let mut version_names: Option<VersionNames> = None;
+    let mut keep_original: Option<Ident> = None;
+    let mut group: Option<GroupRole> = None;
+    let mut tracked = Vec::new();
+
+    let parser = syn::meta::parser(|meta| {
+        if targets.is_some() && (meta.path.is_ident("targets") || meta.path.is_ident("simd")) {
+            return Err(meta.error("can't specify `targets` or `simd` multiple times"));
+        }
+
+        if meta.path.is_ident("targets") {
+            if meta.input.peek(token::Paren) {
+                let content;
+                parenthesized!(content in meta.input);
+                targets = Some(if content.peek(Ident) && content.peek2(token::Eq) {
+                    Targets::Profiles(parse_profiles(&content, &mut tracked)?)
+                } else {
+                    Targets::List(parse_targets(&content)?)
+                });
+            } else {
+                let value = meta.value()?;
+                targets = Some(if value.peek(LitStr) {
+                    Targets::List(parse_target_list(value, &mut tracked)?)
+                } else {
+                    let path: Path = value.parse()?;
+                    if !value.peek(token::Not) {
+                        return Err(Error::new(
+                            path.span(),
+                            "expected a target list macro, such as `targets = my_targets!()` \
+                             (constants can't be read by `multiversion`, see `target_list!`)",
+                        ));
+                    }
+                    value.parse::<token::Not>()?;
+                    let content;
+                    parenthesized!(content in value);
+                    if path.is_ident("env") {
+                        Targets::List(env_targets(&content.parse()?)?)
+                    } else {
+                        if !content.is_empty() {
+                            return Err(content.error("expected no arguments"));
+                        }
+                        Targets::Macro(path)
+                    }
+                });
+            }
+            return Ok(());
+        }
+
+        if meta.path.is_ident("attrs") {
+            if inner_attrs.is_some() {
+                return Err(meta.error("can't specify `attrs` multiple times"));
+            }
+            inner_attrs = Some(Vec::new());
+            let content;
+            parenthesized!(content in meta.input);
+            let attrs: Vec<Attribute> =
+                Punctuated::<Meta, token::Comma>::parse_terminated(&content)?
+                    .into_iter()
+                    .map(|meta| parse_quote! { #[#meta] })
+                    .collect();
+            // Every version would have the same symbol name.
+            if let Some((attr, name)) = attrs
+                .iter()
+                .find_map(|attr| util::symbol_attr(attr).map(|name| (attr, name)))
+            {
+                return Err(Error::new(
+                    attr.span(),
+                    format!("`{name}` can't be applied to each version, apply it to the multiversioned function instead"),
+                ));
+            }
+            inner_attrs = Some(attrs);
+            return Ok(());
+        }
+
+        if meta.path.is_ident("forward_attrs") {
+            if forward_attrs.is_some() {
+                return Err(meta.error("can't specify `forward_attrs` multiple times"));
+            }
+            let mut outer: Option<Vec<Path>> = None;
+            let mut inner: Option<Vec<Path>> = None;
+            meta.parse_nested_meta(|meta| {
+                let list = if meta.path.is_ident("outer") {
+                    &mut outer
+                } else if meta.path.is_ident("inner") {
+                    &mut inner
+                } else {
+                    return Err(meta.error("expected `outer` or `inner`"));
+                };
+                if list.is_some() {
+                    return Err(meta.error("can't specify the same list multiple times"));
+                }
+                let mut paths = Vec::new();
+                meta.parse_nested_meta(|meta| {
+                    paths.push(meta.path);
+                    Ok(())
+                })?;
+                *list = Some(paths);
+                Ok(())
+            })?;
+            forward_attrs = Some((outer, inner.unwrap_or_default()));
+            return Ok(());
+        }
+
+        if meta.path.is_ident("baseline") {
+            if baselines.is_some() {
+                return Err(meta.error("can't specify `baseline` multiple times"));
+            }
+            let list = if meta.input.peek(token::Paren) {
+                let content;
+                parenthesized!(content in meta.input);
+                Punctuated::<LitStr, token::Comma>::parse_terminated(&content)?
+                    .into_iter()
+                    .collect()
+            } else {
+                vec![meta.value()?.parse::<LitStr>()?]
+            };
+            let mut parsed: Vec<Target> = Vec::new();
+            for s in list {
+                let target = Target::parse_untuned(&s)?;
+                if !target.has_features_specified() {
+                    return Err(Error::new(
+                        s.span(),
+                        "baseline must have features specified",
+                    ));
+                }
+                if parsed.iter().any(|t| t.arch() == target.arch()) {
+                    return Err(Error::new(
+                        s.span(),
+                        "can't specify multiple baselines for the same architecture",
+                    ));
+                }
+                parsed.push(target);
+            }
+            baselines = Some(parsed);
+            return Ok(());
+        }
+
+        if meta.path.is_ident("resolve") {
+            if resolve {
+                return Err(meta.error("can't specify `resolve` multiple times"));
+            }
+            resolve = true;
+            return Ok(());
+        }
+
+        if meta.path.is_ident("fn_ptr") {
+            if fn_ptr {
+                return Err(meta.error("can't specify `fn_ptr` multiple times"));
+            }
+            fn_ptr = true;
+            return Ok(());
+        }
+
+        if meta.path.is_ident("pinned") {
+            if pinned {
+                return Err(meta.error("can't specify `pinned` multiple times"));
+            }
+            pinned = true;
+            return Ok(());
+        }
+
+        if meta.path.is_ident("trampoline") {
+            if trampoline {
+                return Err(meta.error("can't specify `trampoline` multiple times"));
+            }
+            trampoline = true;
+            return Ok(());
+        }
+
+        if meta.path.is_ident("detector") {
+            if detector.is_some() {
+                return Err(meta.error("can't specify `detector` multiple times"));
+            }
+            let value = meta.value()?;
+            detector = Some(if value.peek(LitStr) {
+                value.parse::<LitStr>()?.parse()?
+            } else {
+                value.parse()?
+            });
+            return Ok(());
+        }
+
+        if meta.path.is_ident("priority_model") {
+            if priority_model.is_some() {
+                return Err(meta.error("can't specify `priority_model` multiple times"));
+            }
+            let path: LitStr = meta.value()?.parse()?;
+            priority_model = Some(PriorityModel::read(&path)?);
+            return Ok(());
+        }
+
+        if meta.path.is_ident("avx512_width") {
+            if avx512_auto.is_some() {
+                return Err(meta.error("can't specify `avx512_width` multiple times"));
+            }
+            let s: LitStr = meta.value()?.parse()?;
+            avx512_auto = Some(match s.value().as_str() {
+                "auto" => true,
+                "512" => false,
+                _ => return Err(Error::new(s.span(), "expected `auto` or `512`")),
+            });
+            return Ok(());
+        }
+
+        if meta.path.is_ident("detect") {
+            if all_cores.is_some() {
+                return Err(meta.error("can't specify `detect` multiple times"));
+            }
+            let s: LitStr = meta.value()?.parse()?;
+            all_cores = Some(match s.value().as_str() {
+                "current_core" => false,
+                "all_cores" => true,
+                _ => return Err(meta.error("expected `current_core` or `all_cores`")),
+            });
+            return Ok(());
+        }
+
+        if meta.path.is_ident("min_stack") {
+            if min_stack.is_some() {
+                return Err(meta.error("can't specify `min_stack` multiple times"));
+            }
+            if !cfg!(feature = "std") {
+                return Err(meta.error("`min_stack` requires the `std` cargo feature"));
+            }
+            min_stack = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+            return Ok(());
+        }
+
+        // Added by the `group` attribute.
+        if meta.path.is_ident("__group") {
+            let content;
+            parenthesized!(content in meta.input);
+            let role: Ident = content.parse()?;
+            group = Some(if role == "member" {
+                GroupRole::Member
+            } else if role == "selector" {
+                GroupRole::Selector
+            } else {
+                return Err(Error::new(role.span(), "expected `member` or `selector`"));
+            });
+            return Ok(());
+        }
+
+        if meta.path.is_ident("variants") {
+            if variants.is_some() {
+                return Err(meta.error("can't specify `variants` multiple times"));
+            }
+            let content;
+            parenthesized!(content in meta.input);
+            let vis: Visibility = content.parse()?;
+            if !matches!(vis, Visibility::Inherited) && !content.is_empty() {
+                content.parse::<token::Comma>()?;
+            }
+            let mut module = func.sig.ident.clone();
+            if !content.is_empty() {
+                let keyword: Ident = content.parse()?;
+                if keyword != "module" {
+                    return Err(Error::new(keyword.span(), "expected `module`"));
+                }
+                content.parse::<token::Eq>()?;
+                module = content.parse::<LitStr>()?.parse()?;
+                content.parse::<Option<token::Comma>>()?;
+            }
+            if !content.is_empty() {
+                return Err(content.error("unexpected token"));
+            }
+            variants = Some(Variants { vis, module });
+            return Ok(());
+        }
+
+        if meta.path.is_ident("version_names") {
+            if version_names.is_some() {
+                return Err(meta.error("can't specify `version_names` multiple times"));
+            }
+            let value = meta.value()?;
+            let s: LitStr = value.parse()?;
+            version_names = Some(match s.value().as_str() {
+                "features" => VersionNames::Features,
+                "short" => VersionNames::Short,
+                _ => return Err(Error::new(s.span(), "expected `features` or `short`")),
+            });
+            return Ok(());
+        }
+
+        if meta.path.is_ident("keep_original") {
+            if keep_original.is_some() {
+                return Err(meta.error("can't specify `keep_original` multiple times"));
+            }
+            let value = meta.value()?;
+            keep_original = Some(value.parse::<LitStr>()?.parse()?);
+            return Ok(());
+        }
+
+        if meta.path.is_ident("force_target") {
+            if force_target {
+                return Err(meta.error("can't specify `force_target` multiple times"));
+            }
+            force_target = true;
+            return Ok(());
+        }
+
+        if meta.path.is_ident("boxed_future") {
+            if boxed_future {
+                return Err(meta.error("can't specify `boxed_future` multiple times"));
+            }
+            boxed_future = true;
+            return Ok(());
+        }
+
+        if meta.path.is_ident("elision") {
+            if elision.is_some() {
+                return Err(meta.error("can't specify `elision` multiple times"));
+            }
+            let value = meta.value()?;
+            elision = Some(value.parse::<LitBool>()?.value);
+            return Ok(());
+        }
+
+        if meta.path.is_ident("helpers") {
+            if helpers.is_some() {
+                return Err(meta.error("can't specify `helpers` multiple times"));
+            }
+            let value = meta.value()?;
+            helpers = Some(value.parse::<LitBool>()?.value);
+            return Ok(());
+        }
+
+        if meta.path.is_ident("dispatcher") {
+            if dispatcher.is_some() {
+                return Err(meta.error("can't specify `dispatcher` multiple times"));
+            }
+            let value = meta.value()?;
+            let s: LitStr = value.parse()?;
+            dispatcher =
+                Some(match s.value().as_str() {
+                    "default" => DispatchMethod::Default,
+                    "static" => DispatchMethod::Static,
+                    "direct" => DispatchMethod::Direct,
+                    "indirect" => DispatchMethod::Indirect,
+                    "once" => DispatchMethod::Once,
+                    "tls" => DispatchMethod::Tls,
+                    "ifunc" => DispatchMethod::Ifunc,
+                    "inline" => DispatchMethod::Inline,
+                    "ctor" => DispatchMethod::Ctor,
+                    _ => return Err(meta.error(
+                        "expected `default`, `static`, `direct`, `indirect`, `once`, `tls`, `ifunc`, `inline`, or `ctor`",
+                    )),
+                });
+            return Ok(());
+        };
+
+        Err(meta.error("unrecognized option"))
+    });
+
+    let span = attr.span();
+    parser.parse2(attr.clone())?;
+
+    // Target list macros are expanded first, and reapply the attribute with their targets.
+    if let Some(Targets::Macro(path)) = &targets {
+        let options = split_options(attr)
+            .into_iter()
+            .filter(|option| !matches!(option.first(), Some(TokenTree::Ident(ident)) if ident == "targets"))
+            .map(|option| option.into_iter().collect::<TokenStream>())
+            .chain((!foreign_attrs.is_empty()).then(|| {
+                let metas = foreign_attrs.iter().map(|attr| &attr.meta);
+                quote! { foreign_attrs(#(#metas),*) }
+            }));
+        return Ok(quote! {
+            #path! { @multiversion [#(#options),*] #func }
+        });
+    }
+
+    let mut fallback = take_fallback(&mut func)?;
+
+    // The original function is emitted unchanged, other than its name.  Symbol names only apply
+    // to the multiversioned function.
+    let original = keep_original.map(|ident| {
+        let mut original = func.clone();
+        original.sig.ident = ident;
+        original
+            .attrs
+            .retain(|attr| util::symbol_attr(attr).is_none());
+        original
+    });
+    let mut func = with_foreign_attrs(func);
+
+    let enabled_features = take_target_features(&mut func)?;
+    let targets = if let Some(mut targets) = targets {
+        let lists: Vec<&mut Vec<Target>> = match &mut targets {
+            Targets::List(list) => vec![list],
+            Targets::Profiles(profiles) => profiles.iter_mut().map(|(_, list)| list).collect(),
+            Targets::Macro(_) => unreachable!(),
+        };
+        for list in lists {
+            if !enabled_features.is_empty() {
+                merge_enabled_features(
+                    &enabled_features,
+                    list,
+                    baselines.get_or_insert_with(Vec::new),
+                )?;
+            }
+            // Each AVX-512 target is preceded by its 256-bit variant, which is only selected on
+            // CPUs that prefer 256-bit vectors.
+            if avx512_auto == Some(true) {
+                *list = list
+                    .drain(..)
+                    .flat_map(|target| target.narrow_avx512().into_iter().chain(Some(target)))
+                    .collect();
+            }
+            targets::require_features(list, span)?;
+            // The index of the selected target is stored in a byte.
+            if list.len() >= u8::MAX as usize {
+                return Err(Error::new(span, "too many targets"));
+            }
+        }
+        targets
+    } else {
+        return Err(Error::new(span, "expected `targets`"));
+    };
+
+    // Attributes of the function that are forwarded to the clones are removed from the
+    // multiversioned function.  If `outer` is specified, the multiversioned function only keeps
+    // the attributes it lists, and its `cfg`s.
+    let mut inner_attrs = inner_attrs.unwrap_or_default();
+    if let Some((outer, inner)) = forward_attrs {
+        let listed = |list: &[Path], attr: &Attribute| list.iter().any(|path| attr.path() == path);
+        let mut attrs = Vec::new();
+        for attr in func.attrs.drain(..) {
+            let forwarded = listed(&inner, &attr);
+            if forwarded {
+                if let Some(name) = util::symbol_attr(&attr) {
+                    return Err(Error::new(
+                        attr.span(),
+                        format!("`{name}` can't be applied to each version, apply it to the multiversioned function instead"),
+                    ));
+                }
+                inner_attrs.push(attr.clone());
+            }
+            let kept = match &outer {
+                Some(outer) => listed(outer, &attr) || attr.path().is_ident("cfg"),
+                None => !forwarded,
+            };
+            if kept {
+                attrs.push(attr);
+            }
+        }
+        func.attrs = attrs;
+    }
+
+    // Lint expectations are fulfilled by any clone, so they're applied to the outer function once
+    // rather than to each clone.
+    let (expect_attrs, inner_attrs): (Vec<_>, Vec<_>) = inner_attrs
+        .into_iter()
+        .partition(|attr: &Attribute| attr.path().is_ident("expect"));
+    func.attrs.extend(expect_attrs);
+    let mut dispatcher = dispatcher.unwrap_or(DispatchMethod::Default);
+    // Functions in a group dispatch directly with the group's selection, which also calls the
+    // versions, so the versions are placed in a module.
+    if group == Some(GroupRole::Member) {
+        if !matches!(dispatcher, DispatchMethod::Default | DispatchMethod::Direct) {
+            return Err(Error::new(
+                span,
+                "functions in a dispatch group can only use the `direct` dispatcher",
+            ));
+        }
+        dispatcher = DispatchMethod::Direct;
+        variants.get_or_insert_with(|| Variants {
+            vis: Visibility::Inherited,
+            module: func.sig.ident.clone(),
+        });
+    }
+    if boxed_future {
+        if func.sig.asyncness.is_none() {
+            return Err(Error::new(
+                span,
+                "`boxed_future` can only be used with async functions",
+            ));
+        }
+        if !matches!(
+            dispatcher,
+            DispatchMethod::Default | DispatchMethod::Indirect
+        ) {
+            return Err(Error::new(
+                span,
+                "`boxed_future` can only be used with the `default` or `indirect` dispatcher",
+            ));
+        }
+    }
+    let all_cores = all_cores.unwrap_or(false);
+    if all_cores {
+        if detector.is_some() {
+            return Err(Error::new(
+                span,
+                "`detect = \"all_cores\"` can't be used with a `detector`",
+            ));
+        }
+        if dispatcher == DispatchMethod::Ifunc {
+            return Err(Error::new(
+                span,
+                "`detect = \"all_cores\"` can't be used with the `ifunc` dispatcher",
+            ));
+        }
+    }
+    // The resolver runs before relocations are processed, where arbitrary code isn't safe.
+    if detector.is_some() && dispatcher == DispatchMethod::Ifunc {
+        return Err(Error::new(
+            span,
+            "`detector` can't be used with the `ifunc` dispatcher",
+        ));
+    }
+    // Identifying the CPU for the model may read files, which can't be done in an indirect
+    // function resolver.
+    if priority_model.is_some() && dispatcher == DispatchMethod::Ifunc {
+        return Err(Error::new(
+            span,
+            "`priority_model` can't be used with the `ifunc` dispatcher",
+        ));
+    }
+    if priority_model.is_some()
+        && matches!(dispatcher, DispatchMethod::Static | DispatchMethod::Inline)
+    {
+        return Err(Error::new(
+            span,
+            "`priority_model` can't be used with the `static` or `inline` dispatcher",
+        ));
+    }
+    let baselines = baselines.unwrap_or_default();
+
+    // The items that track configuration files are placed in the body, since the function may be
+    // in an impl block, where unnamed constants aren't allowed.
+    let tracked = tracked
+        .iter()
+        .map(|item| parse_quote! { #item })
+        .collect::<Vec<Stmt>>();
+    func.block.stmts.splice(0..0, tracked.iter().cloned());
+    if let Some(fallback) = &mut fallback {
+        fallback.stmts.splice(0..0, tracked);
+    }
+
+    let make_dispatcher = |targets| {
+        Dispatcher {
+            targets,
+            func: func.clone(),
+            inner_attrs: inner_attrs.clone(),
+            dispatcher,
+            baselines: baselines.clone(),
+            resolve,
+            fn_ptr,
+            pinned,
+            trampoline,
+            helpers,
+            boxed_future,
+            force_target,
+            all_cores,
+            elision: elision.unwrap_or(true),
+            min_stack,
+            variants: variants.clone(),
+            version_names: version_names.unwrap_or(VersionNames::Features),
+            group,
+            detector: detector.clone(),
+            priority_model: priority_model.clone(),
+            fallback: fallback.clone(),
+            readable: cfg!(readable),
+        }
+        .to_token_stream()
+    };
+
+    let dispatcher = match targets {
+        Targets::List(targets) => make_dispatcher(targets),
+        Targets::Macro(_) => unreachable!(),
+        Targets::Profiles(profiles) => {
+            // The profile isn't known to the proc macro, so expand every profile and let the
+            // runtime crate select the current one.  Unlisted profiles aren't multiversioned.
+            let unlisted = make_dispatcher(Vec::new());
+            let (profile, listed): (Vec<_>, Vec<_>) = profiles
+                .into_iter()
+                .map(|(profile, targets)| (profile, make_dispatcher(targets)))
+                .unzip();
+            quote! {
+                multiversion::__select_profile! {
+                    [#unlisted]
+                    #(#profile { #listed })*
+                }
+            }
+        }
+    };
+
+    Ok(quote! {
+        #dispatcher
+        #original
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn symbol_names() {
+        let func: ItemFn = parse_quote! {
+            #[no_mangle]
+            #[export_name = "g"]
+            extern "C" fn f() {}
+        };
+        make_multiversioned_fn(quote! { targets("x86_64+avx2") }, func).unwrap_err();
+
+        let func: ItemFn = parse_quote! {
+            #[no_mangle]
+            extern "C" fn f() {}
+        };
+        make_multiversioned_fn(quote! { targets("x86_64+avx2") }, func.clone()).unwrap();
+        make_multiversioned_fn(
+            quote! { targets("x86_64+avx2"), attrs(export_name = "g") },
+            func,
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn ifunc_detector() {
+        let func: ItemFn = parse_quote! { fn f() {} };
+        make_multiversioned_fn(
+            quote! { targets("x86_64+avx2"), dispatcher = "ifunc", detector = detect },
+            func,
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn profiles() {
+        let func: ItemFn = parse_quote! { fn f() {} };
+        make_multiversioned_fn(quote! { targets(release = "simd", dev = ()) }, func.clone())
+            .unwrap();
+        make_multiversioned_fn(
+            quote! { targets(release = "simd", bench = ()) },
+            func.clone(),
+        )
+        .unwrap_err();
+        make_multiversioned_fn(quote! { targets(test = "simd") }, func).unwrap_err();
+    }
+
+    #[test]
+    fn shared_backend() {
+        let options = quote! { targets("x86_64+avx2"), share_generic_backend = true };
+        let func: ItemFn = parse_quote! {
+            fn f<T: AsRef<[u8]>>(x: T) -> usize {
+                #[backend]
+                fn g(x: &[u8]) -> usize {
+                    x.len()
+                }
+                g(x.as_ref())
+            }
+        };
+        let tokens = make_multiversioned_fn(options.clone(), func)
+            .unwrap()
+            .to_string();
+        assert!(!tokens.contains("backend"));
+        assert!(tokens.starts_with("fn f < T : AsRef < [u8] > >"));
+
+        let func: ItemFn = parse_quote! {
+            fn f<T>(x: T) {}
+        };
+        make_multiversioned_fn(options.clone(), func).unwrap_err();
+
+        let func: ItemFn = parse_quote! {
+            fn f<T>(x: T) {
+                #[backend]
+                fn g<U>(x: U) {}
+            }
+        };
+        make_multiversioned_fn(options, func).unwrap_err();
+
+        let func: ItemFn = parse_quote! {
+            fn f() {}
+        };
+        make_multiversioned_fn(
+            quote! { share_generic_backend = true, share_generic_backend = false },
+            func,
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn target_feature() {
+        let func: ItemFn = parse_quote! {
+            #[target_feature(enable = "popcnt")]
+            unsafe fn f() {}
+        };
+        let tokens = make_multiversioned_fn(
+            quote! { targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon") },
+            func,
+        )
+        .unwrap()
+        .to_string();
+        assert!(tokens.contains("f_x86_64_baseline_version"));
+        assert!(tokens.contains("f_popcnt_sse_sse2_sse3_sse41_ssse3_version"));
+
+        let err = make_multiversioned_fn(
+            quote! { targets("x86_64+avx2", "x86_64+sse4.1") },
+            parse_quote! {
+                #[target_feature(enable = "avx2")]
+                unsafe fn f() {}
+            },
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("is already enabled by `#[target_feature]`"));
+
+        let err = make_multiversioned_fn(
+            quote! { targets("aarch64+neon") },
+            parse_quote! {
+                #[target_feature(enable = "sse4.2")]
+                unsafe fn f() {}
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("aren't available"));
+
+        let err = make_multiversioned_fn(
+            quote! { targets("x86_64+avx2") },
+            parse_quote! {
+                #[target_feature(enable = "popcnt")]
+                fn f() {}
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("requires an `unsafe fn`"));
+    }
+
+    #[test]
+    fn boxed_return() {
+        let func: ItemFn = parse_quote! {
+            fn evens(x: &[u32]) -> impl Iterator<Item = &u32> {
+                x.iter().filter(|x| *x % 2 == 0)
+            }
+        };
+        let tokens = make_boxed_return(quote! { targets = "simd" }, func.clone())
+            .unwrap()
+            .to_string();
+        assert!(tokens.contains(
+            &quote! {
+                -> std::boxed::Box<dyn Iterator<Item = &'__mv_elided_0 u32> + '__mv_return>
+            }
+            .to_string()
+        ));
+        assert!(tokens.contains(&quote! { '__mv_elided_0: '__mv_return }.to_string()));
+        assert!(make_multiversioned_fn(quote! { targets = "simd" }, func).is_err());
+
+        let func: ItemFn = parse_quote! {
+            fn ready<'a>(x: &'a u32) -> impl Future<Output = u32> + Send + 'a {
+                async move { *x }
+            }
+        };
+        let tokens = make_multiversioned_fn(quote! { targets = "simd", boxed_return }, func)
+            .unwrap()
+            .to_string();
+        assert!(tokens.contains(
+            &quote! { -> core::pin::Pin<std::boxed::Box<dyn Future<Output = u32> + Send + 'a> > }
+                .to_string()
+        ));
+
+        let func: ItemFn = parse_quote! {
+            fn f() -> u32 { 0 }
+        };
+        assert!(make_multiversioned_fn(quote! { targets = "simd", boxed_return }, func).is_err());
+    }
+
+    #[test]
+    fn variadic() {
+        let func: ItemFn = parse_quote! {
+            #[no_mangle]
+            unsafe extern "C" fn sum(count: usize, mut args: ...) -> i32 {
+                (0..count).map(|_| unsafe { args.next_arg::<i32>() }).sum()
+            }
+        };
+        let tokens = make_multiversioned_fn(quote! { targets = "simd" }, func);
+        if cfg!(feature = "nightly") {
+            let tokens = tokens.unwrap().to_string();
+            assert!(tokens.contains(
+                &quote! { #[no_mangle] unsafe extern "C" fn sum(count: usize, args: ...) -> i32 }
+                    .to_string()
+            ));
+            assert!(tokens.contains(
+                &quote! {
+                    unsafe fn sum<'__mv_va_list>(
+                        count: usize,
+                        mut args: core::ffi::VaList<'__mv_va_list>
+                    ) -> i32
+                }
+                .to_string()
+            ));
+            assert!(tokens.contains(&quote! { unsafe { sum(count, args) } }.to_string()));
+        } else {
+            assert!(tokens.is_err());
+        }
+    }
+
+    #[test]
+    fn const_fn() {
+        let func: ItemFn = parse_quote! {
+            const fn add(x: u32, y: u32) -> u32 {
+                x + y
+            }
+        };
+        let tokens = make_multiversioned_fn(
+            quote! { targets("x86_64+avx2"), dispatcher = "static" },
+            func.clone(),
+        )
+        .unwrap()
+        .to_string();
+        assert!(tokens.contains("const fn add (x : u32 , y : u32) -> u32"));
+        assert!(!tokens.contains("__static_target_override"));
+
+        for attr in [
+            quote! { targets("x86_64+avx2"), dispatcher = "direct" },
+            quote! { targets("x86_64+avx2"), dispatcher = "static", fn_ptr },
+        ] {
+            let tokens = make_multiversioned_fn(attr, func.clone())
+                .unwrap()
+                .to_string();
+            assert!(tokens.contains("compile_error"));
+        }
+    }
+
+    #[test]
+    fn forward_attrs() {
+        let func: ItemFn = parse_quote! {
+            #[must_use]
+            #[doc = "Adds."]
+            #[no_mangle]
+            fn add(x: u32, y: u32) -> u32 {
+                x + y
+            }
+        };
+        let forwarded = |attr| {
+            make_multiversioned_fn(attr, func.clone())
+                .map(|tokens| tokens.to_string())
+                .map_err(|_| ())
+        };
+
+        // Attributes listed in both lists are applied to the dispatcher and the versions.
+        let tokens = forwarded(quote! {
+            targets("x86_64+avx2"), forward_attrs(inner(must_use, doc), outer(doc, no_mangle))
+        })
+        .unwrap();
+        assert!(tokens.starts_with(&quote! { #[doc = "Adds."] #[no_mangle] fn add }.to_string()));
+        assert!(tokens.contains(&quote! { #[must_use] #[doc = "Adds."] }.to_string()));
+
+        // With `outer`, the dispatcher only keeps the attributes it lists.
+        let tokens =
+            forwarded(quote! { targets("x86_64+avx2"), forward_attrs(outer(doc)) }).unwrap();
+        assert!(tokens.starts_with(&quote! { #[doc = "Adds."] fn add }.to_string()));
+        assert!(!tokens.contains("must_use"));
+
+        assert!(
+            forwarded(quote! { targets("x86_64+avx2"), forward_attrs(inner(no_mangle)) }).is_err()
+        );
+        assert!(forwarded(quote! { targets("x86_64+avx2"), forward_attrs(both(doc)) }).is_err());
+    }
+
+    #[test]
+    fn foreign_attrs() {
+        let func: ItemFn = parse_quote! {
+            /// Adds.
+            fn add(x: u32, y: u32) -> u32 {
+                x + y
+            }
+        };
+        let tokens = make_multiversioned_fn(
+            quote! { targets("x86_64+avx2"), foreign_attrs(wasm_bindgen, pyo3::pyfunction) },
+            func.clone(),
+        )
+        .unwrap()
+        .to_string();
+        assert!(tokens.starts_with(
+            &quote! { #[doc = r" Adds."] #[wasm_bindgen] #[pyo3::pyfunction] fn add }.to_string()
+        ));
+        assert_eq!(tokens.matches("wasm_bindgen").count(), 1);
+
+        // The original function doesn't have the foreign attributes.
+        let tokens = make_multiversioned_fn(
+            quote! { targets("x86_64+avx2"), foreign_attrs(deprecated), keep_original = "orig" },
+            func.clone(),
+        )
+        .unwrap()
+        .to_string();
+        assert_eq!(tokens.matches("deprecated").count(), 1);
+        assert!(tokens.contains(&quote! { fn orig }.to_string()));
+
+        assert!(make_multiversioned_fn(
+            quote! { targets("x86_64+avx2"), foreign_attrs(a), foreign_attrs(b) },
+            func,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn fallback() {
+        let func: ItemFn = parse_quote! {
+            fn sum(x: &[f32]) -> f32 {
+                #[fallback]
+                {
+                    scalar_sum(x)
+                }
+                simd_sum(x)
+            }
+        };
+        let tokens = make_multiversioned_fn(
+            quote! { targets("x86_64+avx2"), dispatcher = "static", keep_original = "sum_original" },
+            func,
+        )
+        .unwrap()
+        .to_string();
+        assert!(!tokens.contains("fallback"));
+        assert_eq!(tokens.matches("scalar_sum").count(), 1);
+        assert!(tokens
+            .contains(&quote! { fn sum_original(x: &[f32]) -> f32 { simd_sum(x) } }.to_string()));
+
+        let twice = make_multiversioned_fn(
+            quote! { targets("x86_64+avx2") },
+            parse_quote! {
+                fn f() {
+                    #[fallback]
+                    {}
+                    #[fallback]
+                    {}
+                }
+            },
+        );
+        assert!(twice.is_err());
+
+        // Functions that are wrapped keep the fallback for the nested multiversioned function.
+        let boxed = make_multiversioned_fn(
+            quote! { targets("x86_64+avx2"), boxed_return },
+            parse_quote! {
+                fn f(x: &[u32]) -> impl Iterator<Item = u32> + '_ {
+                    #[fallback]
+                    {
+                        x.iter().copied()
+                    }
+                    x.iter().copied().rev()
+                }
+            },
+        )
+        .unwrap()
+        .to_string();
+        assert_eq!(boxed.matches("# [fallback]").count(), 1);
+        assert!(boxed.contains(
+            "# [fallback] { let __multiversion_value = (move || { x . iter () . copied () }) ()"
+        ));
+        let wrapped = make_multiversioned_fn(
+            quote! { targets("x86_64+avx2"), dispatcher_visibility = "pub(crate)" },
+            parse_quote! {
+                fn f() -> u32 {
+                    #[fallback]
+                    {
+                        1
+                    }
+                    2
+                }
+            },
+        )
+        .unwrap()
+        .to_string();
+        assert!(!wrapped.contains("fallback"));
+    }
+
+    #[test]
+    fn self_type() {
+        let func: ItemFn = parse_quote! {
+            fn scale(x: u32) -> Self {
+                Self::new(x * Self::FACTOR)
+            }
+        };
+        let tokens = make_multiversioned_fn(
+            quote! { targets("x86_64+avx2"), self_type = "Scaler<u32>", dispatcher = "static" },
+            func.clone(),
+        )
+        .unwrap()
+        .to_string();
+        assert!(!tokens.contains("Self"));
+        assert!(
+            tokens.contains(&quote! { Scaler::<u32>::new(x * Scaler::<u32>::FACTOR) }.to_string())
+        );
+
+        assert!(make_multiversioned_fn(
+            quote! { targets("x86_64+avx2"), self_type = "Scaler", fn_ptr },
+            func,
+        )
+        .is_err());
+        assert!(make_multiversioned_fn(
+            quote! { targets("x86_64+avx2"), self_type = "Scaler" },
+            parse_quote! { fn get(&self) -> u32 { self.0 } },
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn dispatcher_visibility() {
+        let func: ItemFn = parse_quote! {
+            /// Adds.
+            #[inline(never)]
+            #[cfg(all())]
+            pub fn add<T>(mut x: T, y: u32) -> T { x }
+        };
+        let tokens = make_multiversioned_fn(
+            quote! { targets = "simd", dispatcher_visibility = "pub(crate)" },
+            func,
+        )
+        .unwrap()
+        .to_string();
+        assert!(tokens.starts_with(
+            &quote! {
+                #[doc = r" Adds."]
+                #[inline(never)]
+                #[cfg(all())]
+                pub fn add<T>(mut x: T, y: u32) -> T {
+                    let _ = &mut x;
+                    add_dispatcher::<T>(x, y)
+                }
+            }
+            .to_string()
+        ));
+        assert!(tokens.contains("pub (crate) fn add_dispatcher"));
+    }
+}
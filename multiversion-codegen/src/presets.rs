@@ -0,0 +1,161 @@
+// Target lists for the special values accepted by `targets = "..."`.
+//
+// Unstable target features must not be included, since they would break builds on stable
+// compilers.
+//
+// Versioned presets, such as "simd-2024", must never change once released.  Changes to the
+// recommended SIMD targets should be added as a new versioned preset, and "simd" updated to
+// refer to it.
+
+const SIMD_2024: &[&str] = &[
+    // "x86_64+avx512f+avx512bw+avx512cd+avx512dq+avx512vl",
+    "x86_64+avx2+fma",
+    "x86_64+sse4.2",
+    // "x86+avx512f+avx512bw+avx512cd+avx512dq+avx512vl",
+    "x86+avx2+fma",
+    "x86+sse4.2",
+    "x86+sse2",
+    "aarch64+neon",
+    // "arm+neon",
+    // "mips+msa",
+    // "mips64+msa",
+    // "powerpc+vsx",
+    // "powerpc+altivec",
+    // "powerpc64+vsx",
+    // "powerpc64+altivec",
+];
+
+// Integer workloads mostly benefit from wider integer instructions (AVX2) and the byte shuffles
+// and min/max instructions from SSSE3 and SSE4.1.
+const SIMD_INT: &[&str] = &[
+    "x86_64+avx2",
+    "x86_64+sse4.1",
+    "x86_64+ssse3",
+    "x86+avx2",
+    "x86+sse4.1",
+    "x86+ssse3",
+    "x86+sse2",
+    "aarch64+neon",
+];
+
+// Floating point workloads benefit from FMA and 256-bit float vectors, which are available with
+// AVX even without AVX2.
+const SIMD_FLOAT: &[&str] = &[
+    "x86_64+avx2+fma",
+    "x86_64+avx",
+    "x86_64+sse4.1",
+    "x86+avx2+fma",
+    "x86+avx",
+    "x86+sse4.1",
+    "x86+sse2",
+    "aarch64+neon",
+];
+
+// Cryptographic workloads depend on the dedicated AES, carry-less multiplication, and SHA
+// instructions more than on vector width.
+const CRYPTO: &[&str] = &[
+    "x86_64+avx2+aes+pclmulqdq+sha",
+    "x86_64+avx2+aes+pclmulqdq",
+    "x86_64+sse4.1+aes+pclmulqdq+sha",
+    "x86_64+ssse3+aes+pclmulqdq",
+    "x86+avx2+aes+pclmulqdq+sha",
+    "x86+avx2+aes+pclmulqdq",
+    "x86+sse4.1+aes+pclmulqdq+sha",
+    "x86+ssse3+aes+pclmulqdq",
+    "aarch64+aes+sha2+sha3",
+    "aarch64+aes+sha2",
+];
+
+// Checksum and compression workloads, such as CRC32, Adler-32, and zstd, use the CRC32 instruction
+// from SSE4.2 (or the CRC extension on AArch64) and fold with carry-less multiplication.  Wider
+// vectors fold more data per iteration.
+const CRC: &[&str] = &[
+    // "x86_64+avx512f+avx512bw+avx512vl+vpclmulqdq+pclmulqdq+sse4.2",
+    "x86_64+avx2+pclmulqdq+sse4.2",
+    "x86_64+sse4.2+pclmulqdq",
+    "x86_64+sse4.2",
+    "x86+avx2+pclmulqdq+sse4.2",
+    "x86+sse4.2+pclmulqdq",
+    "x86+sse4.2",
+    "aarch64+neon+crc+aes",
+    "aarch64+neon+crc",
+    // "powerpc64+vsx+power8-vector+power8-crypto",
+];
+
+const PRESETS: &[(&str, &[&str])] = &[
+    ("simd", SIMD_2024),
+    ("simd-2024", SIMD_2024),
+    ("simd-int", SIMD_INT),
+    ("simd-float", SIMD_FLOAT),
+    ("crypto", CRYPTO),
+    ("crc", CRC),
+];
+
+/// Returns the target list for a preset, if it exists.
+pub(crate) fn preset(name: &str) -> Option<&'static [&'static str]> {
+    PRESETS
+        .iter()
+        .find(|(preset, _)| *preset == name)
+        .map(|(_, targets)| *targets)
+}
+
+/// Returns a human-readable list of the available presets, for error messages.
+pub(crate) fn preset_names() -> String {
+    PRESETS
+        .iter()
+        .map(|(name, _)| format!("\"{name}\""))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::target::Target;
+    use proc_macro2::Span;
+    use syn::LitStr;
+
+    #[test]
+    fn presets_parse() {
+        for (name, targets) in PRESETS {
+            for target in targets.iter() {
+                let target = Target::parse(&LitStr::new(target, Span::call_site()))
+                    .unwrap_or_else(|_| panic!("invalid target {target} in preset {name}"));
+                assert!(target.has_features_specified());
+            }
+        }
+    }
+
+    #[test]
+    fn crc_ladder() {
+        // Every target has a CRC32 instruction, and each architecture's targets are ordered from
+        // the widest to the narrowest.
+        let targets = preset("crc")
+            .unwrap()
+            .iter()
+            .map(|target| Target::parse(&LitStr::new(target, Span::call_site())).unwrap())
+            .collect::<Vec<_>>();
+        for target in &targets {
+            let crc = match target.arch() {
+                "x86" | "x86_64" => "sse4.2",
+                _ => "crc",
+            };
+            assert!(target.features().iter().any(|f| f == crc));
+        }
+        for pair in targets.windows(2) {
+            if pair[0].arch() == pair[1].arch() {
+                assert!(pair[0].features().len() > pair[1].features().len());
+            }
+        }
+    }
+
+    #[test]
+    fn simd_is_latest() {
+        let latest = PRESETS
+            .iter()
+            .filter(|(name, _)| name.starts_with("simd-2"))
+            .max_by_key(|(name, _)| *name)
+            .unwrap();
+        assert_eq!(preset("simd"), Some(latest.1));
+    }
+}
@@ -0,0 +1,140 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    token, Attribute, Ident, Path, Result, Type, Visibility,
+};
+
+// An entry of a table, such as `add` or `add: path::to::sum`.
+struct Entry {
+    field: Ident,
+    func: Path,
+}
+
+impl Parse for Entry {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let field: Ident = input.parse()?;
+        let func = if input.parse::<Option<token::Colon>>()?.is_some() {
+            input.parse()?
+        } else {
+            field.clone().into()
+        };
+        Ok(Self { field, func })
+    }
+}
+
+// A `static` table, such as `static OPS: Ops = { add, mul };`.
+struct Table {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    ident: Ident,
+    ty: Type,
+    entries: Punctuated<Entry, token::Comma>,
+}
+
+impl Parse for Table {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<token::Static>()?;
+        let ident = input.parse()?;
+        input.parse::<token::Colon>()?;
+        let ty = input.parse()?;
+        input.parse::<token::Eq>()?;
+        let content;
+        braced!(content in input);
+        let entries = content.parse_terminated(Entry::parse, token::Comma)?;
+        input.parse::<token::Semi>()?;
+        Ok(Self {
+            attrs,
+            vis,
+            ident,
+            ty,
+            entries,
+        })
+    }
+}
+
+impl ToTokens for Table {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let Self {
+            attrs,
+            vis,
+            ident,
+            ty,
+            entries,
+        } = self;
+
+        // Each entry is the function pointer returned by the function generated by `fn_ptr`.
+        let fields = entries.iter().map(|entry| &entry.field);
+        let fn_ptrs = entries.iter().map(|entry| {
+            let mut func = entry.func.clone();
+            if let Some(last) = func.segments.last_mut() {
+                let ident = &last.ident;
+                last.ident = Ident::new(&format!("{ident}_fn"), ident.span());
+            }
+            func
+        });
+        quote! {
+            #(#attrs)*
+            #vis static #ident: multiversion::table::Table<#ty> = {
+                fn __multiversion_init() -> #ty {
+                    // Struct expressions can't use arbitrary types, but can use aliases.
+                    type __MultiversionTable = #ty;
+                    __MultiversionTable {
+                        #(#fields: #fn_ptrs(),)*
+                    }
+                }
+                multiversion::table::Table::new(__multiversion_init)
+            };
+        }
+        .to_tokens(tokens)
+    }
+}
+
+// The arguments of `table!`: any number of tables.
+pub(crate) struct Tables(Vec<Table>);
+
+impl Parse for Tables {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let mut tables = Vec::new();
+        while !input.is_empty() {
+            tables.push(input.parse()?);
+        }
+        Ok(Self(tables))
+    }
+}
+
+impl ToTokens for Tables {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        for table in &self.0 {
+            table.to_tokens(tokens);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_table() {
+        let tables: Tables = syn::parse_quote! {
+            /// Operations.
+            pub static OPS: Ops = { add, scale: kernels::mul, };
+            static EMPTY: Empty = {};
+        };
+        assert_eq!(tables.0.len(), 2);
+        let tokens = tables.into_token_stream().to_string();
+        assert!(tokens.contains(
+            &quote! { __MultiversionTable { add: add_fn(), scale: kernels::mul_fn(), } }
+                .to_string()
+        ));
+        assert!(tokens
+            .contains(&quote! { pub static OPS: multiversion::table::Table<Ops> = }.to_string()));
+
+        assert!(syn::parse2::<Tables>(quote! { static OPS: Ops = { add } }).is_err());
+    }
+}
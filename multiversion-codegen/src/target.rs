@@ -0,0 +1,459 @@
+use multiversion_spec::{tune_vendor, Spec};
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_quote, Attribute, Error, ItemFn, Lit, LitStr, Meta, Path, Result,
+};
+
+/// A target, such as `"x86_64+avx2"`.
+///
+/// The features of a target include the features they imply, and are sorted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Target {
+    architecture: String,
+    features: Vec<String>,
+    tune: Option<String>,
+    attrs: Vec<Attribute>,
+    cfgs: Vec<Meta>,
+    narrow: bool,
+}
+
+impl Target {
+    /// Parses a target specification string, such as `"x86_64+avx2"` or `"x86_64/skylake"`.
+    pub fn parse(s: &LitStr) -> Result<Self> {
+        let value = s.value();
+        let spec = Spec::parse(&value).map_err(|err| Error::new(s.span(), err))?;
+        let architecture = spec.arch().to_string();
+        let mut features = spec
+            .target()
+            .features()
+            .map(|f| f.name().to_string())
+            .collect::<Vec<_>>();
+        features.sort_unstable();
+        let tune = spec.tune();
+
+        Ok(Self {
+            architecture,
+            features,
+            tune: tune.map(str::to_string),
+            attrs: Vec::new(),
+            cfgs: Vec::new(),
+            narrow: false,
+        })
+    }
+
+    /// Returns the architecture of the target.
+    pub fn arch(&self) -> &str {
+        &self.architecture
+    }
+
+    /// Returns the features of the target.
+    pub fn features(&self) -> &[String] {
+        self.features.as_ref()
+    }
+
+    // Parses a target where tuning isn't supported.
+    pub(crate) fn parse_untuned(s: &LitStr) -> Result<Self> {
+        let target = Self::parse(s)?;
+        if target.is_tuned() {
+            return Err(Error::new(
+                s.span(),
+                "tuning is only supported by `multiversion` targets",
+            ));
+        }
+        Ok(target)
+    }
+
+    /// Returns the features of the target as a string that can be used in an identifier.
+    pub fn features_string(&self) -> String {
+        let mut s = self.features.join("_").replace('.', "");
+        if let Some(tune) = &self.tune {
+            s.push_str("_tune_");
+            s.push_str(&tune.replace('-', "_"));
+        }
+        if self.narrow {
+            s.push_str("_256bit");
+        }
+        s
+    }
+
+    /// Returns the features of the target that aren't implied by its other features as a string
+    /// that can be used in an identifier, such as `avx2_fma` for `x86_64+avx2+fma`.
+    pub fn short_features_string(&self) -> String {
+        let implies = |feature: &str, other: &str| {
+            matches!(
+                Spec::parse(&format!("{}+{}", self.architecture, feature)),
+                Ok(spec) if spec.target().supports_feature_str(other)
+            )
+        };
+        let features = self
+            .features
+            .iter()
+            .filter(|feature| {
+                !self.features.iter().any(|other| {
+                    other != *feature && implies(other, feature) && !implies(feature, other)
+                })
+            })
+            .map(|feature| feature.replace('.', ""))
+            .collect::<Vec<_>>();
+        let mut s = features.join("_");
+        if let Some(tune) = &self.tune {
+            s.push_str("_tune_");
+            s.push_str(&tune.replace('-', "_"));
+        }
+        if self.narrow {
+            s.push_str("_256bit");
+        }
+        s
+    }
+
+    /// Returns the 256-bit variant of an AVX-512 target, specified by `avx512_width = "auto"`.
+    ///
+    /// The variant is compiled without the AVX-512 features, so that only 256-bit vectors are
+    /// used, and is only selected on CPUs that prefer 256-bit vectors.  Returns `None` if the
+    /// target doesn't have AVX-512 features.
+    pub fn narrow_avx512(&self) -> Option<Self> {
+        if !matches!(self.architecture.as_str(), "x86" | "x86_64")
+            || !self.features.iter().any(|feature| feature == "avx512f")
+        {
+            return None;
+        }
+        Some(Self {
+            features: self
+                .features
+                .iter()
+                .filter(|feature| !feature.starts_with("avx512"))
+                .cloned()
+                .collect(),
+            narrow: true,
+            ..self.clone()
+        })
+    }
+
+    // Adds the features of another target of the same architecture, including the features they
+    // imply.
+    pub(crate) fn merge_features(&mut self, other: &Target) {
+        self.features.extend(other.features.iter().cloned());
+        self.features.sort_unstable();
+        self.features.dedup();
+    }
+
+    // Returns `true` if every feature of the target is also a feature of `other`.
+    pub(crate) fn features_subset_of(&self, other: &Target) -> bool {
+        self.features
+            .iter()
+            .all(|feature| other.features.contains(feature))
+    }
+
+    /// Returns `true` if the target is the 256-bit variant of an AVX-512 target.
+    pub fn is_narrow(&self) -> bool {
+        self.narrow
+    }
+
+    /// Returns `true` if the target compiles the same version as `other`, ignoring whether either
+    /// is the 256-bit variant of an AVX-512 target.
+    pub fn same_version(&self, other: &Target) -> bool {
+        self.architecture == other.architecture
+            && self.features == other.features
+            && self.tune == other.tune
+            && self.attrs == other.attrs
+            && self.cfgs == other.cfgs
+    }
+
+    /// Returns `true` if the target is tuned for a CPU, with `"target~cpu"`.
+    pub fn is_tuned(&self) -> bool {
+        self.tune.is_some()
+    }
+
+    /// Returns the CPU the target is tuned for.
+    pub fn tune(&self) -> Option<&str> {
+        self.tune.as_deref()
+    }
+
+    /// Returns `true` if the target has any features.
+    pub fn has_features_specified(&self) -> bool {
+        !self.features.is_empty()
+    }
+
+    /// Returns a `#[cfg(target_arch = ...)]` attribute for the architecture of the target.
+    ///
+    /// If the target has `cfg` predicates, they're also required by the attribute.
+    pub fn target_arch(&self) -> Attribute {
+        let predicate = self.cfg_predicate();
+        parse_quote! {
+            #[cfg(#predicate)]
+        }
+    }
+
+    /// Returns the `cfg` predicate that compiles the target, which requires its architecture and
+    /// any `cfg` predicates of the target.
+    pub fn cfg_predicate(&self) -> TokenStream {
+        let arch = &self.architecture;
+        if self.cfgs.is_empty() {
+            quote! { target_arch = #arch }
+        } else {
+            let cfgs = &self.cfgs;
+            quote! { all(target_arch = #arch, #(#cfgs),*) }
+        }
+    }
+
+    /// Adds a `cfg` predicate that must be satisfied for the target to be compiled.
+    pub fn add_cfg(&mut self, predicate: Meta) {
+        self.cfgs.push(predicate);
+    }
+
+    /// Returns `true` if the target has any `cfg` predicates.
+    pub fn has_cfgs(&self) -> bool {
+        !self.cfgs.is_empty()
+    }
+
+    /// Returns a `#[target_feature(enable = ...)]` attribute for each feature of the target.
+    pub fn target_feature(&self) -> Vec<Attribute> {
+        self.features
+            .iter()
+            .map(|feature| {
+                parse_quote! {
+                    #[target_feature(enable = #feature)]
+                }
+            })
+            .collect()
+    }
+
+    /// Adds an attribute to the function for this target only.
+    pub fn add_attr(&mut self, attr: Attribute) {
+        self.attrs.push(attr);
+    }
+
+    /// Returns the attributes of a function compiled for this target.
+    pub fn fn_attrs(&self) -> Vec<Attribute> {
+        let mut attrs = self.target_feature();
+        attrs.push(self.target_arch());
+        attrs.extend(self.attrs.iter().cloned());
+        attrs
+    }
+
+    /// Returns an expression that is `true` if the features are enabled at compile time.
+    pub fn features_enabled(&self) -> TokenStream {
+        // The CPU vendor can't be known at compile time
+        if self.is_tuned() || self.narrow {
+            return quote! { false };
+        }
+        let feature = self.features.iter();
+        quote! {
+            true #( && core::cfg!(target_feature = #feature) )*
+        }
+    }
+
+    /// Returns an expression that is `true` if the features are detected at runtime.
+    pub fn features_detected(&self) -> TokenStream {
+        self.features_detected_with(None, false)
+    }
+
+    /// Like [`features_detected`](Self::features_detected), but queries the target returned by
+    /// `detector`, if provided.  If `all_cores` is set, features must be supported by every core.
+    pub fn features_detected_with(&self, detector: Option<&Path>, all_cores: bool) -> TokenStream {
+        let mut detected = self.isa_detected(detector, all_cores);
+        if self.narrow {
+            detected = quote! { (#detected) && multiversion::cpu::__prefers_256_bit() };
+        }
+        match self.vendor() {
+            Some(vendor) => quote! { (#detected) && multiversion::cpu::__is_vendor(#vendor) },
+            None => detected,
+        }
+    }
+
+    // Returns the CPU vendor that a tuned target requires, if any.
+    pub(crate) fn vendor(&self) -> Option<&'static str> {
+        self.tune.as_deref().and_then(tune_vendor)
+    }
+
+    fn isa_detected(&self, detector: Option<&Path>, all_cores: bool) -> TokenStream {
+        let feature = self.features.iter();
+        if let Some(detector) = detector {
+            return quote! {
+                {
+                    let __detected: multiversion::target::Target = #detector();
+                    true #( && multiversion::target::__supports_feature(&__detected, #feature) )*
+                }
+            };
+        }
+        let arch = &self.architecture;
+        let is_feature_detected = feature_detector(all_cores);
+        quote! {
+            true #( && #is_feature_detected(#arch, #feature) )*
+        }
+    }
+}
+
+// Returns the function that detects a feature, given the architecture and feature names.
+pub(crate) fn feature_detector(all_cores: bool) -> TokenStream {
+    if all_cores {
+        quote! { multiversion::detect::__is_feature_detected_all_cores }
+    } else if cfg!(feature = "detect-backend-custom") {
+        quote! { multiversion::detect::is_feature_detected }
+    } else {
+        quote! { multiversion::detect::__is_std_feature_detected }
+    }
+}
+
+impl std::convert::TryFrom<&Lit> for Target {
+    type Error = Error;
+
+    fn try_from(lit: &Lit) -> Result<Self> {
+        match lit {
+            Lit::Str(s) => Self::parse(s),
+            _ => Err(Error::new(lit.span(), "expected literal string")),
+        }
+    }
+}
+
+impl Parse for Target {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        Target::parse(&input.parse()?)
+    }
+}
+
+impl ToTokens for Target {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let mut s = self.architecture.clone();
+        for feature in &self.features {
+            s.push('+');
+            s.push_str(feature);
+        }
+        if let Some(tune) = &self.tune {
+            s.push('~');
+            s.push_str(tune);
+        }
+        LitStr::new(&s, Span::call_site()).to_tokens(tokens);
+    }
+}
+
+pub(crate) fn make_target_fn(target: LitStr, func: ItemFn) -> Result<TokenStream> {
+    let target = Target::parse_untuned(&target)?;
+    let target_arch = target.target_arch();
+    let target_feature = target.target_feature();
+    Ok(parse_quote! { #target_arch #(#target_feature)* #func })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_architecture() {}
+
+    #[test]
+    fn parse_no_features() {
+        let s = LitStr::new("x86", Span::call_site());
+        let target = Target::parse(&s).unwrap();
+        assert_eq!(target.architecture, "x86");
+        assert!(target.features.is_empty());
+    }
+
+    #[test]
+    fn parse_features() {
+        let s = LitStr::new("x86_64+sse4.2+xsave", Span::call_site());
+        let target = Target::parse(&s).unwrap();
+        assert_eq!(target.architecture, "x86_64");
+        assert!(target.features.iter().any(|f| f == "sse4.2"));
+        assert!(target.features.iter().any(|f| f == "xsave"));
+    }
+
+    #[test]
+    fn short_features_string() {
+        for (spec, short) in [
+            ("x86_64+avx2+fma", "avx2_fma"),
+            ("x86_64+sse4.2+xsave", "sse42_xsave"),
+            ("x86_64+avx2~znver3", "avx2_tune_znver3"),
+            ("aarch64+neon", "neon"),
+        ] {
+            let target = Target::parse(&LitStr::new(spec, Span::call_site())).unwrap();
+            assert_eq!(target.short_features_string(), short);
+        }
+    }
+
+    #[test]
+    fn parse_extra_plus_start() {
+        let s = LitStr::new("+x86+sse4.2+xsave", Span::call_site());
+        Target::parse(&s).unwrap_err();
+    }
+
+    #[test]
+    fn parse_extra_plus_end() {
+        let s = LitStr::new("x86+sse4.2+xsave+", Span::call_site());
+        Target::parse(&s).unwrap_err();
+    }
+
+    #[test]
+    fn parse_cpu() {
+        let s = LitStr::new("powerpc64/pwr7", Span::call_site());
+        let target = Target::parse(&s).unwrap();
+        assert_eq!(target.architecture, "powerpc64");
+        assert!(target.features.iter().any(|f| f == "altivec"));
+        assert!(target.features.iter().any(|f| f == "vsx"));
+    }
+
+    #[test]
+    fn parse_cpu_features() {
+        let s = LitStr::new("x86/i686+xsave", Span::call_site());
+        let target = Target::parse(&s).unwrap();
+        assert_eq!(target.architecture, "x86");
+        assert!(target.features.iter().any(|f| f == "sse2"));
+        assert!(target.features.iter().any(|f| f == "xsave"));
+    }
+
+    #[test]
+    fn parse_tune() {
+        let s = LitStr::new("x86_64+avx2~znver3", Span::call_site());
+        let target = Target::parse(&s).unwrap();
+        assert!(target.features.iter().any(|f| f == "avx2"));
+        assert_eq!(target.tune.as_deref(), Some("znver3"));
+        assert!(target.features_string().ends_with("_tune_znver3"));
+        assert_eq!(tune_vendor("alderlake"), Some("GenuineIntel"));
+
+        for s in [
+            "x86_64+avx2~x86-64-v3",
+            "x86_64+avx2~foo",
+            "aarch64+neon~znver3",
+        ] {
+            Target::parse(&LitStr::new(s, Span::call_site())).unwrap_err();
+        }
+    }
+
+    #[test]
+    fn generate_target_arch() {
+        let s = LitStr::new("x86+avx", Span::call_site());
+        let target = Target::parse(&s).unwrap();
+        assert_eq!(
+            target.target_arch(),
+            parse_quote! { #[cfg(target_arch = "x86")] }
+        );
+    }
+
+    #[test]
+    fn generate_target_feature() {
+        let s = LitStr::new("x86+avx+xsave", Span::call_site());
+        let target = Target::parse(&s).unwrap();
+        assert!(target
+            .target_feature()
+            .contains(&parse_quote! { #[target_feature(enable = "avx")] }));
+        assert!(target
+            .target_feature()
+            .contains(&parse_quote! { #[target_feature(enable = "xsave")] }));
+    }
+
+    #[test]
+    fn narrow_avx512() {
+        let s = LitStr::new("x86_64+avx512f+avx512bw", Span::call_site());
+        let narrow = Target::parse(&s).unwrap().narrow_avx512().unwrap();
+        assert!(narrow.is_narrow());
+        assert!(narrow.features().iter().any(|f| f == "avx2"));
+        assert!(!narrow.features().iter().any(|f| f.starts_with("avx512")));
+        assert!(narrow.features_string().ends_with("_256bit"));
+        assert_eq!(narrow.features_enabled().to_string(), "false");
+
+        let s = LitStr::new("x86_64+avx2", Span::call_site());
+        assert!(Target::parse(&s).unwrap().narrow_avx512().is_none());
+    }
+}
@@ -0,0 +1,69 @@
+use crate::target::Target;
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    token, Attribute, Error, Ident, LitStr, Result,
+};
+
+pub(crate) struct TargetList {
+    attrs: Vec<Attribute>,
+    ident: Ident,
+    targets: Vec<LitStr>,
+}
+
+impl Parse for TargetList {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let macro_rules: Ident = input.parse()?;
+        if macro_rules != "macro_rules" {
+            return Err(Error::new(macro_rules.span(), "expected `macro_rules`"));
+        }
+        input.parse::<token::Not>()?;
+        let ident = input.parse()?;
+        let content;
+        braced!(content in input);
+        let targets: Vec<LitStr> = Punctuated::<LitStr, token::Comma>::parse_terminated(&content)?
+            .into_iter()
+            .collect();
+        for target in &targets {
+            if !Target::parse(target)?.has_features_specified() {
+                return Err(Error::new(
+                    target.span(),
+                    "target must have features specified",
+                ));
+            }
+        }
+        Ok(Self {
+            attrs,
+            ident,
+            targets,
+        })
+    }
+}
+
+impl ToTokens for TargetList {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let Self {
+            attrs,
+            ident,
+            targets,
+        } = self;
+
+        // The `@multiversion` arm is invoked by `#[multiversion(targets = list!())]` with the
+        // remaining options and the function, and reapplies the attribute with the targets.
+        quote! {
+            #(#attrs)*
+            macro_rules! #ident {
+                () => { [#(#targets),*] };
+                { @multiversion [$($options:tt)*] $($item:tt)* } => {
+                    #[multiversion::multiversion(targets(#(#targets),*), $($options)*)]
+                    $($item)*
+                };
+            }
+        }
+        .to_tokens(tokens)
+    }
+}
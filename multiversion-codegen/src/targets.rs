@@ -0,0 +1,284 @@
+//! Parsing of target lists, as accepted by the `targets` option.
+
+use crate::config::{self, Config};
+use crate::presets;
+use crate::target::Target;
+use proc_macro2::{Span, TokenStream};
+use syn::{
+    parenthesized,
+    parse::{ParseStream, Parser},
+    parse_quote,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    token, Attribute, Error, Ident, LitInt, LitStr,
+};
+
+mod kw {
+    syn::custom_keyword!(priority);
+    syn::custom_keyword!(no_sanitize);
+}
+
+const SANITIZERS: &[&str] = &[
+    "address",
+    "cfi",
+    "hwaddress",
+    "kcfi",
+    "memory",
+    "memtag",
+    "shadow_call_stack",
+    "thread",
+];
+
+// Parses `no_sanitize(...)`, returning the attribute that disables the sanitizers.
+fn parse_no_sanitize(input: ParseStream) -> syn::Result<Attribute> {
+    let keyword = input.parse::<kw::no_sanitize>()?;
+    if !cfg!(feature = "nightly") {
+        return Err(Error::new(
+            keyword.span,
+            "`no_sanitize` requires the `nightly` cargo feature",
+        ));
+    }
+    let content;
+    parenthesized!(content in input);
+    let sanitizers = Punctuated::<Ident, token::Comma>::parse_terminated(&content)?;
+    for sanitizer in &sanitizers {
+        if !SANITIZERS.iter().any(|s| sanitizer == s) {
+            return Err(Error::new(
+                sanitizer.span(),
+                format!(
+                    "unknown sanitizer, expected one of: {}",
+                    SANITIZERS.join(", ")
+                ),
+            ));
+        }
+    }
+    let sanitizers = sanitizers.iter();
+    Ok(parse_quote! { #[sanitize(#(#sanitizers = "off"),*)] })
+}
+
+/// Parses a list of targets, each optionally followed by `priority = N` and `no_sanitize(...)`.
+///
+/// Targets are sorted by descending priority.  Targets with equal priority (including the default
+/// priority of 0) remain in the order they are listed.
+pub fn parse_targets(input: ParseStream) -> syn::Result<Vec<Target>> {
+    let mut targets = Vec::new();
+    while !input.is_empty() {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let mut target: Target = input.parse()?;
+        for attr in attrs {
+            if attr.path().is_ident("cfg") {
+                target.add_cfg(attr.parse_args()?);
+            } else if attr.path().is_ident("doc") {
+                target.add_attr(attr);
+            } else {
+                return Err(Error::new(
+                    attr.span(),
+                    "only `cfg` and `doc` attributes can be applied to a target",
+                ));
+            }
+        }
+        let mut priority = None;
+        loop {
+            if input.peek(kw::priority) {
+                let keyword = input.parse::<kw::priority>()?;
+                if priority.is_some() {
+                    return Err(Error::new(
+                        keyword.span,
+                        "can't specify `priority` multiple times",
+                    ));
+                }
+                input.parse::<token::Eq>()?;
+                priority = Some(input.parse::<LitInt>()?.base10_parse::<i32>()?);
+            } else if input.peek(kw::no_sanitize) {
+                target.add_attr(parse_no_sanitize(input)?);
+            } else {
+                break;
+            }
+        }
+        targets.push((priority.unwrap_or(0), target));
+        if input.is_empty() {
+            break;
+        }
+        input.parse::<token::Comma>()?;
+    }
+    targets.sort_by_key(|(priority, _)| std::cmp::Reverse(*priority));
+    Ok(targets.into_iter().map(|(_, target)| target).collect())
+}
+
+/// Returns the targets of a preset, such as `"simd"`.
+pub fn preset_targets(s: &LitStr) -> syn::Result<Vec<Target>> {
+    if let Some(preset) = presets::preset(&s.value()) {
+        return Ok(preset
+            .iter()
+            .map(|x| Target::parse(&LitStr::new(x, s.span())).unwrap())
+            .collect());
+    }
+
+    Err(Error::new(
+        s.span(),
+        format!(
+            "expected a list of features, \"crate\", or one of {}",
+            presets::preset_names()
+        ),
+    ))
+}
+
+/// Reads the `targets` key from the `multiversion.toml` file in `CARGO_MANIFEST_DIR`.
+///
+/// Tokens that cause the compiler to track changes to the file are added to `tracked`, and should
+/// be included in the output of the macro.
+pub fn crate_targets(s: &LitStr, tracked: &mut Vec<TokenStream>) -> syn::Result<Vec<Target>> {
+    let config = Config::read(s.span())?;
+    tracked.push(config.track());
+    config_targets(&config, s)
+}
+
+/// Reads the `targets` key from the `multiversion.toml` file in a directory.
+pub fn crate_targets_in(dir: &std::path::Path, s: &LitStr) -> syn::Result<Vec<Target>> {
+    config_targets(&Config::read_in(s.span(), dir)?, s)
+}
+
+fn config_targets(config: &Config, s: &LitStr) -> syn::Result<Vec<Target>> {
+    match config.get("targets") {
+        Some(config::Value::Array(targets)) => targets
+            .iter()
+            .map(|target| Target::parse(&LitStr::new(target, s.span())))
+            .collect(),
+        Some(config::Value::String(preset)) => preset_targets(&LitStr::new(preset, s.span())),
+        Some(config::Value::Integer(_)) => Err(Error::new(
+            s.span(),
+            format!(
+                "`targets` in `{}` must be a string or an array of strings",
+                config::FILE_NAME
+            ),
+        )),
+        None => Err(Error::new(
+            s.span(),
+            format!("`targets` is not specified in `{}`", config::FILE_NAME),
+        )),
+    }
+}
+
+/// Reads a list of targets from an environment variable.
+pub fn env_targets(var: &LitStr) -> syn::Result<Vec<Target>> {
+    let value = std::env::var(var.value()).map_err(|_| {
+        Error::new(
+            var.span(),
+            format!("environment variable `{}` is not set", var.value()),
+        )
+    })?;
+    let tokens: TokenStream = value.parse().map_err(|_| {
+        Error::new(
+            var.span(),
+            format!("expected a list of targets in `{}`", var.value()),
+        )
+    })?;
+    // Use the span of the variable name for any errors in the targets.
+    let tokens = tokens
+        .into_iter()
+        .map(|mut token| {
+            token.set_span(var.span());
+            token
+        })
+        .collect::<TokenStream>();
+    parse_targets.parse2(tokens)
+}
+
+// Parses either a parenthesized list of targets, a preset name, or "crate".
+pub(crate) fn parse_target_list(
+    input: ParseStream,
+    tracked: &mut Vec<TokenStream>,
+) -> syn::Result<Vec<Target>> {
+    if input.peek(token::Paren) {
+        let content;
+        parenthesized!(content in input);
+        parse_targets(&content)
+    } else {
+        let s: LitStr = input.parse()?;
+        if s.value() == "crate" {
+            crate_targets(&s, tracked)
+        } else {
+            preset_targets(&s)
+        }
+    }
+}
+
+/// Checks that every target has features specified.
+pub fn require_features(targets: &[Target], span: Span) -> syn::Result<()> {
+    if targets
+        .iter()
+        .any(|target| !target.has_features_specified())
+    {
+        // TODO add span to Target
+        return Err(Error::new(span, "target must have features specified"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn env_target_list() {
+        std::env::set_var(
+            "MULTIVERSION_TEST_ENV_TARGETS",
+            r#""x86_64+avx2" priority = 1, "aarch64+neon""#,
+        );
+        let targets = env_targets(&LitStr::new(
+            "MULTIVERSION_TEST_ENV_TARGETS",
+            Span::call_site(),
+        ))
+        .unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].arch(), "x86_64");
+
+        env_targets(&LitStr::new(
+            "MULTIVERSION_TEST_ENV_TARGETS_UNSET",
+            Span::call_site(),
+        ))
+        .unwrap_err();
+    }
+
+    #[test]
+    fn target_attrs() {
+        let targets = parse_targets
+            .parse_str(
+                r#"/// Documented
+                #[cfg(feature = "wide")] "x86_64+avx2" priority = 1, "aarch64+neon""#,
+            )
+            .unwrap();
+        let expected: Attribute =
+            parse_quote! { #[cfg(all(target_arch = "x86_64", feature = "wide"))] };
+        assert_eq!(targets[0].target_arch(), expected);
+        assert!(targets[0]
+            .fn_attrs()
+            .iter()
+            .any(|attr| attr.path().is_ident("doc")));
+        assert!(!targets[1].has_cfgs());
+
+        parse_targets
+            .parse_str(r#"#[inline] "x86_64+avx2""#)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn no_sanitize() {
+        let parsed = parse_targets.parse_str(
+            r#""x86_64+avx512f" no_sanitize(address, memory) priority = 1, "x86_64+avx2""#,
+        );
+        if cfg!(feature = "nightly") {
+            let targets = parsed.unwrap();
+            let attrs = targets[0].fn_attrs();
+            let expected: Attribute = parse_quote! { #[sanitize(address = "off", memory = "off")] };
+            assert!(attrs.contains(&expected));
+            assert!(!targets[1].fn_attrs().contains(&expected));
+
+            parse_targets
+                .parse_str(r#""x86_64+avx2" no_sanitize(leak_everything)"#)
+                .unwrap_err();
+        } else {
+            parsed.unwrap_err();
+        }
+    }
+}
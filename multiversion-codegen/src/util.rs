@@ -0,0 +1,784 @@
+use proc_macro2::{Spacing, Span, TokenStream, TokenTree};
+use quote::quote;
+use std::collections::HashSet;
+use syn::{
+    parse_quote, spanned::Spanned, visit::Visit, visit_mut::VisitMut, Attribute, BareFnArg, Error,
+    Expr, FnArg, GenericParam, Ident, Item, ItemFn, Lifetime, Macro, Meta,
+    ParenthesizedGenericArguments, Pat, PatIdent, PatType, Result, ReturnType, Signature,
+    TraitBound, Type, TypeBareFn, TypeImplTrait, TypeParamBound, TypeReference, Visibility,
+};
+
+pub(crate) fn arg_exprs(sig: &Signature) -> Vec<Expr> {
+    sig.inputs
+        .iter()
+        .map(|x| match x {
+            FnArg::Receiver(rec) => {
+                let self_token = rec.self_token;
+                parse_quote! { #self_token }
+            }
+            FnArg::Typed(arg) => {
+                if let Pat::Ident(ident) = &*arg.pat {
+                    let ident = &ident.ident;
+                    parse_quote! { #ident }
+                } else {
+                    panic!("pattern should have been ident")
+                }
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn normalize_signature(sig: &Signature) -> (Signature, Vec<Expr>) {
+    let args = sig
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(i, x)| match x {
+            FnArg::Receiver(_) => x.clone(),
+            FnArg::Typed(arg) => FnArg::Typed(PatType {
+                pat: Box::new(Pat::Ident(PatIdent {
+                    attrs: Vec::new(),
+                    by_ref: None,
+                    mutability: None,
+                    ident: match arg.pat.as_ref() {
+                        Pat::Ident(pat) => pat.ident.clone(),
+                        _ => Ident::new(&format!("__multiversion_arg_{i}"), x.span()),
+                    },
+                    subpat: None,
+                })),
+                ..arg.clone()
+            }),
+        })
+        .collect::<Vec<_>>();
+    let sig = Signature {
+        inputs: parse_quote! { #(#args),* },
+        ..sig.clone()
+    };
+    let callable_args = arg_exprs(&sig);
+    (sig, callable_args)
+}
+
+// Like `normalize_signature`, but keeps arguments that are declared `mut`, so the signature of
+// the dispatcher matches the original function as closely as possible, for other attribute
+// macros that inspect it.  Also returns statements that use the mutable arguments, since the
+// dispatcher never mutates them.
+pub(crate) fn dispatcher_signature(sig: &Signature) -> (Signature, TokenStream) {
+    let (mut normalized, _) = normalize_signature(sig);
+    let mut uses = TokenStream::new();
+    if let Some(receiver) = sig.receiver() {
+        if receiver.reference.is_none() && receiver.mutability.is_some() {
+            uses.extend(quote! { let _ = &mut self; });
+        }
+    }
+    for (arg, normalized) in sig.inputs.iter().zip(normalized.inputs.iter_mut()) {
+        if let (FnArg::Typed(arg), FnArg::Typed(normalized)) = (arg, normalized) {
+            if let (Pat::Ident(pat), Pat::Ident(normalized)) =
+                (arg.pat.as_ref(), normalized.pat.as_mut())
+            {
+                if pat.mutability.is_some() && pat.by_ref.is_none() && pat.subpat.is_none() {
+                    normalized.mutability = pat.mutability;
+                    let ident = &pat.ident;
+                    uses.extend(quote! { let _ = &mut #ident; });
+                }
+            }
+        }
+    }
+    (normalized, uses)
+}
+
+// Binds the patterns of the arguments of `sig` to the arguments of its normalized signature.
+pub(crate) fn bind_patterns(sig: &Signature) -> TokenStream {
+    sig.inputs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, x)| match x {
+            FnArg::Typed(arg) => match arg.pat.as_ref() {
+                Pat::Ident(pat)
+                    if pat.by_ref.is_none() && pat.mutability.is_none() && pat.subpat.is_none() =>
+                {
+                    None
+                }
+                Pat::Ident(pat) if pat.subpat.is_none() => {
+                    let ident = &pat.ident;
+                    Some(quote! { let #pat = #ident; })
+                }
+                pat => {
+                    let ident = Ident::new(&format!("__multiversion_arg_{i}"), x.span());
+                    Some(quote! { let #pat = #ident; })
+                }
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+pub(crate) fn impl_trait_present(sig: &Signature) -> bool {
+    struct ImplTraitPresent(bool);
+    impl Visit<'_> for ImplTraitPresent {
+        fn visit_type_impl_trait(&mut self, _: &TypeImplTrait) {
+            self.0 = true;
+        }
+    }
+
+    let mut visitor = ImplTraitPresent(false);
+    visitor.visit_signature(sig);
+    visitor.0
+}
+
+// Returns the names of the lifetimes in the return type, and whether any lifetimes are elided.
+pub(crate) fn return_lifetimes(sig: &Signature) -> (HashSet<String>, bool) {
+    struct ReturnLifetimes(HashSet<String>, bool);
+    impl Visit<'_> for ReturnLifetimes {
+        fn visit_type_reference(&mut self, i: &TypeReference) {
+            if i.lifetime.is_none() {
+                self.1 = true;
+            }
+            syn::visit::visit_type_reference(self, i);
+        }
+
+        fn visit_lifetime(&mut self, i: &Lifetime) {
+            if i.ident == "_" {
+                self.1 = true;
+            } else {
+                self.0.insert(i.ident.to_string());
+            }
+        }
+
+        fn visit_type_bare_fn(&mut self, _: &TypeBareFn) {}
+
+        fn visit_parenthesized_generic_arguments(&mut self, _: &ParenthesizedGenericArguments) {}
+    }
+
+    let mut visitor = ReturnLifetimes(HashSet::new(), false);
+    visitor.visit_return_type(&sig.output);
+    (visitor.0, visitor.1)
+}
+
+// Returns `true` if the function has a receiver or uses `Self`, outside of nested items.
+pub(crate) fn uses_self(func: &ItemFn) -> bool {
+    struct UsesSelf(bool);
+    impl Visit<'_> for UsesSelf {
+        fn visit_item(&mut self, _: &Item) {}
+        fn visit_ident(&mut self, i: &Ident) {
+            self.0 |= i == "Self";
+        }
+        fn visit_macro(&mut self, i: &Macro) {
+            self.0 |= idents(i.tokens.clone()).contains("Self");
+        }
+    }
+
+    let mut visitor = UsesSelf(func.sig.receiver().is_some());
+    visitor.visit_signature(&func.sig);
+    visitor.visit_block(&func.block);
+    visitor.0
+}
+
+// Returns `true` if the function returns the never type, `!`.
+pub(crate) fn returns_never(sig: &Signature) -> bool {
+    matches!(&sig.output, ReturnType::Type(_, ty) if matches!(**ty, Type::Never(_)))
+}
+
+// Returns `true` if the function returns `impl Coroutine`.
+pub(crate) fn returns_coroutine(sig: &Signature) -> bool {
+    match &sig.output {
+        ReturnType::Type(_, ty) => match &**ty {
+            Type::ImplTrait(ty) => ty.bounds.iter().any(|bound| {
+                matches!(bound, TypeParamBound::Trait(bound) if matches!(bound.path.segments.last(), Some(segment) if segment.ident == "Coroutine"))
+            }),
+            _ => false,
+        },
+        ReturnType::Default => false,
+    }
+}
+
+struct LifetimeRenamer;
+
+impl VisitMut for LifetimeRenamer {
+    fn visit_lifetime_mut(&mut self, i: &mut Lifetime) {
+        i.ident = Ident::new(&format!("__mv_inner_{}", i.ident), i.ident.span());
+    }
+}
+
+fn bare_fn_args(sig: &Signature) -> Result<Vec<BareFnArg>> {
+    sig.inputs
+        .iter()
+        .map(|x| {
+            Ok(BareFnArg {
+                attrs: Vec::new(),
+                name: None,
+                ty: match x {
+                    FnArg::Receiver(rec) => Err(Error::new(
+                        rec.self_token.span,
+                        "cannot determine type of associated fn",
+                    )),
+                    FnArg::Typed(arg) => Ok(arg.ty.as_ref().clone()),
+                }?,
+            })
+        })
+        .collect()
+}
+
+// Returns the lifetimes that can't be bound by a `for<...>` binder.
+//
+// Lifetimes that are bounded, or used in the where clause, are early-bound and can't be generic
+// in a function pointer.  Lifetimes used only in the return type are rejected in function
+// pointers (E0581).
+fn unbindable_lifetimes(sig: &Signature) -> Vec<Lifetime> {
+    struct Lifetimes(Vec<Lifetime>);
+    impl Visit<'_> for Lifetimes {
+        fn visit_lifetime(&mut self, i: &Lifetime) {
+            self.0.push(i.clone());
+        }
+    }
+
+    let mut input_lifetimes = Lifetimes(Vec::new());
+    for input in &sig.inputs {
+        input_lifetimes.visit_fn_arg(input);
+    }
+    let mut where_lifetimes = Lifetimes(Vec::new());
+    if let Some(where_clause) = &sig.generics.where_clause {
+        where_lifetimes.visit_where_clause(where_clause);
+    }
+
+    sig.generics
+        .lifetimes()
+        .filter(|param| {
+            !param.bounds.is_empty()
+                || where_lifetimes.0.contains(&param.lifetime)
+                || !input_lifetimes.0.contains(&param.lifetime)
+        })
+        .map(|param| param.lifetime.clone())
+        .collect()
+}
+
+/// Returns the type of a function pointer to a function with the signature.
+///
+/// Lifetime parameters are bound by the function pointer type, so it can be used outside the
+/// function, such as in a `static`.
+pub(crate) fn fn_type_from_signature(sig: &Signature) -> Result<TypeBareFn> {
+    if let Some(lifetime) = unbindable_lifetimes(sig).first() {
+        return Err(Error::new(
+            lifetime.span(),
+            format!(
+                "lifetime `{lifetime}` is bounded or only used in the return type, so the function can't be converted to a function pointer"
+            ),
+        ));
+    }
+    assert!(
+        sig.variadic.is_none(),
+        "cannot multiversion function with variadic arguments"
+    );
+    let lifetimes = sig
+        .generics
+        .lifetimes()
+        .map(|param| &param.lifetime)
+        .collect::<Vec<_>>();
+    let args = bare_fn_args(sig)?;
+    let mut fn_ty = TypeBareFn {
+        lifetimes: if lifetimes.is_empty() {
+            None
+        } else {
+            Some(parse_quote! { for<#(#lifetimes),*> })
+        },
+        unsafety: sig.unsafety,
+        abi: sig.abi.clone(),
+        fn_token: sig.fn_token,
+        paren_token: sig.paren_token,
+        inputs: parse_quote! { #(#args),* },
+        variadic: None,
+        output: sig.output.clone(),
+    };
+    LifetimeRenamer {}.visit_type_bare_fn_mut(&mut fn_ty);
+    Ok(fn_ty)
+}
+
+/// Returns the type of a function pointer to a function with the signature, using the lifetime
+/// parameters of the signature rather than binding them.
+///
+/// This type can only be used in the body of a function with the signature, but supports
+/// lifetimes that can't be bound by the function pointer type.
+pub(crate) fn fn_type_in_scope(sig: &Signature) -> Result<TypeBareFn> {
+    assert!(
+        sig.variadic.is_none(),
+        "cannot multiversion function with variadic arguments"
+    );
+    let args = bare_fn_args(sig)?;
+    Ok(TypeBareFn {
+        lifetimes: None,
+        unsafety: sig.unsafety,
+        abi: sig.abi.clone(),
+        fn_token: sig.fn_token,
+        paren_token: sig.paren_token,
+        inputs: parse_quote! { #(#args),* },
+        variadic: None,
+        output: sig.output.clone(),
+    })
+}
+
+// Names the elided lifetimes of the inputs, adding them to the generic parameters.
+//
+// Elided lifetimes in function pointer types and `Fn` traits are higher-ranked, so they are left
+// alone.  Lifetimes elided from paths, such as `Foo` for `Foo<'_>`, can't be found.
+pub(crate) fn name_elided_lifetimes(sig: &Signature) -> Signature {
+    struct Namer(Vec<Lifetime>);
+    impl Namer {
+        fn fresh(&mut self, span: Span) -> Lifetime {
+            let lifetime = Lifetime::new(&format!("'__mv_elided_{}", self.0.len()), span);
+            self.0.push(lifetime.clone());
+            lifetime
+        }
+    }
+    impl VisitMut for Namer {
+        fn visit_type_reference_mut(&mut self, i: &mut TypeReference) {
+            if i.lifetime.is_none() {
+                i.lifetime = Some(self.fresh(i.and_token.span));
+            }
+            syn::visit_mut::visit_type_reference_mut(self, i);
+        }
+
+        fn visit_lifetime_mut(&mut self, i: &mut Lifetime) {
+            if i.ident == "_" {
+                *i = self.fresh(i.span());
+            }
+        }
+
+        fn visit_type_bare_fn_mut(&mut self, _: &mut TypeBareFn) {}
+
+        fn visit_parenthesized_generic_arguments_mut(
+            &mut self,
+            _: &mut ParenthesizedGenericArguments,
+        ) {
+        }
+    }
+
+    let mut sig = sig.clone();
+    let mut namer = Namer(Vec::new());
+    for input in sig.inputs.iter_mut() {
+        namer.visit_fn_arg_mut(input);
+    }
+    for lifetime in namer.0 {
+        sig.generics.params.push(parse_quote! { #lifetime });
+    }
+    sig
+}
+
+// Replaces the elided lifetimes in the trait bound with `lifetime`.
+pub(crate) fn fill_elided_lifetimes(bound: &mut TraitBound, lifetime: &Lifetime) {
+    struct Filler<'a>(&'a Lifetime);
+    impl VisitMut for Filler<'_> {
+        fn visit_type_reference_mut(&mut self, i: &mut TypeReference) {
+            if i.lifetime.is_none() {
+                i.lifetime = Some(self.0.clone());
+            }
+            syn::visit_mut::visit_type_reference_mut(self, i);
+        }
+
+        fn visit_lifetime_mut(&mut self, i: &mut Lifetime) {
+            if i.ident == "_" {
+                *i = self.0.clone();
+            }
+        }
+
+        fn visit_type_bare_fn_mut(&mut self, _: &mut TypeBareFn) {}
+
+        fn visit_parenthesized_generic_arguments_mut(
+            &mut self,
+            _: &mut ParenthesizedGenericArguments,
+        ) {
+        }
+    }
+
+    Filler(lifetime).visit_trait_bound_mut(bound);
+}
+
+pub(crate) fn fn_params(sig: &Signature) -> Vec<Ident> {
+    sig.generics
+        .params
+        .iter()
+        .filter_map(|x| match x {
+            GenericParam::Type(ty) => Some(ty.ident.clone()),
+            GenericParam::Const(c) => Some(c.ident.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+// Returns the identifiers in the tokens, including those in nested groups.
+pub(crate) fn idents(tokens: TokenStream) -> HashSet<String> {
+    let mut idents = HashSet::new();
+    let mut stack = vec![tokens];
+    while let Some(tokens) = stack.pop() {
+        for token in tokens {
+            match token {
+                TokenTree::Ident(ident) => {
+                    idents.insert(ident.to_string());
+                }
+                TokenTree::Group(group) => stack.push(group.stream()),
+                _ => {}
+            }
+        }
+    }
+    idents
+}
+
+// Returns the names of the macros invoked in the tokens, including those in the arguments of other
+// macros.  Macros invoked by a path to another crate, such as `log::debug!` or `::std::println!`,
+// aren't included.
+pub(crate) fn macro_invocations(tokens: TokenStream) -> HashSet<String> {
+    let mut macros = HashSet::new();
+    let mut stack = vec![tokens];
+    while let Some(tokens) = stack.pop() {
+        let tokens = tokens.into_iter().collect::<Vec<_>>();
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Ident(ident) => {
+                    if let (Some(TokenTree::Punct(bang)), Some(TokenTree::Group(_))) =
+                        (tokens.get(i + 1), tokens.get(i + 2))
+                    {
+                        // Keywords may be followed by a negation, such as `if !(...)`.
+                        let keyword =
+                            syn::parse2::<Ident>(TokenTree::from(ident.clone()).into()).is_err();
+                        if bang.as_char() == '!'
+                            && bang.spacing() == Spacing::Alone
+                            && !keyword
+                            && !external_path(&tokens[..i])
+                        {
+                            macros.insert(ident.to_string());
+                        }
+                    }
+                }
+                TokenTree::Group(group) => stack.push(group.stream()),
+                _ => {}
+            }
+        }
+    }
+    macros
+}
+
+// Returns true if the tokens end with the prefix of a path to another crate, such as `log::` or
+// `::std::ptr::`.
+fn external_path(mut prefix: &[TokenTree]) -> bool {
+    let mut first = None;
+    while let [head @ .., TokenTree::Punct(colon), TokenTree::Punct(second)] = prefix {
+        if colon.as_char() != ':' || colon.spacing() != Spacing::Joint || second.as_char() != ':' {
+            break;
+        }
+        match head {
+            [rest @ .., TokenTree::Ident(segment)] => {
+                first = Some(segment.to_string());
+                prefix = rest;
+            }
+            // A leading `::` is always another crate.
+            _ => return true,
+        }
+    }
+    matches!(first, Some(first) if !["crate", "self", "super", "$crate"].contains(&first.as_str()))
+}
+
+// Returns the name of the attribute if it sets the symbol name, such as `no_mangle` or
+// `export_name`, including when wrapped in `unsafe(...)`.
+pub(crate) fn symbol_attr(attr: &Attribute) -> Option<String> {
+    let path = if attr.path().is_ident("unsafe") {
+        attr.parse_args::<Meta>().ok()?.path().clone()
+    } else {
+        attr.path().clone()
+    };
+    ["no_mangle", "export_name"]
+        .iter()
+        .find(|name| path.is_ident(name))
+        .map(|name| name.to_string())
+}
+
+// Returns the visibility equivalent to `vis` for an item in a child module.  Private items are
+// visible to the parent module.
+pub(crate) fn nested_visibility(vis: &Visibility) -> Visibility {
+    match vis {
+        Visibility::Inherited => parse_quote! { pub(super) },
+        Visibility::Restricted(restricted) => {
+            let path = &restricted.path;
+            match path.segments.first() {
+                Some(first) if first.ident == "self" => {
+                    let rest = path.segments.iter().skip(1);
+                    parse_quote! { pub(in super #(::#rest)*) }
+                }
+                Some(first) if first.ident == "super" => parse_quote! { pub(in super::#path) },
+                _ => vis.clone(),
+            }
+        }
+        Visibility::Public(_) => vis.clone(),
+    }
+}
+
+// Returns a call to `path`, with the arguments of the normalized signature `sig`.
+pub(crate) fn forward_call(path: TokenStream, sig: &Signature) -> TokenStream {
+    let args = arg_exprs(sig);
+    let params = fn_params(sig);
+    let turbofish = if impl_trait_present(sig) || params.is_empty() {
+        None
+    } else {
+        Some(quote! { ::<#(#params),*> })
+    };
+    let mut call = quote! { #path #turbofish(#(#args),*) };
+    if sig.asyncness.is_some() {
+        call.extend(await_tokens());
+    }
+    call
+}
+
+pub(crate) fn await_tokens() -> TokenStream {
+    let kw = Ident::new("await", Span::call_site());
+    quote! { .#kw }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    const LIFETIMES: &[&str] = &["'a", "'b", "'c"];
+
+    fn lifetime() -> impl Strategy<Value = Option<&'static str>> {
+        prop::option::of(prop::sample::select(LIFETIMES))
+    }
+
+    fn reference(lifetime: Option<&str>, mutable: bool) -> String {
+        format!(
+            "&{} {} u8",
+            lifetime.unwrap_or_default(),
+            if mutable { "mut" } else { "" }
+        )
+    }
+
+    // Generates signatures similar to those produced by `async_trait`, with references,
+    // boxed trait objects, and lifetime bounds.
+    fn signature() -> impl Strategy<Value = Signature> {
+        (
+            prop::collection::vec((lifetime(), any::<bool>()), 0..4),
+            prop::option::of((lifetime(), any::<bool>())),
+            prop::collection::vec(
+                (
+                    prop::sample::select(LIFETIMES),
+                    prop::sample::select(LIFETIMES),
+                ),
+                0..3,
+            ),
+            any::<bool>(),
+        )
+            .prop_map(|(inputs, output, predicates, bounded_params)| {
+                let inputs = inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (lifetime, mutable))| {
+                        format!("x{i}: {}", reference(*lifetime, *mutable))
+                    })
+                    .collect::<Vec<_>>();
+                let output = match output {
+                    Some((lifetime, true)) => {
+                        format!("-> Box<dyn Fn() -> u8 + {}>", lifetime.unwrap_or("'static"))
+                    }
+                    Some((lifetime, false)) => format!("-> {}", reference(lifetime, false)),
+                    None => String::new(),
+                };
+                let params = LIFETIMES
+                    .iter()
+                    .enumerate()
+                    .map(|(i, lifetime)| match (bounded_params, i) {
+                        (true, 1) => format!("{lifetime}: 'a"),
+                        _ => lifetime.to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                let predicates = predicates
+                    .iter()
+                    .filter(|(a, b)| a != b)
+                    .map(|(a, b)| format!("{a}: {b}"))
+                    .collect::<Vec<_>>();
+                let sig = format!(
+                    "fn f<{}>({}) {output} where {}",
+                    params.join(", "),
+                    inputs.join(", "),
+                    predicates.join(", ")
+                );
+                syn::parse_str(&sig).unwrap()
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn fn_types(sig in signature()) {
+            // The in-scope type supports every signature
+            let in_scope = fn_type_in_scope(&sig).unwrap();
+            prop_assert!(in_scope.lifetimes.is_none());
+
+            let unbindable = unbindable_lifetimes(&sig);
+            match fn_type_from_signature(&sig) {
+                Ok(fn_ty) => {
+                    prop_assert!(unbindable.is_empty());
+                    // Binders never contain bounds, and every named lifetime is bound
+                    if let Some(binder) = &fn_ty.lifetimes {
+                        for param in &binder.lifetimes {
+                            if let GenericParam::Lifetime(param) = param {
+                                prop_assert!(param.bounds.is_empty());
+                                prop_assert!(param.colon_token.is_none());
+                            }
+                        }
+                    }
+                    let tokens = quote!(#fn_ty).to_string();
+                    for lifetime in LIFETIMES {
+                        let unbound = tokens.contains(&format!("{lifetime} "));
+                        prop_assert!(!unbound);
+                    }
+                    prop_assert!(syn::parse_str::<syn::Type>(&tokens).is_ok());
+                }
+                Err(_) => prop_assert!(!unbindable.is_empty()),
+            }
+        }
+    }
+
+    #[test]
+    fn nested_idents() {
+        let idents = idents(quote! { let x = { foo!(bar[baz]) }; });
+        for ident in ["let", "x", "foo", "bar", "baz"] {
+            assert!(idents.contains(ident));
+        }
+        assert_eq!(idents.len(), 5);
+    }
+
+    #[test]
+    fn nested_macros() {
+        let macros = macro_invocations(quote! {
+            let x = vec![foo!(bar), a != b];
+            if !(x.is_empty()) {
+                std::println!("{}", baz! {});
+            }
+            while !(x.is_empty()) {
+                match !x.is_empty() { _ => return !(a), }
+            }
+            log::debug!("{}", ::core::stringify!(qux!()));
+            crate::local!();
+            super::parent!();
+        });
+        let mut macros = macros.into_iter().collect::<Vec<_>>();
+        macros.sort();
+        assert_eq!(macros, ["baz", "foo", "local", "parent", "qux", "vec"]);
+    }
+
+    #[test]
+    fn methods() {
+        assert!(uses_self(&parse_quote! { fn f(&self) {} }));
+        assert!(uses_self(&parse_quote! { fn f() -> Self { todo!() } }));
+        assert!(uses_self(&parse_quote! { fn f() { vec![Self::X]; } }));
+        assert!(!uses_self(&parse_quote! {
+            fn f() {
+                struct S;
+                impl S {
+                    fn new() -> Self { S }
+                }
+            }
+        }));
+    }
+
+    #[test]
+    fn elided_lifetimes() {
+        let sig: Signature = parse_quote! {
+            fn f<'a>(x: &'a u8, y: &mut Foo<'_>, z: &dyn Fn(&u8)) -> &'a u8
+        };
+        let named = name_elided_lifetimes(&sig);
+        assert_eq!(named.generics.lifetimes().count(), 4);
+        let expected: Signature = parse_quote! {
+            fn f<'a, '__mv_elided_0, '__mv_elided_1, '__mv_elided_2>(
+                x: &'a u8,
+                y: &'__mv_elided_0 mut Foo<'__mv_elided_1>,
+                z: &'__mv_elided_2 dyn Fn(&u8)
+            ) -> &'a u8
+        };
+        assert_eq!(quote!(#named).to_string(), quote!(#expected).to_string());
+    }
+
+    #[test]
+    fn coroutine() {
+        let sig: Signature =
+            parse_quote! { fn f() -> impl core::ops::Coroutine<Yield = u8, Return = ()> };
+        assert!(returns_coroutine(&sig));
+        let sig: Signature = parse_quote! { fn f() -> impl Iterator<Item = u8> };
+        assert!(!returns_coroutine(&sig));
+    }
+
+    #[test]
+    fn nested_visibilities() {
+        let cases: [(Visibility, Visibility); 6] = [
+            (parse_quote! {}, parse_quote! { pub(super) }),
+            (parse_quote! { pub }, parse_quote! { pub }),
+            (parse_quote! { pub(crate) }, parse_quote! { pub(crate) }),
+            (parse_quote! { pub(self) }, parse_quote! { pub(in super) }),
+            (
+                parse_quote! { pub(super) },
+                parse_quote! { pub(in super::super) },
+            ),
+            (
+                parse_quote! { pub(in self::a) },
+                parse_quote! { pub(in super::a) },
+            ),
+        ];
+        for (vis, expected) in cases {
+            let nested = nested_visibility(&vis);
+            assert_eq!(quote!(#nested).to_string(), quote!(#expected).to_string());
+        }
+    }
+
+    #[test]
+    fn symbol_attrs() {
+        let attrs: [Attribute; 4] = [
+            parse_quote! { #[no_mangle] },
+            parse_quote! { #[unsafe(no_mangle)] },
+            parse_quote! { #[export_name = "f"] },
+            parse_quote! { #[inline] },
+        ];
+        let names = attrs.iter().map(symbol_attr).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                Some("no_mangle".to_string()),
+                Some("no_mangle".to_string()),
+                Some("export_name".to_string()),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn abi() {
+        let sig: Signature = parse_quote! { unsafe extern "C" fn f(x: *const u8) -> u8 };
+        let expected: TypeBareFn = parse_quote! { unsafe extern "C" fn(*const u8) -> u8 };
+        for fn_ty in [fn_type_from_signature(&sig), fn_type_in_scope(&sig)] {
+            let fn_ty = fn_ty.unwrap();
+            assert_eq!(quote!(#fn_ty).to_string(), quote!(#expected).to_string());
+        }
+        let (normalized, _) = normalize_signature(&sig);
+        assert_eq!(normalized.abi, sig.abi);
+    }
+
+    #[test]
+    fn async_trait_signature() {
+        let sig: Signature = parse_quote! {
+            fn f<'life0, 'async_trait>(x: &'life0 u8) -> Pin<Box<dyn Future<Output = u8> + Send + 'async_trait>>
+            where
+                'life0: 'async_trait
+        };
+        assert_eq!(unbindable_lifetimes(&sig).len(), 2);
+        fn_type_from_signature(&sig).unwrap_err();
+        fn_type_in_scope(&sig).unwrap();
+    }
+
+    #[test]
+    fn dispatcher_signature_keeps_mut() {
+        let sig: Signature = parse_quote! { fn f(mut x: u8, (y, z): (u8, u8), ref w: u8) };
+        let (dispatcher, uses) = dispatcher_signature(&sig);
+        let expected: Signature =
+            parse_quote! { fn f(mut x: u8, __multiversion_arg_1: (u8, u8), w: u8) };
+        assert_eq!(
+            quote!(#dispatcher).to_string(),
+            quote!(#expected).to_string()
+        );
+        assert_eq!(uses.to_string(), quote! { let _ = &mut x; }.to_string());
+    }
+}
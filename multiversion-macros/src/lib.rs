@@ -1,28 +1,71 @@
 //! Implementation crate for `multiversion`.
+//!
+//! The macros are implemented by `multiversion-codegen`.
 extern crate proc_macro;
 
-mod cfg;
-mod dispatcher;
-mod match_target;
-mod multiversion;
-mod target;
-mod util;
-
 use proc_macro2::TokenStream;
-use quote::{quote, ToTokens};
+use quote::quote;
 use syn::{parse::Nothing, parse_macro_input, punctuated::Punctuated, ItemFn};
 
+fn expand(result: syn::Result<TokenStream>) -> proc_macro::TokenStream {
+    result.unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
 #[proc_macro_attribute]
 pub fn multiversion(
     attr: proc_macro::TokenStream,
     input: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    let func = parse_macro_input!(input as ItemFn);
-    match multiversion::make_multiversioned_fn(attr.into(), func) {
-        Ok(tokens) => tokens.into_token_stream(),
-        Err(err) => err.to_compile_error(),
+    match parse_macro_input!(input as syn::Item) {
+        syn::Item::Fn(func) => expand(multiversion_codegen::multiversion(attr.into(), func)),
+        syn::Item::Impl(imp) => expand(multiversion_codegen::multiversion_impl(attr.into(), imp)),
+        syn::Item::Trait(tr) => expand(multiversion_codegen::multiversion_trait(attr.into(), tr)),
+        item => syn::Error::new_spanned(item, "expected a function, an impl block, or a trait")
+            .to_compile_error()
+            .into(),
     }
-    .into()
+}
+
+#[proc_macro]
+pub fn multiversion_closure(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(multiversion_codegen::multiversion_closure(input.into()))
+}
+
+#[proc_macro]
+pub fn multiversion_item(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(multiversion_codegen::multiversion_item(input.into()))
+}
+
+#[proc_macro]
+pub fn table(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(multiversion_codegen::table(input.into()))
+}
+
+#[proc_macro_attribute]
+pub fn default_targets(
+    attr: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let module = parse_macro_input!(input as syn::ItemMod);
+    expand(multiversion_codegen::default_targets(attr.into(), module))
+}
+
+#[proc_macro_attribute]
+pub fn group(
+    attr: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let module = parse_macro_input!(input as syn::ItemMod);
+    expand(multiversion_codegen::group(attr.into(), module))
+}
+
+#[proc_macro_attribute]
+pub fn clone_module(
+    attr: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let module = parse_macro_input!(input as syn::ItemMod);
+    expand(multiversion_codegen::clone_module(attr.into(), module))
 }
 
 #[proc_macro_attribute]
@@ -32,11 +75,7 @@ pub fn target(
 ) -> proc_macro::TokenStream {
     let target = parse_macro_input!(attr as syn::LitStr);
     let func = parse_macro_input!(input as ItemFn);
-    match target::make_target_fn(target, func) {
-        Ok(tokens) => tokens.into_token_stream(),
-        Err(err) => err.to_compile_error(),
-    }
-    .into()
+    expand(multiversion_codegen::target(target, func))
 }
 
 #[proc_macro_attribute]
@@ -61,6 +100,15 @@ pub fn selected_target(input: proc_macro::TokenStream) -> proc_macro::TokenStrea
     .into()
 }
 
+#[proc_macro]
+pub fn selected_token(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    parse_macro_input!(input as Nothing);
+    quote! {
+        __multiversion::Token
+    }
+    .into()
+}
+
 #[proc_macro_attribute]
 pub fn target_cfg(
     attr: proc_macro::TokenStream,
@@ -96,6 +144,17 @@ pub fn target_cfg_f(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     .into()
 }
 
+#[proc_macro_attribute]
+pub fn target_cfg_for(
+    attr: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    expand(multiversion_codegen::target_cfg_for(
+        attr.into(),
+        input.into(),
+    ))
+}
+
 #[proc_macro_attribute]
 pub fn target_cfg_impl(
     attr: proc_macro::TokenStream,
@@ -104,7 +163,7 @@ pub fn target_cfg_impl(
     let meta = parse_macro_input!(attr with Punctuated::parse_terminated);
     let input = TokenStream::from(input);
 
-    match cfg::transform(meta) {
+    match multiversion_codegen::target_cfg_predicate(meta) {
         Ok(meta) => {
             quote! {
                 #[cfg(#meta)]
@@ -124,8 +183,15 @@ pub fn target_cfg_attr_impl(
     let mut meta = parse_macro_input!(attr with Punctuated::parse_terminated);
     let input = TokenStream::from(input);
 
-    let attr = meta.pop().unwrap();
-    match cfg::transform(meta) {
+    let attr = match meta.pop() {
+        Some(attr) => attr.into_value(),
+        None => {
+            return syn::Error::new(proc_macro2::Span::call_site(), "expected an attribute")
+                .to_compile_error()
+                .into()
+        }
+    };
+    match multiversion_codegen::target_cfg_predicate(meta) {
         Ok(meta) => {
             quote! {
                 #[cfg_attr(#meta, #attr)]
@@ -141,7 +207,7 @@ pub fn target_cfg_attr_impl(
 pub fn target_cfg_f_impl(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let meta = parse_macro_input!(input with Punctuated::parse_terminated);
 
-    match cfg::transform(meta) {
+    match multiversion_codegen::target_cfg_predicate(meta) {
         Ok(meta) => {
             quote! {
                 cfg!(#meta)
@@ -163,6 +229,15 @@ pub fn match_target(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 
 #[proc_macro]
 pub fn match_target_impl(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let match_target = parse_macro_input!(input as match_target::MatchTarget);
-    match_target.into_token_stream().into()
+    expand(multiversion_codegen::match_target(input.into()))
+}
+
+#[proc_macro]
+pub fn capability_ladder(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(multiversion_codegen::capability_ladder(input.into()))
+}
+
+#[proc_macro]
+pub fn target_list(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(multiversion_codegen::target_list(input.into()))
 }
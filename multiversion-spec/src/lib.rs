@@ -0,0 +1,279 @@
+//! Parsing of target specification strings, such as `"x86_64+avx2"`.
+//!
+//! This crate is shared by the `multiversion` macros and runtime, so that target specification
+//! strings are parsed the same way by both.
+#![no_std]
+#![warn(missing_docs)]
+
+use core::fmt;
+use target_features::{Architecture, Feature, Target};
+
+/// Returned when a target specification string is invalid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error<'a> {
+    /// The architecture is empty or contains invalid characters.
+    InvalidArchitecture,
+    /// A feature is empty, such as in `"x86_64+"`.
+    EmptyFeature,
+    /// The CPU isn't known for the architecture.
+    UnknownCpu(&'a str),
+    /// The feature isn't known for the architecture.
+    UnknownFeature(&'a str),
+    /// The target is tuned, but the architecture isn't x86 or x86-64.
+    TuneUnsupported,
+    /// The target is tuned for a generic CPU, which doesn't have a vendor.
+    GenericTune(&'a str),
+    /// The target is tuned for a CPU whose vendor isn't known.
+    UnknownVendor(&'a str),
+}
+
+impl fmt::Display for Error<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidArchitecture => f.write_str("invalid architecture specifier"),
+            Self::EmptyFeature => f.write_str("feature string cannot be empty"),
+            Self::UnknownCpu(cpu) => write!(f, "unknown target CPU: {cpu}"),
+            Self::UnknownFeature(feature) => write!(f, "unknown target feature: {feature}"),
+            Self::TuneUnsupported => f.write_str("tuning is only supported on x86 and x86-64"),
+            Self::GenericTune(cpu) => write!(f, "can't tune for generic CPU: {cpu}"),
+            Self::UnknownVendor(cpu) => write!(f, "unknown vendor of target CPU: {cpu}"),
+        }
+    }
+}
+
+// CPUs that aren't made by a particular vendor, matched by prefix.
+const GENERIC: &[&str] = &["generic", "i386", "i486", "i586", "i686", "x86-64"];
+
+/// Returns the CPU vendor identification string of a CPU, for tuning.
+///
+/// Returns `None` for generic CPUs, such as `x86-64-v3`, and CPUs whose vendor isn't known.
+pub fn tune_vendor(cpu: &str) -> Option<&'static str> {
+    const AMD: &[&str] = &[
+        "amdfam10",
+        "athlon",
+        "barcelona",
+        "bdver",
+        "btver",
+        "geode",
+        "k6",
+        "k8",
+        "opteron",
+        "znver",
+    ];
+    const CENTAUR: &[&str] = &["c3", "winchip"];
+    const INTEL: &[&str] = &[
+        "alderlake",
+        "arrowlake",
+        "atom",
+        "bonnell",
+        "broadwell",
+        "cannonlake",
+        "cascadelake",
+        "clearwaterforest",
+        "cooperlake",
+        "core",
+        "emeraldrapids",
+        "goldmont",
+        "gracemont",
+        "grandridge",
+        "graniterapids",
+        "haswell",
+        "icelake",
+        "ivybridge",
+        "knl",
+        "knm",
+        "lakemont",
+        "lunarlake",
+        "meteorlake",
+        "mic_avx512",
+        "nehalem",
+        "nocona",
+        "pantherlake",
+        "penryn",
+        "pentium",
+        "prescott",
+        "raptorlake",
+        "rocketlake",
+        "sandybridge",
+        "sapphirerapids",
+        "sierraforest",
+        "silvermont",
+        "skx",
+        "skylake",
+        "slm",
+        "tigerlake",
+        "tremont",
+        "westmere",
+        "yonah",
+    ];
+    let matches = |prefixes: &[&str]| prefixes.iter().any(|prefix| cpu.starts_with(prefix));
+    if matches(GENERIC) {
+        None
+    } else if matches(AMD) {
+        Some("AuthenticAMD")
+    } else if matches(CENTAUR) {
+        Some("CentaurHauls")
+    } else if matches(INTEL) {
+        Some("GenuineIntel")
+    } else {
+        None
+    }
+}
+
+/// A parsed target specification string.
+///
+/// Target specification strings have the form `arch[/cpu][+feature]...[~tune]`, such as
+/// `"x86_64+avx2"`, `"x86_64/skylake"`, or `"x86_64+avx2~znver3"`.
+#[derive(Clone, Copy)]
+pub struct Spec<'a> {
+    arch: &'a str,
+    cpu: Option<&'a str>,
+    features: Option<&'a str>,
+    tune: Option<&'a str>,
+    target: Target,
+}
+
+impl<'a> Spec<'a> {
+    /// Parses a target specification string.
+    pub fn parse(s: &'a str) -> Result<Self, Error<'a>> {
+        // The target may be tuned for a CPU with "target~cpu"
+        let (s, tune) = match s.split_once('~') {
+            Some((s, tune)) => (s, Some(tune)),
+            None => (s, None),
+        };
+
+        let (arch_cpu, features) = match s.split_once('+') {
+            Some((arch_cpu, features)) => (arch_cpu, Some(features)),
+            None => (s, None),
+        };
+
+        // Architecture can be either "architecture" or "architecture/cpu"
+        let (arch, cpu) = match arch_cpu.split_once('/') {
+            Some((arch, cpu)) => (arch, Some(cpu)),
+            None => (arch_cpu, None),
+        };
+        if arch.is_empty() || !arch.chars().all(|x| x.is_alphanumeric() || x == '_') {
+            return Err(Error::InvalidArchitecture);
+        }
+
+        let specified_features = features
+            .into_iter()
+            .flat_map(|features| features.split('+'));
+        if specified_features.clone().any(str::is_empty) {
+            return Err(Error::EmptyFeature);
+        }
+
+        let architecture = Architecture::from_str(arch);
+        let mut target = match cpu {
+            Some(cpu) => Target::from_cpu(architecture, cpu).map_err(|_| Error::UnknownCpu(cpu))?,
+            None => Target::new(architecture),
+        };
+        for feature in specified_features {
+            target = target.with_feature(
+                Feature::new(architecture, feature).map_err(|_| Error::UnknownFeature(feature))?,
+            );
+        }
+
+        if let Some(tune) = tune {
+            if architecture != Architecture::X86 {
+                return Err(Error::TuneUnsupported);
+            }
+            Target::from_cpu(Architecture::X86, tune).map_err(|_| Error::UnknownCpu(tune))?;
+            if GENERIC.iter().any(|prefix| tune.starts_with(prefix)) {
+                return Err(Error::GenericTune(tune));
+            }
+            if tune_vendor(tune).is_none() {
+                return Err(Error::UnknownVendor(tune));
+            }
+        }
+
+        Ok(Self {
+            arch,
+            cpu,
+            features,
+            tune,
+            target,
+        })
+    }
+
+    /// Returns the architecture, such as `"x86_64"`.
+    pub fn arch(&self) -> &'a str {
+        self.arch
+    }
+
+    /// Returns the CPU specified with `arch/cpu`, if any.
+    pub fn cpu(&self) -> Option<&'a str> {
+        self.cpu
+    }
+
+    /// Returns the features specified with `+feature`, in the order they're written.
+    ///
+    /// This doesn't include the features of the CPU, or the features they imply.
+    pub fn specified_features(&self) -> impl Iterator<Item = &'a str> + Clone {
+        self.features
+            .into_iter()
+            .flat_map(|features| features.split('+'))
+    }
+
+    /// Returns the CPU the target is tuned for with `~tune`, if any.
+    pub fn tune(&self) -> Option<&'a str> {
+        self.tune
+    }
+
+    /// Returns the target, including the features of the CPU and every implied feature.
+    pub fn target(&self) -> Target {
+        self.target
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let spec = Spec::parse("x86_64/skylake+avx512f+avx2~znver3").unwrap();
+        assert_eq!(spec.arch(), "x86_64");
+        assert_eq!(spec.cpu(), Some("skylake"));
+        assert!(spec.specified_features().eq(["avx512f", "avx2"]));
+        assert_eq!(spec.tune(), Some("znver3"));
+        assert!(spec.target().supports_feature_str("avx512f"));
+        assert!(spec.target().supports_feature_str("sse4.2"));
+
+        let spec = Spec::parse("riscv64").unwrap();
+        assert_eq!(spec.arch(), "riscv64");
+        assert_eq!(spec.specified_features().count(), 0);
+    }
+
+    #[test]
+    fn errors() {
+        for (s, err) in [
+            ("", Error::InvalidArchitecture),
+            ("+x86_64", Error::InvalidArchitecture),
+            ("x86-64+avx", Error::InvalidArchitecture),
+            ("x86_64+avx+", Error::EmptyFeature),
+            ("x86_64/foo", Error::UnknownCpu("foo")),
+            ("x86_64+avx3", Error::UnknownFeature("avx3")),
+            ("aarch64+neon~znver3", Error::TuneUnsupported),
+            ("x86_64+avx2~foo", Error::UnknownCpu("foo")),
+            ("x86_64+avx2~x86-64-v3", Error::GenericTune("x86-64-v3")),
+        ] {
+            assert_eq!(Spec::parse(s).err(), Some(err), "{s}");
+        }
+    }
+
+    #[test]
+    fn vendors() {
+        for (cpu, vendor) in [
+            ("alderlake", Some("GenuineIntel")),
+            ("core-avx2", Some("GenuineIntel")),
+            ("znver3", Some("AuthenticAMD")),
+            ("winchip2", Some("CentaurHauls")),
+            ("x86-64-v3", None),
+            ("future-cpu", None),
+        ] {
+            assert_eq!(tune_vendor(cpu), vendor, "{cpu}");
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Checks that the per-call overhead of the `direct` dispatcher stays within a few nanoseconds of
+//! a regular function call.
+//!
+//! Run with `cargo bench --bench overhead`.  When run by `cargo test`, each function is only
+//! called once.
+use criterion::black_box;
+use std::time::Instant;
+
+#[cfg(feature = "std")]
+#[multiversion::multiversion(targets = "simd", dispatcher = "direct")]
+fn direct_fn(x: u32) -> u32 {
+    x.wrapping_mul(x)
+}
+
+// Runtime dispatch requires the `std` feature.
+#[cfg(not(feature = "std"))]
+use base_fn as direct_fn;
+
+#[inline(never)]
+fn base_fn(x: u32) -> u32 {
+    x.wrapping_mul(x)
+}
+
+const CALLS: u32 = 10_000_000;
+const RUNS: usize = 5;
+const MAX_OVERHEAD_NS: f64 = 5.0;
+
+// Returns the fastest time per call, in nanoseconds.
+fn time_per_call(f: fn(u32) -> u32) -> f64 {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            for i in 0..CALLS {
+                black_box(f(black_box(i)));
+            }
+            start.elapsed().as_secs_f64() * 1e9 / f64::from(CALLS)
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+fn main() {
+    // Don't measure the initial feature detection.
+    assert_eq!(direct_fn(3), base_fn(3));
+    if !std::env::args().any(|arg| arg == "--bench") {
+        return;
+    }
+
+    let direct = time_per_call(direct_fn);
+    let base = time_per_call(base_fn);
+    println!("direct dispatcher: {direct:.2} ns/call");
+    println!("no multiversioning: {base:.2} ns/call");
+    assert!(
+        direct - base < MAX_OVERHEAD_NS,
+        "dispatch overhead of {:.2} ns exceeds {MAX_OVERHEAD_NS} ns",
+        direct - base
+    );
+}
@@ -0,0 +1,294 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// Returns true if the code compiles for the target, with the compiler and flags used to build
+// the crate.
+fn probe(out_dir: &Path, code: &str) -> bool {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let target = std::env::var("TARGET").unwrap();
+    let mut command = match std::env::var_os("RUSTC_WRAPPER") {
+        Some(wrapper) if !wrapper.is_empty() => {
+            let mut command = Command::new(wrapper);
+            command.arg(rustc);
+            command
+        }
+        _ => Command::new(rustc),
+    };
+    if let Ok(flags) = std::env::var("CARGO_ENCODED_RUSTFLAGS") {
+        command.args(flags.split('\x1f').filter(|flag| !flag.is_empty()));
+    }
+    let child = command
+        .args([
+            "--crate-name=multiversion_probe",
+            "--crate-type=lib",
+            "--emit=metadata",
+        ])
+        .args(["--edition=2021", "--target", &target, "--out-dir"])
+        .arg(out_dir)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+    let written = child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(code.as_bytes())
+        .is_ok();
+    written && matches!(child.wait(), Ok(status) if status.success())
+}
+
+// The arguments accepted by the feature detection macros in `std`.  Not every argument is
+// available in every compiler, so each one is probed before it's added to the feature table.
+const X86_FEATURES: &[&str] = &[
+    "abm",
+    "adx",
+    "aes",
+    "avx",
+    "avx2",
+    "avx512bf16",
+    "avx512bitalg",
+    "avx512bw",
+    "avx512cd",
+    "avx512dq",
+    "avx512f",
+    "avx512fp16",
+    "avx512ifma",
+    "avx512vbmi",
+    "avx512vbmi2",
+    "avx512vl",
+    "avx512vnni",
+    "avx512vp2intersect",
+    "avx512vpopcntdq",
+    "avxifma",
+    "avxneconvert",
+    "avxvnni",
+    "avxvnniint16",
+    "avxvnniint8",
+    "bmi1",
+    "bmi2",
+    "cmpxchg16b",
+    "ermsb",
+    "f16c",
+    "fma",
+    "fxsr",
+    "gfni",
+    "kl",
+    "lzcnt",
+    "movbe",
+    "pclmulqdq",
+    "popcnt",
+    "rdrand",
+    "rdseed",
+    "rtm",
+    "sha",
+    "sha512",
+    "sm3",
+    "sm4",
+    "sse",
+    "sse2",
+    "sse3",
+    "sse4.1",
+    "sse4.2",
+    "sse4a",
+    "ssse3",
+    "tbm",
+    "vaes",
+    "vpclmulqdq",
+    "widekl",
+    "xop",
+    "xsave",
+    "xsavec",
+    "xsaveopt",
+    "xsaves",
+];
+
+const AARCH64_FEATURES: &[&str] = &[
+    "aes",
+    "asimd",
+    "bf16",
+    "bti",
+    "crc",
+    "cssc",
+    "dit",
+    "dotprod",
+    "dpb",
+    "dpb2",
+    "ecv",
+    "f32mm",
+    "f64mm",
+    "faminmax",
+    "fcma",
+    "fhm",
+    "flagm",
+    "flagm2",
+    "fp",
+    "fp16",
+    "fp8",
+    "fp8dot2",
+    "fp8dot4",
+    "fp8fma",
+    "fpmr",
+    "frintts",
+    "hbc",
+    "i8mm",
+    "jsconv",
+    "lse",
+    "lse128",
+    "lse2",
+    "lut",
+    "mops",
+    "mte",
+    "neon",
+    "paca",
+    "pacg",
+    "pmull",
+    "rand",
+    "rcpc",
+    "rcpc2",
+    "rcpc3",
+    "rdm",
+    "sb",
+    "sha2",
+    "sha3",
+    "sm4",
+    "sme",
+    "sme2",
+    "ssbs",
+    "sve",
+    "sve2",
+    "sve2-aes",
+    "sve2-bitperm",
+    "sve2-sha3",
+    "sve2-sm4",
+    "tme",
+    "wfxt",
+];
+
+const RISCV_FEATURES: &[&str] = &["a", "c", "d", "f", "m", "v", "zba", "zbb", "zbc", "zbs"];
+
+// Returns the features that the detection macro accepts.  Usually every feature is accepted, so
+// all of them are probed at once, and otherwise each half is probed separately.
+fn probe_features<'a>(out_dir: &Path, detect: &str, features: &[&'a str]) -> Vec<&'a str> {
+    let code = features
+        .iter()
+        .map(|feature| format!("let _ = std::arch::{detect}!({feature:?});"))
+        .collect::<String>();
+    if probe(out_dir, &format!("pub fn probe() {{ {code} }}")) {
+        return features.to_vec();
+    }
+    if features.len() <= 1 {
+        return Vec::new();
+    }
+    let (left, right) = features.split_at(features.len() / 2);
+    let mut accepted = probe_features(out_dir, detect, left);
+    accepted.extend(probe_features(out_dir, detect, right));
+    accepted
+}
+
+fn main() {
+    // Cargo doesn't tell build scripts which profile is in use, but the output directory is
+    // `<target-dir>/[<triple>/]<profile>/build/<package>/out`, and the `dev` profile is placed in
+    // the `debug` directory.  Profiles are matched by the name of the output directory, so other
+    // layouts fall back to `dev`.
+    let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+    let name = |n| {
+        out_dir
+            .ancestors()
+            .nth(n)
+            .and_then(|dir| dir.file_name())
+            .and_then(|name| name.to_str())
+    };
+    let profile = match (name(0), name(2), name(3)) {
+        (Some("out"), Some("build"), Some("debug")) => "dev".to_string(),
+        (Some("out"), Some("build"), Some(profile)) => profile.replace('-', "_"),
+        _ => {
+            println!(
+                "cargo::warning=couldn't determine the profile from the output directory `{}`, \
+                 `targets(profile = ...)` uses the `dev` targets",
+                out_dir.display()
+            );
+            "dev".to_string()
+        }
+    };
+
+    // Selects the tokens for the current profile, used by `targets(profile = ...)`.
+    let select_profile = format!(
+        r#"
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __select_profile {{
+    {{ [$($unlisted:tt)*] }} => {{ $($unlisted)* }};
+    {{ [$($unlisted:tt)*] {profile} {{ $($selected:tt)* }} $($rest:tt)* }} => {{ $($selected)* }};
+    {{ [$($unlisted:tt)*] $other:ident {{ $($ignored:tt)* }} $($rest:tt)* }} => {{
+        $crate::__select_profile! {{ [$($unlisted)*] $($rest)* }}
+    }};
+}}
+"#
+    );
+    std::fs::write(out_dir.join("select_profile.rs"), select_profile).unwrap();
+
+    // The feature detection macros in `std` are stabilized at different times, and some feature
+    // names change, so the table of features detected by `std` only contains the features that
+    // the compiler accepts, rather than relying on the compiler version.  Architectures without
+    // a usable macro aren't detected at runtime.
+    let arch = std::env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+    let (detect, candidates) = match arch.as_str() {
+        "x86" | "x86_64" => ("is_x86_feature_detected", X86_FEATURES),
+        "aarch64" => ("is_aarch64_feature_detected", AARCH64_FEATURES),
+        "riscv32" | "riscv64" => ("is_riscv_feature_detected", RISCV_FEATURES),
+        _ => ("", &[][..]),
+    };
+    let features = if std::env::var_os("CARGO_FEATURE_STD").is_some() && !candidates.is_empty() {
+        probe_features(&out_dir, detect, candidates)
+    } else {
+        Vec::new()
+    };
+    let table = if features.is_empty() {
+        "pub(super) const FEATURES: &[&str] = &[];\n\npub(super) fn detect() -> u128 {\n    0\n}\n"
+            .to_string()
+    } else {
+        format!("features!({detect}, {features:?});\n")
+    };
+    std::fs::write(out_dir.join("detect_features.rs"), table).unwrap();
+    println!("cargo::rerun-if-env-changed=RUSTC_WRAPPER");
+
+    // Coroutines are only available on nightly compilers, and are tested if available.
+    println!("cargo::rustc-check-cfg=cfg(multiversion_coroutines)");
+    if std::env::var_os("CARGO_FEATURE_NIGHTLY").is_some()
+        && probe(
+            &out_dir,
+            r#"#![feature(coroutines, coroutine_trait)]
+            pub fn probe() -> impl core::ops::Coroutine<Yield = u8, Return = ()> {
+                #[coroutine]
+                || {
+                    yield 0;
+                }
+            }"#,
+        )
+    {
+        println!("cargo::rustc-cfg=multiversion_coroutines");
+    }
+
+    // C-variadic functions can only be defined on nightly compilers, and are tested if available.
+    println!("cargo::rustc-check-cfg=cfg(multiversion_c_variadic)");
+    if std::env::var_os("CARGO_FEATURE_NIGHTLY").is_some()
+        && probe(
+            &out_dir,
+            r#"#![feature(c_variadic)]
+            pub unsafe extern "C" fn probe(n: usize, mut args: ...) -> i32 {
+                args.next_arg::<i32>() + n as i32
+            }"#,
+        )
+    {
+        println!("cargo::rustc-cfg=multiversion_c_variadic");
+    }
+
+    println!("cargo::rerun-if-changed=build.rs");
+}
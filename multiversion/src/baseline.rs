@@ -0,0 +1,60 @@
+use crate::detect::feature_detected;
+use target_features::CURRENT_TARGET;
+
+/// Returned by [`verify_baseline`] when the CPU doesn't support the compile-time target features.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BaselineMismatch {
+    missing: Vec<&'static str>,
+}
+
+impl BaselineMismatch {
+    /// Returns the target features that were enabled at compile time, but not detected.
+    pub fn missing_features(&self) -> &[&'static str] {
+        &self.missing
+    }
+}
+
+impl std::fmt::Display for BaselineMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "this program was compiled with target features that are not supported by the CPU: {}",
+            self.missing.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for BaselineMismatch {}
+
+/// Verify that the CPU supports the target features enabled at compile time.
+///
+/// Code compiled with `-Ctarget-cpu` or `-Ctarget-feature` assumes those features are always
+/// available, including the default (fallback) version of every multiversioned function.  Running
+/// such a program on an unsupported CPU usually crashes with an illegal instruction somewhere
+/// deep in the program.  Calling this function at startup allows reporting a clear error instead.
+///
+/// Features that can't be detected at runtime are assumed to be supported.
+///
+/// This function is only available with the `std` cargo feature.
+///
+/// # Example
+/// ```
+/// fn main() {
+///     if let Err(err) = multiversion::verify_baseline() {
+///         eprintln!("{err}");
+///         std::process::abort();
+///     }
+/// }
+/// ```
+pub fn verify_baseline() -> Result<(), BaselineMismatch> {
+    let missing = CURRENT_TARGET
+        .features()
+        .map(|feature| feature.name())
+        .filter(|feature| feature_detected(feature) == Some(false))
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(BaselineMismatch { missing })
+    }
+}
@@ -0,0 +1,174 @@
+//! Detection of features that aren't supported by every core.
+//!
+//! On CPUs with heterogeneous cores, such as performance and efficiency cores, each core may
+//! report different features.  Feature detection normally only queries the core it runs on, so a
+//! function selected on one core may not be supported by another.
+//!
+//! On x86 and x86-64 Linux, `cpuid` is executed on every core the process may run on, by pinning
+//! a thread to each core in turn.  On AArch64 Linux, the kernel already reports only the features
+//! supported by every core.  On other platforms, every core is assumed to support the same
+//! features.
+
+// Returns `true` if the feature isn't supported by every core.
+pub(crate) fn is_heterogeneous(feature: &str) -> bool {
+    #[cfg(all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        any(target_os = "linux", target_os = "android")
+    ))]
+    return x86::is_heterogeneous(feature);
+
+    #[allow(unreachable_code)]
+    {
+        let _ = feature;
+        false
+    }
+}
+
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    any(target_os = "linux", target_os = "android")
+))]
+mod x86 {
+    use crate::detect::BitsetCache;
+    use std::os::raw::{c_int, c_ulong};
+
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::{__cpuid_count, __get_cpuid_max};
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::{__cpuid_count, __get_cpuid_max};
+
+    // Large enough for 1024 CPUs, like glibc's `cpu_set_t`.
+    const BITS_PER_WORD: usize = 8 * std::mem::size_of::<c_ulong>();
+    type CpuSet = [c_ulong; 1024 / BITS_PER_WORD];
+
+    extern "C" {
+        fn sched_getaffinity(pid: c_int, size: usize, set: *mut CpuSet) -> c_int;
+        fn sched_setaffinity(pid: c_int, size: usize, set: *const CpuSet) -> c_int;
+    }
+
+    // Registers reported by `cpuid`.
+    const LEAF1_ECX: usize = 0;
+    const LEAF1_EDX: usize = 1;
+    const LEAF7_EBX: usize = 2;
+    const LEAF7_ECX: usize = 3;
+    const LEAF7_EDX: usize = 4;
+
+    // Feature name, register, and bit.
+    const FEATURES: &[(&str, usize, u32)] = &[
+        ("sse3", LEAF1_ECX, 0),
+        ("pclmulqdq", LEAF1_ECX, 1),
+        ("ssse3", LEAF1_ECX, 9),
+        ("fma", LEAF1_ECX, 12),
+        ("cmpxchg16b", LEAF1_ECX, 13),
+        ("sse4.1", LEAF1_ECX, 19),
+        ("sse4.2", LEAF1_ECX, 20),
+        ("movbe", LEAF1_ECX, 22),
+        ("popcnt", LEAF1_ECX, 23),
+        ("aes", LEAF1_ECX, 25),
+        ("xsave", LEAF1_ECX, 26),
+        ("avx", LEAF1_ECX, 28),
+        ("f16c", LEAF1_ECX, 29),
+        ("rdrand", LEAF1_ECX, 30),
+        ("fxsr", LEAF1_EDX, 24),
+        ("sse", LEAF1_EDX, 25),
+        ("sse2", LEAF1_EDX, 26),
+        ("bmi1", LEAF7_EBX, 3),
+        ("avx2", LEAF7_EBX, 5),
+        ("bmi2", LEAF7_EBX, 8),
+        ("avx512f", LEAF7_EBX, 16),
+        ("avx512dq", LEAF7_EBX, 17),
+        ("rdseed", LEAF7_EBX, 18),
+        ("adx", LEAF7_EBX, 19),
+        ("avx512ifma", LEAF7_EBX, 21),
+        ("avx512cd", LEAF7_EBX, 28),
+        ("sha", LEAF7_EBX, 29),
+        ("avx512bw", LEAF7_EBX, 30),
+        ("avx512vl", LEAF7_EBX, 31),
+        ("avx512vbmi", LEAF7_ECX, 1),
+        ("avx512vbmi2", LEAF7_ECX, 6),
+        ("gfni", LEAF7_ECX, 8),
+        ("vaes", LEAF7_ECX, 9),
+        ("vpclmulqdq", LEAF7_ECX, 10),
+        ("avx512vnni", LEAF7_ECX, 11),
+        ("avx512bitalg", LEAF7_ECX, 12),
+        ("avx512vpopcntdq", LEAF7_ECX, 14),
+        ("avx512vp2intersect", LEAF7_EDX, 8),
+        ("avx512fp16", LEAF7_EDX, 23),
+    ];
+
+    const _: () = assert!(FEATURES.len() <= 128);
+
+    static CACHE: BitsetCache = BitsetCache::new();
+
+    pub(super) fn is_heterogeneous(feature: &str) -> bool {
+        matches!(
+            FEATURES.iter().position(|(name, _, _)| *name == feature),
+            Some(index) if CACHE.get(heterogeneous_features) & (1 << index) != 0
+        )
+    }
+
+    // Returns the feature registers of the current core.
+    fn registers() -> [u32; 5] {
+        // Safety: cpuid is available on every CPU that runs Linux with `std`.
+        #[allow(unused_unsafe)]
+        unsafe {
+            let (max_leaf, _) = __get_cpuid_max(0);
+            let leaf1 = __cpuid_count(1, 0);
+            let leaf7 = if max_leaf >= 7 {
+                __cpuid_count(7, 0)
+            } else {
+                std::mem::zeroed()
+            };
+            [leaf1.ecx, leaf1.edx, leaf7.ebx, leaf7.ecx, leaf7.edx]
+        }
+    }
+
+    // Returns the bitset of features that are reported by some cores but not others.
+    //
+    // The cores are probed sequentially by a single thread, so that the affinity of the calling
+    // thread is unchanged.  If no core can be probed, the cores are assumed to be the same, as on
+    // other platforms.
+    fn heterogeneous_features() -> u128 {
+        let mut allowed: CpuSet = [0; 1024 / BITS_PER_WORD];
+        // Safety: the set is the size passed to the function.
+        if unsafe { sched_getaffinity(0, std::mem::size_of::<CpuSet>(), &mut allowed) } != 0 {
+            return 0;
+        }
+
+        let probe = std::thread::Builder::new().spawn(move || {
+            let mut all = [u32::MAX; 5];
+            let mut any = [0; 5];
+            let mut probed = false;
+            for cpu in 0..allowed.len() * BITS_PER_WORD {
+                if allowed[cpu / BITS_PER_WORD] & (1 << (cpu % BITS_PER_WORD)) == 0 {
+                    continue;
+                }
+                let mut set: CpuSet = [0; 1024 / BITS_PER_WORD];
+                set[cpu / BITS_PER_WORD] = 1 << (cpu % BITS_PER_WORD);
+                // Safety: the set is the size passed to the function.
+                if unsafe { sched_setaffinity(0, std::mem::size_of::<CpuSet>(), &set) } != 0 {
+                    continue;
+                }
+                for ((all, any), register) in all.iter_mut().zip(&mut any).zip(registers()) {
+                    *all &= register;
+                    *any |= register;
+                }
+                probed = true;
+            }
+            if probed {
+                Some((all, any))
+            } else {
+                None
+            }
+        });
+        let (all, any) = match probe.ok().and_then(|probe| probe.join().ok().flatten()) {
+            Some(registers) => registers,
+            None => return 0,
+        };
+        FEATURES
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, register, bit))| (all[*register] ^ any[*register]) & (1 << bit) != 0)
+            .fold(0, |mask, (index, _)| mask | 1 << index)
+    }
+}
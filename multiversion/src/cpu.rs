@@ -0,0 +1,451 @@
+//! CPU identification.
+//!
+//! CPUs with the same target features can have very different performance characteristics.
+//! This module identifies the CPU, so that a function can be selected for a particular
+//! microarchitecture, for example in a [`detector`](crate::multiversion) or when selecting
+//! functions manually:
+//! ```
+//! use multiversion::cpu::{self, Vendor};
+//!
+//! // AMD Zen 3 and later
+//! let zen3 = cpu::vendor() == Vendor::Amd
+//!     && matches!(cpu::signature(), Some(signature) if signature.family() >= 0x19);
+//! if zen3 {
+//!     // use an implementation tuned for Zen 3
+//! }
+//! ```
+//!
+//! Programs may also run under an emulator or binary translator, such as Rosetta 2 or
+//! `qemu-user`, which may report the same features as a real CPU but execute wide vector
+//! instructions much more slowly.  [`emulator`] detects some emulators, so that simpler versions
+//! can be preferred.
+//!
+//! CPUs are identified with `cpuid` on x86 and x86-64.  On AArch64 Linux and Android, CPUs are
+//! identified with the main ID register reported by the kernel, which requires the `std` cargo
+//! feature.  Apple CPUs are identified by the operating system.
+
+/// A CPU vendor.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Vendor {
+    /// Intel
+    Intel,
+    /// AMD
+    Amd,
+    /// Hygon
+    Hygon,
+    /// Centaur (VIA)
+    Centaur,
+    /// Zhaoxin
+    Zhaoxin,
+    /// Arm
+    Arm,
+    /// Apple
+    Apple,
+    /// Qualcomm
+    Qualcomm,
+    /// NVIDIA
+    Nvidia,
+    /// Ampere Computing
+    Ampere,
+    /// Fujitsu
+    Fujitsu,
+    /// HiSilicon
+    HiSilicon,
+    /// Any other vendor, or a vendor that couldn't be identified.
+    Unknown,
+}
+
+/// The family, model, and stepping of a CPU.
+///
+/// On x86 and x86-64, these are the display family, model, and stepping reported by `cpuid`.
+/// On AArch64, the family is the primary part number, the model is the variant, and the
+/// stepping is the revision from the main ID register.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Signature {
+    family: u32,
+    model: u32,
+    stepping: u32,
+}
+
+impl Signature {
+    /// Returns the family.
+    pub const fn family(&self) -> u32 {
+        self.family
+    }
+
+    /// Returns the model.
+    pub const fn model(&self) -> u32 {
+        self.model
+    }
+
+    /// Returns the stepping.
+    pub const fn stepping(&self) -> u32 {
+        self.stepping
+    }
+}
+
+/// Returns the vendor of the CPU.
+///
+/// The CPU is identified each time this function is called.
+pub fn vendor() -> Vendor {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if let Some(id) = vendor_id() {
+        return match &id {
+            b"GenuineIntel" => Vendor::Intel,
+            b"AuthenticAMD" => Vendor::Amd,
+            b"HygonGenuine" => Vendor::Hygon,
+            b"CentaurHauls" => Vendor::Centaur,
+            b"  Shanghai  " => Vendor::Zhaoxin,
+            _ => Vendor::Unknown,
+        };
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    if let Some(midr) = aarch64::midr() {
+        return match midr >> 24 & 0xff {
+            0x41 => Vendor::Arm,
+            0x46 => Vendor::Fujitsu,
+            0x48 => Vendor::HiSilicon,
+            0x4e => Vendor::Nvidia,
+            0x51 => Vendor::Qualcomm,
+            0x61 => Vendor::Apple,
+            0xc0 => Vendor::Ampere,
+            _ => Vendor::Unknown,
+        };
+    }
+
+    #[cfg(all(target_arch = "aarch64", target_vendor = "apple"))]
+    return Vendor::Apple;
+
+    #[allow(unreachable_code)]
+    Vendor::Unknown
+}
+
+/// Returns the family, model, and stepping of the CPU, if available.
+///
+/// The CPU is identified each time this function is called.
+pub fn signature() -> Option<Signature> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if let Some(eax) = cpuid(1).map(|(eax, _, _, _)| eax) {
+        let base_family = eax >> 8 & 0xf;
+        let base_model = eax >> 4 & 0xf;
+        let (family, model) = match base_family {
+            0xf => (
+                base_family + (eax >> 20 & 0xff),
+                base_model | (eax >> 16 & 0xf) << 4,
+            ),
+            0x6 => (base_family, base_model | (eax >> 16 & 0xf) << 4),
+            _ => (base_family, base_model),
+        };
+        return Some(Signature {
+            family,
+            model,
+            stepping: eax & 0xf,
+        });
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    if let Some(midr) = aarch64::midr() {
+        return Some(Signature {
+            family: midr >> 4 & 0xfff,
+            model: midr >> 20 & 0xf,
+            stepping: midr & 0xf,
+        });
+    }
+
+    #[allow(unreachable_code)]
+    None
+}
+
+/// An emulator or binary translator.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Emulator {
+    /// Apple Rosetta 2, on macOS or in Linux virtual machines
+    Rosetta,
+    /// QEMU user mode emulation
+    Qemu,
+}
+
+/// Returns the emulator running the program, if it's detected.
+///
+/// Detection is a best-effort heuristic:
+/// * On macOS, Rosetta 2 is detected with the `sysctl.proc_translated` sysctl.
+/// * On x86 and x86-64, Rosetta 2 is detected by the processor brand string.
+/// * On Linux and Android, with the `std` cargo feature, Rosetta 2 and `qemu-user` are detected
+///   if the kernel's architecture (read from `/proc/sys/kernel/arch`) can't run the program
+///   natively, by the binary format registered for the program's architecture.  Other emulators
+///   aren't detected.
+///
+/// The emulator is detected each time this function is called.
+///
+/// The result can be used by a custom feature detector to prefer simpler versions under
+/// emulation:
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use multiversion::{cpu, detect};
+///
+/// fn detector(_arch: &str, feature: &str) -> Option<bool> {
+///     // Emulated AVX is slower than SSE
+///     if feature.starts_with("avx") && cpu::emulator().is_some() {
+///         Some(false)
+///     } else {
+///         None
+///     }
+/// }
+///
+/// detect::set_detector(detector).unwrap();
+/// # }
+/// ```
+pub fn emulator() -> Option<Emulator> {
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    if macos::is_translated() {
+        return Some(Emulator::Rosetta);
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if matches!(brand_string(), Some(brand) if brand.windows(12).any(|w| w == b"VirtualApple")) {
+        return Some(Emulator::Rosetta);
+    }
+
+    #[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+    if let Some(emulator) = linux::binfmt_emulator() {
+        return Some(emulator);
+    }
+
+    #[allow(unreachable_code)]
+    None
+}
+
+/// Returns `true` if the CPU vendor identification string matches `vendor`.
+#[doc(hidden)]
+pub fn __is_vendor(vendor: &str) -> bool {
+    matches!(vendor_id(), Some(id) if id == *vendor.as_bytes())
+}
+
+/// Returns `true` if the CPU prefers 256-bit vectors to 512-bit vectors, selecting the 256-bit
+/// variants of AVX-512 targets with `avx512_width = "auto"`.
+///
+/// Intel CPUs before Sapphire Rapids reduce their clock frequency while executing 512-bit
+/// instructions, which often slows down the surrounding code more than the wider vectors speed up
+/// the function.
+#[doc(hidden)]
+pub fn __prefers_256_bit() -> bool {
+    vendor() == Vendor::Intel
+        && matches!(
+            signature(),
+            Some(signature) if signature.family() == 6 && matches!(
+                signature.model(),
+                // Skylake-SP, Cascade Lake, Cooper Lake
+                0x55
+                // Cannon Lake
+                | 0x66
+                // Ice Lake
+                | 0x6a | 0x6c | 0x7d | 0x7e
+                // Tiger Lake
+                | 0x8c | 0x8d
+            )
+        )
+}
+
+/// Returns the scores of a priority model for the current CPU, from the scores of each vendor and
+/// optionally family.  Scores for the family of the CPU are preferred over scores for its vendor.
+#[doc(hidden)]
+pub fn __model_scores(cpus: &[(Vendor, Option<u32>, &'static [i32])]) -> Option<&'static [i32]> {
+    let vendor = vendor();
+    let family = signature().map(|signature| signature.family());
+    let mut vendor_scores = None;
+    for &(cpu_vendor, cpu_family, scores) in cpus {
+        if cpu_vendor != vendor {
+            continue;
+        }
+        match cpu_family {
+            None => vendor_scores = vendor_scores.or(Some(scores)),
+            Some(cpu_family) if Some(cpu_family) == family => return Some(scores),
+            Some(_) => {}
+        }
+    }
+    vendor_scores
+}
+
+// Returns eax, ebx, ecx, and edx for a `cpuid` leaf, if supported.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn cpuid(leaf: u32) -> Option<(u32, u32, u32, u32)> {
+    cpuid_count(leaf, 0)
+}
+
+// Returns eax, ebx, ecx, and edx for a `cpuid` leaf and subleaf, if supported.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn cpuid_count(leaf: u32, subleaf: u32) -> Option<(u32, u32, u32, u32)> {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::{__cpuid_count, __get_cpuid_max, has_cpuid};
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::{__cpuid_count, __get_cpuid_max};
+
+    #[cfg(target_arch = "x86")]
+    if !has_cpuid() {
+        return None;
+    }
+
+    // Safety: cpuid is supported, checked above on x86 and always available on x86-64.  The
+    // extended leaves have a separate maximum.
+    #[allow(unused_unsafe)]
+    let (max_leaf, _) = unsafe { __get_cpuid_max(leaf & 0x8000_0000) };
+    if leaf > max_leaf {
+        return None;
+    }
+    // Safety: the leaf is supported, checked above.
+    #[allow(unused_unsafe)]
+    let result = unsafe { __cpuid_count(leaf, subleaf) };
+    Some((result.eax, result.ebx, result.ecx, result.edx))
+}
+
+// Returns the CPU vendor identification string, if available.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn vendor_id() -> Option<[u8; 12]> {
+    let (_, ebx, ecx, edx) = cpuid(0)?;
+    let mut id = [0; 12];
+    id[0..4].copy_from_slice(&ebx.to_le_bytes());
+    id[4..8].copy_from_slice(&edx.to_le_bytes());
+    id[8..12].copy_from_slice(&ecx.to_le_bytes());
+    Some(id)
+}
+
+// Returns the processor brand string, if available.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn brand_string() -> Option<[u8; 48]> {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::{__cpuid, __get_cpuid_max};
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::{__cpuid, __get_cpuid_max};
+
+    // Checks that cpuid is supported.
+    cpuid(0)?;
+    // Safety: cpuid is supported, checked above.
+    #[allow(unused_unsafe)]
+    let (max_leaf, _) = unsafe { __get_cpuid_max(0x8000_0000) };
+    if max_leaf < 0x8000_0004 {
+        return None;
+    }
+    let mut brand = [0; 48];
+    for (leaf, chunk) in (0x8000_0002..=0x8000_0004).zip(brand.chunks_mut(16)) {
+        // Safety: the leaf is supported, checked above.
+        #[allow(unused_unsafe)]
+        let result = unsafe { __cpuid(leaf) };
+        for (register, bytes) in [result.eax, result.ebx, result.ecx, result.edx]
+            .iter()
+            .zip(chunk.chunks_mut(4))
+        {
+            bytes.copy_from_slice(&register.to_le_bytes());
+        }
+    }
+    Some(brand)
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+pub(crate) fn vendor_id() -> Option<[u8; 12]> {
+    None
+}
+
+#[cfg(target_arch = "aarch64")]
+mod aarch64 {
+    // Returns the main ID register of the first CPU, as reported by the kernel.
+    #[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+    pub(super) fn midr() -> Option<u32> {
+        let midr =
+            std::fs::read_to_string("/sys/devices/system/cpu/cpu0/regs/identification/midr_el1")
+                .ok()?;
+        let midr = midr.trim();
+        u64::from_str_radix(midr.strip_prefix("0x").unwrap_or(midr), 16)
+            .ok()
+            .map(|midr| midr as u32)
+    }
+
+    #[cfg(not(all(feature = "std", any(target_os = "linux", target_os = "android"))))]
+    pub(super) fn midr() -> Option<u32> {
+        None
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod macos {
+    use core::ffi::c_void;
+
+    // `c_int` is `i32` on Apple platforms.
+    extern "C" {
+        fn sysctlbyname(
+            name: *const u8,
+            oldp: *mut c_void,
+            oldlenp: *mut usize,
+            newp: *mut c_void,
+            newlen: usize,
+        ) -> i32;
+    }
+
+    // Returns `true` if the process is translated by Rosetta 2.
+    pub(super) fn is_translated() -> bool {
+        let mut translated: i32 = 0;
+        let mut size = core::mem::size_of::<i32>();
+        // Safety: the name is nul-terminated, and the output is the size passed to the function.
+        let result = unsafe {
+            sysctlbyname(
+                b"sysctl.proc_translated\0".as_ptr(),
+                (&mut translated as *mut i32).cast(),
+                &mut size,
+                core::ptr::null_mut(),
+                0,
+            )
+        };
+        result == 0 && translated == 1
+    }
+}
+
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+mod linux {
+    use super::Emulator;
+
+    // Returns `true` if the binary format is registered and enabled.
+    fn binfmt_enabled(name: &str) -> bool {
+        matches!(
+            std::fs::read_to_string(format!("/proc/sys/fs/binfmt_misc/{name}")),
+            Ok(binfmt) if binfmt.lines().next() == Some("enabled")
+        )
+    }
+
+    // Returns `true` if a kernel of the given architecture runs programs of the program's
+    // architecture natively, including 32-bit programs on 64-bit kernels.
+    fn native(kernel: &str) -> bool {
+        match std::env::consts::ARCH {
+            "x86" => kernel == "x86_64" || matches!(kernel.as_bytes(), [b'i', _, b'8', b'6']),
+            "arm" => kernel.starts_with("arm") || kernel == "aarch64",
+            "powerpc" => kernel.starts_with("ppc"),
+            "powerpc64" => kernel.starts_with("ppc64"),
+            arch => kernel.starts_with(arch),
+        }
+    }
+
+    // Returns the emulator registered for the program's architecture, if the kernel's
+    // architecture can't run the program natively.  Emulators such as `qemu-user` report the
+    // emulated architecture from `uname`, but not from `/proc/sys/kernel/arch`.
+    pub(super) fn binfmt_emulator() -> Option<Emulator> {
+        let kernel = std::fs::read_to_string("/proc/sys/kernel/arch").ok()?;
+        if native(kernel.trim()) {
+            return None;
+        }
+        if cfg!(target_arch = "x86_64") && binfmt_enabled("rosetta") {
+            return Some(Emulator::Rosetta);
+        }
+        let arch = match std::env::consts::ARCH {
+            "x86" => "i386",
+            "powerpc" => "ppc",
+            "powerpc64" if cfg!(target_endian = "little") => "ppc64le",
+            "powerpc64" => "ppc64",
+            arch => arch,
+        };
+        if binfmt_enabled(&format!("qemu-{arch}")) {
+            return Some(Emulator::Qemu);
+        }
+        None
+    }
+}
@@ -0,0 +1,355 @@
+//! Runtime feature detection by feature name.
+//!
+//! With the `detect-backend-custom` cargo feature, dispatchers detect features with
+//! [`is_feature_detected`] rather than the `is_*_feature_detected` macros in `std`.  This allows
+//! replacing the feature detection with [`set_detector`], for example to detect features that
+//! `std` doesn't support yet.
+//!
+//! The `is_*_feature_detected` macros in `std` only accept string literals, so this module maps
+//! feature names (as used by `target_features`) to the corresponding macro invocations.
+//!
+//! Every feature supported by `std` is detected at once, the first time any feature is detected,
+//! and the result is cached for the whole process.  Programs with many multiversioned functions
+//! only pay for feature detection once.
+//!
+//! With the `env_disable` cargo feature, features listed in the `MULTIVERSION_DISABLE`
+//! environment variable are never detected.  The variable is read once, along with the first
+//! feature detection.
+
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, Ordering};
+
+/// A function that detects a target feature, given the architecture and feature names.
+///
+/// Returns `None` to defer to the default feature detection.
+pub type Detector = fn(arch: &str, feature: &str) -> Option<bool>;
+
+static DETECTOR: AtomicPtr<()> = AtomicPtr::new(std::ptr::null_mut());
+
+/// Registers a function that replaces the default feature detection.
+///
+/// Only one detector may be registered.  If a detector is already registered, the new detector
+/// is returned as an error.
+///
+/// Dispatchers only detect features once, so the detector should be registered before any
+/// multiversioned functions are called.
+pub fn set_detector(detector: Detector) -> Result<(), Detector> {
+    DETECTOR
+        .compare_exchange(
+            std::ptr::null_mut(),
+            detector as *mut (),
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .map(|_| ())
+        .map_err(|_| detector)
+}
+
+/// Detect a target feature at runtime.
+///
+/// Uses the detector registered with [`set_detector`], if any, and otherwise uses the feature
+/// detection in `std`.  Features of architectures other than the current architecture are
+/// never detected.
+pub fn is_feature_detected(arch: &str, feature: &str) -> bool {
+    let detector = DETECTOR.load(Ordering::Acquire);
+    if !detector.is_null() {
+        // Safety: the pointer was created from a `Detector` in `set_detector`
+        let detector = unsafe { std::mem::transmute::<*mut (), Detector>(detector) };
+        if let Some(detected) = detector(arch, feature) {
+            return detected;
+        }
+    }
+    arch == std::env::consts::ARCH && feature_detected(feature).unwrap_or(false)
+}
+
+// Defines the features detectable by `std`, and a function that detects all of them as a bitset
+// indexed by position in `FEATURES`.
+#[allow(unused_macros)]
+macro_rules! features {
+    { $detect:ident, [$($name:tt),* $(,)?] } => {
+        pub(super) const FEATURES: &[&str] = &[$($name),*];
+
+        pub(super) fn detect() -> u128 {
+            let mut detected = 0;
+            let mut bit = 1;
+            $(
+                if std::arch::$detect!($name) {
+                    detected |= bit;
+                }
+                bit <<= 1;
+            )*
+            let _ = bit;
+            detected
+        }
+    }
+}
+
+/// Detect a target feature at runtime, on every core of the CPU.
+///
+/// Dispatchers with `detect = "all_cores"` call this function.  Features are detected with the
+/// configured detection backend, and features that aren't supported by every core are excluded.
+#[doc(hidden)]
+pub fn __is_feature_detected_all_cores(arch: &str, feature: &str) -> bool {
+    #[cfg(feature = "detect-backend-custom")]
+    let detected = is_feature_detected(arch, feature);
+    #[cfg(not(feature = "detect-backend-custom"))]
+    let detected = __is_std_feature_detected(arch, feature);
+    detected && arch == std::env::consts::ARCH && !crate::cores::is_heterogeneous(feature)
+}
+
+/// Detect a target feature at runtime with the feature detection in `std`.
+///
+/// Dispatchers call this function rather than the `is_*_feature_detected` macros directly, so
+/// that changes to the macros only affect this crate.
+#[doc(hidden)]
+pub fn __is_std_feature_detected(arch: &str, feature: &str) -> bool {
+    arch == std::env::consts::ARCH && feature_detected(feature).unwrap_or(false)
+}
+
+// The bitset is split into 32-bit words, since not every target supports 64-bit atomics.
+const _: () = assert!(features::FEATURES.len() <= 128);
+
+// A bitset that is computed once.
+pub(crate) struct BitsetCache {
+    words: [AtomicU32; 4],
+    filled: AtomicBool,
+}
+
+impl BitsetCache {
+    pub(crate) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU32 = AtomicU32::new(0);
+        Self {
+            words: [ZERO; 4],
+            filled: AtomicBool::new(false),
+        }
+    }
+
+    // Returns the bitset, computing it with `f` the first time.
+    pub(crate) fn get(&self, f: impl FnOnce() -> u128) -> u128 {
+        if self.filled.load(Ordering::Acquire) {
+            self.words
+                .iter()
+                .enumerate()
+                .fold(0, |bits, (index, word)| {
+                    bits | u128::from(word.load(Ordering::Relaxed)) << (32 * index)
+                })
+        } else {
+            // Racing threads store the same value.
+            let bits = f();
+            for (index, word) in self.words.iter().enumerate() {
+                word.store((bits >> (32 * index)) as u32, Ordering::Relaxed);
+            }
+            self.filled.store(true, Ordering::Release);
+            bits
+        }
+    }
+}
+
+static CACHE: BitsetCache = BitsetCache::new();
+
+// Returns the bitset of detected features, detecting them the first time.
+fn detected_features() -> u128 {
+    CACHE.get(|| features::detect() & !disabled_features())
+}
+
+// Returns the bitset of features disabled by the `MULTIVERSION_DISABLE` environment variable.
+#[cfg(feature = "env_disable")]
+fn disabled_features() -> u128 {
+    features::FEATURES
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| is_disabled(name))
+        .fold(0, |mask, (index, _)| mask | 1 << index)
+}
+
+#[cfg(not(feature = "env_disable"))]
+fn disabled_features() -> u128 {
+    0
+}
+
+#[cfg(feature = "env_disable")]
+fn is_disabled(feature: &str) -> bool {
+    std::env::var("MULTIVERSION_DISABLE")
+        .map(|disabled| disabled.split(',').any(|name| name.trim() == feature))
+        .unwrap_or(false)
+}
+
+/// Detect a feature of the current architecture at runtime.
+///
+/// Features that `std` can't detect fall back to the capabilities reported by the operating
+/// system or by `cpuid`, if available.  Returns `None` if the feature can't be detected at
+/// runtime.
+pub(crate) fn feature_detected(feature: &str) -> Option<bool> {
+    let detected = features::FEATURES
+        .iter()
+        .position(|name| *name == feature)
+        .map(|index| detected_features() & (1 << index) != 0);
+    if detected.is_some() {
+        return detected;
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    let detected = crate::hwcap::is_feature_detected(feature);
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    let detected = cpuid_feature_detected(feature);
+
+    #[cfg(feature = "env_disable")]
+    let detected = detected.map(|detected| detected && !is_disabled(feature));
+
+    detected
+}
+
+/// Detect a target feature in a GNU indirect function resolver.
+///
+/// Resolvers run while the dynamic loader processes relocations, before the program is
+/// initialized, so features are detected with `cpuid` on x86 and x86-64 and with `getauxval` on
+/// AArch64 Linux, without the detection in `std` or caching.  Features that can't be detected
+/// this way, including every feature on other platforms, are never detected.
+#[doc(hidden)]
+pub fn __is_ifunc_feature_detected(arch: &str, feature: &str) -> bool {
+    if arch != std::env::consts::ARCH {
+        return false;
+    }
+
+    #[cfg(all(target_arch = "aarch64", target_os = "linux"))]
+    return crate::hwcap::is_feature_detected(feature).unwrap_or(false);
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    return cpuid_feature_detected(feature).unwrap_or(false);
+
+    #[allow(unreachable_code)]
+    {
+        let _ = feature;
+        false
+    }
+}
+
+// The registers returned by `cpuid`.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[derive(Copy, Clone)]
+enum Register {
+    Eax,
+    Ebx,
+    Ecx,
+    Edx,
+}
+
+// The `XCR0` state components that the operating system must enable for AVX and AVX-512.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const AVX_STATE: u64 = 0b110;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const AVX512_STATE: u64 = 0b1110_0110;
+
+// Feature name, `cpuid` leaf and subleaf, register, bit, and required `XCR0` state components.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[rustfmt::skip]
+const CPUID_FEATURES: &[(&str, u32, u32, Register, u32, u64)] = {
+    use Register::*;
+    &[
+        ("fxsr", 1, 0, Edx, 24, 0),
+        ("sse", 1, 0, Edx, 25, 0),
+        ("sse2", 1, 0, Edx, 26, 0),
+        ("sse3", 1, 0, Ecx, 0, 0),
+        ("pclmulqdq", 1, 0, Ecx, 1, 0),
+        ("ssse3", 1, 0, Ecx, 9, 0),
+        ("fma", 1, 0, Ecx, 12, AVX_STATE),
+        ("cmpxchg16b", 1, 0, Ecx, 13, 0),
+        ("sse4.1", 1, 0, Ecx, 19, 0),
+        ("sse4.2", 1, 0, Ecx, 20, 0),
+        ("movbe", 1, 0, Ecx, 22, 0),
+        ("popcnt", 1, 0, Ecx, 23, 0),
+        ("aes", 1, 0, Ecx, 25, 0),
+        ("xsave", 1, 0, Ecx, 26, 0),
+        ("avx", 1, 0, Ecx, 28, AVX_STATE),
+        ("f16c", 1, 0, Ecx, 29, AVX_STATE),
+        ("rdrand", 1, 0, Ecx, 30, 0),
+        ("bmi1", 7, 0, Ebx, 3, 0),
+        ("avx2", 7, 0, Ebx, 5, AVX_STATE),
+        ("bmi2", 7, 0, Ebx, 8, 0),
+        ("ermsb", 7, 0, Ebx, 9, 0),
+        ("rtm", 7, 0, Ebx, 11, 0),
+        ("avx512f", 7, 0, Ebx, 16, AVX512_STATE),
+        ("avx512dq", 7, 0, Ebx, 17, AVX512_STATE),
+        ("rdseed", 7, 0, Ebx, 18, 0),
+        ("adx", 7, 0, Ebx, 19, 0),
+        ("avx512ifma", 7, 0, Ebx, 21, AVX512_STATE),
+        ("avx512cd", 7, 0, Ebx, 28, AVX512_STATE),
+        ("sha", 7, 0, Ebx, 29, 0),
+        ("avx512bw", 7, 0, Ebx, 30, AVX512_STATE),
+        ("avx512vl", 7, 0, Ebx, 31, AVX512_STATE),
+        ("avx512vbmi", 7, 0, Ecx, 1, AVX512_STATE),
+        ("avx512vbmi2", 7, 0, Ecx, 6, AVX512_STATE),
+        ("gfni", 7, 0, Ecx, 8, 0),
+        ("vaes", 7, 0, Ecx, 9, AVX_STATE),
+        ("vpclmulqdq", 7, 0, Ecx, 10, AVX_STATE),
+        ("avx512vnni", 7, 0, Ecx, 11, AVX512_STATE),
+        ("avx512bitalg", 7, 0, Ecx, 12, AVX512_STATE),
+        ("avx512vpopcntdq", 7, 0, Ecx, 14, AVX512_STATE),
+        ("avx512fp16", 7, 0, Edx, 23, AVX512_STATE),
+        ("sha512", 7, 1, Eax, 0, AVX_STATE),
+        ("sm3", 7, 1, Eax, 1, 0),
+        ("sm4", 7, 1, Eax, 2, AVX_STATE),
+        ("avxvnni", 7, 1, Eax, 4, AVX_STATE),
+        ("avx512bf16", 7, 1, Eax, 5, AVX512_STATE),
+        ("xsaveopt", 0xd, 1, Eax, 0, 0),
+        ("xsavec", 0xd, 1, Eax, 1, 0),
+        ("xsaves", 0xd, 1, Eax, 3, 0),
+        ("lahfsahf", 0x8000_0001, 0, Ecx, 0, 0),
+        ("lzcnt", 0x8000_0001, 0, Ecx, 5, 0),
+        ("sse4a", 0x8000_0001, 0, Ecx, 6, 0),
+        ("prfchw", 0x8000_0001, 0, Ecx, 8, 0),
+        ("tbm", 0x8000_0001, 0, Ecx, 21, 0),
+    ]
+};
+
+// Detects a feature with `cpuid`, including the features that `std` can't detect.  Returns `None`
+// if the feature isn't known.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn cpuid_feature_detected(feature: &str) -> Option<bool> {
+    let &(_, leaf, subleaf, register, bit, state) = CPUID_FEATURES
+        .iter()
+        .find(|(name, _, _, _, _, _)| *name == feature)?;
+    let supported = match crate::cpu::cpuid_count(leaf, subleaf) {
+        Some((eax, ebx, ecx, edx)) => {
+            let value = match register {
+                Register::Eax => eax,
+                Register::Ebx => ebx,
+                Register::Ecx => ecx,
+                Register::Edx => edx,
+            };
+            value & (1 << bit) != 0
+        }
+        None => false,
+    };
+    Some(supported && enabled_state() & state == state)
+}
+
+// Returns the state components enabled by the operating system in `XCR0`.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn enabled_state() -> u64 {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::_xgetbv;
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::_xgetbv;
+
+    #[target_feature(enable = "xsave")]
+    unsafe fn xcr0() -> u64 {
+        _xgetbv(0)
+    }
+
+    // `xgetbv` is only available if the operating system enabled `xsave`.
+    let osxsave = matches!(crate::cpu::cpuid(1), Some((_, _, ecx, _)) if ecx & (1 << 27) != 0);
+    if osxsave {
+        // Safety: `xgetbv` is supported, checked above.
+        unsafe { xcr0() }
+    } else {
+        0
+    }
+}
+
+// The features detected by `std`, generated by the build script with the features that the
+// compiler accepts.
+mod features {
+    include!(concat!(env!("OUT_DIR"), "/detect_features.rs"));
+}
@@ -0,0 +1,146 @@
+//! Feature detection using the capabilities reported by the operating system.
+//!
+//! On Linux, the kernel reports CPU capabilities with the `AT_HWCAP` and `AT_HWCAP2` auxiliary
+//! vector entries.  New capabilities are often reported by the kernel before they can be detected
+//! with the `is_*_feature_detected` macros in `std`, so this module can be used to detect features
+//! that `std` doesn't support yet.
+//!
+//! Currently, only AArch64 Linux and Android are supported.  On other platforms, no features are
+//! detected.
+//!
+//! Dispatchers generated by [`multiversion`](crate::multiversion) use the detection in `std`, and
+//! fall back to this module for features that `std` can't detect.  This module can also be used
+//! to select functions manually:
+//! ```
+//! if multiversion::hwcap::is_feature_detected("sve2") == Some(true) {
+//!     // use SVE2
+//! }
+//! ```
+
+/// Detect a feature with the capabilities reported by the operating system.
+///
+/// Features are named as in [`target_feature`].  Returns `None` if the feature can't be
+/// detected on this platform.
+///
+/// [`target_feature`]: https://doc.rust-lang.org/reference/conditional-compilation.html#target_feature
+#[allow(unused_variables)]
+pub fn is_feature_detected(feature: &str) -> Option<bool> {
+    #[cfg(all(
+        target_arch = "aarch64",
+        any(target_os = "linux", target_os = "android")
+    ))]
+    return aarch64::is_feature_detected(feature);
+
+    #[allow(unreachable_code)]
+    None
+}
+
+/// Returns the SVE vector length in bytes, if SVE is supported.
+///
+/// The vector length can be configured by the operating system per process, so it can't be
+/// determined from the CPU alone.
+pub fn sve_vector_bytes() -> Option<usize> {
+    #[cfg(all(
+        target_arch = "aarch64",
+        any(target_os = "linux", target_os = "android")
+    ))]
+    return aarch64::sve_vector_bytes();
+
+    #[allow(unreachable_code)]
+    None
+}
+
+#[cfg(all(
+    target_arch = "aarch64",
+    any(target_os = "linux", target_os = "android")
+))]
+mod aarch64 {
+    use std::os::raw::{c_int, c_ulong};
+
+    extern "C" {
+        fn getauxval(kind: c_ulong) -> c_ulong;
+        fn prctl(option: c_int, ...) -> c_int;
+    }
+
+    const AT_HWCAP: c_ulong = 16;
+    const AT_HWCAP2: c_ulong = 26;
+    const PR_SVE_GET_VL: c_int = 51;
+    const PR_SVE_VL_LEN_MASK: c_int = 0xffff;
+
+    // Feature name, required `AT_HWCAP` bits, and required `AT_HWCAP2` bits.
+    const FEATURES: &[(&str, u64, u64)] = &[
+        ("fp", 1 << 0, 0),
+        ("neon", 1 << 1, 0),
+        ("aes", 1 << 3 | 1 << 4, 0),
+        ("sha2", 1 << 5 | 1 << 6, 0),
+        ("crc", 1 << 7, 0),
+        ("lse", 1 << 8, 0),
+        ("fp16", 1 << 9 | 1 << 10, 0),
+        ("rdm", 1 << 12, 0),
+        ("jsconv", 1 << 13, 0),
+        ("fcma", 1 << 14, 0),
+        ("rcpc", 1 << 15, 0),
+        ("dpb", 1 << 16, 0),
+        ("sha3", 1 << 17 | 1 << 21, 0),
+        ("sm4", 1 << 18 | 1 << 19, 0),
+        ("dotprod", 1 << 20, 0),
+        ("sve", 1 << 22, 0),
+        ("fhm", 1 << 23, 0),
+        ("dit", 1 << 24, 0),
+        ("lse2", 1 << 25, 0),
+        ("rcpc2", 1 << 26, 0),
+        ("flagm", 1 << 27, 0),
+        ("ssbs", 1 << 28, 0),
+        ("sb", 1 << 29, 0),
+        ("paca", 1 << 30, 0),
+        ("pacg", 1 << 31, 0),
+        ("dpb2", 0, 1 << 0),
+        ("sve2", 0, 1 << 1),
+        ("sve2-aes", 0, 1 << 2 | 1 << 3),
+        ("sve2-bitperm", 0, 1 << 4),
+        ("sve2-sha3", 0, 1 << 5),
+        ("sve2-sm4", 0, 1 << 6),
+        ("frintts", 0, 1 << 8),
+        ("f32mm", 0, 1 << 10),
+        ("f64mm", 0, 1 << 11),
+        ("i8mm", 0, 1 << 13),
+        ("bf16", 0, 1 << 14),
+        ("rand", 0, 1 << 16),
+        ("bti", 0, 1 << 17),
+        ("mte", 0, 1 << 18),
+        ("ecv", 0, 1 << 19),
+        ("sme", 0, 1 << 23),
+        ("sme-i16i64", 0, 1 << 24),
+        ("sme-f64f64", 0, 1 << 25),
+        ("sme-fa64", 0, 1 << 30),
+        ("wfxt", 0, 1 << 31),
+        ("cssc", 0, 1 << 34),
+        ("sve2p1", 0, 1 << 36),
+        ("sme2", 0, 1 << 37),
+        ("sme2p1", 0, 1 << 38),
+        ("mops", 0, 1 << 43),
+        ("hbc", 0, 1 << 44),
+    ];
+
+    pub(super) fn is_feature_detected(feature: &str) -> Option<bool> {
+        let (_, hwcap, hwcap2) = FEATURES.iter().find(|(name, _, _)| *name == feature)?;
+        // Safety: getauxval has no preconditions, and returns 0 for missing entries.
+        let (auxv_hwcap, auxv_hwcap2) =
+            unsafe { (getauxval(AT_HWCAP) as u64, getauxval(AT_HWCAP2) as u64) };
+        Some(auxv_hwcap & hwcap == *hwcap && auxv_hwcap2 & hwcap2 == *hwcap2)
+    }
+
+    pub(super) fn sve_vector_bytes() -> Option<usize> {
+        if is_feature_detected("sve") != Some(true) {
+            return None;
+        }
+        // Safety: PR_SVE_GET_VL takes no additional arguments, and returns a negative value on
+        // error.
+        let vl = unsafe { prctl(PR_SVE_GET_VL) };
+        if vl < 0 {
+            None
+        } else {
+            Some((vl & PR_SVE_VL_LEN_MASK) as usize)
+        }
+    }
+}
@@ -1,3 +1,4 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![allow(clippy::needless_doctest_main)]
 //! This crate provides the [`multiversion`] attribute for implementing function multiversioning.
 //!
@@ -12,19 +13,157 @@
 //! with various features enabled and safely detecting which version to use at runtime.
 //!
 //! # Cargo features
-//! There is one cargo feature, `std`, enabled by default.  When enabled, [`multiversion`] will
-//! use CPU feature detection at runtime to dispatch the appropriate function. Disabling this
-//! feature will only allow compile-time function dispatch using `#[cfg(target_feature)]` and can
-//! be used in `#[no_std]` crates.
+//! The `std` cargo feature is enabled by default.  When enabled, [`multiversion`] will use CPU
+//! feature detection at runtime to dispatch the appropriate function. Disabling this feature will
+//! only allow compile-time function dispatch using `#[cfg(target_feature)]` and can be used in
+//! `#[no_std]` crates.
+//!
+//! By default, features are detected with the `is_*_feature_detected` macros in `std` (the
+//! `detect-backend-stdarch` cargo feature).  The `detect-backend-custom` cargo feature instead
+//! detects features with [`detect::is_feature_detected`], which can be replaced with a custom
+//! detector.
+//!
+//! The `nightly` cargo feature enables options that require a nightly compiler, such as
+//! `no_sanitize`.
+//!
+//! The `track_selection` cargo feature records the target selected by each multiversioned
+//! function, which can be retrieved with [`report::snapshot`].
+//!
+//! The `log` and `tracing` cargo features log the target selected by each multiversioned function
+//! at the debug level, using the [`log`](https://docs.rs/log) or
+//! [`tracing`](https://docs.rs/tracing) crates.  Like `track_selection`, only targets selected at
+//! runtime are logged.
+//!
+//! The `env_disable` cargo feature reads a comma-separated list of features from the
+//! `MULTIVERSION_DISABLE` environment variable, such as `MULTIVERSION_DISABLE=avx512f,avx2`, and
+//! treats those features as unsupported when detecting features at runtime.  This can be used to
+//! work around faulty hardware or to benchmark other targets without rebuilding.
+//!
+//! The `stack_usage` cargo feature estimates the stack frame size of each function version that
+//! is called, which can be retrieved with [`stack::frame_sizes`].
+//!
+//! The `stats` cargo feature counts the calls to each function version, which can be retrieved
+//! with [`stats::call_counts`].
+//!
+//! The `registry` cargo feature registers every dispatcher that selects a version at runtime, so
+//! that they can all be resolved at startup with [`resolve_all`].
+//!
+//! Building with the `MULTIVERSION_FORCE_STATIC=1` environment variable makes every dispatcher
+//! use static dispatch, even if another dispatcher is specified.  This is useful for deployments
+//! to a known CPU baseline, such as containers, or to build a baseline for benchmarks.  Unlike a
+//! cargo feature, it applies to the entire build, rather than being enabled by any crate in the
+//! dependency graph.
+//!
+//! Building with the `MULTIVERSION_READABLE=1` environment variable annotates the generated code
+//! with doc comments, which are shown by tools like `cargo expand`.  Each version is labeled with
+//! its target, and each dispatcher lists its versions in priority order, which makes it easier to
+//! review what the dispatcher does.  The generated code is otherwise unchanged.
+//!
+//! The `serde` cargo feature implements `Serialize` and `Deserialize` for
+//! [`TargetSpec`](target::TargetSpec).
+//!
+//! The `slice` cargo feature enables the [`slice`] module of multiversioned slice operations, and
+//! the `mem` cargo feature enables the [`mem`] module of multiversioned memory operations.
 //!
 //! # Capabilities
 //! The intention of this crate is to allow nearly any function to be multiversioned.
 //! The following cases are not supported:
-//! * functions that use `self` or `Self`
-//! * `impl Trait` return types (arguments are fine)
+//! * `impl Trait` return types (arguments are fine), unless the return value is boxed with
+//!   `boxed_return`
+//!
+//! Methods and associated functions, which use `self` or `Self`, can be multiversioned
+//! individually.  Their versions are associated functions in the same impl block, so they can
+//! only use the `static`, `direct`, or `inline` dispatcher, and can't use options that generate
+//! other items, such as `fn_ptr`.  Trait impls can't contain other functions, so the attribute
+//! must be applied to the whole impl block instead.
+//!
+//! Associated functions without a `self` receiver can instead name the implementing type with
+//! the `self_type` option, such as `#[multiversion(targets = "simd", self_type = "Matrix")]`.
+//! `Self` is replaced by that type, so `Self::CONST` and `Self::helper()` work with any
+//! dispatcher, and the function can be used in trait impls.
+//!
+//! Functions that never return, such as `fn run() -> !`, can use any dispatcher, and methods and
+//! the `pinned` and `trampoline` options are supported as well.
+//!
+//! `const fn` can be multiversioned with the `static` dispatcher, which selects a version purely
+//! with `cfg(target_feature)`.  The function can still be called in const contexts, and runtime
+//! callers get the version for the features enabled at compile time.  Options that detect
+//! features or generate other items at runtime, such as `fn_ptr` or `min_stack`, can't be used.
+//!
+//! Functions with a non-Rust ABI, such as `extern "C"`, keep their ABI.  The multiversioned
+//! function can be passed to C as a callback, and the function pointers returned by `fn_ptr` have
+//! the same ABI.
+//!
+//! The `no_mangle` and `export_name` attributes only apply to the multiversioned function, so the
+//! exported symbol always dispatches.  The versions have internal names, and can't be given
+//! symbol names with the `attrs` option.
+//!
+//! Other attribute macros that export the function, such as `ffi_export` from `safer_ffi`, should
+//! be placed after `#[multiversion]`, so that they're applied to the multiversioned function and
+//! not the versions.  The multiversioned function has the same signature as the original
+//! function, except that arguments that are destructured with a pattern are renamed.
+//! Alternatively, the `foreign_attrs` option applies them to the multiversioned function
+//! regardless of the order of the attributes, such as
+//! `#[multiversion(targets = "simd", foreign_attrs(wasm_bindgen))]`.
+//!
+//! With the `nightly` cargo feature, functions may return an `impl Coroutine`, such as
+//! generator-based parsers.  Each version's coroutine is driven by an immovable coroutine returned
+//! by the multiversioned function, so these functions can only use the `static`, `direct`, or
+//! `inline` dispatcher, and can't use `fn_ptr`.  `gen fn` isn't supported until it can be parsed
+//! on stable compilers.
+//!
+//! With the `nightly` cargo feature and `#![feature(c_variadic)]`, `unsafe extern "C"` functions
+//! with variable arguments, such as printf-style FFI shims, can be multiversioned.  Variable
+//! arguments can't be forwarded to another C-variadic function, so the versions take them as a
+//! [`core::ffi::VaList`] instead, and are dispatched by the function with the original signature.
+//! C-variadic methods can't be multiversioned.
+//!
+//! Trait methods can be multiversioned by applying the attribute to an impl of the trait, or to
+//! the trait definition to multiversion the default bodies.  The methods keep their signatures,
+//! so the trait can still be used as a trait object:
+//! ```
+//! use multiversion::multiversion;
+//!
+//! trait Kernel {
+//!     fn apply(&self, x: &mut [f32]);
+//! }
+//!
+//! struct Scale(f32);
+//!
+//! #[multiversion(targets = "simd")]
+//! impl Kernel for Scale {
+//!     fn apply(&self, x: &mut [f32]) {
+//!         for v in x {
+//!             *v *= self.0;
+//!         }
+//!     }
+//! }
+//!
+//! let kernel: Box<dyn Kernel> = Box::new(Scale(2.));
+//! let mut x = [1., 2.];
+//! kernel.apply(&mut x);
+//! assert_eq!(x, [2., 4.]);
+//! ```
 //!
 //! If any other functions do not work please file an issue on GitHub.
 //!
+//! # Dynamic libraries
+//! Multiversioned functions can be used in libraries that are loaded and unloaded at runtime, such
+//! as plugins built as a `cdylib`.  The selected version of each function is stored in statics
+//! generated alongside the function, so each loaded library selects its own versions, and never
+//! stores pointers to functions in another library.  When a library is unloaded and loaded again,
+//! its functions select their versions again.  Symbols generated by the `ifunc` dispatcher are
+//! hidden, so they're never resolved by another library.
+//!
+//! The global state of this crate, such as the cached feature detection, the hook registered with
+//! [`on_select`], and the detector registered with `detect::set_detector`, belongs to the copy of
+//! this crate linked into each library.  Usually, each `cdylib` contains its own copy, so hooks
+//! registered by the program aren't called by functions in the library, and are never left
+//! pointing to an unloaded library.  If this crate is instead shared by linking it as a Rust
+//! `dylib` (such as with `-C prefer-dynamic`), hooks and detectors must not be registered by a
+//! library that may be unloaded, and the reports of the `track_selection` and `stack_usage`
+//! features must not be used after a library that recorded them is unloaded.
+//!
 //! # Target specification strings
 //! Targets are specified as a combination of architecture (as specified in [`target_arch`]) and
 //! feature (as specified in [`target_feature`]).
@@ -38,6 +177,20 @@
 //! * `"arch/cpu"`
 //! * `"arch/cpu+feature"`
 //!
+//! On x86 and x86-64, a target used by [`multiversion`] can be tuned for a particular CPU with a
+//! tilde, such as `"x86_64+avx2~znver3"`.  Tuning doesn't change the enabled features, but a tuned
+//! target is only selected at runtime if the CPU vendor matches the tuned CPU's vendor, so the
+//! same features can have separate clones for different vendors:
+//! ```
+//! #[multiversion::multiversion(targets("x86_64+avx2~znver3", "x86_64+avx2"))]
+//! fn square(x: &mut [f32]) {
+//!     for v in x {
+//!         *v *= *v;
+//!     }
+//! }
+//! ```
+//! Tuned targets are never selected by static dispatch.
+//!
 //! The following are some valid target specification strings:
 //! * `"x86"` (matches the `"x86"` architecture)
 //! * `"x86_64+avx+avx2"` (matches the `"x86_64"` architecture with the `"avx"` and `"avx2"`
@@ -55,6 +208,58 @@
 //! [`target_arch`]: https://doc.rust-lang.org/reference/conditional-compilation.html#target_arch
 //! [`target_feature`]: https://doc.rust-lang.org/reference/conditional-compilation.html#target_feature
 
+// Generated code refers to this crate as `multiversion`.
+extern crate self as multiversion;
+
+#[cfg(feature = "std")]
+mod baseline;
+#[cfg(feature = "std")]
+mod cores;
+pub mod cpu;
+#[cfg(feature = "std")]
+pub mod detect;
+#[cfg(feature = "std")]
+pub mod hwcap;
+#[cfg(any(feature = "log", feature = "tracing"))]
+#[doc(hidden)]
+pub mod logging;
+#[cfg(feature = "mem")]
+pub mod mem;
+#[cfg(feature = "std")]
+pub mod overhead;
+#[cfg(feature = "registry")]
+mod registry;
+#[cfg(feature = "track_selection")]
+pub mod report;
+mod select_hook;
+#[cfg(feature = "slice")]
+pub mod slice;
+mod spec;
+#[cfg(feature = "std")]
+pub mod stack;
+mod static_override;
+#[cfg(feature = "stats")]
+pub mod stats;
+pub mod table;
+#[cfg(feature = "std")]
+mod target_set;
+#[cfg(feature = "std")]
+mod target_spec;
+mod token;
+pub mod trampoline;
+mod vector;
+
+#[cfg(feature = "std")]
+pub use baseline::{verify_baseline, BaselineMismatch};
+#[cfg(feature = "registry")]
+pub use registry::resolve_all;
+#[cfg(feature = "registry")]
+#[doc(hidden)]
+pub use registry::{__linkme, __RESOLVERS};
+#[doc(hidden)]
+pub use select_hook::__selected;
+pub use select_hook::{on_select, SelectHook};
+
 /// Provides function multiversioning.
 ///
 /// The annotated function is compiled multiple times, once for each target, and the
@@ -64,21 +269,384 @@
 /// * `targets`
 ///   * Takes a list of targets, such as `targets("x86_64+avx2", "x86_64+sse4.1")`.
 ///   * Target priority is first to last.  The first matching target is used.
+///   * Priority may also be specified explicitly, such as
+///     `targets("x86_64+avx512f" priority = 10, "x86_64+avx2" priority = 20)`.  Targets with
+///     higher priority are preferred.  Targets without an explicit priority have priority 0, and
+///     targets with equal priority keep their listed order.
+///   * Sanitizers may be disabled for individual targets, such as
+///     `targets("x86_64+avx512f" no_sanitize(address), "x86_64+avx2")`.  This is useful for
+///     clones that intentionally read out of bounds, such as with masked loads.  Requires the
+///     `nightly` cargo feature, and `#![feature(sanitize)]` in the crate.
+///   * Individual targets may have `cfg` attributes, which only compile the target if the
+///     predicate is satisfied, and doc comments, which document the version:
+///     ```
+///     # use multiversion::multiversion;
+///     #[multiversion(targets(
+///         /// Servers with AVX-512.
+///         #[cfg(feature = "wide")]
+///         "x86_64+avx512f+avx512vl",
+///         "x86_64+avx2",
+///     ))]
+///     fn double(x: &mut [f32]) {
+///         x.iter_mut().for_each(|x| *x *= 2.);
+///     }
+///     ```
 ///   * May also take a special value `targets = "simd"` to automatically multiversion for common
-///     SIMD target features.
+///     SIMD target features.  The targets included in `"simd"` may change between releases.  To
+///     avoid this, use a versioned preset such as `targets = "simd-2024"`, which never changes.
+///   * Workload-specific presets are also available, which select smaller sets of targets:
+///     * `targets = "simd-int"`: SIMD target features relevant to integer arithmetic.
+///     * `targets = "simd-float"`: SIMD target features relevant to floating point arithmetic,
+///       including AVX without AVX2.
+///     * `targets = "crypto"`: AES, carry-less multiplication, and SHA target features.
+///     * `targets = "crc"`: the CRC32 and carry-less multiplication target features used by
+///       checksum and compression kernels, such as CRC32, Adler-32, and zstd.
+///   * May also take a special value `targets = "crate"` to read the list of targets from the
+///     `multiversion.toml` file in the root of the crate.  The file contains a `targets` key with
+///     either a list of targets or a preset:
+///     ```toml
+///     targets = ["x86_64+avx2+fma", "x86_64+sse4.2", "aarch64+neon"]
+///     ```
+///   * May also take a target list macro declared with [`target_list!`], such as
+///     `targets = my_targets!()`, so that a list of targets can be shared between crates.
+///   * May also read the list of targets from an environment variable at compile time, such as
+///     `targets = env!("MY_TARGETS")`.  The variable contains a comma-separated list of quoted
+///     targets, as formatted by [`TargetSet`](target::TargetSet), and is usually set by a build
+///     script with `cargo::rustc-env`.
+///   * Lists of targets configured outside of the source code can be checked before building
+///     with the `multiversion-check` tool, which uses the same parsing as this macro.
+///   * Targets may be selected per cargo profile, such as
+///     `targets(release = "simd", dev = ())`.  Profiles that aren't listed are not
+///     multiversioned.  Profile names containing `-` are written with `_` instead.
+///   * Profiles are matched by the name of the output directory in the target directory, not by
+///     the cargo profile itself, since cargo doesn't tell build scripts the profile.  The `dev`
+///     profile builds into `debug`, and other profiles into a directory with their name.  The
+///     `bench` and `test` profiles build into the directories of the `release` and `dev` profiles
+///     they inherit from, so they can't be listed.  If the output directory isn't recognized,
+///     such as with a custom build directory layout, a warning is emitted and the `dev` targets
+///     are used.
+/// * `baseline`
+///   * Takes a target, such as `baseline = "x86_64+sse4.2"`, or a list of targets for different
+///     architectures, such as `baseline("x86_64+sse4.2", "x86+sse2")`.
+///   * The default (fallback) version is compiled with the baseline features, rather than only
+///     the features enabled at compile time.
+///   * The baseline features are assumed to exist and are not detected at runtime.  Calling the
+///     function on a CPU without the baseline features is undefined behavior.
+///   * Features enabled by `#[target_feature(enable = "...")]` on the function are also a
+///     baseline, since the function already requires them.  They're added to the baseline and to
+///     every target of each architecture that has the features.  It's an error if the features
+///     don't exist on the architecture of any target, if a target only has features that are
+///     already enabled, or if multiple targets become the same.  The features aren't detected, so
+///     the function must be an `unsafe fn`.
 /// * `attrs`
 ///   * Takes a list of attributes to attach to each target clone function.
+///   * Attributes that set the symbol name (`no_mangle` and `export_name`) aren't allowed, since
+///     every version would have the same symbol.
+///   * Lint expectations (`expect`) are attached to the multiversioned function instead, since
+///     the clones are nested in it.  Like lint expectations on the multiversioned function
+///     itself, they're fulfilled if the lint is emitted in any clone.
+/// * `forward_attrs`
+///   * Controls which attributes of the function are applied to the multiversioned function
+///     (`outer`) and to each clone (`inner`), such as
+///     `forward_attrs(outer(doc, inline), inner(inline, must_use, cfg_attr))`.  Attributes are
+///     matched by path, and both lists are optional.
+///   * By default, attributes are only applied to the multiversioned function.  Attributes listed
+///     in `inner` are applied to the clones instead.  If `outer` is specified, the multiversioned
+///     function only keeps the attributes listed in it, and its `cfg` attributes, so an attribute
+///     listed in neither list is removed.  This allows other attribute macros to see only the
+///     attributes meant for them.
+///   * Like `attrs`, attributes that set the symbol name can't be forwarded to the clones.
+/// * `foreign_attrs`
+///   * Takes a list of attributes, usually attribute macros of binding generators such as
+///     `wasm_bindgen` or `pyo3::pyfunction`, to apply only to the multiversioned function.
+///   * The attributes are applied in order after the other attributes of the function, so
+///     they're always expanded after `multiversion`, unlike attributes written before it.  With
+///     `dispatcher_visibility`, they're applied to the wrapper.
+/// * `variants`
+///   * Places the versions of the function in a module, rather than nesting them in the
+///     function, so they have predictable paths in backtraces and profilers and can be called
+///     directly.  Takes an optional visibility and module name, such as
+///     `variants(pub, module = "simd_impls")`.  The module is named after the function by
+///     default, and the module and versions are private by default.
+///   * The versions are named `<name>_<features>_version`, where the features include implied
+///     features and are sorted, such as `square_avx_avx2_sse_sse2_sse3_sse41_sse42_ssse3_version`,
+///     and `<name>_default_version`.  The versions with target features are `unsafe`, and must
+///     only be called if their features are supported.
+///   * The module imports everything in the parent module, but paths starting with `self` or
+///     `super` in the function body refer to different modules.
+/// * `version_names`
+///   * Selects the naming scheme of the versions, which appear in `variants` modules,
+///     backtraces, profilers, and `cargo-bloat` output.
+///     * `features`: The default.  Versions are named `<name>_<features>_version` after every
+///       feature, including implied features, `<name>_default_version`, and
+///       `<name>_<arch>_baseline_version`.
+///     * `short`: Versions are named `<name>_<features>` after the features that aren't implied
+///       by other features, such as `square_avx2_fma` for `x86_64+avx2+fma`, `<name>_default`,
+///       and `<name>_<arch>_baseline`.  Tuned targets are suffixed with `_tune_<cpu>`.
+/// * `self_type`
+///   * Takes the type implemented by the impl block containing the function, such as
+///     `self_type = "Matrix<f32>"`, and replaces `Self` with it, so associated functions without
+///     a `self` receiver are multiversioned like free functions and can use any dispatcher.
+///     Generic parameters of the impl block can't be used.
+///   * Cannot be used with `fn_ptr`, `pinned`, `resolve`, `trampoline`, `force_target`, or
+///     `variants`, which generate items next to the function.
+/// * `keep_original`
+///   * Takes a function name, such as `keep_original = "square_scalar"`.  The original function
+///     is also emitted unchanged under that name, without target features or dispatch, with the
+///     same visibility and attributes, except for `no_mangle` and `export_name`.  This is useful
+///     for comparing the versions against the original in tests and benchmarks.
+///   * The original isn't multiversioned, so it can't use helpers such as
+///     [`target::selected_target`].  Can't be used on impl blocks or traits.
+/// * `min_stack`
+///   * Takes a number of bytes, such as `min_stack = 65536`.  Each version of the function
+///     panics if less than that much stack remains on the current thread when it's called, rather
+///     than risking a stack overflow.  This is useful for versions that spill wide vector
+///     registers, such as AVX-512, to the stack.  See the [`stack`] module for details.
+///   * Requires the `std` cargo feature.
+/// * `helpers`
+///   * By default, the code supporting the helper macros (such as [`target::selected_target`] and
+///     [`target::match_target`]) is only generated for the helpers named in the function body.
+///     Every helper is generated if the body invokes other macros of the crate, such as those
+///     defined with `macro_rules!`, which may expand to helpers.  Macros of the standard library
+///     and macros invoked by a path to another crate, such as `log::debug!`, are assumed not to.
+///   * If `true`, every helper is generated, which is necessary if the helpers are renamed or
+///     used by macros of other crates.  If `false`, no helpers are generated.
+/// * `resolve`
+///   * Generates an additional function named `<name>_resolve`, with the same visibility as the
+///     multiversioned function, that performs feature detection ahead of time.  Calling it during
+///     startup avoids the cost of feature detection on the first call.
+///   * With the `static` and `inline` dispatchers, and with the `ifunc` dispatcher on supported
+///     platforms, the resolve function has no effect.
+/// * `fn_ptr`
+///   * Generates an additional function named `<name>_fn`, with the same visibility as the
+///     multiversioned function, that returns the selected version as a function pointer.  The
+///     pointer can be stored or called in a loop without repeating the dispatch.
+///   * Has the same restrictions as the `indirect` dispatcher, which must be used (or `static`,
+///     which returns the multiversioned function).  The function pointer has the same safety as
+///     the multiversioned function.
+/// * `pinned`
+///   * Generates an additional function named `<name>_pinned`, with the same visibility as the
+///     multiversioned function, that returns a callable for the selected version.  The callable
+///     captures the selection, so calls in a tight loop don't load it again.  It's `Copy`, `Send`
+///     and `Sync`, and calls the version directly rather than through a function pointer:
+///     ```
+///     # #[cfg(feature = "std")] {
+///     use multiversion::multiversion;
+///
+///     #[multiversion(targets = "simd", pinned)]
+///     fn scale(x: f32) -> f32 {
+///         x * 2.
+///     }
+///
+///     let scale = scale_pinned();
+///     let total: f32 = (0..1000).map(|i| scale(i as f32)).sum();
+///     assert_eq!(total, 999000.);
+///     # }
+///     ```
+///   * Cannot be used for generic, `async` or `unsafe` functions, functions that take or return
+///     an `impl Trait`, or with `force_target`.  With the `direct` and `indirect` dispatchers,
+///     the versions are shared and the multiversioned function calls the callable.  With
+///     `fn_ptr`, the callable captures the function pointer, and with other dispatchers it calls
+///     the multiversioned function.
+///   * The never type can't be the output of a callable, so the callable of a function that
+///     never returns returns [`Infallible`](core::convert::Infallible) instead.
+/// * `detector`
+///   * Takes the path to a function, as a path or a string, that returns the
+///     [`Target`](target::Target) supported by the CPU.  The function replaces the runtime
+///     feature detection of this function's dispatcher, and is called each time the dispatcher
+///     detects features.
+///   * Runtime dispatch with a detector doesn't require the `std` cargo feature (except for the
+///     `once` dispatcher), so it can be used in embedded or sandboxed environments with their own
+///     ways of detecting CPU capabilities.
+///   * Cannot be used with the `ifunc` dispatcher, since the detector would run while the program
+///     is loaded.
+/// * `detect`
+///   * Selects how features are detected at runtime.  Defaults to `current_core`.
+///     * `current_core`: Detects the features of the core that performs feature detection.
+///     * `all_cores`: Only detects features supported by every core.  On CPUs with heterogeneous
+///       cores, such as performance and efficiency cores, the core that detects features may
+///       support features that other cores don't.  On x86 and x86-64 Linux, a thread is
+///       temporarily pinned to each core when features are first detected.  On AArch64 Linux, the
+///       kernel already reports only the features supported by every core.  On other platforms,
+///       this is the same as `current_core`.
+///   * Cannot be used with a `detector` or the `ifunc` dispatcher.
+/// * `priority_model`
+///   * Takes the path to a priority model file, relative to the crate root, such as
+///     `priority_model = "multiversion-model.toml"`.  The model scores targets on particular
+///     CPUs, and on those CPUs the detected target with the highest score is selected, rather
+///     than the first detected target.  This is useful when the fastest target depends on the
+///     microarchitecture, such as CPUs that slow down when executing AVX-512 instructions.
+///   * Each table of the model is a CPU vendor, optionally followed by a CPU family as reported
+///     by [`cpu::signature`], and each key is a target and its score:
+///     ```toml
+///     [intel]
+///     "x86_64+avx512f+avx512bw" = 5
+///     "x86_64+avx2" = 10
+///
+///     # AMD Zen 4
+///     [amd.0x19]
+///     "x86_64+avx512f+avx512bw" = 20
+///     ```
+///   * The table of the CPU's family is used if present, and otherwise the table of its vendor.
+///     Targets that aren't in the table have a score of 0, and targets with equal scores keep
+///     their priority.  On CPUs that aren't in the model, targets are selected by priority.
+///   * The vendors are named like [`cpu::Vendor`], in lowercase.  Dispatch isn't elided when the
+///     highest priority target is enabled at compile time, since it may not be preferred on the
+///     CPU.  Cannot be used with the `static` or `inline` dispatcher.
+/// * `avx512_width`
+///   * With `avx512_width = "auto"`, each AVX-512 target is also compiled as a 256-bit variant,
+///     which is selected instead on CPUs that reduce their clock frequency while executing 512-bit
+///     instructions, such as Intel Skylake-SP through Tiger Lake.  This avoids a common pitfall
+///     of AVX-512, where wider vectors slow down a program rather than speed it up.
+///   * The 256-bit variant is compiled without the AVX-512 features, which limits it to 256-bit
+///     vectors, since Rust can't set the preferred vector width of individual functions.  If
+///     another target has the same features, the variant calls its version instead of compiling
+///     the function again.
+///   * Defaults to `avx512_width = "512"`, which only compiles the full-width version.
+/// * `boxed_future`
+///   * Allows `async` functions to use the `indirect` dispatcher, by wrapping each version in a
+///     function that returns its future as a `Pin<Box<dyn Future + Send>>`.  Each call allocates
+///     the future, but features aren't checked on every call like with the `direct` dispatcher.
+///   * The futures of every version must be `Send`.  Cannot be used for generic functions or
+///     functions that take or return an `impl Trait`.
+/// * `dispatcher_visibility`
+///   * Takes a visibility, such as `dispatcher_visibility = "pub(crate)"`.
+///   * The multiversioned function is renamed to `<name>_dispatcher` with the given visibility,
+///     and a wrapper with the original name, visibility, and attributes calls it.  The public
+///     interface is only the wrapper, which carries the documentation, and the generated items,
+///     such as versions and `variants` modules, are named after the dispatcher.
+///   * `#[target_feature]` attributes apply to the dispatcher, and `cfg` and lint attributes
+///     apply to both functions.  Methods call the dispatcher as an associated function, so
+///     trait implementations can't use this option.
+///     ```
+///     use multiversion::multiversion;
+///
+///     /// Sums the values.
+///     #[multiversion(targets = "simd", dispatcher_visibility = "pub(crate)")]
+///     pub fn sum(x: &[f32]) -> f32 {
+///         x.iter().sum()
+///     }
+///
+///     assert_eq!(sum(&[1., 2.]), sum_dispatcher(&[1., 2.]));
+///     ```
+/// * `boxed_return`
+///   * Allows functions that return an `impl Trait` to be multiversioned, by returning a
+///     `Box<dyn Trait>` from each version, or a `Pin<Box<dyn Future>>` if the trait is a
+///     `Future`.  The function still returns an `impl Trait`, so its signature is unchanged, but
+///     each call allocates the return value.
+///   * Only one trait other than auto traits such as `Send` can be used.  Without an explicit
+///     lifetime bound, the return value can only borrow the lifetimes named or elided in the
+///     return type.  Cannot be used with `async` functions or methods, and requires `std`.
+///   * Only the work done before returning is multiversioned.  The methods of the return value
+///     are called by the caller through the box, without the features of the version, so lazy
+///     values such as iterator adapters and futures gain nothing.  Return a value that has
+///     already been computed instead:
+///     ```
+///     use multiversion::multiversion;
+///
+///     #[multiversion(targets = "simd", boxed_return)]
+///     fn evens(x: &[u32]) -> impl Iterator<Item = u32> {
+///         x.iter()
+///             .copied()
+///             .filter(|x| x % 2 == 0)
+///             .collect::<Vec<_>>()
+///             .into_iter()
+///     }
+///
+///     assert_eq!(evens(&[1, 2, 3, 4]).collect::<Vec<_>>(), [2, 4]);
+///     ```
+/// * `share_generic_backend`
+///   * Takes a boolean, such as `share_generic_backend = true`.  Defaults to `false`.
+///   * If `true`, the function itself isn't multiversioned.  Instead, the function marked
+///     `#[backend]` in its body is multiversioned with the same options.  Nested functions can't
+///     use the generic parameters of the outer function, so every instantiation of a generic
+///     function shares the same versions of the backend, rather than multiplying the versions by
+///     the number of instantiations:
+///     ```
+///     use multiversion::multiversion;
+///
+///     #[multiversion(targets = "simd", share_generic_backend = true)]
+///     fn sum<T: AsRef<[f32]>>(x: T) -> f32 {
+///         #[backend]
+///         fn backend(x: &[f32]) -> f32 {
+///             x.iter().sum()
+///         }
+///
+///         backend(x.as_ref())
+///     }
+///
+///     assert_eq!(sum([1., 2.]), sum(vec![1., 2.]));
+///     ```
+///   * The backend can't have type or const generic parameters.
+/// * `trampoline`
+///   * Generates an additional function named `<name>_trampoline`, with the same visibility as
+///     the multiversioned function, that returns a C callback calling the multiversioned
+///     function.  See the [`trampoline`] module for details.
+///   * Cannot be used for generic functions, `async` functions, or functions that take or return
+///     an `impl Trait`.
+/// * `force_target`
+///   * Generates an additional function named `<name>_force_target`, with the same visibility as
+///     the multiversioned function, that forces subsequent calls to use the version for a
+///     particular target, such as `foo_force_target(Some("x86_64+sse4.2"))`.  This is useful for
+///     testing or benchmarking each version.  Passing `None` restores runtime selection.
+///   * Targets read at runtime, such as from a configuration file, can be validated by parsing
+///     them as a [`TargetSpec`](target::TargetSpec), and passed with `TargetSpec::as_str`.
+///   * Targets whose features aren't detected can't be forced.  The forced target is checked on
+///     every call, which adds a small cost even if no target is forced.
+///   * Cannot be used with `fn_ptr`.
+/// * `elision`
+///   * Takes a boolean, such as `elision = false`.  Defaults to `true`.
+///   * If `false`, the dispatcher isn't elided when features are known at compile time, and every
+///     target is detected at runtime.  See [dispatcher elision](#dispatcher-elision).
 /// * `dispatcher`
 ///   * Selects the preferred dispatcher. Defaults to `default`.
 ///     * `default`: If the `std` feature is enabled, uses either `direct` or `indirect`,
 ///       attempting to choose the fastest choice.  If the `std` feature is not enabled, uses `static`.
-///     * `static`: Detects features at compile time from the enabled target features.
+///     * `static`: Detects features at compile time from the enabled target features.  Tests can
+///       select other versions with [`target::with_static_target`], except for `const fn`, which
+///       can only use this dispatcher.
 ///     * `indirect`: Detect features at runtime, and dispatches with an indirect function call.
-///       Cannot be used for generic functions, `async` functions, or functions that take or return an
-///       `impl Trait`.  This is usually the default.
+///       Cannot be used for functions with type generic parameters, `async` functions (without
+///       `boxed_future`), or functions that take or return an `impl Trait`.  This is usually the
+///       default.  Functions with const generic parameters detect features once, and each
+///       instantiation calls the selected version from its own table of function pointers.
 ///     * `direct`: Detects features at runtime, and dispatches with direct function calls. This is
 ///       the default on functions that do not support indirect dispatch, or in the presence of
-///       indirect branch exploit mitigations such as retpolines.
+///       indirect branch exploit mitigations such as retpolines.  The selected version is stored
+///       in an `AtomicU8`, so this is also the default on targets without pointer atomics, such
+///       as some embedded targets.  Unless retpolines are enabled, functions that aren't generic
+///       call the selected version from a constant table of function pointers.
+///     * `once`: Detects features at runtime, and stores the selected function in a
+///       `std::sync::OnceLock`.  Has the same restrictions as `indirect`, and requires Rust 1.70
+///       or newer.  Unlike `indirect`, concurrent first calls wait for a single thread to perform
+///       feature detection.
+///     * `tls`: Detects features at runtime, and stores the selected function in a thread-local.
+///       Has the same restrictions as `indirect`, and requires the `std` cargo feature.  Each
+///       thread selects the function on its first call, and subsequent calls don't access memory
+///       shared with other threads.  This may be faster when many threads call the function on
+///       platforms where atomic loads are expensive.  Selections are recorded by each thread.
+///     * `ifunc`: Detects features when the program is loaded, using a GNU indirect function.
+///       Calls are resolved by the dynamic loader, so there is no overhead beyond a regular call
+///       to a shared library function.  Has the same restrictions as `indirect`, and requires
+///       Rust 1.66 or newer.  Only available on x86, x86-64, and AArch64 Linux with glibc, and
+///       x86 and x86-64 FreeBSD; other platforms use `indirect` instead.  Feature detection runs
+///       while the program is loaded, so features are detected with `cpuid` or `getauxval` rather
+///       than `std`, detectors registered with [`detect::set_detector`] and the
+///       `MULTIVERSION_DISABLE` environment variable are not used, and selections are not
+///       recorded by the `track_selection` feature.  Cannot be used with a `detector` or
+///       `priority_model`.
+///     * `inline`: Detects features at runtime on every call, and dispatches with direct function
+///       calls.  Unlike the other runtime dispatchers, doesn't generate any `static` items, which
+///       may be useful for code that is dynamically unloaded, or for rarely called functions.
+///       Feature detection is still cached by this crate.
+///     * `ctor`: Detects features in a constructor that runs before `main`, and dispatches with an
+///       indirect function call.  Has the same restrictions as `indirect`.  Calls before the
+///       constructor runs, or on platforms without constructors, select the function like
+///       `indirect`.  Selections are recorded by the `track_selection` feature before `main`, but
+///       can't be reported to [`on_select`] or logged by the `log` and `tracing` features, since
+///       no hook or logger is registered yet.
+///   * The cost of each dispatcher on the current machine can be measured with the [`overhead`]
+///     module.
 ///
 /// # Example
 /// This function is a good candidate for optimization using SIMD.
@@ -109,6 +677,101 @@
 /// }
 /// ```
 ///
+/// # Fallback body
+/// The default version may have a different body than the versions for the targets, such as a
+/// lookup table rather than a vectorizable loop.  A block marked `#[fallback]` at the top level
+/// of the function body is removed from the other versions, and is the entire body of the
+/// default version and of the `baseline` versions:
+///
+/// ```
+/// use multiversion::multiversion;
+///
+/// #[multiversion(targets = "simd")]
+/// fn popcount(x: &[u8]) -> u32 {
+///     #[fallback]
+///     {
+///         const TABLE: [u8; 16] = [0, 1, 1, 2, 1, 2, 2, 3, 1, 2, 2, 3, 2, 3, 3, 4];
+///         x.iter()
+///             .map(|b| (TABLE[(b & 0xf) as usize] + TABLE[(b >> 4) as usize]) as u32)
+///             .sum()
+///     }
+///     x.iter().map(|b| b.count_ones()).sum()
+/// }
+///
+/// assert_eq!(popcount(&[0xff, 0x01]), 9);
+/// ```
+///
+/// If the features of a target are enabled at compile time, the default version may call that
+/// target's version instead.  The original function emitted by `keep_original` uses the main
+/// body.  Every function in a [`clone_module`] is cloned, so they can't have a fallback block.
+///
+/// # Impl blocks
+/// The attribute may also be applied to an impl block, which multiversions each method with the
+/// same options.  Methods may take `self` and refer to `Self`:
+/// ```
+/// use multiversion::multiversion;
+///
+/// struct Samples(Vec<f32>);
+///
+/// #[multiversion(targets = "simd")]
+/// impl Samples {
+///     fn sum(&self) -> f32 {
+///         self.0.iter().sum()
+///     }
+///
+///     fn scaled(&self, factor: f32) -> Self {
+///         Self(self.0.iter().map(|x| x * factor).collect())
+///     }
+/// }
+///
+/// assert_eq!(Samples(vec![1., 2.]).scaled(2.).sum(), 6.);
+/// ```
+///
+/// Each method calls a hidden multiversioned function generated alongside the impl block, where
+/// `self` is an ordinary argument and `Self` is replaced by the implementing type.  In trait impls,
+/// paths to items of the impl, such as `Self::Item`, are qualified by the trait.  Each method is
+/// dispatched separately, but feature detection itself is cached, so detecting the target of
+/// each method is inexpensive.  Options that generate additional functions, such as `resolve`
+/// or `fn_ptr`, name them after the hidden functions, so they aren't useful with impl blocks.
+///
+/// `async fn` methods are supported, as well as methods that return an `impl Future` by
+/// evaluating an async block, such as desugared `async fn` in traits with a `Send` bound.  These
+/// methods keep their `impl Future` signature, and the hidden function is an `async fn`.
+///
+/// # Traits
+/// The attribute may also be applied to a trait, which multiversions the default body of each
+/// method with the same options.  Methods without a default body are unaffected:
+/// ```
+/// use multiversion::multiversion;
+///
+/// #[multiversion(targets = "simd")]
+/// trait Samples {
+///     fn values(&self) -> &[f32];
+///
+///     fn sum(&self) -> f32 {
+///         self.values().iter().sum()
+///     }
+///
+///     async fn mean(&self) -> f32 {
+///         self.sum() / self.values().len() as f32
+///     }
+/// }
+///
+/// struct Vector(Vec<f32>);
+///
+/// impl Samples for Vector {
+///     fn values(&self) -> &[f32] {
+///         &self.0
+///     }
+/// }
+///
+/// assert_eq!(Vector(vec![1., 2.]).sum(), 3.);
+/// ```
+///
+/// The hidden functions are generic over the implementing type, which replaces `Self`, so each
+/// implementation that uses a default body has its own versions.  Generic dispatch requires the
+/// `direct` dispatcher (the default) or the `static` or `inline` dispatcher.
+///
 /// # Notes on dispatcher performance
 ///
 /// ### Feature detection is performed only once
@@ -120,6 +783,8 @@
 ///   atomic load.
 /// * If called in multiple threads, there is no contention.  Both threads may perform feature
 ///   detection, but the atomic ensures these are synchronized correctly.
+/// * The `direct` dispatcher only stores the index of the selected function in a byte, and doesn't
+///   require pointer-sized atomics.
 ///
 /// ### Dispatcher elision
 /// If the optimal set of features is already known to exist at compile time, the entire dispatcher
@@ -127,10 +792,230 @@
 /// compiled with `RUSTFLAGS=-Ctarget-cpu=skylake-avx512`, the function is not multiversioned and
 /// the highest priority target is used.
 ///
+/// Otherwise, targets whose features are known to exist at compile time aren't detected at
+/// runtime, and targets with lower priority are never selected.  For example, if the function is
+/// compiled with `RUSTFLAGS=-Ctarget-cpu=haswell`, a target requiring `sse4.2` is selected
+/// without detecting any features, unless a higher priority target is detected.  Similarly, if
+/// the lowest priority target is known to exist, the default version calls it rather than being
+/// compiled separately.
+///
+/// Elision can be disabled with `elision = false`, so that features are always detected at
+/// runtime.  This ensures that tests exercise the dispatcher, even when built with
+/// `-Ctarget-cpu=native`.
+///
 /// [`target`]: attr.target.html
 /// [`multiversion`]: attr.multiversion.html
+/// [`target_list!`]: macro.target_list.html
 pub use multiversion_macros::multiversion;
 
+/// Multiversions a closure.
+///
+/// Takes the same options as [`multiversion`], followed by a closure, and returns a closure with
+/// the same arguments and captures.  The body of the closure is compiled once for each target,
+/// and the best target is selected each time the closure is called.  This is useful for
+/// iterator adaptors and callbacks, where declaring a separate function is inconvenient:
+/// ```
+/// use multiversion::multiversion_closure;
+///
+/// let scale = 2.;
+/// let rows = vec![vec![1f32, 2., 3.], vec![4., 5., 6.]];
+/// let sums = rows
+///     .iter()
+///     .map(multiversion_closure!(
+///         targets("x86_64+avx2+fma", "x86_64+sse4.1", "aarch64+neon"),
+///         |row: &Vec<f32>| -> f32 { row.iter().map(|x| x * scale).sum() }
+///     ))
+///     .collect::<Vec<_>>();
+/// assert_eq!(sums, [12., 30.]);
+/// ```
+///
+/// The body is passed to a multiversioned generic function, so dispatchers that don't support type
+/// generic parameters, such as `indirect`, can't be selected.  Async closures and coroutines
+/// aren't supported.  The body isn't a multiversioned function, so helpers such as
+/// [`selected_target!`](target::selected_target) aren't available in it.
+///
+/// [`multiversion`]: attr.multiversion.html
+pub use multiversion_macros::multiversion_closure;
+
+/// Multiversions functions, impl blocks, and traits, like the [`multiversion`] attribute.
+///
+/// Attributes can't always be applied, such as to items produced by other macros.  This macro
+/// takes the options of the attribute, followed by a semicolon and any number of functions, impl
+/// blocks, or traits, which are multiversioned with the same options:
+/// ```
+/// use multiversion::multiversion_item;
+///
+/// macro_rules! sum {
+///     ($name:ident, $ty:ty) => {
+///         multiversion_item! {
+///             targets = "simd";
+///             fn $name(x: &[$ty]) -> $ty {
+///                 x.iter().sum()
+///             }
+///         }
+///     };
+/// }
+///
+/// sum!(sum_f32, f32);
+/// sum!(sum_f64, f64);
+/// assert_eq!(sum_f32(&[1., 2.]), 3.);
+/// ```
+///
+/// Attribute and function-like macros share a namespace, so this macro can't be named
+/// `multiversion!`.
+///
+/// [`multiversion`]: attr.multiversion.html
+pub use multiversion_macros::multiversion_item;
+
+/// Declares `static` tables of multiversioned function pointers.
+///
+/// Each table is a [`Table`](table::Table) of a struct of function pointers.  The fields are
+/// listed like a struct expression, where each field is initialized with the function pointer
+/// returned by the `fn_ptr` option of the named function, so `{ add, scale: kernels::mul }`
+/// initializes `add` with `add_fn()` and `scale` with `kernels::mul_fn()`.  Each function must
+/// be multiversioned with `fn_ptr`.
+///
+/// The versions are selected when the table is first used, rather than when it's declared:
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use multiversion::{multiversion, table};
+///
+/// pub struct Kernels {
+///     pub sum: fn(&[f32]) -> f32,
+/// }
+///
+/// #[multiversion(targets = "simd", fn_ptr)]
+/// fn sum(x: &[f32]) -> f32 {
+///     x.iter().sum()
+/// }
+///
+/// table! {
+///     /// The kernels for the current CPU.
+///     pub static KERNELS: Kernels = { sum };
+/// }
+///
+/// assert_eq!((KERNELS.sum)(&[1., 2., 3.]), 6.);
+/// # }
+/// ```
+pub use multiversion_macros::table;
+
+/// Specifies the default targets for multiversioned functions in a module.
+///
+/// Any function in the module marked with [`multiversion`] that doesn't specify `targets` uses
+/// the default targets instead.  The default targets may be a list of targets, a preset, or
+/// anything else accepted by `targets`, such as `#[default_targets("x86_64+avx2", "aarch64+neon")]`
+/// or `#[default_targets("simd")]`.
+///
+/// This attribute can only be applied to inline modules, and only affects functions marked with
+/// `#[multiversion]` or `#[multiversion::multiversion]`.  Nested modules may specify their own
+/// default targets.
+///
+/// # Example
+/// ```
+/// #[multiversion::default_targets("x86_64+avx2", "aarch64+neon")]
+/// mod kernels {
+///     use multiversion::multiversion;
+///
+///     #[multiversion]
+///     pub fn square(x: &mut [f32]) {
+///         for v in x {
+///             *v *= *v
+///         }
+///     }
+///
+///     #[multiversion(dispatcher = "static")]
+///     pub fn double(x: &mut [f32]) {
+///         for v in x {
+///             *v *= 2.
+///         }
+///     }
+/// }
+/// ```
+///
+/// [`multiversion`]: attr.multiversion.html
+pub use multiversion_macros::default_targets;
+
+/// Multiversions the functions in a module, selecting one target for all of them.
+///
+/// Takes the same options as [`multiversion`], which are applied to each function in the module
+/// that isn't private.  Private functions, such as helpers, aren't multiversioned.
+/// Features are detected once for the whole group, rather than once per function, and every
+/// function uses the `direct` dispatcher with the group's selection.
+///
+/// The attribute also generates a `Selection` type in the module, which holds the selected
+/// target.  Each function in the group is also a method of `Selection`, which calls the version
+/// for the selected target without loading the selection again, so pipelines of functions only
+/// load the selection once.
+///
+/// The versions of each function are placed in a module named after the function, as with the
+/// `variants` option of [`multiversion`].  Only functions directly in the module are included in
+/// the group, and they can't also be marked with [`multiversion`].  Groups require the `std`
+/// cargo feature or a `detector`.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")] {
+/// #[multiversion::group(targets("x86_64+avx2", "aarch64+neon"))]
+/// mod kernels {
+///     pub fn square(x: &mut [f32]) {
+///         for v in x {
+///             *v *= *v
+///         }
+///     }
+///
+///     pub fn sum(x: &[f32]) -> f32 {
+///         x.iter().sum()
+///     }
+/// }
+///
+/// let mut x = [1., 2., 3.];
+/// kernels::square(&mut x);
+///
+/// // Select the target once, and call several functions
+/// let selection = kernels::Selection::get();
+/// selection.square(&mut x);
+/// assert_eq!(selection.sum(&x), 98.);
+/// # }
+/// ```
+///
+/// [`multiversion`]: attr.multiversion.html
+pub use multiversion_macros::group;
+
+/// Clones a module for each target, so that its functions are specialized together.
+///
+/// Every function directly in the module, including private helpers, is compiled once for each
+/// target.  Calls between functions in a clone call the other functions in the same clone, so
+/// helpers are compiled with the target's features even if they aren't inlined.
+///
+/// Each public function dispatches to the clone for the selected target.  It's multiversioned with
+/// the options of the attribute, which are the same as [`multiversion`], except that `targets`
+/// must be a list of targets or a preset, and tuned targets aren't supported.  Private functions
+/// called from outside the clones use the features enabled at compile time.
+///
+/// Other items, such as types and constants, aren't cloned.  The clones import everything in the
+/// module, but paths starting with `self` or `super` in function bodies refer to different
+/// modules.  The helpers [`target::selected_target`] and [`target::selected_token`] are available in
+/// every function, but other helpers, such as [`target::match_target`], aren't supported.
+///
+/// # Example
+/// ```
+/// #[multiversion::clone_module(targets("x86_64+avx2+fma", "aarch64+neon"))]
+/// mod kernels {
+///     fn square(x: f32) -> f32 {
+///         x * x
+///     }
+///
+///     pub fn sum_of_squares(x: &[f32]) -> f32 {
+///         x.iter().copied().map(square).sum()
+///     }
+/// }
+///
+/// assert_eq!(kernels::sum_of_squares(&[1., 2., 3.]), 14.);
+/// ```
+///
+/// [`multiversion`]: attr.multiversion.html
+pub use multiversion_macros::clone_module;
+
 /// Provides a less verbose equivalent to the `cfg(target_arch)` and `target_feature` attributes.
 ///
 /// A function tagged with `#[target("x86_64+avx+avx2")]`, for example, is equivalent to a
@@ -161,6 +1046,73 @@ pub use multiversion_macros::target;
 /// }
 pub use multiversion_macros::inherit_target;
 
+/// Declares an enum of capability levels from a list of targets.
+///
+/// Non-multiversioned code can use the capability level for decisions that should be consistent
+/// with the targets selected by multiversioned functions, such as buffer sizes or algorithm
+/// selection.
+///
+/// Each variant is associated with a target, listed from highest to lowest priority.  An
+/// additional `Baseline` variant is generated for when no target is supported.  The enum derives
+/// `Ord`, with higher priority levels comparing greater, but comparisons are only meaningful
+/// between levels of the same architecture.
+///
+/// The generated `detect` function returns the highest priority level supported by the CPU.
+/// If the `std` feature is not enabled, only statically-enabled features are considered.
+///
+/// # Example
+/// ```
+/// multiversion::capability_ladder! {
+///     pub enum CapabilityLevel {
+///         Avx2 = "x86_64+avx2+fma",
+///         Sse42 = "x86_64+sse4.2",
+///         Neon = "aarch64+neon",
+///     }
+/// }
+///
+/// let buffer_size = match CapabilityLevel::detect() {
+///     CapabilityLevel::Avx2 => 32,
+///     CapabilityLevel::Sse42 | CapabilityLevel::Neon => 16,
+///     CapabilityLevel::Baseline => 8,
+/// };
+/// ```
+pub use multiversion_macros::capability_ladder;
+
+/// Declares a macro containing a list of targets.
+///
+/// The macro can be used with [`multiversion`] as `targets = name!()`, which allows a crate to
+/// publish a list of targets that other crates use consistently.  Invoking the macro as an
+/// expression returns an array of the target specification strings.
+///
+/// The target list must be a macro, rather than a constant, since constants can't be evaluated
+/// when expanding [`multiversion`].
+///
+/// # Example
+/// ```
+/// use multiversion::multiversion;
+///
+/// multiversion::target_list! {
+///     #[macro_export]
+///     macro_rules! float_targets {
+///         "x86_64+avx2+fma",
+///         "x86_64+avx",
+///         "aarch64+neon",
+///     }
+/// }
+///
+/// #[multiversion(targets = float_targets!())]
+/// fn square(x: &mut [f32]) {
+///     for v in x {
+///         *v *= *v
+///     }
+/// }
+///
+/// assert_eq!(float_targets!().len(), 3);
+/// ```
+///
+/// [`multiversion`]: attr.multiversion.html
+pub use multiversion_macros::target_list;
+
 /// Information related to the current target.
 pub mod target {
     // used by docs
@@ -187,9 +1139,52 @@ pub mod target {
     /// }
     pub use multiversion_macros::selected_target;
 
-    /// Equivalent to `#[cfg]`, but considers `target_feature`s detected at runtime.
+    /// Get a [`TargetToken`] for the selected target in a multiversioned function.
     ///
     /// This macro only works in a function marked with [`multiversion`].
+    pub use multiversion_macros::selected_token;
+
+    pub use crate::token::TargetToken;
+
+    pub use crate::vector::TargetExt;
+
+    #[cfg(feature = "std")]
+    pub use crate::vector::AlignedBuffer;
+
+    #[cfg(feature = "std")]
+    pub use crate::target_set::TargetSet;
+    #[cfg(feature = "std")]
+    pub use crate::target_spec::{InvalidTarget, TargetSpec};
+
+    /// Equivalent to `#[cfg]`, but considers `target_feature`s detected at runtime.
+    ///
+    /// This macro only works in a function marked with [`multiversion`].  Items in the function
+    /// are compiled with each version, so the attribute can select items for each target, such as
+    /// implementations of a trait for the [`selected_token`] of each version:
+    /// ```
+    /// use multiversion::{multiversion, target::{selected_token, target_cfg}};
+    ///
+    /// trait Lanes {
+    ///     const LANES: usize;
+    /// }
+    ///
+    /// #[multiversion(targets("x86_64+avx2", "x86_64+sse4.1"))]
+    /// fn lanes() -> usize {
+    ///     #[target_cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+    ///     impl Lanes for selected_token!() {
+    ///         const LANES: usize = 8;
+    ///     }
+    ///
+    ///     #[target_cfg(not(all(target_arch = "x86_64", target_feature = "avx2")))]
+    ///     impl Lanes for selected_token!() {
+    ///         const LANES: usize = 4;
+    ///     }
+    ///
+    ///     <selected_token!() as Lanes>::LANES
+    /// }
+    ///
+    /// assert!(lanes() == 4 || lanes() == 8);
+    /// ```
     pub use multiversion_macros::target_cfg;
 
     /// Equivalent to `#[cfg_attr]`, but considers `target_feature`s detected at runtime.
@@ -225,14 +1220,47 @@ pub mod target {
     /// This macro only works in a function marked with [`multiversion`].
     pub use multiversion_macros::target_cfg_f;
 
+    /// Equivalent to [`target_cfg`], but for the target specified by the first argument.
+    ///
+    /// The features of the target, such as `"x86_64+avx2"`, are considered enabled by the
+    /// predicate in the second argument, as in the version of a multiversioned function for that
+    /// target.  Unlike [`target_cfg`], this attribute can be used outside of a multiversioned
+    /// function, such as by macros that generate items for each target:
+    /// ```
+    /// use multiversion::target::target_cfg_for;
+    ///
+    /// #[target_cfg_for("x86_64+avx2", target_feature = "avx2")]
+    /// const AVX2: bool = true;
+    ///
+    /// #[target_cfg_for("x86_64+avx2", not(target_feature = "avx2"))]
+    /// const AVX2: bool = false;
+    ///
+    /// assert!(AVX2);
+    /// ```
+    pub use multiversion_macros::target_cfg_for;
+
     #[doc(hidden)]
     pub use multiversion_macros::{
         match_target_impl, target_cfg_attr_impl, target_cfg_f_impl, target_cfg_impl,
     };
 
+    #[doc(hidden)]
+    pub use crate::token::supports as __supports_feature;
+
+    #[doc(hidden)]
+    pub use crate::spec::is_target as __is_target;
+
+    #[cfg(feature = "std")]
+    pub use crate::static_override::with_static_target;
+
+    #[doc(hidden)]
+    pub use crate::static_override::__static_target_override;
+
     #[doc(no_inline)]
-    pub use target_features::Target;
+    pub use target_features::{Architecture, Target};
 }
 
 #[doc(hidden)]
 pub use target_features;
+
+include!(concat!(env!("OUT_DIR"), "/select_profile.rs"));
@@ -0,0 +1,10 @@
+//! Logs the targets selected by multiversioned functions.
+
+/// Called by dispatchers the first time a target is selected.
+pub fn __log_selection(function: &'static str, target: &'static str) {
+    #[cfg(feature = "log")]
+    log::debug!(target: "multiversion", "multiversion resolved `{}` to `{}`", function, target);
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(function, target, "multiversion resolved");
+}
@@ -0,0 +1,142 @@
+//! Multiversioned memory operations.
+//!
+//! These functions copy and fill memory with the best strategy for the CPU, such as wide vector
+//! registers, or `rep movsb` and `rep stosb` on x86-64 CPUs with fast string operations (ERMSB):
+//! ```
+//! let src = [1u32, 2, 3, 4];
+//! let mut dst = [0; 4];
+//! multiversion::mem::copy(&src, &mut dst);
+//! assert_eq!(dst, src);
+//!
+//! multiversion::mem::fill(&mut dst, 7);
+//! assert_eq!(dst, [7; 4]);
+//! ```
+//!
+//! This module is only available with the `mem` cargo feature.
+
+use crate::multiversion;
+
+// Copies larger than this use `rep movsb` or `rep stosb`, if fast.
+#[cfg(target_arch = "x86_64")]
+const REP_THRESHOLD: usize = 2048;
+
+// The width of the chunks copied in each iteration.
+const CHUNK: usize = 64;
+
+/// Copies the elements of `src` to `dst`.
+///
+/// # Panics
+/// Panics if `src` and `dst` have different lengths.
+pub fn copy<T: Copy>(src: &[T], dst: &mut [T]) {
+    assert_eq!(src.len(), dst.len(), "slices have different lengths");
+    // Safety: the slices have the same size and can't overlap, since `dst` is a unique
+    // reference.  The bytes are copied untyped, so padding is never read as `u8`.
+    unsafe {
+        copy_bytes(
+            src.as_ptr().cast::<u8>(),
+            dst.as_mut_ptr().cast::<u8>(),
+            core::mem::size_of_val(src),
+        );
+    }
+}
+
+/// Sets every element of `dst` to `value`.
+pub fn fill<T: Copy + 'static>(dst: &mut [T], value: T) {
+    // Only types that are always initialized bytes can be filled as bytes.
+    let id = core::any::TypeId::of::<T>();
+    if id == core::any::TypeId::of::<u8>() || id == core::any::TypeId::of::<i8>() {
+        // Safety: `T` is `u8` or `i8`, which have the same layout and valid values as `u8`.
+        let (dst, value) = unsafe {
+            (
+                core::slice::from_raw_parts_mut(dst.as_mut_ptr().cast::<u8>(), dst.len()),
+                core::mem::transmute_copy::<T, u8>(&value),
+            )
+        };
+        fill_bytes(dst, value);
+    } else {
+        fill_elements(dst, value);
+    }
+}
+
+// Copies `len` bytes from `src` to `dst`.
+//
+// Safety: `src` must be valid for reads and `dst` must be valid for writes of `len` bytes, and
+// they must not overlap.
+#[multiversion(targets("x86_64+avx512f", "x86_64+avx", "aarch64+neon"))]
+unsafe fn copy_bytes(src: *const u8, dst: *mut u8, len: usize) {
+    #[cfg(target_arch = "x86_64")]
+    if len >= REP_THRESHOLD && fast_strings() {
+        // Safety: guaranteed by the caller.
+        unsafe {
+            core::arch::asm!(
+                "rep movsb",
+                inout("rcx") len => _,
+                inout("rsi") src => _,
+                inout("rdi") dst => _,
+                options(nostack, preserves_flags)
+            );
+        }
+        return;
+    }
+
+    // Copies of a constant size are inlined as vector loads and stores.
+    let chunks = len / CHUNK;
+    for i in 0..chunks {
+        // Safety: guaranteed by the caller.
+        unsafe {
+            core::ptr::copy_nonoverlapping(src.add(i * CHUNK), dst.add(i * CHUNK), CHUNK);
+        }
+    }
+    let offset = chunks * CHUNK;
+    // Safety: guaranteed by the caller.
+    unsafe {
+        core::ptr::copy_nonoverlapping(src.add(offset), dst.add(offset), len - offset);
+    }
+}
+
+#[multiversion(targets("x86_64+avx512f", "x86_64+avx", "aarch64+neon"))]
+fn fill_bytes(dst: &mut [u8], value: u8) {
+    #[cfg(target_arch = "x86_64")]
+    if dst.len() >= REP_THRESHOLD && fast_strings() {
+        // Safety: `rep stosb` writes `dst.len()` bytes to `dst`.
+        unsafe {
+            core::arch::asm!(
+                "rep stosb",
+                inout("rcx") dst.len() => _,
+                inout("rdi") dst.as_mut_ptr() => _,
+                in("al") value,
+                options(nostack, preserves_flags)
+            );
+        }
+        return;
+    }
+
+    for d in dst {
+        *d = value;
+    }
+}
+
+#[multiversion(targets("x86_64+avx512f", "x86_64+avx", "aarch64+neon"))]
+fn fill_elements<T: Copy>(dst: &mut [T], value: T) {
+    for d in dst {
+        *d = value;
+    }
+}
+
+// Returns `true` if the CPU supports enhanced `rep movsb` and `rep stosb` (ERMSB).
+#[cfg(target_arch = "x86_64")]
+fn fast_strings() -> bool {
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    // 0 if unknown, 1 if unsupported, and 2 if supported.
+    static FAST_STRINGS: AtomicU8 = AtomicU8::new(0);
+    match FAST_STRINGS.load(Ordering::Relaxed) {
+        0 => {
+            let supported =
+                matches!(crate::cpu::cpuid(7), Some((_, ebx, _, _)) if ebx & (1 << 9) != 0);
+            FAST_STRINGS.store(if supported { 2 } else { 1 }, Ordering::Relaxed);
+            supported
+        }
+        state => state == 2,
+    }
+}
@@ -0,0 +1,120 @@
+//! Measures the overhead of dispatching.
+//!
+//! The cost of each dispatcher depends on the CPU, the function, and how it's called.  This
+//! module compares calls through a dispatcher to direct calls on the current machine, which can
+//! help choose between the `direct`, `indirect` and `static` dispatchers without a separate
+//! benchmark harness:
+//! ```
+//! use multiversion::multiversion;
+//!
+//! #[multiversion(targets = "simd", pinned)]
+//! fn add(x: u64, y: u64) -> u64 {
+//!     x.wrapping_add(y)
+//! }
+//!
+//! let x = std::env::args().count() as u64;
+//! let pinned = add_pinned();
+//! let overhead = multiversion::overhead::measure(|| add(x, 1), || pinned(x, 1));
+//! println!(
+//!     "dispatched: {:.2} ns, direct: {:.2} ns",
+//!     overhead.dispatched_ns(),
+//!     overhead.direct_ns(),
+//! );
+//! ```
+//!
+//! The direct call can be the callable returned by the `pinned` option, a function pointer
+//! returned by the `fn_ptr` option, or a version in the module generated by the `variants`
+//! option.  Arguments should be captured by the closures, rather than written as constants, so
+//! that the calls can't be optimized away.
+//!
+//! This module is only available with the `std` cargo feature.
+
+use std::time::{Duration, Instant};
+
+// The number of alternating samples of each call.  The fastest sample is used, since noise only
+// makes calls slower.
+const SAMPLES: usize = 15;
+
+// Each sample runs for at least this long.
+const MIN_SAMPLE_TIME: Duration = Duration::from_millis(1);
+
+/// The measured cost of a call through a dispatcher and a direct call.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Overhead {
+    dispatched: f64,
+    direct: f64,
+}
+
+impl Overhead {
+    /// Returns the time of a call through the dispatcher, in nanoseconds.
+    pub fn dispatched_ns(&self) -> f64 {
+        self.dispatched
+    }
+
+    /// Returns the time of a direct call, in nanoseconds.
+    pub fn direct_ns(&self) -> f64 {
+        self.direct
+    }
+
+    /// Returns the additional time of a call through the dispatcher, in nanoseconds.
+    ///
+    /// When the overhead is smaller than the noise of the measurement, this may be negative.
+    pub fn overhead_ns(&self) -> f64 {
+        self.dispatched - self.direct
+    }
+}
+
+/// Measures the time of calls through a dispatcher, compared to direct calls.
+///
+/// Both functions should perform the same call, such as a call to the multiversioned function and
+/// a call to the callable returned by its `pinned` option.  Each function is called many times,
+/// so that the whole measurement takes tens of milliseconds.
+pub fn measure<R>(mut dispatched: impl FnMut() -> R, mut direct: impl FnMut() -> R) -> Overhead {
+    // Warm up both calls, which also performs feature detection.
+    black_box(dispatched());
+    black_box(direct());
+
+    let iterations = calibrate(&mut dispatched);
+    let mut overhead = Overhead {
+        dispatched: f64::INFINITY,
+        direct: f64::INFINITY,
+    };
+    for _ in 0..SAMPLES {
+        overhead.dispatched = overhead
+            .dispatched
+            .min(time_per_call(&mut dispatched, iterations));
+        overhead.direct = overhead.direct.min(time_per_call(&mut direct, iterations));
+    }
+    overhead
+}
+
+// Returns the number of iterations that takes at least the minimum sample time.
+fn calibrate<R>(f: &mut impl FnMut() -> R) -> u64 {
+    let mut iterations = 1;
+    while time(f, iterations) < MIN_SAMPLE_TIME && iterations < 1 << 32 {
+        iterations *= 2;
+    }
+    iterations
+}
+
+fn time_per_call<R>(f: &mut impl FnMut() -> R, iterations: u64) -> f64 {
+    time(f, iterations).as_secs_f64() * 1e9 / iterations as f64
+}
+
+fn time<R>(f: &mut impl FnMut() -> R, iterations: u64) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        // Hide the function from the optimizer, so that calls can't be hoisted out of the loop.
+        let f = black_box(&mut *f);
+        black_box(f());
+    }
+    start.elapsed()
+}
+
+// `std::hint::black_box` requires a newer compiler than the minimum supported version.
+fn black_box<T>(x: T) -> T {
+    // Safety: the value is moved out of `x`, which is forgotten.
+    let y = unsafe { core::ptr::read_volatile(&x) };
+    core::mem::forget(x);
+    y
+}
@@ -0,0 +1,41 @@
+/// Selects the version of every registered multiversioned function, so that their first calls
+/// don't perform feature detection.
+///
+/// This function is only available with the `registry` cargo feature.  When enabled, every
+/// dispatcher that selects a version at runtime is registered, including those in other crates,
+/// and those of every [`group`](crate::group).  Calling `resolve_all` during startup avoids
+/// feature detection on the first call, which may be important for latency-sensitive programs:
+/// ```
+/// use multiversion::multiversion;
+///
+/// #[multiversion(targets = "simd")]
+/// fn square(x: &mut [f32]) {
+///     for v in x {
+///         *v *= *v
+///     }
+/// }
+///
+/// fn main() {
+///     multiversion::resolve_all();
+///     square(&mut [1.0, 2.0]);
+/// }
+/// ```
+///
+/// The `tls` dispatcher selects a version in each thread, so it isn't registered.  The `ctor` and
+/// `ifunc` dispatchers already select a version before `main`, and the `static` and `inline`
+/// dispatchers don't select a version at runtime.
+///
+/// Registration uses link sections, which are supported on Linux, Android, macOS, iOS, Windows,
+/// and most other platforms with a linker.
+pub fn resolve_all() {
+    for resolve in __RESOLVERS {
+        resolve();
+    }
+}
+
+#[doc(hidden)]
+pub use linkme as __linkme;
+
+#[doc(hidden)]
+#[linkme::distributed_slice]
+pub static __RESOLVERS: [fn()];
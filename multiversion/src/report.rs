@@ -0,0 +1,125 @@
+//! Records which function versions were selected, for inclusion in crash reports.
+//!
+//! This module is only available with the `track_selection` cargo feature.  When enabled, the
+//! runtime dispatchers record the target selected by each multiversioned function the first time
+//! it is called.  Functions using the `static` dispatcher, or which were not multiversioned due to
+//! dispatcher elision, are not recorded.
+//!
+//! # Example
+//! ```
+//! use multiversion::multiversion;
+//!
+//! #[multiversion(targets = "simd")]
+//! fn square(x: &mut [f32]) {
+//!     for v in x {
+//!         *v *= *v
+//!     }
+//! }
+//!
+//! square(&mut [1.0, 2.0]);
+//! println!("{}", multiversion::report::snapshot());
+//! ```
+
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+static SELECTIONS: Mutex<Vec<Selection>> = Mutex::new(Vec::new());
+
+/// The target selected for a multiversioned function.
+#[derive(Clone, Debug)]
+pub struct Selection {
+    function: &'static str,
+    target: &'static str,
+    time: SystemTime,
+}
+
+impl Selection {
+    /// Returns the path of the multiversioned function.
+    pub fn function(&self) -> &'static str {
+        self.function
+    }
+
+    /// Returns the selected target, or `"default"` if the default version was selected.
+    pub fn target(&self) -> &'static str {
+        self.target
+    }
+
+    /// Returns the time when the target was first selected.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+}
+
+/// A snapshot of the selected targets, returned by [`snapshot`].
+#[derive(Clone, Debug)]
+pub struct Report {
+    arch: &'static str,
+    vendor: Option<String>,
+    selections: Vec<Selection>,
+}
+
+impl Report {
+    /// Returns the architecture of the program.
+    pub fn arch(&self) -> &'static str {
+        self.arch
+    }
+
+    /// Returns the CPU vendor identification string, if available.
+    pub fn vendor(&self) -> Option<&str> {
+        self.vendor.as_deref()
+    }
+
+    /// Returns the selected targets, in the order they were selected.
+    pub fn selections(&self) -> &[Selection] {
+        &self.selections
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "arch: {}", self.arch)?;
+        if let Some(vendor) = &self.vendor {
+            write!(f, ", vendor: {vendor}")?;
+        }
+        for selection in &self.selections {
+            let time = selection
+                .time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default();
+            write!(
+                f,
+                "\n{}: {} (selected at {}.{:06})",
+                selection.function,
+                selection.target,
+                time.as_secs(),
+                time.subsec_micros()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the targets selected so far.
+pub fn snapshot() -> Report {
+    let selections = SELECTIONS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone();
+    Report {
+        arch: std::env::consts::ARCH,
+        vendor: crate::cpu::vendor_id().map(|id| String::from_utf8_lossy(&id).into_owned()),
+        selections,
+    }
+}
+
+#[doc(hidden)]
+pub fn __record(function: &'static str, target: &'static str) {
+    let mut selections = SELECTIONS.lock().unwrap_or_else(|err| err.into_inner());
+    if !selections.iter().any(|s| s.function == function) {
+        selections.push(Selection {
+            function,
+            target,
+            time: SystemTime::now(),
+        });
+    }
+}
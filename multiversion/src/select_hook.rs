@@ -0,0 +1,59 @@
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// A function called when a multiversioned function selects a target, given the path of the
+/// function and the selected target (or `"default"`).
+pub type SelectHook = fn(function: &'static str, target: &'static str);
+
+static HOOK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Registers a function that is called when a multiversioned function selects a target at
+/// runtime.
+///
+/// This can be used to log or export metrics about which versions are used:
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use multiversion::multiversion;
+///
+/// #[multiversion(targets = "simd")]
+/// fn square(x: &mut [f32]) {
+///     for v in x {
+///         *v *= *v
+///     }
+/// }
+///
+/// multiversion::on_select(|function, target| {
+///     println!("`{function}` selected `{target}`");
+/// })
+/// .unwrap();
+/// square(&mut [1.0, 2.0]);
+/// # }
+/// ```
+///
+/// The hook is called the first time each function selects a target, or more than once if the
+/// function is first called on multiple threads concurrently.  Functions that are dispatched
+/// statically, or select the target before `main` with the `ifunc` or `ctor` dispatcher, don't
+/// call the hook, since it can't be registered yet.  Functions in a [`group`](crate::group) call the hook once, with the path of the group's
+/// module.
+///
+/// Only one hook may be registered.  If a hook is already registered, the new hook is returned as
+/// an error.  The hook should be registered before any multiversioned functions are called.
+pub fn on_select(hook: SelectHook) -> Result<(), SelectHook> {
+    HOOK.compare_exchange(
+        core::ptr::null_mut(),
+        hook as *mut (),
+        Ordering::AcqRel,
+        Ordering::Acquire,
+    )
+    .map(|_| ())
+    .map_err(|_| hook)
+}
+
+#[doc(hidden)]
+pub fn __selected(function: &'static str, target: &'static str) {
+    let hook = HOOK.load(Ordering::Acquire);
+    if !hook.is_null() {
+        // Safety: the pointer was created from a `SelectHook` in `on_select`
+        let hook = unsafe { core::mem::transmute::<*mut (), SelectHook>(hook) };
+        hook(function, target);
+    }
+}
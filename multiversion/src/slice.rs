@@ -0,0 +1,105 @@
+//! Multiversioned slice operations.
+//!
+//! These functions are multiversioned for the `"simd"` targets, so loops over slices are
+//! vectorized for the best available instruction set without writing any
+//! [`multiversion`](crate::multiversion) attributes:
+//! ```
+//! use multiversion::slice;
+//!
+//! let a = [1., 2., 3., 4.];
+//! let b = [4., 3., 2., 1.];
+//! let mut sum = [0.; 4];
+//! slice::zip_map(&a, &b, &mut sum, |a, b| a + b);
+//! assert_eq!(sum, [5.; 4]);
+//! assert_eq!(slice::dot(&a, &b), 20.);
+//! ```
+//!
+//! The closures are inlined into each version, so they should be small and shouldn't call
+//! functions that can't be inlined.
+//!
+//! This module is only available with the `slice` cargo feature.
+
+use crate::multiversion;
+use core::ops::{Add, Mul};
+
+// The number of independent accumulators used by reductions, which allows them to be vectorized.
+const LANES: usize = 8;
+
+/// Applies `f` to each element of `input`, storing the results in `output`.
+///
+/// # Panics
+/// Panics if `input` and `output` have different lengths.
+#[multiversion(targets = "simd")]
+pub fn map<T: Copy, U>(input: &[T], output: &mut [U], f: impl Fn(T) -> U) {
+    assert_eq!(input.len(), output.len(), "slices have different lengths");
+    for (x, out) in input.iter().zip(output.iter_mut()) {
+        *out = f(*x);
+    }
+}
+
+/// Applies `f` to each pair of elements of `a` and `b`, storing the results in `output`.
+///
+/// # Panics
+/// Panics if `a`, `b`, and `output` don't all have the same length.
+#[multiversion(targets = "simd")]
+pub fn zip_map<T: Copy, U: Copy, V>(a: &[T], b: &[U], output: &mut [V], f: impl Fn(T, U) -> V) {
+    assert_eq!(a.len(), b.len(), "slices have different lengths");
+    assert_eq!(a.len(), output.len(), "slices have different lengths");
+    for ((x, y), out) in a.iter().zip(b).zip(output.iter_mut()) {
+        *out = f(*x, *y);
+    }
+}
+
+/// Combines the elements of `input` with `f`, starting from `identity`.
+///
+/// The elements are combined in an unspecified order, so that the reduction can be vectorized.
+/// `f` should be associative and commutative, and `identity` should be its identity element
+/// (such as `0` for addition).  Floating point addition isn't associative, so floating point sums
+/// may differ slightly from a sequential sum.
+#[multiversion(targets = "simd")]
+pub fn reduce<T: Copy>(input: &[T], identity: T, f: impl Fn(T, T) -> T) -> T {
+    let mut acc = [identity; LANES];
+    let chunks = input.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+    for chunk in chunks {
+        for (acc, x) in acc.iter_mut().zip(chunk) {
+            *acc = f(*acc, *x);
+        }
+    }
+    let mut result = identity;
+    for x in acc.iter().chain(remainder) {
+        result = f(result, *x);
+    }
+    result
+}
+
+/// Returns the dot product of `a` and `b`.
+///
+/// Like [`reduce`], the products are summed in an unspecified order.
+///
+/// # Panics
+/// Panics if `a` and `b` have different lengths.
+#[multiversion(targets = "simd")]
+pub fn dot<T>(a: &[T], b: &[T]) -> T
+where
+    T: Copy + Default + Add<Output = T> + Mul<Output = T>,
+{
+    assert_eq!(a.len(), b.len(), "slices have different lengths");
+    let mut acc = [T::default(); LANES];
+    let a_chunks = a.chunks_exact(LANES);
+    let b_chunks = b.chunks_exact(LANES);
+    let remainder = a_chunks.remainder().iter().zip(b_chunks.remainder());
+    for (a, b) in a_chunks.zip(b_chunks) {
+        for i in 0..LANES {
+            acc[i] = acc[i] + a[i] * b[i];
+        }
+    }
+    let mut result = T::default();
+    for acc in acc {
+        result = result + acc;
+    }
+    for (a, b) in remainder {
+        result = result + *a * *b;
+    }
+    result
+}
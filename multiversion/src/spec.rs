@@ -0,0 +1,25 @@
+use multiversion_spec::Spec;
+use target_features::{Architecture, Feature, Target};
+
+/// Returns `true` if the target specification string `spec` describes the target with the
+/// architecture `arch`, the features `features` (including implied features), and the tuning
+/// CPU `tune` (empty if untuned).
+pub fn is_target(spec: &str, arch: &str, features: &[&str], tune: &str) -> bool {
+    let spec = match Spec::parse(spec) {
+        Ok(spec) => spec,
+        Err(_) => return false,
+    };
+    if spec.arch() != arch || spec.tune().unwrap_or("") != tune {
+        return false;
+    }
+
+    let architecture = Architecture::from_str(arch);
+    let mut expected = Target::new(architecture);
+    for feature in features {
+        match Feature::new(architecture, feature) {
+            Ok(feature) => expected = expected.with_feature(feature),
+            Err(_) => return false,
+        }
+    }
+    spec.target() == expected
+}
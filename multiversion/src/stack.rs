@@ -0,0 +1,249 @@
+//! Stack usage of function versions.
+//!
+//! Versions compiled with wide vector extensions, such as AVX-512, may spill much larger
+//! registers to the stack than the default version, and can overflow small thread stacks that
+//! the default version fits in.  The `min_stack` option of [`multiversion`](crate::multiversion)
+//! checks that a minimum amount of stack remains when each version is called:
+//! ```
+//! use multiversion::multiversion;
+//!
+//! #[multiversion(targets("x86_64+avx512f", "x86_64+avx2"), min_stack = 65536)]
+//! fn kernel(x: &mut [f32]) {
+//!     for v in x {
+//!         *v *= *v
+//!     }
+//! }
+//!
+//! // Panics, rather than overflowing the stack, if less than 64 KiB of stack remains.
+//! kernel(&mut [1., 2., 3.]);
+//! ```
+//!
+//! The `stack_usage` cargo feature estimates the size of the stack frame of each version that has
+//! been called, which can be retrieved with [`frame_sizes`].  This adds overhead to every call,
+//! and is intended for debugging.
+//!
+//! The remaining stack is only known on Linux, Android, macOS, iOS, and Windows.  On other
+//! platforms, [`remaining`] returns `None` and `min_stack` has no effect.
+//!
+//! This module is only available with the `std` cargo feature.
+
+use std::cell::Cell;
+
+thread_local! {
+    // The lowest address of the current thread's stack, if known.
+    static STACK_LIMIT: Cell<Option<Option<usize>>> = const { Cell::new(None) };
+}
+
+// Returns an address within the caller's stack frame.
+#[inline(always)]
+fn stack_pointer() -> usize {
+    let marker = 0u8;
+    &marker as *const u8 as usize
+}
+
+/// Returns the number of bytes of stack remaining on the current thread, if known.
+pub fn remaining() -> Option<usize> {
+    let limit = STACK_LIMIT.with(|limit| {
+        limit.get().unwrap_or_else(|| {
+            let stack_limit = platform::stack_limit();
+            limit.set(Some(stack_limit));
+            stack_limit
+        })
+    })?;
+    Some(stack_pointer().saturating_sub(limit))
+}
+
+#[doc(hidden)]
+pub fn __check_remaining(min: usize, function: &'static str, target: &'static str) {
+    if let Some(remaining) = remaining() {
+        if remaining < min {
+            panic!(
+                "`{function}` ({target}) requires {min} bytes of stack, but only {remaining} bytes remain"
+            );
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod platform {
+    use std::ffi::c_void;
+    use std::os::raw::{c_int, c_ulong};
+
+    // Larger than `pthread_attr_t` on every supported platform.
+    #[repr(C, align(16))]
+    struct Attr([u8; 128]);
+
+    extern "C" {
+        fn pthread_self() -> c_ulong;
+        fn pthread_getattr_np(thread: c_ulong, attr: *mut Attr) -> c_int;
+        fn pthread_attr_getstack(
+            attr: *const Attr,
+            addr: *mut *mut c_void,
+            size: *mut usize,
+        ) -> c_int;
+        fn pthread_attr_destroy(attr: *mut Attr) -> c_int;
+    }
+
+    pub(super) fn stack_limit() -> Option<usize> {
+        let mut attr = Attr([0; 128]);
+        let mut addr = std::ptr::null_mut();
+        let mut size = 0;
+        // Safety: the attribute is larger than `pthread_attr_t`, and is destroyed after it's
+        // initialized.
+        unsafe {
+            if pthread_getattr_np(pthread_self(), &mut attr) != 0 {
+                return None;
+            }
+            let result = pthread_attr_getstack(&attr, &mut addr, &mut size);
+            pthread_attr_destroy(&mut attr);
+            if result == 0 {
+                Some(addr as usize)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod platform {
+    use std::ffi::c_void;
+
+    extern "C" {
+        fn pthread_self() -> *mut c_void;
+        fn pthread_get_stackaddr_np(thread: *mut c_void) -> *mut c_void;
+        fn pthread_get_stacksize_np(thread: *mut c_void) -> usize;
+    }
+
+    pub(super) fn stack_limit() -> Option<usize> {
+        // Safety: the functions are called with the current thread.
+        unsafe {
+            let thread = pthread_self();
+            // The address is the top of the stack, which grows down.
+            let top = pthread_get_stackaddr_np(thread) as usize;
+            Some(top - pthread_get_stacksize_np(thread))
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThreadStackLimits(low: *mut usize, high: *mut usize);
+    }
+
+    pub(super) fn stack_limit() -> Option<usize> {
+        let mut low = 0;
+        let mut high = 0;
+        // Safety: the pointers are valid.
+        unsafe { GetCurrentThreadStackLimits(&mut low, &mut high) };
+        Some(low)
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    windows
+)))]
+mod platform {
+    pub(super) fn stack_limit() -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(feature = "stack_usage")]
+pub use usage::*;
+
+#[cfg(feature = "stack_usage")]
+mod usage {
+    use std::cell::Cell;
+    use std::sync::Mutex;
+
+    static FRAMES: Mutex<Vec<FrameSize>> = Mutex::new(Vec::new());
+
+    thread_local! {
+        // The stack address of the most recent dispatcher, until a version is called.
+        static DISPATCHER: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // Returns an address below the caller's stack frame.
+    #[inline(never)]
+    fn stack_pointer() -> usize {
+        super::stack_pointer()
+    }
+
+    /// The estimated stack frame size of a function version.
+    ///
+    /// This type is only available with the `stack_usage` cargo feature.
+    #[derive(Clone, Debug)]
+    pub struct FrameSize {
+        function: &'static str,
+        target: &'static str,
+        bytes: usize,
+    }
+
+    impl FrameSize {
+        /// Returns the path of the multiversioned function.
+        pub fn function(&self) -> &'static str {
+            self.function
+        }
+
+        /// Returns the target of the version, or `"default"` for the default version.
+        pub fn target(&self) -> &'static str {
+            self.target
+        }
+
+        /// Returns the largest estimated size of the version's stack frame, in bytes.
+        ///
+        /// The estimate is the distance between the stack of the dispatcher and the stack of the
+        /// version, so it includes any stack used to call the version, but not the stack used by
+        /// functions it calls.  Versions that are inlined into the dispatcher, or called directly
+        /// through a function pointer, may not be measured.
+        pub fn bytes(&self) -> usize {
+            self.bytes
+        }
+    }
+
+    /// Returns the estimated stack frame sizes of the versions called so far, in the order they
+    /// were first called.
+    ///
+    /// This function is only available with the `stack_usage` cargo feature.
+    pub fn frame_sizes() -> Vec<FrameSize> {
+        FRAMES.lock().unwrap_or_else(|err| err.into_inner()).clone()
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    pub fn __dispatch() {
+        let sp = stack_pointer();
+        DISPATCHER.with(|dispatcher| dispatcher.set(sp));
+    }
+
+    #[doc(hidden)]
+    #[inline(always)]
+    pub fn __record_frame(function: &'static str, target: &'static str) {
+        let sp = stack_pointer();
+        let dispatcher = DISPATCHER.with(|dispatcher| dispatcher.replace(0));
+        // The stack grows down, and versions that weren't called by a dispatcher aren't measured.
+        if dispatcher < sp {
+            return;
+        }
+        let bytes = dispatcher - sp;
+        let mut frames = FRAMES.lock().unwrap_or_else(|err| err.into_inner());
+        match frames
+            .iter_mut()
+            .find(|frame| frame.function == function && frame.target == target)
+        {
+            Some(frame) => frame.bytes = frame.bytes.max(bytes),
+            None => frames.push(FrameSize {
+                function,
+                target,
+                bytes,
+            }),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use target_features::Target;
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static OVERRIDE: core::cell::Cell<Option<Target>> = const { core::cell::Cell::new(None) };
+}
+
+/// Calls `f` as if `target` were the target enabled at compile time, for functions with the
+/// `static` dispatcher.
+///
+/// Static dispatch selects a version from the features enabled at compile time, so testing each
+/// version usually requires building with different target features.  In tests, the static
+/// dispatcher instead selects the highest priority version supported by `target`:
+/// ```
+/// use multiversion::{
+///     multiversion,
+///     target::{selected_target, with_static_target, Architecture, Target},
+/// };
+///
+/// #[multiversion(targets("x86_64+avx2", "x86_64+sse4.2"), dispatcher = "static")]
+/// fn selected() -> Target {
+///     selected_target!()
+/// }
+///
+/// let sse42 = Target::new(Architecture::from_str("x86_64")).with_feature_str("sse4.2");
+/// with_static_target(sse42, || {
+///     // In tests, this calls the `sse4.2` version, if `sse4.2` is detected at runtime.
+///     selected();
+/// });
+/// ```
+///
+/// The override only applies to functions compiled with `cfg(test)`, on the current thread.
+/// Versions whose features aren't detected at runtime are never selected, so every version that
+/// can run on the machine can be tested.  Dispatchers that are elided, because the highest
+/// priority version is supported by the features enabled at compile time, aren't affected.
+///
+/// This function is only available with the `std` cargo feature.
+#[cfg(feature = "std")]
+pub fn with_static_target<R>(target: Target, f: impl FnOnce() -> R) -> R {
+    // Restores the previous override, even if `f` panics.
+    struct Restore(Option<Target>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let _ = OVERRIDE.try_with(|o| o.set(self.0));
+        }
+    }
+
+    let _restore = Restore(OVERRIDE.with(|o| o.replace(Some(target))));
+    f()
+}
+
+#[doc(hidden)]
+pub fn __static_target_override() -> Option<Target> {
+    #[cfg(feature = "std")]
+    {
+        OVERRIDE.try_with(|o| o.get()).ok().flatten()
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        None
+    }
+}
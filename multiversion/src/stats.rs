@@ -0,0 +1,125 @@
+//! Counts the calls to each function version.
+//!
+//! This module is only available with the `stats` cargo feature.  When enabled, every version of
+//! every multiversioned function counts its calls, which can be used to confirm which versions are
+//! used in production before removing targets:
+//! ```
+//! use multiversion::multiversion;
+//!
+//! #[multiversion(targets = "simd")]
+//! fn square(x: &mut [f32]) {
+//!     for v in x {
+//!         *v *= *v
+//!     }
+//! }
+//!
+//! square(&mut [1.0, 2.0]);
+//! square(&mut [3.0, 4.0]);
+//! for count in multiversion::stats::call_counts() {
+//!     println!("{} ({}): {} calls", count.function(), count.target(), count.calls());
+//! }
+//! ```
+//!
+//! Each call increments an atomic counter, which is cheap but not free, especially for small
+//! functions called from multiple threads.  Versions that are inlined into the dispatcher, such as
+//! with the `static` dispatcher, are counted like any other call.
+
+use std::sync::Mutex;
+
+#[cfg(target_has_atomic = "64")]
+use std::sync::atomic::AtomicU64 as AtomicCount;
+#[cfg(not(target_has_atomic = "64"))]
+use std::sync::atomic::AtomicUsize as AtomicCount;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static COUNTERS: Mutex<Vec<&'static __Counter>> = Mutex::new(Vec::new());
+
+#[doc(hidden)]
+pub struct __Counter {
+    function: &'static str,
+    target: &'static str,
+    calls: AtomicCount,
+    registered: AtomicBool,
+}
+
+impl __Counter {
+    pub const fn new(function: &'static str, target: &'static str) -> Self {
+        Self {
+            function,
+            target,
+            calls: AtomicCount::new(0),
+            registered: AtomicBool::new(false),
+        }
+    }
+
+    #[inline(always)]
+    pub fn increment(&'static self) {
+        if self.calls.fetch_add(1, Ordering::Relaxed) == 0 {
+            self.register();
+        }
+    }
+
+    #[cold]
+    fn register(&'static self) {
+        if !self.registered.swap(true, Ordering::Relaxed) {
+            COUNTERS
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .push(self);
+        }
+    }
+}
+
+/// The number of calls to a function version.
+#[derive(Clone, Debug)]
+pub struct CallCount {
+    function: &'static str,
+    target: &'static str,
+    calls: u64,
+}
+
+impl CallCount {
+    /// Returns the path of the multiversioned function.
+    pub fn function(&self) -> &'static str {
+        self.function
+    }
+
+    /// Returns the target of the version, or `"default"` for the default version.
+    pub fn target(&self) -> &'static str {
+        self.target
+    }
+
+    /// Returns the number of calls to the version.
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+}
+
+/// Returns the number of calls to each version called so far, in the order they were first
+/// called.
+pub fn call_counts() -> Vec<CallCount> {
+    COUNTERS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .iter()
+        .map(|counter| CallCount {
+            function: counter.function,
+            target: counter.target,
+            #[allow(clippy::unnecessary_cast)]
+            calls: counter.calls.load(Ordering::Relaxed) as u64,
+        })
+        .collect()
+}
+
+/// Resets the number of calls to every version to zero.
+///
+/// Versions that have been called remain in [`call_counts`], with zero calls.
+pub fn reset() {
+    for counter in COUNTERS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .iter()
+    {
+        counter.calls.store(0, Ordering::Relaxed);
+    }
+}
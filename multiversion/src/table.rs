@@ -0,0 +1,136 @@
+//! Tables of multiversioned function pointers.
+//!
+//! Plugin-style code often stores kernels in a struct of function pointers, rather than calling
+//! named functions.  The [`table!`](crate::table!) macro declares a `static` [`Table`] of the
+//! function pointers returned by the `fn_ptr` option of [`multiversion`](crate::multiversion),
+//! which are selected when the table is first used.
+//!
+//! ```
+//! # #[cfg(feature = "std")] {
+//! use multiversion::{multiversion, table};
+//!
+//! struct Ops {
+//!     add: fn(&mut [f32], &[f32]),
+//!     scale: fn(&mut [f32], f32),
+//! }
+//!
+//! #[multiversion(targets = "simd", fn_ptr)]
+//! fn add(x: &mut [f32], y: &[f32]) {
+//!     x.iter_mut().zip(y).for_each(|(x, y)| *x += y);
+//! }
+//!
+//! #[multiversion(targets = "simd", fn_ptr)]
+//! fn mul(x: &mut [f32], y: f32) {
+//!     x.iter_mut().for_each(|x| *x *= y);
+//! }
+//!
+//! table! {
+//!     static OPS: Ops = { add, scale: mul };
+//! }
+//!
+//! let mut x = [1., 2.];
+//! (OPS.add)(&mut x, &[1., 1.]);
+//! (OPS.scale)(&mut x, 2.);
+//! assert_eq!(x, [4., 6.]);
+//! # }
+//! ```
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+const UNINITIALIZED: u8 = 0;
+const INITIALIZING: u8 = 1;
+const INITIALIZED: u8 = 2;
+
+/// A value that is initialized on first use, such as a table of function pointers.
+///
+/// Usually declared with the [`table!`](crate::table!) macro.  Unlike `std::sync::OnceLock`, this
+/// is available without the `std` cargo feature.  Concurrent first uses wait for a single thread
+/// to initialize the value.
+pub struct Table<T> {
+    state: AtomicU8,
+    value: UnsafeCell<MaybeUninit<T>>,
+    init: fn() -> T,
+}
+
+// Safety: the value is only written once, before it's shared.
+unsafe impl<T: Send + Sync> Sync for Table<T> {}
+
+impl<T> Table<T> {
+    /// Creates a table that is initialized by `init` on first use.
+    pub const fn new(init: fn() -> T) -> Self {
+        Self {
+            state: AtomicU8::new(UNINITIALIZED),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+            init,
+        }
+    }
+
+    /// Returns the value, initializing it if necessary.
+    #[inline]
+    pub fn get(&self) -> &T {
+        if self.state.load(Ordering::Acquire) != INITIALIZED {
+            self.initialize();
+        }
+        // Safety: the value is initialized.
+        unsafe { &*(*self.value.get()).as_ptr() }
+    }
+
+    #[cold]
+    fn initialize(&self) {
+        // Allows another thread to initialize the value if `init` panics.
+        struct Reset<'a>(&'a AtomicU8);
+        impl Drop for Reset<'_> {
+            fn drop(&mut self) {
+                self.0.store(UNINITIALIZED, Ordering::Release);
+            }
+        }
+
+        loop {
+            match self.state.compare_exchange_weak(
+                UNINITIALIZED,
+                INITIALIZING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let reset = Reset(&self.state);
+                    let value = (self.init)();
+                    core::mem::forget(reset);
+                    // Safety: only this thread is initializing the value.
+                    unsafe { (*self.value.get()).as_mut_ptr().write(value) };
+                    self.state.store(INITIALIZED, Ordering::Release);
+                    return;
+                }
+                Err(INITIALIZED) => return,
+                Err(_) => core::hint::spin_loop(),
+            }
+        }
+    }
+}
+
+impl<T> Deref for Table<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T> Drop for Table<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == INITIALIZED {
+            // Safety: the value is initialized.
+            unsafe { core::ptr::drop_in_place((*self.value.get()).as_mut_ptr()) }
+        }
+    }
+}
+
+impl<T: core::fmt::Debug> core::fmt::Debug for Table<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("Table").field(self.get()).finish()
+    }
+}
@@ -0,0 +1,133 @@
+use crate::target_spec::{InvalidTarget, TargetSpec};
+use std::fmt;
+use std::str::FromStr;
+
+/// An ordered set of targets.
+///
+/// Target sets can be combined with set operations and formatted as a list of target
+/// specification strings, for example in a build script:
+/// ```
+/// use multiversion::target::TargetSet;
+///
+/// let simd: TargetSet = r#""x86_64+avx2+fma", "x86_64+sse4.2", "aarch64+neon""#.parse().unwrap();
+/// let excluded: TargetSet = r#""x86_64+sse4.2""#.parse().unwrap();
+/// let targets = simd.difference(&excluded);
+/// assert_eq!(targets.to_string(), r#""x86_64+avx2+fma", "aarch64+neon""#);
+///
+/// // In a build script:
+/// // println!("cargo::rustc-env=MY_TARGETS={targets}");
+/// // and then `#[multiversion(targets = env!("MY_TARGETS"))]`
+/// ```
+///
+/// Like the list of targets passed to [`multiversion`](crate::multiversion), target sets are
+/// ordered by priority, from first to last.  Targets are compared ignoring the order of their
+/// features.
+///
+/// This type is only available with the `std` cargo feature.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TargetSet {
+    targets: Vec<TargetSpec>,
+}
+
+impl TargetSet {
+    /// Creates an empty target set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a target to the end of the set.
+    ///
+    /// Returns `false` if the set already contains the target.
+    pub fn insert(&mut self, target: &str) -> Result<bool, InvalidTarget> {
+        let target = target.parse()?;
+        if self.targets.contains(&target) {
+            Ok(false)
+        } else {
+            self.targets.push(target);
+            Ok(true)
+        }
+    }
+
+    /// Returns `true` if the set contains the target.
+    pub fn contains(&self, target: &str) -> bool {
+        matches!(target.parse(), Ok(target) if self.targets.contains(&target))
+    }
+
+    /// Returns the number of targets in the set.
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Returns `true` if the set contains no targets.
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Returns an iterator over the target specification strings, in priority order.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.targets.iter().map(TargetSpec::as_str)
+    }
+
+    /// Returns the targets in either set.
+    ///
+    /// Targets in `self` are ordered before targets only in `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        let mut targets = self.targets.clone();
+        for target in &other.targets {
+            if !targets.contains(target) {
+                targets.push(target.clone());
+            }
+        }
+        Self { targets }
+    }
+
+    /// Returns the targets in both sets, in the order of `self`.
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.filter(|target| other.targets.contains(target))
+    }
+
+    /// Returns the targets in `self` but not in `other`.
+    pub fn difference(&self, other: &Self) -> Self {
+        self.filter(|target| !other.targets.contains(target))
+    }
+
+    fn filter(&self, f: impl Fn(&TargetSpec) -> bool) -> Self {
+        Self {
+            targets: self.targets.iter().filter(|x| f(x)).cloned().collect(),
+        }
+    }
+}
+
+/// Parses a comma-separated list of target specification strings, optionally quoted.
+impl FromStr for TargetSet {
+    type Err = InvalidTarget;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut set = Self::new();
+        for target in s.split(',') {
+            let target = target.trim();
+            let target = target
+                .strip_prefix('"')
+                .and_then(|target| target.strip_suffix('"'))
+                .unwrap_or(target);
+            if !target.is_empty() {
+                set.insert(target)?;
+            }
+        }
+        Ok(set)
+    }
+}
+
+/// Formats the set as a list of quoted target specification strings, as accepted by
+/// `targets(...)`.
+impl fmt::Display for TargetSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, target) in self.targets.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "\"{target}\"")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use multiversion_spec::Spec;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// Returned when a target specification string is invalid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidTarget {
+    target: String,
+    reason: String,
+}
+
+impl fmt::Display for InvalidTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid target specification string `{}`: {}",
+            self.target, self.reason
+        )
+    }
+}
+
+impl std::error::Error for InvalidTarget {}
+
+/// A target specification string, such as `"x86_64+avx2"`.
+///
+/// Target specification strings are parsed exactly like the targets passed to
+/// [`multiversion`](crate::multiversion), so a string that parses can be passed to the macro, or
+/// to a `force_target` function:
+/// ```
+/// use multiversion::target::TargetSpec;
+///
+/// let target: TargetSpec = "x86_64+fma+avx2".parse().unwrap();
+/// assert_eq!(target, "x86_64+avx2+fma".parse().unwrap());
+/// assert_eq!(target.as_str(), "x86_64+avx2+fma");
+/// assert_eq!(target.arch(), "x86_64");
+/// assert!(target.target().supports_feature_str("avx"));
+///
+/// assert!("x86_64+avx3".parse::<TargetSpec>().is_err());
+/// ```
+///
+/// The string is normalized so that equivalent targets compare equal: the specified features are
+/// sorted, and duplicates are removed.  The features implied by the target are not added.
+///
+/// With the `serde` cargo feature, targets are serialized as their specification strings.
+///
+/// This type is only available with the `std` cargo feature.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TargetSpec {
+    spec: String,
+}
+
+impl TargetSpec {
+    /// Returns the normalized target specification string.
+    pub fn as_str(&self) -> &str {
+        &self.spec
+    }
+
+    /// Returns the architecture, such as `"x86_64"`.
+    pub fn arch(&self) -> &str {
+        self.parsed().arch()
+    }
+
+    /// Returns the CPU the target is tuned for with `~tune`, if any.
+    pub fn tune(&self) -> Option<&str> {
+        self.parsed().tune()
+    }
+
+    /// Returns the target, including the features of the CPU and every implied feature.
+    pub fn target(&self) -> crate::target::Target {
+        self.parsed().target()
+    }
+
+    fn parsed(&self) -> Spec<'_> {
+        Spec::parse(&self.spec).expect("normalized target is valid")
+    }
+}
+
+impl FromStr for TargetSpec {
+    type Err = InvalidTarget;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let spec = Spec::parse(s).map_err(|err| InvalidTarget {
+            target: s.to_string(),
+            reason: err.to_string(),
+        })?;
+
+        let mut features = spec.specified_features().collect::<Vec<_>>();
+        features.sort_unstable();
+        features.dedup();
+
+        let mut normalized = spec.arch().to_string();
+        if let Some(cpu) = spec.cpu() {
+            normalized.push('/');
+            normalized.push_str(cpu);
+        }
+        for feature in features {
+            normalized.push('+');
+            normalized.push_str(feature);
+        }
+        if let Some(tune) = spec.tune() {
+            normalized.push('~');
+            normalized.push_str(tune);
+        }
+        Ok(Self { spec: normalized })
+    }
+}
+
+impl TryFrom<&str> for TargetSpec {
+    type Error = InvalidTarget;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl AsRef<str> for TargetSpec {
+    fn as_ref(&self) -> &str {
+        &self.spec
+    }
+}
+
+impl fmt::Display for TargetSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.spec)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for TargetSpec {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.spec)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TargetSpec {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = TargetSpec;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a target specification string")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<TargetSpec, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
@@ -0,0 +1,64 @@
+use target_features::{Architecture, Feature, Target};
+
+// Like `Target::supports_feature_str`, but returns false for unknown features.
+pub const fn supports(target: &Target, feature: &str) -> bool {
+    match Feature::new(target.architecture(), feature) {
+        Ok(feature) => target.supports_feature(feature),
+        Err(_) => false,
+    }
+}
+
+const fn has_fma(target: &Target) -> bool {
+    match target.architecture() {
+        Architecture::X86 => supports(target, "fma"),
+        Architecture::AArch64 => supports(target, "neon"),
+        Architecture::Arm => supports(target, "vfp4"),
+        Architecture::PowerPC => supports(target, "vsx"),
+        Architecture::RiscV => supports(target, "f"),
+        _ => false,
+    }
+}
+
+const fn simd_width(width: Option<usize>) -> usize {
+    match width {
+        Some(width) => width,
+        None => 1,
+    }
+}
+
+/// A zero-sized type representing the selected target of a multiversioned function.
+///
+/// Generic code can take a token as a type parameter and query the target through associated
+/// constants, which are known at compile time.  Branches on these constants are removed in each
+/// version of the multiversioned function.
+///
+/// Tokens are obtained with [`selected_token`](crate::target::selected_token).
+///
+/// # Example
+/// ```
+/// use multiversion::{multiversion, target::{selected_token, TargetToken}};
+///
+/// fn multiply_add<T: TargetToken>(_: T, a: &[f32], b: &[f32], c: &mut [f32]) {
+///     for ((a, b), c) in a.iter().zip(b).zip(c) {
+///         *c = if T::HAS_FMA { a.mul_add(*b, *c) } else { a * b + *c };
+///     }
+/// }
+///
+/// #[multiversion(targets = "simd")]
+/// fn kernel(a: &[f32], b: &[f32], c: &mut [f32]) {
+///     multiply_add(selected_token!(), a, b, c)
+/// }
+/// ```
+pub trait TargetToken: Copy + 'static {
+    /// The selected target.
+    const TARGET: Target;
+
+    /// Whether fused multiply-add instructions are supported.
+    const HAS_FMA: bool = has_fma(&Self::TARGET);
+
+    /// The suggested number of `f32` lanes in a SIMD vector, or 1 if SIMD is not supported.
+    const SIMD_WIDTH_F32: usize = simd_width(Self::TARGET.suggested_simd_width::<f32>());
+
+    /// The suggested number of `f64` lanes in a SIMD vector, or 1 if SIMD is not supported.
+    const SIMD_WIDTH_F64: usize = simd_width(Self::TARGET.suggested_simd_width::<f64>());
+}
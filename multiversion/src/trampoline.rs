@@ -0,0 +1,93 @@
+//! C callbacks that call multiversioned functions.
+//!
+//! Event loops and job systems often take callbacks of type `unsafe extern "C" fn(*mut c_void)`.
+//! The `trampoline` option of [`multiversion`](crate::multiversion) generates a function named
+//! `<name>_trampoline` that returns such a callback.  The callback takes a pointer to a
+//! [`Context`], which holds the arguments and receives the return value.  The type of the context
+//! must exactly match the argument and return types of the function, so it should be annotated.
+//!
+//! ```
+//! use multiversion::{multiversion, trampoline::Context};
+//!
+//! #[multiversion(targets("x86_64+avx2", "aarch64+neon"), trampoline)]
+//! fn sum(x: &[f32]) -> f32 {
+//!     x.iter().sum()
+//! }
+//!
+//! let callbacks = [sum_trampoline()];
+//! let data = [1., 2., 3.];
+//! let mut context: Context<(&[f32],), f32> = Context::new((&data[..],));
+//! // Safety: the context has the argument and return types of `sum`.
+//! unsafe { callbacks[0].call(context.as_mut_ptr()) };
+//! assert_eq!(context.into_output(), Some(6.));
+//! ```
+
+use core::ffi::c_void;
+
+/// A callback with the C ABI.
+///
+/// This type has the same layout as `unsafe extern "C" fn(*mut c_void)`, so it can be used in
+/// tables that are passed to C.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug)]
+pub struct Trampoline(unsafe extern "C" fn(*mut c_void));
+
+impl Trampoline {
+    /// Wraps a callback.
+    pub const fn new(f: unsafe extern "C" fn(*mut c_void)) -> Self {
+        Self(f)
+    }
+
+    /// Returns the callback.
+    pub const fn as_fn(self) -> unsafe extern "C" fn(*mut c_void) {
+        self.0
+    }
+
+    /// Calls the callback.
+    ///
+    /// # Safety
+    /// `context` must point to a [`Context`] with the argument and return types of the function
+    /// the trampoline was generated for, and the context must contain arguments.
+    pub unsafe fn call(self, context: *mut c_void) {
+        (self.0)(context)
+    }
+}
+
+/// The arguments and return value of a call through a [`Trampoline`].
+///
+/// The arguments are a tuple, with one element per function argument.
+#[derive(Debug)]
+pub struct Context<A, R> {
+    args: Option<A>,
+    output: Option<R>,
+}
+
+impl<A, R> Context<A, R> {
+    /// Creates a context containing the arguments.
+    pub const fn new(args: A) -> Self {
+        Self {
+            args: Some(args),
+            output: None,
+        }
+    }
+
+    /// Returns a pointer to the context, to be passed to a trampoline.
+    pub fn as_mut_ptr(&mut self) -> *mut c_void {
+        self as *mut Self as *mut c_void
+    }
+
+    /// Returns the return value of the function, if it has been called.
+    pub fn into_output(self) -> Option<R> {
+        self.output
+    }
+
+    #[doc(hidden)]
+    pub fn __take_args(&mut self) -> Option<A> {
+        self.args.take()
+    }
+
+    #[doc(hidden)]
+    pub fn __set_output(&mut self, output: R) {
+        self.output = Some(output);
+    }
+}
@@ -0,0 +1,159 @@
+use target_features::Target;
+
+/// Additional queries of a [`Target`].
+///
+/// This trait is implemented for the target returned by
+/// [`selected_target`](crate::target::selected_target).
+pub trait TargetExt {
+    /// Returns the suggested size of a SIMD vector in bytes, or `None` if SIMD is not supported.
+    ///
+    /// This is the widest vector suggested for any element type, and is also a suitable alignment
+    /// for buffers processed with SIMD instructions:
+    /// ```
+    /// use multiversion::{multiversion, target::{selected_target, TargetExt}};
+    ///
+    /// #[multiversion(targets = "simd")]
+    /// fn vector_bytes() -> usize {
+    ///     selected_target!().suggested_vector_bytes().unwrap_or(1)
+    /// }
+    ///
+    /// assert!(vector_bytes().is_power_of_two());
+    /// ```
+    fn suggested_vector_bytes(&self) -> Option<usize>;
+}
+
+impl TargetExt for Target {
+    fn suggested_vector_bytes(&self) -> Option<usize> {
+        let widths = [
+            self.suggested_simd_width::<u8>(),
+            self.suggested_simd_width::<f32>().map(|width| 4 * width),
+            self.suggested_simd_width::<f64>().map(|width| 8 * width),
+        ];
+        widths.iter().flatten().max().copied()
+    }
+}
+
+#[cfg(feature = "std")]
+pub use buffer::AlignedBuffer;
+
+#[cfg(feature = "std")]
+mod buffer {
+    use super::TargetExt;
+    use std::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+    use std::fmt;
+    use std::ops::{Deref, DerefMut};
+    use std::ptr::NonNull;
+    use target_features::Target;
+
+    /// A heap-allocated slice aligned to a SIMD vector.
+    ///
+    /// Aligned buffers can be loaded and stored with aligned vector instructions, and avoid
+    /// vectors that are split across cache lines:
+    /// ```
+    /// use multiversion::{multiversion, target::{selected_target, AlignedBuffer}};
+    ///
+    /// #[multiversion(targets = "simd")]
+    /// fn squares(len: usize) -> AlignedBuffer<f32> {
+    ///     let mut buffer = AlignedBuffer::for_target(&selected_target!(), len);
+    ///     for (i, v) in buffer.iter_mut().enumerate() {
+    ///         *v = (i * i) as f32;
+    ///     }
+    ///     buffer
+    /// }
+    ///
+    /// let buffer = squares(100);
+    /// assert_eq!(buffer[3], 9.);
+    /// assert_eq!(buffer.as_ptr() as usize % buffer.align(), 0);
+    /// ```
+    ///
+    /// This type is only available with the `std` cargo feature.
+    pub struct AlignedBuffer<T> {
+        ptr: NonNull<T>,
+        len: usize,
+        align: usize,
+    }
+
+    // Safety: the buffer owns its elements, like `Vec`.
+    unsafe impl<T: Send> Send for AlignedBuffer<T> {}
+    unsafe impl<T: Sync> Sync for AlignedBuffer<T> {}
+
+    impl<T: Default> AlignedBuffer<T> {
+        /// Creates a buffer of `len` default elements, aligned to at least `align` bytes.
+        ///
+        /// The buffer is also aligned to the alignment of `T`.
+        ///
+        /// # Panics
+        /// Panics if `align` is not a power of two, or the size of the buffer overflows `isize`.
+        pub fn new(len: usize, align: usize) -> Self {
+            let align = align.max(std::mem::align_of::<T>());
+            let layout = std::mem::size_of::<T>()
+                .checked_mul(len)
+                .and_then(|size| Layout::from_size_align(size, align).ok())
+                .expect("invalid buffer layout");
+            let ptr = if layout.size() == 0 {
+                // Zero-sized buffers are never allocated, but must still be aligned.
+                NonNull::new(align as *mut T).unwrap()
+            } else {
+                // Safety: the layout has a non-zero size.
+                let ptr = unsafe { alloc(layout) } as *mut T;
+                NonNull::new(ptr).unwrap_or_else(|| handle_alloc_error(layout))
+            };
+            for i in 0..len {
+                // Safety: the element is within the allocation.
+                unsafe { ptr.as_ptr().add(i).write(T::default()) };
+            }
+            Self { ptr, len, align }
+        }
+
+        /// Creates a buffer of `len` default elements, aligned to the suggested vector size of
+        /// `target`.
+        pub fn for_target(target: &Target, len: usize) -> Self {
+            Self::new(len, target.suggested_vector_bytes().unwrap_or(1))
+        }
+    }
+
+    impl<T> AlignedBuffer<T> {
+        /// Returns the alignment of the buffer in bytes.
+        pub fn align(&self) -> usize {
+            self.align
+        }
+    }
+
+    impl<T> Deref for AlignedBuffer<T> {
+        type Target = [T];
+
+        fn deref(&self) -> &[T] {
+            // Safety: the buffer contains `len` initialized elements.
+            unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+        }
+    }
+
+    impl<T> DerefMut for AlignedBuffer<T> {
+        fn deref_mut(&mut self) -> &mut [T] {
+            // Safety: the buffer contains `len` initialized elements.
+            unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+        }
+    }
+
+    impl<T> Drop for AlignedBuffer<T> {
+        fn drop(&mut self) {
+            // Safety: the elements are initialized, and the layout was checked when allocated.
+            unsafe {
+                std::ptr::drop_in_place(&mut **self as *mut [T]);
+                let size = std::mem::size_of::<T>() * self.len;
+                if size != 0 {
+                    dealloc(
+                        self.ptr.as_ptr() as *mut u8,
+                        Layout::from_size_align_unchecked(size, self.align),
+                    );
+                }
+            }
+        }
+    }
+
+    impl<T: fmt::Debug> fmt::Debug for AlignedBuffer<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            fmt::Debug::fmt(&**self, f)
+        }
+    }
+}
@@ -0,0 +1,36 @@
+#![cfg(feature = "std")]
+
+use multiversion::{multiversion, target::selected_target};
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse2", "aarch64+neon"),
+    detect = "all_cores"
+)]
+fn all_cores() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse2", "aarch64+neon"),
+    detect = "current_core"
+)]
+fn current_core() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[test]
+fn detect_all_cores() {
+    // Every core supports at most the features of the current core
+    let all = all_cores();
+    let current = current_core();
+    assert!(all.features().all(|f| current.supports_feature(f)));
+
+    for feature in ["avx2", "sse2", "neon"] {
+        if multiversion::detect::__is_feature_detected_all_cores(std::env::consts::ARCH, feature) {
+            assert!(multiversion::detect::is_feature_detected(
+                std::env::consts::ARCH,
+                feature
+            ));
+        }
+    }
+}
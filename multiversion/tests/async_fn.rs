@@ -4,6 +4,34 @@ async fn async_add(a: &mut [f32], b: &[f32]) {
     a.iter_mut().zip(b.iter()).for_each(|(a, b)| *a += b);
 }
 
+// The signature produced by `async_trait`, which has lifetimes that can't be higher-ranked.
+#[cfg(feature = "std")]
+#[multiversion::multiversion(
+    targets("x86_64+avx", "x86_64+sse", "aarch64+neon"),
+    dispatcher = "indirect"
+)]
+#[allow(clippy::needless_lifetimes)]
+fn boxed_add<'life0, 'life1, 'async_trait>(
+    a: &'life0 mut [f32],
+    b: &'life1 [f32],
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send + 'async_trait>>
+where
+    'life0: 'async_trait,
+    'life1: 'async_trait,
+{
+    Box::pin(async move {
+        a.iter_mut().zip(b.iter()).for_each(|(a, b)| *a += b);
+    })
+}
+
+// Dispatched indirectly by boxing the futures of each version.
+#[cfg(feature = "std")]
+#[multiversion::multiversion(targets("x86_64+avx", "x86_64+sse", "aarch64+neon"), boxed_future)]
+async fn indirect_add<'b>(a: &mut [f32], b: &'b [f32], _: &'static str) -> &'b [f32] {
+    a.iter_mut().zip(b.iter()).for_each(|(a, b)| *a += b);
+    b
+}
+
 mod test {
 
     // Adapted from David Tolnay's async-trait.
@@ -51,4 +79,31 @@ mod test {
         block_on(fut);
         assert_eq!(a, vec![1f32, 3f32, 5f32]);
     }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn boxed_future() {
+        let mut a = vec![0f32, 2f32, 4f32];
+        let b = vec![1f32, 1f32, 1f32];
+        block_on(super::boxed_add(&mut a, &b));
+        assert_eq!(a, vec![1f32, 3f32, 5f32]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn indirect_async_fn() {
+        let mut a = vec![0f32, 2f32, 4f32];
+        let b = vec![1f32, 1f32, 1f32];
+        for _ in 0..2 {
+            assert_eq!(block_on(super::indirect_add(&mut a, &b, "")), &b[..]);
+        }
+        assert_eq!(a, vec![2f32, 4f32, 6f32]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn indirect_async_fn_is_send() {
+        fn assert_send<T: Send>(_: T) {}
+        assert_send(super::indirect_add(&mut [], &[], ""));
+    }
 }
@@ -0,0 +1,89 @@
+// Async functions in traits require Rust 1.75.
+#[rustversion::since(1.75)]
+mod afit {
+    use multiversion::multiversion;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        struct Noop;
+        impl Wake for Noop {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = pin!(fut);
+        loop {
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    // Default bodies are multiversioned for each implementation.
+    #[multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"))]
+    trait Samples {
+        fn values(&self) -> &[f32];
+
+        async fn sum(&self) -> f32 {
+            self.values().iter().sum()
+        }
+
+        fn sum_send(&self) -> impl Future<Output = f32> + Send
+        where
+            Self: Sync,
+        {
+            async move { self.values().iter().sum() }
+        }
+
+        fn mean(&self) -> f32 {
+            Self::scale() * self.values().iter().sum::<f32>() / self.values().len() as f32
+        }
+
+        fn scale() -> f32 {
+            1.
+        }
+    }
+
+    struct Vector(Vec<f32>);
+
+    impl Samples for Vector {
+        fn values(&self) -> &[f32] {
+            &self.0
+        }
+    }
+
+    trait Product {
+        async fn product(&self) -> f32;
+
+        fn product_send(&self) -> impl Future<Output = f32> + Send;
+    }
+
+    #[multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"))]
+    impl Product for Vector {
+        async fn product(&self) -> f32 {
+            self.0.iter().product()
+        }
+
+        fn product_send(&self) -> impl Future<Output = f32> + Send {
+            async move { self.0.iter().product() }
+        }
+    }
+
+    fn assert_send<T: Send>(x: T) -> T {
+        x
+    }
+
+    #[test]
+    fn async_trait() {
+        let x = Vector(vec![1., 2., 3., 4.]);
+        assert_eq!(block_on(x.sum()), 10.);
+        assert_eq!(block_on(assert_send(x.sum_send())), 10.);
+        assert_eq!(x.mean(), 2.5);
+        assert_eq!(block_on(x.product()), 24.);
+        assert_eq!(block_on(assert_send(x.product_send())), 24.);
+    }
+}
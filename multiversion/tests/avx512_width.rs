@@ -0,0 +1,46 @@
+#![cfg(feature = "std")]
+
+use multiversion::{multiversion, target::selected_target};
+
+#[multiversion(
+    targets("x86_64+avx512f+avx512bw", "x86_64+avx2", "aarch64+neon"),
+    avx512_width = "auto",
+    dispatcher = "indirect"
+)]
+fn vector_width() -> Option<usize> {
+    selected_target!().suggested_simd_width::<f32>()
+}
+
+#[multiversion(
+    targets("x86_64+avx512f+avx512bw", "x86_64+avx2"),
+    avx512_width = "auto",
+    dispatcher = "inline"
+)]
+fn vector_width_inline() -> Option<usize> {
+    selected_target!().suggested_simd_width::<f32>()
+}
+
+// The 256-bit variant calls the version for `x86_64+avx2+fma+f16c`, which has the same features.
+#[multiversion(
+    targets("x86_64+avx512f", "x86_64+avx2+fma+f16c"),
+    avx512_width = "auto"
+)]
+fn vector_width_shared() -> Option<usize> {
+    selected_target!().suggested_simd_width::<f32>()
+}
+
+#[test]
+fn avx512_width() {
+    let width = vector_width();
+    assert_eq!(vector_width_inline(), width);
+    assert_eq!(vector_width_shared(), width);
+    #[cfg(target_arch = "x86_64")]
+    {
+        if std::arch::is_x86_feature_detected!("avx512f") {
+            // Either the full-width version or its 256-bit variant is selected.
+            assert!(width == Some(16) || width == Some(8));
+        } else if std::arch::is_x86_feature_detected!("avx2") {
+            assert_eq!(width, Some(8));
+        }
+    }
+}
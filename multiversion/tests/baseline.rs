@@ -0,0 +1,32 @@
+#[cfg(feature = "std")]
+#[test]
+fn baseline() {
+    multiversion::verify_baseline().unwrap();
+}
+
+#[multiversion::multiversion(
+    targets("x86_64+avx2", "aarch64+neon"),
+    baseline("x86_64+sse4.2", "x86+sse2"),
+    dispatcher = "static"
+)]
+fn selected() -> multiversion::target::Target {
+    multiversion::target::selected_target!()
+}
+
+#[test]
+fn baseline_features() {
+    if cfg!(all(target_arch = "x86_64", not(target_feature = "avx2"))) {
+        assert!(selected().supports_feature_str("sse4.2"));
+    }
+}
+
+#[multiversion::multiversion(targets("x86_64+avx2", "aarch64+neon"), baseline("x86+sse2"))]
+fn patterns((x, y): (i64, i64), mut z: i64) -> i64 {
+    z += x * y;
+    z
+}
+
+#[test]
+fn baseline_patterns() {
+    assert_eq!(patterns((2, 3), 1), 7);
+}
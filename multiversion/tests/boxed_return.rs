@@ -0,0 +1,59 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+fn block_on<F: Future>(fut: F) -> F::Output {
+    struct Noop;
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let waker = Waker::from(Arc::new(Noop));
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = Box::pin(fut);
+    loop {
+        if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+#[multiversion::multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"), boxed_return)]
+fn evens(x: &[u32]) -> impl Iterator<Item = &u32> {
+    x.iter().filter(|x| *x % 2 == 0)
+}
+
+#[multiversion::multiversion(targets = "simd", boxed_return)]
+fn scaled<'a, T: Copy + Into<f64>>(x: &'a [T], scale: f64) -> impl Iterator<Item = f64> + 'a {
+    if scale == 1. {
+        return x
+            .iter()
+            .map(|x| (*x).into())
+            .collect::<Vec<_>>()
+            .into_iter();
+    }
+    x.iter()
+        .map(|x| (*x).into() * scale)
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+#[multiversion::multiversion(targets("x86_64+avx2", "aarch64+neon"), boxed_return)]
+fn label((name, value): (&str, u32)) -> impl Display {
+    format!("{name} = {value}")
+}
+
+#[multiversion::multiversion(targets = "simd", boxed_return)]
+fn sum(x: &[u32]) -> impl Future<Output = u32> + Send + '_ {
+    async move { x.iter().sum() }
+}
+
+#[test]
+fn boxed_return() {
+    assert_eq!(evens(&[1, 2, 3, 4]).copied().collect::<Vec<_>>(), [2, 4]);
+    assert_eq!(scaled(&[1u8, 2], 2.).collect::<Vec<_>>(), [2., 4.]);
+    assert_eq!(scaled(&[1f32, 2.], 1.).collect::<Vec<_>>(), [1., 2.]);
+    assert_eq!(label(("x", 1)).to_string(), "x = 1");
+    assert_eq!(block_on(sum(&[1, 2, 3])), 6);
+}
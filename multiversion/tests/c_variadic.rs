@@ -0,0 +1,7 @@
+// C-variadic functions can't be parsed on stable compilers, even when the code is disabled, so the
+// tests are in a separate file that's only loaded when C-variadic functions are available.
+#![cfg_attr(multiversion_c_variadic, feature(c_variadic))]
+
+#[cfg(multiversion_c_variadic)]
+#[path = "c_variadic/mod.rs"]
+mod c_variadic;
@@ -0,0 +1,64 @@
+use multiversion::multiversion;
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    dispatcher = "static"
+)]
+unsafe extern "C" fn sum_static(count: usize, mut args: ...) -> i32 {
+    (0..count).map(|_| unsafe { args.next_arg::<i32>() }).sum()
+}
+
+#[cfg(feature = "std")]
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    dispatcher = "direct"
+)]
+unsafe extern "C" fn sum_direct(count: usize, mut args: ...) -> i32 {
+    (0..count).map(|_| unsafe { args.next_arg::<i32>() }).sum()
+}
+
+#[cfg(feature = "std")]
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    dispatcher = "indirect"
+)]
+unsafe extern "C" fn sum_indirect(count: usize, mut args: ...) -> i32 {
+    (0..count).map(|_| unsafe { args.next_arg::<i32>() }).sum()
+}
+
+#[repr(C)]
+struct Affine {
+    x: i32,
+    y: i32,
+}
+
+#[multiversion(targets = "simd", dispatcher = "static")]
+unsafe extern "C" fn scale(Affine { x, y }: Affine, mut args: ...) -> i32 {
+    x * unsafe { args.next_arg::<i32>() } + y
+}
+
+#[multiversion(targets = "simd", dispatcher = "static")]
+#[no_mangle]
+unsafe extern "C" fn multiversion_c_variadic_ignored(x: i32, _: ...) -> i32 {
+    x
+}
+
+#[test]
+fn c_variadic() {
+    unsafe {
+        assert_eq!(sum_static(3, 1i32, 2i32, 3i32), 6);
+        #[cfg(feature = "std")]
+        {
+            assert_eq!(sum_direct(2, 4i32, 5i32), 9);
+            assert_eq!(sum_indirect(0), 0);
+        }
+        assert_eq!(scale(Affine { x: 2, y: 1 }, 3i32), 7);
+        assert_eq!(multiversion_c_variadic_ignored(5, 1.0f64), 5);
+    }
+}
+
+#[test]
+fn c_variadic_fn_ptr() {
+    let f: unsafe extern "C" fn(usize, ...) -> i32 = sum_static;
+    assert_eq!(unsafe { f(2, 1i32, 1i32) }, 2);
+}
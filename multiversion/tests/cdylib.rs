@@ -0,0 +1,109 @@
+//! Loads and unloads a plugin that uses multiversioned functions.
+#![cfg(all(feature = "std", any(target_os = "linux", target_os = "macos")))]
+
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static HOST_SELECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+// Builds the plugin, returning the path to the library.
+fn build_plugin() -> Option<PathBuf> {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cdylib/Cargo.toml");
+    // The plugin isn't included in the published package.
+    if !manifest.exists() {
+        return None;
+    }
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cdylib");
+    // The dependencies were already downloaded to build this test.
+    let status = std::process::Command::new(env!("CARGO"))
+        .args(["build", "--offline", "--quiet", "--manifest-path"])
+        .arg(&manifest)
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    Some(target_dir.join("debug").join(format!(
+        "{}multiversion_cdylib_test{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    )))
+}
+
+struct Plugin(*mut libc::c_void);
+
+impl Plugin {
+    fn load(path: &Path, flags: libc::c_int) -> Option<Self> {
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        let handle = unsafe { libc::dlopen(path.as_ptr(), flags) };
+        if handle.is_null() {
+            None
+        } else {
+            Some(Self(handle))
+        }
+    }
+
+    unsafe fn symbol<T: Copy>(&self, name: &str) -> T {
+        let name = CString::new(name).unwrap();
+        let symbol = libc::dlsym(self.0, name.as_ptr());
+        assert!(!symbol.is_null());
+        std::mem::transmute_copy(&symbol)
+    }
+
+    fn init(&self) -> bool {
+        unsafe { self.symbol::<extern "C" fn() -> bool>("plugin_init")() }
+    }
+
+    fn sum(&self, x: &[f32]) -> f32 {
+        unsafe {
+            self.symbol::<extern "C" fn(*const f32, usize) -> f32>("plugin_sum")(
+                x.as_ptr(),
+                x.len(),
+            )
+        }
+    }
+
+    fn selections(&self) -> usize {
+        unsafe { self.symbol::<extern "C" fn() -> usize>("plugin_selections")() }
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        assert_eq!(unsafe { libc::dlclose(self.0) }, 0);
+    }
+}
+
+#[test]
+fn load_unload() {
+    let path = match build_plugin() {
+        Some(path) => path,
+        None => return,
+    };
+    multiversion::on_select(|_, _| {
+        HOST_SELECTIONS.fetch_add(1, Ordering::Relaxed);
+    })
+    .unwrap();
+    let dispatched = cfg!(any(target_arch = "x86_64", target_arch = "aarch64"));
+
+    let mut still_loaded = false;
+    for _ in 0..3 {
+        let plugin = Plugin::load(&path, libc::RTLD_NOW | libc::RTLD_LOCAL).unwrap();
+        // The plugin has its own copy of `multiversion`, so it has its own hook, which can be
+        // registered again if the plugin was actually unloaded.
+        assert_eq!(plugin.init(), !still_loaded);
+        assert_eq!(plugin.sum(&[1., 2., 3.]), 6.);
+        assert_eq!(plugin.sum(&[4., 5.]), 9.);
+        if dispatched && !still_loaded {
+            assert_eq!(plugin.selections(), 1);
+        }
+        drop(plugin);
+
+        // Unloading is only a request, and may not happen on every platform.
+        still_loaded = Plugin::load(&path, libc::RTLD_NOW | libc::RTLD_NOLOAD).is_some();
+    }
+
+    // Selections in the plugin don't call the hook registered by the program.
+    assert_eq!(HOST_SELECTIONS.load(Ordering::Relaxed), 0);
+}
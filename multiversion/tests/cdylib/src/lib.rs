@@ -0,0 +1,29 @@
+use multiversion::multiversion;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static SELECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"))]
+fn sum(x: &[f32]) -> f32 {
+    x.iter().sum()
+}
+
+/// Registers a hook counting the selections in this library, returning `false` if a hook is
+/// already registered.
+#[no_mangle]
+pub extern "C" fn plugin_init() -> bool {
+    multiversion::on_select(|_, _| {
+        SELECTIONS.fetch_add(1, Ordering::Relaxed);
+    })
+    .is_ok()
+}
+
+#[no_mangle]
+pub extern "C" fn plugin_sum(x: *const f32, len: usize) -> f32 {
+    sum(unsafe { std::slice::from_raw_parts(x, len) })
+}
+
+#[no_mangle]
+pub extern "C" fn plugin_selections() -> usize {
+    SELECTIONS.load(Ordering::Relaxed)
+}
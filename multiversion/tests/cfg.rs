@@ -1,6 +1,9 @@
 use multiversion::{
     multiversion,
-    target::{match_target, selected_target, target_cfg, target_cfg_attr, target_cfg_f},
+    target::{
+        match_target, selected_target, selected_token, target_cfg, target_cfg_attr, target_cfg_f,
+        target_cfg_for,
+    },
 };
 
 #[test]
@@ -80,3 +83,59 @@ fn match_target() {
 
     foo();
 }
+
+#[test]
+fn token_impls() {
+    trait Lanes {
+        const LANES: usize;
+    }
+
+    #[multiversion(targets("x86_64+avx2", "aarch64+neon"))]
+    fn lanes() -> usize {
+        #[target_cfg(any(
+            all(target_arch = "x86_64", target_feature = "avx2"),
+            all(target_arch = "aarch64", target_feature = "neon")
+        ))]
+        impl Lanes for selected_token!() {
+            const LANES: usize = 8;
+        }
+
+        #[target_cfg(not(any(
+            all(target_arch = "x86_64", target_feature = "avx2"),
+            all(target_arch = "aarch64", target_feature = "neon")
+        )))]
+        impl Lanes for selected_token!() {
+            const LANES: usize = 1;
+        }
+
+        <selected_token!() as Lanes>::LANES
+    }
+
+    #[multiversion(targets("x86_64+avx2", "aarch64+neon"))]
+    fn simd() -> bool {
+        match_target! {
+            "x86_64+avx2" | "aarch64+neon" => true,
+            _ => false,
+        }
+    }
+
+    assert_eq!(lanes(), if simd() { 8 } else { 1 });
+}
+
+#[test]
+fn explicit_target() {
+    #[target_cfg_for("x86_64+avx", target_feature = "avx")]
+    const AVX: bool = true;
+    #[target_cfg_for("x86_64+avx", not(target_feature = "avx"))]
+    const AVX: bool = false;
+
+    #[target_cfg_for("aarch64+neon", all(target_feature = "neon", target_feature = "avx"))]
+    const BOTH: bool = true;
+    #[target_cfg_for(
+        "aarch64+neon",
+        not(all(target_feature = "neon", target_feature = "avx"))
+    )]
+    const BOTH: bool = false;
+
+    assert_eq!([AVX, BOTH], [true, cfg!(target_feature = "avx")]);
+}
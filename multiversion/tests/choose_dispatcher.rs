@@ -26,6 +26,42 @@ fn direct_dispatch() {}
 )]
 fn indirect_dispatch() {}
 
+#[cfg(feature = "std")]
+#[multiversion(
+    targets("x86_64+avx", "x86+avx", "x86+sse", "aarch64+neon"),
+    dispatcher = "once"
+)]
+fn once_dispatch(x: &[u8]) -> &u8 {
+    &x[0]
+}
+
+#[cfg(feature = "std")]
+#[multiversion(
+    targets("x86_64+avx", "x86+avx", "x86+sse", "aarch64+neon"),
+    dispatcher = "tls"
+)]
+fn tls_dispatch(x: &[u8]) -> &u8 {
+    &x[0]
+}
+
+#[cfg(feature = "std")]
+#[multiversion(
+    targets("x86_64+avx", "x86+avx", "x86+sse", "aarch64+neon"),
+    dispatcher = "ifunc"
+)]
+fn ifunc_dispatch(x: &[u8]) -> &u8 {
+    &x[1]
+}
+
+#[cfg(feature = "std")]
+#[multiversion(
+    targets("x86_64+avx", "x86+avx", "x86+sse", "aarch64+neon"),
+    dispatcher = "inline"
+)]
+fn inline_dispatch<T: Copy>(x: &[T]) -> T {
+    x[2]
+}
+
 // Since x86_64 always has sse, this should never result in runtime dispatch
 #[multiversion(targets("x86_64+sse"), dispatcher = "default")]
 fn skip_dispatch() {}
@@ -42,6 +78,14 @@ fn dispatchers() {
     direct_dispatch();
     #[cfg(feature = "std")]
     indirect_dispatch();
+    #[cfg(feature = "std")]
+    assert_eq!(*once_dispatch(&[1, 2]), 1);
+    #[cfg(feature = "std")]
+    assert_eq!(*tls_dispatch(&[1, 2]), 1);
+    #[cfg(feature = "std")]
+    assert_eq!(*ifunc_dispatch(&[1, 2]), 2);
+    #[cfg(feature = "std")]
+    assert_eq!(inline_dispatch(&[1, 2, 3]), 3);
     skip_dispatch();
     skip_dispatch_2();
 }
@@ -0,0 +1,41 @@
+#[multiversion::clone_module(targets("x86_64+avx2+fma", "x86_64+sse4.1", "aarch64+neon"))]
+mod kernels {
+    use multiversion::target::Target;
+
+    pub const SCALE: f32 = 2.;
+
+    fn scale(x: f32) -> f32 {
+        x * SCALE
+    }
+
+    pub fn scaled_sum(x: &[f32]) -> f32 {
+        x.iter().copied().map(scale).sum()
+    }
+
+    pub fn dot((a, b): (&[f32], &[f32])) -> f32 {
+        a.iter().zip(b).map(|(a, b)| a * b).sum()
+    }
+
+    pub fn first<T: Copy>(x: &[T]) -> Option<T> {
+        x.first().copied()
+    }
+
+    pub fn selected() -> Target {
+        multiversion::target::selected_target!()
+    }
+}
+
+#[test]
+fn clone_module() {
+    let x = [1., 2., 3.];
+    assert_eq!(kernels::scaled_sum(&x), 12.);
+    assert_eq!(kernels::dot((&x, &x)), 14.);
+    assert_eq!(kernels::first(&[1u8, 2]), Some(1));
+    assert_eq!(kernels::SCALE, 2.);
+
+    #[cfg(all(target_arch = "x86_64", feature = "std"))]
+    assert_eq!(
+        kernels::selected().supports_feature_str("avx2"),
+        std::arch::is_x86_feature_detected!("avx2") && std::arch::is_x86_feature_detected!("fma")
+    );
+}
@@ -0,0 +1,31 @@
+use multiversion::multiversion_closure;
+
+#[test]
+fn closure() {
+    let offset = 1u32;
+    let add = multiversion_closure!(targets = "simd", |x: u32, y| x + y + offset);
+    assert_eq!(add(1, 2), 4);
+
+    // Captures by mutable reference make an `FnMut`.
+    let mut total = 0;
+    (1..=4).for_each(multiversion_closure!(
+        targets("x86_64+avx2", "aarch64+neon"),
+        |x: u32| total += x
+    ));
+    assert_eq!(total, 10);
+
+    // Captures by value make an `FnOnce`.
+    let values = vec![1., 2., 3.];
+    let take = multiversion_closure!(targets("x86_64+avx2", "x86_64+sse4.1"), move |(a, b): (
+        f32,
+        f32
+    )|
+          -> Vec<
+        f32,
+    > {
+        let mut values = values;
+        values.extend([a, b]);
+        values
+    },);
+    assert_eq!(take((4., 5.)), [1., 2., 3., 4., 5.]);
+}
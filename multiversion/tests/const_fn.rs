@@ -0,0 +1,45 @@
+use multiversion::multiversion;
+use multiversion::target::{selected_target, Target};
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    dispatcher = "static"
+)]
+const fn add_one(x: u32) -> u32 {
+    x + 1
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    dispatcher = "static"
+)]
+const fn target() -> Target {
+    selected_target!()
+}
+
+struct Wrapper(u32);
+
+impl Wrapper {
+    #[multiversion(targets = "simd", dispatcher = "static")]
+    const fn doubled(&self) -> u32 {
+        self.0 * 2
+    }
+}
+
+const ADDED: u32 = add_one(1);
+const TARGET: Target = target();
+const DOUBLED: u32 = Wrapper(3).doubled();
+
+#[test]
+fn const_fn() {
+    assert_eq!(ADDED, 2);
+    assert_eq!(add_one(2), 3);
+    assert_eq!(DOUBLED, 6);
+    assert_eq!(Wrapper(4).doubled(), 8);
+}
+
+#[test]
+fn const_fn_selection() {
+    // The version is selected at compile time, so it's the same in const and runtime contexts.
+    assert_eq!(TARGET, target());
+}
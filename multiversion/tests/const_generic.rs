@@ -0,0 +1,41 @@
+#![allow(clippy::needless_lifetimes)]
+#![cfg(feature = "std")]
+
+use multiversion::multiversion;
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"))]
+fn sum<const N: usize>(x: &[f32; N]) -> f32 {
+    x.iter().sum()
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    dispatcher = "indirect"
+)]
+fn first<'a, const N: usize, const M: usize>(x: &'a [[u8; N]; M]) -> &'a [u8; N] {
+    &x[0]
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    dispatcher = "indirect",
+    resolve
+)]
+fn len<const N: usize>() -> usize {
+    N
+}
+
+#[test]
+fn const_generic() {
+    assert_eq!(sum(&[1., 2.]), 3.);
+    assert_eq!(sum(&[1., 2., 3.]), 6.);
+    assert_eq!(sum(&[]), 0.);
+
+    let x = [[1, 2], [3, 4], [5, 6]];
+    assert_eq!(first(&x), &[1, 2]);
+    assert_eq!(first(&[[7]]), &[7]);
+
+    len_resolve();
+    assert_eq!(len::<3>(), 3);
+    assert_eq!(len::<5>(), 5);
+}
@@ -0,0 +1,7 @@
+// `yield` can't be parsed on stable compilers, even when the code is disabled, so the tests are
+// in a separate file that's only loaded when coroutines are available.
+#![cfg_attr(multiversion_coroutines, feature(coroutines, coroutine_trait))]
+
+#[cfg(multiversion_coroutines)]
+#[path = "coroutine/mod.rs"]
+mod coroutine;
@@ -0,0 +1,70 @@
+use core::ops::{Coroutine, CoroutineState};
+use core::pin::pin;
+use multiversion::multiversion;
+
+#[cfg(feature = "std")]
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"))]
+fn doubled(input: &[u8]) -> impl Coroutine<u8, Yield = u8, Return = usize> + '_ {
+    #[coroutine]
+    move |mut offset: u8| {
+        for x in input {
+            offset = yield x * 2 + offset;
+        }
+        input.len()
+    }
+}
+
+#[cfg(feature = "std")]
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    dispatcher = "inline"
+)]
+fn doubled_inline(input: &[u8]) -> impl Coroutine<u8, Yield = u8, Return = usize> + '_ {
+    #[coroutine]
+    move |mut offset: u8| {
+        for x in input {
+            offset = yield x * 2 + offset;
+        }
+        input.len()
+    }
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    dispatcher = "static"
+)]
+fn doubled_static(input: &[u8]) -> impl Coroutine<u8, Yield = u8, Return = usize> + '_ {
+    #[coroutine]
+    move |mut offset: u8| {
+        for x in input {
+            offset = yield x * 2 + offset;
+        }
+        input.len()
+    }
+}
+
+fn check(coroutine: impl Coroutine<u8, Yield = u8, Return = usize>) {
+    let mut coroutine = pin!(coroutine);
+    let mut yielded = Vec::new();
+    let mut resume = 0;
+    loop {
+        match coroutine.as_mut().resume(resume) {
+            CoroutineState::Yielded(x) => yielded.push(x),
+            CoroutineState::Complete(len) => {
+                assert_eq!(len, 3);
+                break;
+            }
+        }
+        resume = 1;
+    }
+    assert_eq!(yielded, [2, 5, 7]);
+}
+
+#[test]
+fn coroutine() {
+    #[cfg(feature = "std")]
+    check(doubled(&[1, 2, 3]));
+    #[cfg(feature = "std")]
+    check(doubled_inline(&[1, 2, 3]));
+    check(doubled_static(&[1, 2, 3]));
+}
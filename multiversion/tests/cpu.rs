@@ -0,0 +1,35 @@
+use multiversion::cpu::{self, Vendor};
+
+#[test]
+fn identify() {
+    let vendor = cpu::vendor();
+    let signature = cpu::signature();
+    if cfg!(any(target_arch = "x86", target_arch = "x86_64")) {
+        assert_eq!(vendor == Vendor::Intel, cpu::__is_vendor("GenuineIntel"));
+        assert_eq!(vendor == Vendor::Amd, cpu::__is_vendor("AuthenticAMD"));
+        // Only x86 CPUs before the Pentium may lack `cpuid`.
+        assert!(
+            signature.map_or(cfg!(target_arch = "x86"), |signature| signature.family()
+                > 0)
+        );
+    } else if !cfg!(target_arch = "aarch64") {
+        assert_eq!(vendor, Vendor::Unknown);
+        assert_eq!(signature, None);
+    }
+}
+
+#[test]
+fn emulator() {
+    let emulator = cpu::emulator();
+    if cfg!(not(any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios"
+    ))) {
+        assert_eq!(emulator, None);
+    }
+    assert_eq!(emulator, cpu::emulator());
+}
@@ -0,0 +1,38 @@
+use multiversion::multiversion;
+
+#[multiversion(targets = "crate")]
+fn add(a: &mut [f32], b: &[f32]) {
+    a.iter_mut().zip(b.iter()).for_each(|(a, b)| *a += b);
+}
+
+#[multiversion(targets(release = "crate", dev = ()))]
+fn sub(a: &mut [f32], b: &[f32]) {
+    a.iter_mut().zip(b.iter()).for_each(|(a, b)| *a -= b);
+}
+
+struct Scale(f32);
+
+impl Scale {
+    #[multiversion(targets = "crate")]
+    fn apply(&self, a: &mut [f32]) {
+        a.iter_mut().for_each(|a| *a *= self.0);
+    }
+
+    #[multiversion(targets = "crate")]
+    fn double(a: &mut [f32]) {
+        a.iter_mut().for_each(|a| *a *= 2f32);
+    }
+}
+
+#[test]
+fn crate_config() {
+    let mut a = vec![0f32, 2f32, 4f32];
+    let b = vec![1f32, 1f32, 1f32];
+    add(&mut a, &b);
+    assert_eq!(a, vec![1f32, 3f32, 5f32]);
+    sub(&mut a, &b);
+    assert_eq!(a, vec![0f32, 2f32, 4f32]);
+    Scale(0.5).apply(&mut a);
+    Scale::double(&mut a);
+    assert_eq!(a, vec![0f32, 2f32, 4f32]);
+}
@@ -0,0 +1,49 @@
+#![cfg(feature = "std")]
+
+use multiversion::{multiversion, target::selected_target};
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse2", "aarch64+neon"),
+    dispatcher = "ctor"
+)]
+fn ctor() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse2", "aarch64+neon"),
+    dispatcher = "indirect"
+)]
+fn indirect() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[test]
+fn ctor_dispatch() {
+    assert_eq!(ctor(), indirect());
+    assert_eq!(ctor(), indirect());
+}
+
+#[cfg(all(
+    feature = "track_selection",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+#[test]
+fn selected_before_main() {
+    #[multiversion(targets("x86_64+avx2", "aarch64+neon"), dispatcher = "ctor")]
+    fn not_called() {}
+
+    // Dispatch is elided on other architectures, or if the features are already enabled
+    let expected = usize::from(cfg!(not(any(
+        target_feature = "avx2",
+        target_feature = "neon"
+    ))));
+    let report = multiversion::report::snapshot();
+    let selections = report
+        .selections()
+        .iter()
+        .filter(|s| s.function() == "ctor::not_called")
+        .count();
+    assert_eq!(selections, expected);
+}
@@ -0,0 +1,37 @@
+#[multiversion::default_targets("x86_64+sse2", "aarch64+neon")]
+mod kernels {
+    use multiversion::{multiversion, target::selected_target};
+
+    #[multiversion]
+    pub fn defaults() -> multiversion::target::Target {
+        selected_target!()
+    }
+
+    #[multiversion(dispatcher = "static")]
+    pub fn defaults_with_options() -> multiversion::target::Target {
+        selected_target!()
+    }
+
+    #[multiversion(targets("x86_64+avx"))]
+    pub fn overridden() -> multiversion::target::Target {
+        selected_target!()
+    }
+
+    #[multiversion::default_targets("simd")]
+    pub mod nested {
+        #[multiversion::multiversion]
+        pub fn nested_defaults() -> multiversion::target::Target {
+            multiversion::target::selected_target!()
+        }
+    }
+}
+
+#[test]
+fn default_targets() {
+    if cfg!(target_arch = "x86_64") {
+        assert!(kernels::defaults().supports_feature_str("sse2"));
+        assert!(kernels::defaults_with_options().supports_feature_str("sse2"));
+        assert!(kernels::nested::nested_defaults().supports_feature_str("sse2"));
+    }
+    kernels::overridden();
+}
@@ -0,0 +1,28 @@
+#![cfg(feature = "detect-backend-custom")]
+
+use multiversion::{detect, multiversion, target::selected_target};
+
+#[multiversion(
+    targets("x86_64+avx", "x86_64+sse2", "aarch64+neon"),
+    dispatcher = "direct"
+)]
+fn detected() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[test]
+fn custom_detector() {
+    // Pretend that only the baseline features are supported
+    detect::set_detector(|_, feature| Some(feature == "sse2")).unwrap();
+    assert!(detect::set_detector(|_, _| None).is_err());
+
+    assert!(!detect::is_feature_detected("x86_64", "avx"));
+    assert!(!detect::is_feature_detected("aarch64", "neon"));
+
+    if cfg!(target_arch = "x86_64") {
+        assert!(detect::is_feature_detected("x86_64", "sse2"));
+        let target = detected();
+        assert!(target.supports_feature_str("sse2"));
+        assert!(!target.supports_feature_str("avx"));
+    }
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "std")]
+
+use multiversion::detect::is_feature_detected;
+
+#[test]
+fn cached_detection_matches_std() {
+    #[cfg(target_arch = "x86_64")]
+    for _ in 0..2 {
+        assert_eq!(
+            is_feature_detected("x86_64", "sse2"),
+            std::arch::is_x86_feature_detected!("sse2")
+        );
+        assert_eq!(
+            is_feature_detected("x86_64", "avx2"),
+            std::arch::is_x86_feature_detected!("avx2")
+        );
+        assert_eq!(
+            is_feature_detected("x86_64", "xsaves"),
+            std::arch::is_x86_feature_detected!("xsaves")
+        );
+        assert_eq!(
+            is_feature_detected("x86_64", "sha512"),
+            std::arch::is_x86_feature_detected!("sha512")
+        );
+    }
+
+    // Features that `std` can't detect are detected with `cpuid`.
+    #[cfg(target_arch = "x86_64")]
+    #[allow(unused_unsafe)]
+    {
+        // Safety: cpuid is always available on x86-64.
+        let max_leaf = unsafe { std::arch::x86_64::__cpuid(0x8000_0000) }.eax;
+        let ecx = if max_leaf >= 0x8000_0001 {
+            unsafe { std::arch::x86_64::__cpuid(0x8000_0001) }.ecx
+        } else {
+            0
+        };
+        assert_eq!(is_feature_detected("x86_64", "lahfsahf"), ecx & 1 != 0);
+        assert_eq!(is_feature_detected("x86_64", "prfchw"), ecx & (1 << 8) != 0);
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    for _ in 0..2 {
+        assert_eq!(
+            is_feature_detected("aarch64", "neon"),
+            std::arch::is_aarch64_feature_detected!("neon")
+        );
+        assert_eq!(
+            is_feature_detected("aarch64", "lse"),
+            std::arch::is_aarch64_feature_detected!("lse")
+        );
+    }
+
+    assert!(!is_feature_detected(
+        std::env::consts::ARCH,
+        "not-a-feature"
+    ));
+}
+
+// Indirect function resolvers detect features with `cpuid` or `getauxval` only.
+#[test]
+fn ifunc_detection_matches_std() {
+    use multiversion::detect::__is_ifunc_feature_detected;
+
+    #[cfg(target_arch = "x86_64")]
+    macro_rules! check {
+        { $($feature:tt),* } => {
+            $(
+                assert_eq!(
+                    __is_ifunc_feature_detected("x86_64", $feature),
+                    std::arch::is_x86_feature_detected!($feature),
+                    $feature
+                );
+            )*
+        }
+    }
+    #[cfg(target_arch = "x86_64")]
+    check! {
+        "sse2", "sse4.2", "popcnt", "avx", "avx2", "fma", "bmi2", "lzcnt", "xsaves", "sha",
+        "avx512f", "avx512vl", "avx512bw", "gfni", "vaes", "avx512bf16"
+    };
+
+    #[cfg(all(target_arch = "aarch64", target_os = "linux"))]
+    assert_eq!(
+        __is_ifunc_feature_detected("aarch64", "neon"),
+        std::arch::is_aarch64_feature_detected!("neon")
+    );
+
+    assert!(!__is_ifunc_feature_detected(
+        std::env::consts::ARCH,
+        "not-a-feature"
+    ));
+}
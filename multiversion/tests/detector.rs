@@ -0,0 +1,73 @@
+use multiversion::{
+    multiversion,
+    target::{selected_target, Architecture, Target},
+};
+
+// Pretend that only the baseline features are supported
+fn baseline() -> Target {
+    let target = Target::new(Architecture::from_str(std::env::consts::ARCH));
+    if cfg!(target_arch = "x86_64") {
+        target.with_feature_str("sse2")
+    } else {
+        target
+    }
+}
+
+// Detectors allow runtime dispatch without the `std` feature
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse2", "aarch64+neon"),
+    detector = "baseline",
+    dispatcher = "direct"
+)]
+fn direct() -> Target {
+    selected_target!()
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse2", "aarch64+neon"),
+    detector = baseline,
+    dispatcher = "indirect"
+)]
+fn indirect() -> Target {
+    selected_target!()
+}
+
+#[test]
+fn custom_detector() {
+    for target in [direct(), indirect()] {
+        assert_eq!(
+            target.supports_feature_str("avx2"),
+            cfg!(target_feature = "avx2")
+        );
+        if cfg!(target_arch = "x86_64") {
+            assert!(target.supports_feature_str("sse2"));
+        }
+    }
+}
+
+static DETECTIONS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+fn count_detections() -> Target {
+    DETECTIONS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    baseline()
+}
+
+#[multiversion(
+    targets("x86_64+avx512f", "x86_64+sse2"),
+    detector = "count_detections",
+    dispatcher = "inline"
+)]
+fn enabled_features() -> Target {
+    selected_target!()
+}
+
+#[test]
+fn enabled_features_not_detected() {
+    for _ in 0..3 {
+        enabled_features();
+    }
+    // Only `avx512f` is detected, since `sse2` is always enabled.
+    if cfg!(all(target_arch = "x86_64", not(target_feature = "avx512f"))) {
+        assert_eq!(DETECTIONS.load(std::sync::atomic::Ordering::Relaxed), 3);
+    }
+}
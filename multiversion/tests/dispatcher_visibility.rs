@@ -0,0 +1,35 @@
+mod api {
+    /// Sums the values.
+    #[multiversion::multiversion(
+        targets("x86_64+avx2", "aarch64+neon"),
+        dispatcher_visibility = "pub(crate)",
+        variants(pub(crate), module = "sum_variants")
+    )]
+    #[must_use]
+    pub fn sum(x: &[f32]) -> f32 {
+        x.iter().sum()
+    }
+
+    pub struct Values(pub Vec<f32>);
+
+    impl Values {
+        #[multiversion::multiversion(
+            targets("x86_64+avx2", "aarch64+neon"),
+            dispatcher_visibility = ""
+        )]
+        pub fn sum(&self) -> f32 {
+            self.0.iter().sum()
+        }
+    }
+}
+
+#[test]
+fn dispatcher_visibility() {
+    assert_eq!(api::sum(&[1., 2., 3.]), 6.);
+    assert_eq!(api::sum_dispatcher(&[1., 2., 3.]), 6.);
+    assert_eq!(
+        api::sum_variants::sum_dispatcher_default_version(&[1., 2.]),
+        3.
+    );
+    assert_eq!(api::Values(vec![1., 2.]).sum(), 3.);
+}
@@ -0,0 +1,39 @@
+use multiversion::{
+    multiversion,
+    target::{selected_target, Architecture, Target},
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static DETECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+fn count_detections() -> Target {
+    DETECTIONS.fetch_add(1, Ordering::Relaxed);
+    let target = Target::new(Architecture::from_str(std::env::consts::ARCH));
+    if cfg!(target_arch = "x86_64") {
+        target.with_feature_str("sse2")
+    } else {
+        target
+    }
+}
+
+// `sse2` is always enabled on x86-64, so the dispatcher would be elided.
+#[multiversion(
+    targets("x86_64+sse2"),
+    detector = "count_detections",
+    dispatcher = "inline",
+    elision = false
+)]
+fn not_elided() -> Target {
+    selected_target!()
+}
+
+#[test]
+fn elision_disabled() {
+    for _ in 0..3 {
+        not_elided();
+    }
+    if cfg!(target_arch = "x86_64") {
+        assert_eq!(DETECTIONS.load(Ordering::Relaxed), 3);
+        assert!(not_elided().supports_feature_str("sse2"));
+    }
+}
@@ -0,0 +1,31 @@
+#![cfg(feature = "env_disable")]
+
+use multiversion::{multiversion, target::selected_target};
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse2", "aarch64+neon"),
+    dispatcher = "direct"
+)]
+fn selected() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[test]
+fn env_disable() {
+    // The variable must be set before any features are detected
+    std::env::set_var("MULTIVERSION_DISABLE", "avx2, neon");
+
+    let target = selected();
+    let disabled = if cfg!(target_arch = "aarch64") {
+        "neon"
+    } else {
+        "avx2"
+    };
+    if cfg!(any(target_arch = "x86_64", target_arch = "aarch64")) {
+        assert!(!target.supports_feature_str(disabled));
+    }
+    assert!(!multiversion::detect::is_feature_detected(
+        std::env::consts::ARCH,
+        disabled
+    ));
+}
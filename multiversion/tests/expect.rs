@@ -0,0 +1,50 @@
+// Lint expectations apply to the function as a whole, rather than to each clone.
+#![deny(unfulfilled_lint_expectations)]
+
+use multiversion::multiversion;
+
+#[rustversion::since(1.81)]
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"))]
+#[expect(clippy::needless_range_loop)]
+fn sum(x: &[f32]) -> f32 {
+    let mut sum = 0.;
+    for i in 0..x.len() {
+        sum += x[i];
+    }
+    sum
+}
+
+#[rustversion::since(1.81)]
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    attrs(expect(clippy::needless_range_loop))
+)]
+fn sum_attrs(x: &[f32]) -> f32 {
+    let mut sum = 0.;
+    for i in 0..x.len() {
+        sum += x[i];
+    }
+    sum
+}
+
+#[cfg(feature = "std")]
+#[rustversion::since(1.81)]
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"), fn_ptr)]
+#[expect(clippy::needless_range_loop)]
+fn sum_fn_ptr(x: &[f32]) -> f32 {
+    let mut sum = 0.;
+    for i in 0..x.len() {
+        sum += x[i];
+    }
+    sum
+}
+
+#[rustversion::since(1.81)]
+#[test]
+fn expect() {
+    let x = [1., 2., 3.];
+    assert_eq!(sum(&x), 6.);
+    assert_eq!(sum_attrs(&x), 6.);
+    #[cfg(feature = "std")]
+    assert_eq!(sum_fn_ptr(&x), 6.);
+}
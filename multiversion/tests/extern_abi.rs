@@ -0,0 +1,62 @@
+use multiversion::multiversion;
+
+macro_rules! c_fns {
+    { $($name:ident: $dispatcher:literal),* } => {
+        $(
+            #[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"), dispatcher = $dispatcher)]
+            extern "C" fn $name(x: *const f32, len: usize) -> f32 {
+                // Safety: called with a valid slice
+                let x = unsafe { core::slice::from_raw_parts(x, len) };
+                x.iter().sum()
+            }
+        )*
+
+        #[test]
+        fn c_abi() {
+            let x = [1., 2., 3.];
+            $(
+                // The dispatcher can be passed to C as a callback
+                let f: extern "C" fn(*const f32, usize) -> f32 = $name;
+                assert_eq!(f(x.as_ptr(), x.len()), 6.);
+            )*
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+c_fns! {
+    sum_default: "default",
+    sum_static: "static",
+    sum_direct: "direct",
+    sum_indirect: "indirect",
+    sum_inline: "inline",
+    sum_ifunc: "ifunc",
+    sum_ctor: "ctor"
+}
+
+#[cfg(feature = "std")]
+#[rustversion::since(1.70)]
+mod once {
+    use super::*;
+    c_fns! { sum_once: "once" }
+}
+
+#[cfg(not(feature = "std"))]
+c_fns! {
+    sum_default: "default",
+    sum_static: "static"
+}
+
+#[cfg(feature = "std")]
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"), fn_ptr)]
+unsafe extern "C" fn sum_fn_ptr(x: *const f32, len: usize) -> f32 {
+    unsafe { core::slice::from_raw_parts(x, len) }.iter().sum()
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn c_abi_fn_ptr() {
+    let x = [1., 2., 3.];
+    let f: unsafe extern "C" fn(*const f32, usize) -> f32 = sum_fn_ptr_fn();
+    assert_eq!(unsafe { f(x.as_ptr(), x.len()) }, 6.);
+}
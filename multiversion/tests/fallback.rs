@@ -0,0 +1,97 @@
+use multiversion::{multiversion, target::selected_target};
+
+// The SIMD-friendly body sums in lanes, and the fallback sums sequentially.
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), dispatcher = "static")]
+fn sum(x: &[u32]) -> (u32, &'static str) {
+    #[fallback]
+    {
+        (x.iter().sum(), "fallback")
+    }
+    let mut lanes = [0; 8];
+    for chunk in x.chunks(8) {
+        for (lane, v) in lanes.iter_mut().zip(chunk) {
+            *lane += v;
+        }
+    }
+    (lanes.iter().sum(), "simd")
+}
+
+#[test]
+fn fallback_static() {
+    let expected = if cfg!(any(
+        all(target_arch = "x86_64", target_feature = "avx2"),
+        all(target_arch = "aarch64", target_feature = "neon")
+    )) {
+        "simd"
+    } else {
+        "fallback"
+    };
+    assert_eq!(sum(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]), (55, expected));
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "aarch64+neon"),
+    baseline("x86_64+sse4.2"),
+    dispatcher = "static"
+)]
+fn selected() -> Option<multiversion::target::Target> {
+    #[fallback]
+    {
+        Some(selected_target!())
+    }
+    None
+}
+
+#[test]
+fn fallback_baseline() {
+    if cfg!(all(target_arch = "x86_64", not(target_feature = "avx2"))) {
+        assert!(selected().unwrap().supports_feature_str("sse4.2"));
+    }
+}
+
+// Wrapped functions keep the fallback.
+#[cfg(feature = "std")]
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), boxed_return)]
+fn evens(x: &[u32]) -> impl Iterator<Item = u32> {
+    #[fallback]
+    {
+        x.iter()
+            .copied()
+            .filter(|v| v % 2 == 0)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+    x.iter()
+        .copied()
+        .filter(|v| v % 2 == 0)
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn fallback_boxed_return() {
+    assert_eq!(evens(&[1, 2, 3, 4]).collect::<Vec<_>>(), [2, 4]);
+}
+
+#[cfg(feature = "std")]
+mod runtime {
+    use multiversion::multiversion;
+
+    #[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"), force_target)]
+    fn which((x, y): (u32, u32)) -> (u32, &'static str) {
+        #[fallback]
+        {
+            (x + y, "fallback")
+        }
+        (x + y, "simd")
+    }
+
+    #[test]
+    fn fallback_runtime() {
+        assert!(which_force_target(Some("default")));
+        assert_eq!(which((1, 2)), (3, "fallback"));
+        assert!(which_force_target(None));
+        assert_eq!(which((1, 2)).0, 3);
+    }
+}
@@ -0,0 +1,32 @@
+use safer_ffi::prelude::*;
+
+// `multiversion` must be applied first, so that the FFI macro applies to the dispatcher.
+#[multiversion::multiversion(targets("x86_64+avx2", "aarch64+neon"))]
+#[ffi_export]
+fn ffi_sum(x: u32, y: u32) -> u32 {
+    x + y
+}
+
+#[multiversion::multiversion(targets("x86_64+avx2", "aarch64+neon"), dispatcher = "static")]
+#[ffi_export]
+fn ffi_static_sum(x: u32, y: u32) -> u32 {
+    x + y
+}
+
+extern "C" {
+    #[link_name = "ffi_sum"]
+    fn exported_sum(x: u32, y: u32) -> u32;
+    #[link_name = "ffi_static_sum"]
+    fn exported_static_sum(x: u32, y: u32) -> u32;
+}
+
+#[test]
+fn ffi_export() {
+    assert_eq!(ffi_sum(1, 2), 3);
+    assert_eq!(ffi_static_sum(1, 2), 3);
+    // Safety: the functions are exported with these signatures.
+    unsafe {
+        assert_eq!(exported_sum(2, 3), 5);
+        assert_eq!(exported_static_sum(2, 3), 5);
+    }
+}
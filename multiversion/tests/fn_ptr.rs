@@ -0,0 +1,37 @@
+#![allow(clippy::needless_lifetimes)]
+#![cfg(feature = "std")]
+
+use multiversion::multiversion;
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"), fn_ptr)]
+fn square(x: &mut [f32]) {
+    for v in x {
+        *v *= *v;
+    }
+}
+
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), fn_ptr, resolve)]
+unsafe fn first<'a>(x: &'a [u8]) -> &'a u8 {
+    unsafe { x.get_unchecked(0) }
+}
+
+struct Kernel {
+    f: fn(&mut [f32]),
+}
+
+#[test]
+fn fn_ptr() {
+    let kernel = Kernel { f: square_fn() };
+    let mut x = [1., 2., 3.];
+    for _ in 0..2 {
+        (kernel.f)(&mut x);
+    }
+    assert_eq!(x, [1., 16., 81.]);
+    square(&mut x[..1]);
+    assert!(square_fn() as usize == kernel.f as usize);
+
+    first_resolve();
+    let f: unsafe fn(&[u8]) -> &u8 = first_fn();
+    assert_eq!(unsafe { *f(&[5, 6]) }, 5);
+    assert_eq!(unsafe { *first(&[7]) }, 7);
+}
@@ -0,0 +1,41 @@
+// Static dispatch is only forced when built with `MULTIVERSION_FORCE_STATIC=1`, such as with
+// `MULTIVERSION_FORCE_STATIC=1 cargo test --test force_static`, which CI runs separately.
+#![cfg(feature = "std")]
+
+use multiversion::{multiversion, target::selected_target};
+
+#[multiversion(
+    targets("x86_64+avx2", "aarch64+neon"),
+    dispatcher = "indirect",
+    fn_ptr
+)]
+fn indirect_dispatcher() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), dispatcher = "direct")]
+fn direct_dispatcher() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[test]
+fn force_static() {
+    if !matches!(option_env!("MULTIVERSION_FORCE_STATIC"), Some(value) if !value.is_empty() && value != "0")
+    {
+        return;
+    }
+
+    // Every dispatcher only uses the features enabled at compile time.
+    for target in [indirect_dispatcher(), direct_dispatcher()] {
+        assert_eq!(
+            target.supports_feature_str("avx2"),
+            cfg!(target_feature = "avx2")
+        );
+    }
+
+    // The function pointer is the statically dispatched function.
+    assert!(
+        indirect_dispatcher_fn() as usize
+            == indirect_dispatcher as fn() -> multiversion::target::Target as usize
+    );
+}
@@ -0,0 +1,37 @@
+#![cfg(feature = "std")]
+
+use multiversion::{multiversion, target::selected_target};
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"), force_target)]
+fn selected() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    force_target,
+    dispatcher = "direct"
+)]
+fn selected_direct<T: Copy>(x: T) -> (T, multiversion::target::Target) {
+    (x, selected_target!())
+}
+
+#[test]
+fn force_target() {
+    assert!(selected_force_target(Some("default")));
+    assert_eq!(selected(), multiversion::target_features::CURRENT_TARGET);
+
+    assert!(!selected_force_target(Some("x86_64+avx512f")));
+    assert!(!selected_force_target(Some("not a target")));
+    assert_eq!(selected(), multiversion::target_features::CURRENT_TARGET);
+
+    if cfg!(target_arch = "x86_64") && std::is_x86_feature_detected!("sse4.2") {
+        // Equivalent target strings are accepted
+        assert!(selected_direct_force_target(Some("x86_64+sse4.1+sse4.2")));
+        assert!(selected_direct(1).1.supports_feature_str("sse4.2"));
+        assert!(!selected_direct(1).1.supports_feature_str("avx2"));
+    }
+
+    assert!(selected_force_target(None));
+    assert!(selected_direct_force_target(None));
+}
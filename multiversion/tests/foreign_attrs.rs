@@ -0,0 +1,36 @@
+use multiversion::multiversion;
+use safer_ffi::ffi_export;
+
+// The foreign attributes are applied to the dispatcher, in order.  `ffi_export` only accepts
+// doc attributes on the function, so it must be last.
+#[multiversion(
+    targets("x86_64+avx2", "aarch64+neon"),
+    foreign_attrs(rustversion::since(1.61), ffi_export)
+)]
+fn foreign_sum(x: u32, y: u32) -> u32 {
+    x + y
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "aarch64+neon"),
+    dispatcher_visibility = "",
+    foreign_attrs(rustversion::since(1.61))
+)]
+fn foreign_product(x: u32, y: u32) -> u32 {
+    x * y
+}
+
+extern "C" {
+    #[link_name = "foreign_sum"]
+    fn exported_sum(x: u32, y: u32) -> u32;
+}
+
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), foreign_attrs(test))]
+fn foreign_attrs() {
+    assert_eq!(foreign_sum(1, 2), 3);
+    assert_eq!(foreign_product(2, 3), 6);
+    // Safety: the functions are exported with these signatures.
+    unsafe {
+        assert_eq!(exported_sum(2, 3), 5);
+    }
+}
@@ -0,0 +1,47 @@
+#![deny(unused_must_use)]
+
+use multiversion::multiversion;
+
+// `must_use` is only applied to the versions, so the result of the dispatcher can be ignored.
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    forward_attrs(inner(must_use, inline, cfg_attr))
+)]
+#[must_use]
+#[inline(never)]
+#[cfg_attr(all(), allow(clippy::needless_range_loop))]
+fn sum(x: &[u32]) -> u32 {
+    let mut total = 0;
+    for i in 0..x.len() {
+        total += x[i];
+    }
+    total
+}
+
+/// Doubles the value.
+#[multiversion(
+    targets("x86_64+avx2", "aarch64+neon"),
+    dispatcher = "static",
+    forward_attrs(outer(doc, inline), inner(doc, inline))
+)]
+#[inline]
+fn double(x: u32) -> u32 {
+    x * 2
+}
+
+// Only `inline` is kept on the dispatcher, so `must_use` is removed entirely.
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), forward_attrs(outer(inline)))]
+#[must_use]
+#[inline]
+fn triple(x: u32) -> u32 {
+    x * 3
+}
+
+#[test]
+fn forward_attrs() {
+    sum(&[1, 2, 3]);
+    assert_eq!(sum(&[1, 2, 3]), 6);
+    assert_eq!(double(2), 4);
+    triple(2);
+    assert_eq!(triple(2), 6);
+}
@@ -0,0 +1,38 @@
+#![cfg(feature = "std")]
+
+#[multiversion::group(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"))]
+mod kernels {
+    use multiversion::target::{selected_target, Target};
+
+    fn helper(x: f32) -> f32 {
+        x * 2.
+    }
+
+    pub fn double(x: &mut [f32]) {
+        for v in x {
+            *v = helper(*v);
+        }
+    }
+
+    pub fn sum<T: Copy + core::iter::Sum<T>>(x: &[T]) -> T {
+        x.iter().copied().sum()
+    }
+
+    pub fn target() -> Target {
+        selected_target!()
+    }
+}
+
+#[test]
+fn group() {
+    let mut x = [1., 2., 3.];
+    kernels::double(&mut x);
+    assert_eq!(x, [2., 4., 6.]);
+    assert_eq!(kernels::sum(&[1, 2, 3]), 6);
+
+    let selection = kernels::Selection::get();
+    selection.double(&mut x);
+    assert_eq!(x, [4., 8., 12.]);
+    assert_eq!(selection.sum(&x), 24.);
+    assert_eq!(selection.target(), kernels::target());
+}
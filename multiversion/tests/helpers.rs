@@ -13,3 +13,62 @@ fn foo() {
 fn helpers() {
     foo()
 }
+
+fn token_consts<T: multiversion::target::TargetToken>(_: T) -> (bool, usize, usize) {
+    (T::HAS_FMA, T::SIMD_WIDTH_F32, T::SIMD_WIDTH_F64)
+}
+
+#[multiversion::multiversion(targets("x86_64+avx2+fma", "x86_64+sse2", "aarch64+neon"))]
+fn token() -> (multiversion::target::Target, bool, usize, usize) {
+    let (has_fma, f32_width, f64_width) = token_consts(multiversion::target::selected_token!());
+    (
+        multiversion::target::selected_target!(),
+        has_fma,
+        f32_width,
+        f64_width,
+    )
+}
+
+#[test]
+fn token_helpers() {
+    let (target, has_fma, f32_width, f64_width) = token();
+    assert_eq!(f32_width, target.suggested_simd_width::<f32>().unwrap_or(1));
+    assert_eq!(f64_width, target.suggested_simd_width::<f64>().unwrap_or(1));
+    if cfg!(target_arch = "x86_64") {
+        assert_eq!(has_fma, target.supports_feature_str("fma"));
+    }
+}
+
+#[multiversion::multiversion(targets("x86_64+avx2", "aarch64+neon"), helpers = false)]
+fn without_helpers(x: &mut [f32]) {
+    // Items named like the helpers don't conflict
+    #[allow(unused)]
+    mod __multiversion {}
+
+    for v in x {
+        *v *= 2.;
+    }
+}
+
+#[test]
+fn no_helpers() {
+    let mut x = [1., 2.];
+    without_helpers(&mut x);
+    assert_eq!(x, [2., 4.]);
+}
+
+// Renamed helpers can't be detected
+use multiversion::target::selected_target as current_target;
+
+#[multiversion::multiversion(targets("x86_64+avx2", "aarch64+neon"), helpers = true)]
+fn renamed() -> multiversion::target::Target {
+    current_target!()
+}
+
+#[test]
+fn all_helpers() {
+    let target = renamed();
+    if cfg!(target_arch = "x86_64") {
+        assert!(target.supports_feature_str("sse2"));
+    }
+}
@@ -0,0 +1,32 @@
+#![cfg(feature = "std")]
+
+use multiversion::hwcap;
+
+#[test]
+fn hwcap() {
+    assert_eq!(hwcap::is_feature_detected("not-a-feature"), None);
+
+    #[cfg(all(
+        target_arch = "aarch64",
+        any(target_os = "linux", target_os = "android")
+    ))]
+    {
+        assert_eq!(
+            hwcap::is_feature_detected("neon"),
+            Some(std::arch::is_aarch64_feature_detected!("neon"))
+        );
+        assert_eq!(
+            hwcap::sve_vector_bytes().is_some(),
+            std::arch::is_aarch64_feature_detected!("sve")
+        );
+    }
+
+    #[cfg(not(all(
+        target_arch = "aarch64",
+        any(target_os = "linux", target_os = "android")
+    )))]
+    {
+        assert_eq!(hwcap::is_feature_detected("neon"), None);
+        assert_eq!(hwcap::sve_vector_bytes(), None);
+    }
+}
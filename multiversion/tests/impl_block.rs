@@ -0,0 +1,71 @@
+use multiversion::{multiversion, target::selected_target};
+
+#[derive(Clone, Debug, PartialEq)]
+struct Samples(Vec<f32>);
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"))]
+impl Samples {
+    const SCALE: f32 = 2.;
+
+    fn new(len: usize) -> Self {
+        Self((0..len).map(|x| x as f32).collect())
+    }
+
+    fn sum(&self) -> f32 {
+        self.0.iter().sum()
+    }
+
+    fn scale(&mut self) {
+        for x in self.0.iter_mut() {
+            *x *= Self::SCALE;
+        }
+    }
+
+    fn into_inner(mut self, (extra, _): (f32, f32)) -> Vec<f32> {
+        self.0.push(extra);
+        self.0
+    }
+
+    fn vector_width(&self) -> Option<usize> {
+        selected_target!().suggested_simd_width::<f32>()
+    }
+
+    // `Self` in nested items refers to the nested item.
+    fn len(&self) -> usize {
+        struct Len(usize);
+        impl Len {
+            fn new(len: usize) -> Self {
+                Self(len)
+            }
+        }
+        Len::new(self.0.len()).0
+    }
+}
+
+struct Wrapper<T>(T);
+
+#[multiversion(targets = "simd")]
+impl<T: Copy + core::ops::Add<Output = T>> Wrapper<T> {
+    fn double(&self) -> Self {
+        Wrapper(self.0 + self.0)
+    }
+
+    fn combine<U: Into<T>>(self, other: U) -> T {
+        self.0 + other.into()
+    }
+}
+
+#[test]
+fn impl_block() {
+    let mut samples = Samples::new(4);
+    assert_eq!(samples.sum(), 6.);
+    samples.scale();
+    assert_eq!(samples, Samples(vec![0., 2., 4., 6.]));
+    assert_eq!(samples.into_inner((1., 0.)), vec![0., 2., 4., 6., 1.]);
+    let _ = Samples::new(0).vector_width();
+    assert_eq!(Samples::new(3).len(), 3);
+
+    let wrapper = Wrapper(3u32).double();
+    assert_eq!(wrapper.0, 6);
+    assert_eq!(wrapper.combine(1u8), 7);
+}
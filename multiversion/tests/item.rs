@@ -0,0 +1,45 @@
+use multiversion::multiversion_item;
+
+// Items produced by other macros can't be annotated with attributes.
+macro_rules! dot {
+    ($name:ident, $ty:ty) => {
+        multiversion_item! {
+            targets("x86_64+avx2+fma", "x86_64+sse4.1", "aarch64+neon");
+
+            /// Computes the dot product.
+            pub fn $name(x: &[$ty], y: &[$ty]) -> $ty {
+                x.iter().zip(y).map(|(x, y)| x * y).sum()
+            }
+        }
+    };
+}
+
+dot!(dot_f32, f32);
+dot!(dot_i64, i64);
+
+struct Scale(f32);
+
+multiversion_item! {
+    targets("x86_64+avx2", "aarch64+neon"), dispatcher = "static";
+
+    fn negate(x: i32) -> i32 {
+        -x
+    }
+
+    impl Scale {
+        fn apply(&self, x: &mut [f32]) {
+            x.iter_mut().for_each(|x| *x *= self.0);
+        }
+    }
+}
+
+#[test]
+fn item() {
+    assert_eq!(dot_f32(&[1., 2.], &[3., 4.]), 11.);
+    assert_eq!(dot_i64(&[1, 2], &[3, 4]), 11);
+    assert_eq!(negate(1), -1);
+
+    let mut x = [1., 2.];
+    Scale(2.).apply(&mut x);
+    assert_eq!(x, [2., 4.]);
+}
@@ -0,0 +1,21 @@
+use multiversion::multiversion;
+
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), keep_original = "sum_scalar")]
+/// Sums the values.
+#[must_use]
+pub fn sum(values: &[u32]) -> u32 {
+    values.iter().sum()
+}
+
+#[multiversion(targets = "simd", keep_original = "no_mangle_scalar")]
+#[no_mangle]
+extern "C" fn keep_original_no_mangle(x: u32) -> u32 {
+    x + 1
+}
+
+#[test]
+fn keep_original() {
+    let values = (0..100).collect::<Vec<_>>();
+    assert_eq!(sum(&values), sum_scalar(&values));
+    assert_eq!(keep_original_no_mangle(1), no_mangle_scalar(1));
+}
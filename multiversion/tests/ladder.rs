@@ -0,0 +1,24 @@
+multiversion::capability_ladder! {
+    /// Capability levels
+    pub enum CapabilityLevel {
+        Avx2 = "x86_64+avx2+fma",
+        Sse42 = "x86_64+sse4.2",
+        Neon = "aarch64+neon",
+    }
+}
+
+#[test]
+fn ladder() {
+    assert!(CapabilityLevel::Avx2 > CapabilityLevel::Sse42);
+    assert!(CapabilityLevel::Sse42 > CapabilityLevel::Baseline);
+    assert_eq!(CapabilityLevel::Avx2.target_str(), Some("x86_64+avx2+fma"));
+    assert_eq!(CapabilityLevel::Baseline.target_str(), None);
+
+    let level = CapabilityLevel::detect();
+    if cfg!(target_arch = "aarch64") {
+        assert_eq!(level, CapabilityLevel::Neon);
+    }
+    if let Some(target) = level.target_str() {
+        assert!(target.starts_with(std::env::consts::ARCH));
+    }
+}
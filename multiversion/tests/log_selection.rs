@@ -0,0 +1,46 @@
+#![cfg(feature = "log")]
+
+use multiversion::multiversion;
+use std::sync::Mutex;
+
+static MESSAGES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target() == "multiversion"
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            MESSAGES.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), dispatcher = "direct")]
+fn logged() {}
+
+#[test]
+fn log_selection() {
+    log::set_logger(&Logger).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+
+    logged();
+    logged();
+
+    // Dispatch is elided on other architectures, or if AVX2 is already enabled
+    let expected = usize::from(cfg!(all(
+        target_arch = "x86_64",
+        not(target_feature = "avx2")
+    )));
+    let messages = MESSAGES.lock().unwrap();
+    let logged = messages
+        .iter()
+        .filter(|message| message.contains("`log_selection::logged`"))
+        .count();
+    assert_eq!(logged, expected);
+}
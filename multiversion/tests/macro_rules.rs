@@ -0,0 +1,52 @@
+use multiversion::multiversion;
+use multiversion::target::{match_target, selected_target, target_cfg_f};
+
+// Kernels generated by a macro, with bodies passed as blocks.
+macro_rules! kernel {
+    ($name:ident, $body:block) => {
+        #[multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"))]
+        fn $name() -> bool $body
+    };
+}
+
+kernel!(selected, {
+    selected_target!().supports_feature_str("avx2")
+});
+kernel!(matched, {
+    match_target! {
+        "x86_64+avx2" => true,
+        _ => false,
+    }
+});
+kernel!(configured, { target_cfg_f!(target_feature = "avx2") });
+
+// Helpers invoked by other macros.
+macro_rules! has_avx2 {
+    () => {
+        selected_target!().supports_feature_str("avx2")
+    };
+}
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"))]
+fn nested() -> bool {
+    has_avx2!()
+}
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"))]
+fn local() -> bool {
+    macro_rules! configured {
+        () => {
+            multiversion::target::target_cfg_f!(target_feature = "avx2")
+        };
+    }
+    configured!()
+}
+
+#[test]
+fn macro_rules() {
+    let avx2 = selected();
+    assert_eq!(matched(), avx2);
+    assert_eq!(configured(), avx2);
+    assert_eq!(nested(), avx2);
+    assert_eq!(local(), avx2);
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "mem")]
+
+use multiversion::mem;
+
+#[test]
+fn copy() {
+    // Small copies, and copies large enough to use string instructions
+    for len in [0, 1, 63, 64, 65, 1000, 5000] {
+        let src = (0..len).map(|x| x as u16).collect::<Vec<_>>();
+        let mut dst = vec![0; len];
+        mem::copy(&src, &mut dst);
+        assert_eq!(src, dst);
+    }
+}
+
+#[test]
+#[should_panic]
+fn copy_lengths() {
+    mem::copy(&[1, 2], &mut [0]);
+}
+
+#[test]
+fn copy_padding() {
+    // Padding bytes are copied without being read as integers.
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Padded(u8, u32);
+    let src = (0..100).map(|x| Padded(x as u8, x)).collect::<Vec<_>>();
+    let mut dst = vec![Padded(0, 0); 100];
+    mem::copy(&src, &mut dst);
+    assert_eq!(src, dst);
+}
+
+#[test]
+fn fill() {
+    for len in [0, 1, 63, 64, 65, 1000, 5000] {
+        let mut bytes = vec![0u8; len];
+        mem::fill(&mut bytes, 0xab);
+        assert!(bytes.iter().all(|x| *x == 0xab));
+
+        let mut signed = vec![0i8; len];
+        mem::fill(&mut signed, -3);
+        assert!(signed.iter().all(|x| *x == -3));
+
+        let mut floats = vec![0f64; len];
+        mem::fill(&mut floats, 1.5);
+        assert!(floats.iter().all(|x| *x == 1.5));
+    }
+}
@@ -0,0 +1,64 @@
+use multiversion::{multiversion, target::selected_target};
+
+#[derive(Clone, Debug, PartialEq)]
+struct Samples(Vec<f32>);
+
+impl Samples {
+    const SCALE: f32 = 2.;
+
+    #[multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"))]
+    fn new(len: usize) -> Self {
+        Self((0..len).map(|x| x as f32).collect())
+    }
+
+    #[multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"))]
+    fn sum(&self) -> f32 {
+        self.0.iter().sum()
+    }
+
+    #[multiversion(targets("x86_64+avx2", "aarch64+neon"), dispatcher = "static")]
+    fn scale(&mut self) {
+        for x in self.0.iter_mut() {
+            *x *= Self::SCALE;
+        }
+    }
+
+    #[multiversion(targets("x86_64+avx2", "aarch64+neon"))]
+    fn into_inner(mut self, (extra, _): (f32, f32)) -> Vec<f32> {
+        self.0.push(extra);
+        self.0
+    }
+
+    #[multiversion(targets("x86_64+avx2", "aarch64+neon"))]
+    fn vector_width(&self) -> Option<usize> {
+        selected_target!().suggested_simd_width::<f32>()
+    }
+}
+
+struct Wrapper<T>(T);
+
+impl<T: Copy + core::ops::Add<Output = T>> Wrapper<T> {
+    #[multiversion(targets = "simd")]
+    pub fn double(&self) -> Self {
+        Wrapper(self.0 + self.0)
+    }
+
+    #[multiversion(targets = "simd")]
+    pub fn combine<U: Into<T>>(self, other: U) -> T {
+        self.0 + other.into()
+    }
+}
+
+#[test]
+fn methods() {
+    let mut samples = Samples::new(4);
+    assert_eq!(samples.sum(), 6.);
+    samples.scale();
+    assert_eq!(samples, Samples(vec![0., 2., 4., 6.]));
+    assert_eq!(samples.into_inner((1., 0.)), vec![0., 2., 4., 6., 1.]);
+    let _ = Samples::new(0).vector_width();
+
+    let wrapper = Wrapper(3u32).double();
+    assert_eq!(wrapper.0, 6);
+    assert_eq!(wrapper.combine(1u8), 7);
+}
@@ -0,0 +1,88 @@
+#![cfg(feature = "std")]
+
+use std::panic::catch_unwind;
+
+macro_rules! never_fns {
+    { $($name:ident: $dispatcher:literal,)* } => {
+        $(
+            #[multiversion::multiversion(
+                targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+                dispatcher = $dispatcher
+            )]
+            fn $name(message: &str) -> ! {
+                panic!("{}", message)
+            }
+        )*
+
+        #[test]
+        fn never() {
+            $(
+                let result = catch_unwind(|| $name(stringify!($name)));
+                assert_eq!(
+                    *result.unwrap_err().downcast::<String>().unwrap(),
+                    stringify!($name)
+                );
+            )*
+        }
+    }
+}
+
+never_fns! {
+    default_dispatcher: "default",
+    static_dispatcher: "static",
+    direct_dispatcher: "direct",
+    indirect_dispatcher: "indirect",
+    inline_dispatcher: "inline",
+    ctor_dispatcher: "ctor",
+    ifunc_dispatcher: "ifunc",
+    once_dispatcher: "once",
+    tls_dispatcher: "tls",
+}
+
+#[multiversion::multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    fn_ptr,
+    resolve
+)]
+fn with_fn_ptr(message: &str) -> ! {
+    panic!("{}", message)
+}
+
+#[multiversion::multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"), force_target)]
+fn with_force_target(message: &str) -> ! {
+    panic!("{}", message)
+}
+
+#[multiversion::multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"), pinned)]
+fn with_pinned(message: &str) -> ! {
+    panic!("{}", message)
+}
+
+// The callback can't unwind, so it's only created.
+#[multiversion::multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"), trampoline)]
+fn with_trampoline(message: &str) -> ! {
+    panic!("{}", message)
+}
+
+struct Runner;
+
+impl Runner {
+    #[multiversion::multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"))]
+    fn run(&self, message: &str) -> ! {
+        panic!("{}", message)
+    }
+}
+
+#[test]
+fn never_options() {
+    with_fn_ptr_resolve();
+    let f: fn(&str) -> ! = with_fn_ptr_fn();
+    assert!(catch_unwind(|| f("fn_ptr")).is_err());
+    assert!(with_force_target_force_target(Some("default")));
+    assert!(catch_unwind(|| with_force_target("force_target")).is_err());
+    // The callable returns `Infallible`, since `!` can't be the output of `Fn`.
+    let pinned = with_pinned_pinned();
+    assert!(catch_unwind(|| match pinned("pinned") {}).is_err());
+    let _ = with_trampoline_trampoline();
+    assert!(catch_unwind(|| Runner.run("method")).is_err());
+}
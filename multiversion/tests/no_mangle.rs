@@ -0,0 +1,46 @@
+use multiversion::multiversion;
+
+#[no_mangle]
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"))]
+pub extern "C" fn multiversion_test_no_mangle(x: u32) -> u32 {
+    x + 1
+}
+
+#[export_name = "multiversion_test_export_name"]
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"))]
+pub extern "C" fn export_name(x: u32) -> u32 {
+    x + 2
+}
+
+#[cfg(feature = "std")]
+#[no_mangle]
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"), fn_ptr)]
+pub extern "C" fn multiversion_test_no_mangle_fn_ptr(x: u32) -> u32 {
+    x + 3
+}
+
+extern "C" {
+    #[link_name = "multiversion_test_no_mangle"]
+    fn no_mangle_symbol(x: u32) -> u32;
+    #[link_name = "multiversion_test_export_name"]
+    fn export_name_symbol(x: u32) -> u32;
+    #[cfg(feature = "std")]
+    #[link_name = "multiversion_test_no_mangle_fn_ptr"]
+    fn no_mangle_fn_ptr_symbol(x: u32) -> u32;
+}
+
+#[test]
+fn symbols() {
+    assert_eq!(multiversion_test_no_mangle(1), 2);
+    assert_eq!(export_name(1), 3);
+    unsafe {
+        assert_eq!(no_mangle_symbol(1), 2);
+        assert_eq!(export_name_symbol(1), 3);
+    }
+
+    #[cfg(feature = "std")]
+    {
+        assert_eq!(multiversion_test_no_mangle_fn_ptr_fn()(1), 4);
+        assert_eq!(unsafe { no_mangle_fn_ptr_symbol(1) }, 4);
+    }
+}
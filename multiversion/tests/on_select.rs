@@ -0,0 +1,71 @@
+#![cfg(feature = "std")]
+
+use multiversion::multiversion;
+use std::sync::Mutex;
+
+static SELECTIONS: Mutex<Vec<(&str, &str)>> = Mutex::new(Vec::new());
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"))]
+fn indirect() {}
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"))]
+fn direct<T>(x: T) -> T {
+    x
+}
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"), pinned)]
+fn pinned() {}
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2"), dispatcher = "static")]
+fn static_dispatch() {}
+
+// Selects before `main`, before the hook is registered.
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    dispatcher = "ctor"
+)]
+fn ctor() {}
+
+#[test]
+fn on_select() {
+    multiversion::on_select(|function, target| {
+        SELECTIONS.lock().unwrap().push((function, target));
+    })
+    .unwrap();
+    assert!(multiversion::on_select(|_, _| {}).is_err());
+
+    indirect();
+    indirect();
+    direct(1);
+    // The dispatcher and the callable share the selection.
+    pinned();
+    pinned_pinned()();
+    static_dispatch();
+    ctor();
+
+    let selections = SELECTIONS.lock().unwrap();
+    let functions = selections
+        .iter()
+        .map(|(function, _)| *function)
+        .collect::<Vec<_>>();
+    if cfg!(all(
+        any(target_arch = "x86_64", target_arch = "aarch64"),
+        any(target_os = "linux", target_os = "android")
+    )) {
+        assert!(!functions.contains(&"on_select::ctor"));
+    }
+    let functions = functions
+        .into_iter()
+        .filter(|function| *function != "on_select::ctor")
+        .collect::<Vec<_>>();
+    if cfg!(any(target_arch = "x86_64", target_arch = "aarch64")) {
+        assert_eq!(
+            functions,
+            [
+                "on_select::indirect",
+                "on_select::direct",
+                "on_select::pinned"
+            ]
+        );
+    }
+}
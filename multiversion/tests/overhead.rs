@@ -0,0 +1,25 @@
+#![cfg(feature = "std")]
+
+use multiversion::multiversion;
+
+#[multiversion(
+    targets("x86_64+avx2", "aarch64+neon"),
+    dispatcher = "indirect",
+    pinned
+)]
+fn add(x: u64, y: u64) -> u64 {
+    x.wrapping_add(y)
+}
+
+#[test]
+fn measure() {
+    let x = std::env::args().count() as u64;
+    let pinned = add_pinned();
+    let overhead = multiversion::overhead::measure(|| add(x, 1), || pinned(x, 1));
+    assert!(overhead.dispatched_ns().is_finite() && overhead.dispatched_ns() >= 0.);
+    assert!(overhead.direct_ns().is_finite() && overhead.direct_ns() >= 0.);
+    assert_eq!(
+        overhead.overhead_ns(),
+        overhead.dispatched_ns() - overhead.direct_ns()
+    );
+}
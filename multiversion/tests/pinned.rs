@@ -0,0 +1,38 @@
+#![cfg(feature = "std")]
+
+use multiversion::multiversion;
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"), pinned)]
+fn sum(x: &[f32]) -> f32 {
+    x.iter().sum()
+}
+
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), pinned, resolve)]
+fn first<'a>(x: &'a [u8], _y: &[u8]) -> &'a u8 {
+    &x[0]
+}
+
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), pinned, dispatcher = "static")]
+fn add((a, b): (u32, u32)) -> u32 {
+    a + b
+}
+
+#[test]
+fn pinned() {
+    let sum = sum_pinned();
+    let mut total = 0.;
+    for _ in 0..4 {
+        total += sum(&[1., 2., 3.]);
+    }
+    assert_eq!(total, 24.);
+
+    first_resolve();
+    let first = first_pinned();
+    let x = [5, 6];
+    assert_eq!(*first(&x, &[]), 5);
+
+    // The callable is `Copy`, `Send` and `Sync`, so it can be shared with other threads.
+    let add = add_pinned();
+    let handle = std::thread::spawn(move || add((1, 2)));
+    assert_eq!(handle.join().unwrap(), add((3, 4)) - 4);
+}
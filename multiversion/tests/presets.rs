@@ -0,0 +1,41 @@
+#[multiversion::multiversion(targets = "simd-int")]
+fn sum_int(x: &[i32]) -> i32 {
+    x.iter().sum()
+}
+
+#[multiversion::multiversion(targets = "simd-float")]
+fn sum_float(x: &[f32]) -> f32 {
+    x.iter().sum()
+}
+
+#[multiversion::multiversion(targets = "simd-2024")]
+fn sum_pinned(x: &[f32]) -> f32 {
+    x.iter().sum()
+}
+
+#[multiversion::multiversion(targets = "crypto")]
+fn xor(x: &mut [u8], key: u8) {
+    for v in x {
+        *v ^= key;
+    }
+}
+
+#[multiversion::multiversion(targets = "crc")]
+fn adler32(x: &[u8]) -> u32 {
+    let (a, b) = x.iter().fold((1u32, 0u32), |(a, b), &x| {
+        let a = (a + x as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+#[test]
+fn presets() {
+    assert_eq!(sum_int(&[1, 2, 3]), 6);
+    assert_eq!(sum_float(&[1., 2., 3.]), 6.);
+    assert_eq!(sum_pinned(&[1., 2., 3.]), 6.);
+    let mut x = [0u8, 1, 2];
+    xor(&mut x, 1);
+    assert_eq!(x, [1, 0, 3]);
+    assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+}
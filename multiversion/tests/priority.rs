@@ -0,0 +1,26 @@
+use multiversion::{multiversion, target::selected_target};
+
+#[multiversion(targets(
+    "x86_64+avx2" priority = -1,
+    "x86_64+sse2+sse3",
+    "x86_64+sse2" priority = 1,
+    "aarch64+neon",
+))]
+fn selected() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[test]
+fn priority() {
+    // SSE2 is always available on x86-64, so the highest priority target is always selected.
+    if cfg!(target_arch = "x86_64") {
+        let target = selected();
+        assert!(target.supports_feature_str("sse2"));
+        if !cfg!(target_feature = "sse3") {
+            assert!(!target.supports_feature_str("sse3"));
+        }
+        if !cfg!(target_feature = "avx2") {
+            assert!(!target.supports_feature_str("avx2"));
+        }
+    }
+}
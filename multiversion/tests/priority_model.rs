@@ -0,0 +1,34 @@
+#![cfg(feature = "std")]
+
+use multiversion::{
+    cpu::{self, Vendor},
+    multiversion,
+    target::selected_target,
+};
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse2", "aarch64+neon"),
+    priority_model = "tests/priority_model.toml"
+)]
+fn selected() -> bool {
+    selected_target!().supports_feature_str("avx2")
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse2", "aarch64+neon"),
+    priority_model = "tests/priority_model.toml",
+    dispatcher = "direct"
+)]
+fn selected_direct() -> bool {
+    selected_target!().supports_feature_str("avx2")
+}
+
+#[test]
+fn priority_model() {
+    let avx2 = selected();
+    assert_eq!(selected_direct(), avx2);
+    if cfg!(target_arch = "x86_64") && matches!(cpu::vendor(), Vendor::Intel | Vendor::Amd) {
+        // `sse2` has the highest score, even if `avx2` is detected.
+        assert!(!avx2);
+    }
+}
@@ -0,0 +1,31 @@
+use multiversion::multiversion;
+
+#[multiversion(targets(release = "simd", dev = ("x86_64+avx", "aarch64+neon")))]
+fn add(a: &mut [f32], b: &[f32]) {
+    a.iter_mut().zip(b.iter()).for_each(|(a, b)| *a += b);
+}
+
+#[multiversion(targets(custom_profile = "simd"))]
+fn sub(a: &mut [f32], b: &[f32]) {
+    a.iter_mut().zip(b.iter()).for_each(|(a, b)| *a -= b);
+}
+
+#[multiversion(targets(release = "simd", dev = ()))]
+fn selected() -> multiversion::target::Target {
+    multiversion::target::selected_target!()
+}
+
+#[test]
+fn profiles() {
+    let mut a = vec![0f32, 2f32, 4f32];
+    let b = vec![1f32, 1f32, 1f32];
+    add(&mut a, &b);
+    assert_eq!(a, vec![1f32, 3f32, 5f32]);
+    sub(&mut a, &b);
+    assert_eq!(a, vec![0f32, 2f32, 4f32]);
+
+    // Debug builds don't have any targets
+    if cfg!(debug_assertions) {
+        assert_eq!(selected(), multiversion::target_features::CURRENT_TARGET);
+    }
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "registry")]
+
+use multiversion::multiversion;
+use std::sync::Mutex;
+
+static SELECTED: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+fn hook(function: &'static str, _target: &'static str) {
+    SELECTED.lock().unwrap().push(function);
+}
+
+#[multiversion(targets("x86_64+avx", "x86_64+sse2", "aarch64+neon"), resolve)]
+fn indirect(x: u32) -> u32 {
+    x + 1
+}
+
+#[multiversion(
+    targets("x86_64+avx", "x86_64+sse2", "aarch64+neon"),
+    dispatcher = "direct",
+    resolve
+)]
+fn direct<T: Copy>(x: T) -> T {
+    x
+}
+
+#[multiversion(targets("x86_64+avx", "x86_64+sse2", "aarch64+neon"))]
+fn unresolved_indirect(x: u32) -> u32 {
+    x + 1
+}
+
+#[multiversion(
+    targets("x86_64+avx", "x86_64+sse2", "aarch64+neon"),
+    dispatcher = "direct"
+)]
+fn unresolved_direct<T: Copy>(x: T) -> T {
+    x
+}
+
+#[multiversion(
+    targets("x86_64+avx", "x86_64+sse2", "aarch64+neon"),
+    dispatcher = "once"
+)]
+fn once(x: u32) -> u32 {
+    x + 1
+}
+
+#[multiversion(targets("x86_64+avx", "x86_64+sse2", "aarch64+neon"))]
+fn const_generic<const N: usize>(x: [u32; N]) -> usize {
+    x.len()
+}
+
+struct Counter(u32);
+
+impl Counter {
+    #[multiversion(targets("x86_64+avx", "x86_64+sse2", "aarch64+neon"))]
+    fn method(&self) -> u32 {
+        self.0
+    }
+}
+
+#[multiversion::group(targets("x86_64+avx", "x86_64+sse2", "aarch64+neon"))]
+mod kernels {
+    pub fn double(x: u32) -> u32 {
+        2 * x
+    }
+}
+
+#[test]
+fn resolve_all() {
+    multiversion::on_select(hook).unwrap();
+    multiversion::resolve_all();
+    let selected = SELECTED.lock().unwrap().clone();
+    for function in [
+        "registry::indirect",
+        "registry::direct",
+        "registry::kernels",
+        "registry::unresolved_indirect",
+        "registry::unresolved_direct",
+        "registry::once",
+        "registry::const_generic",
+        "registry::method",
+    ] {
+        assert_eq!(
+            selected.iter().filter(|f| **f == function).count(),
+            1,
+            "{function}"
+        );
+    }
+
+    // The functions don't select a target again.
+    assert_eq!(indirect(1), 2);
+    assert_eq!(direct(1), 1);
+    assert_eq!(kernels::double(1), 2);
+    assert_eq!(unresolved_indirect(1), 2);
+    assert_eq!(unresolved_direct(1), 1);
+    assert_eq!(once(1), 2);
+    assert_eq!(const_generic([1, 2]), 2);
+    assert_eq!(Counter(1).method(), 1);
+    assert_eq!(SELECTED.lock().unwrap().len(), selected.len());
+}
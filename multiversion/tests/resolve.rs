@@ -0,0 +1,39 @@
+use multiversion::{multiversion, target::selected_target};
+
+#[multiversion(targets("x86_64+avx", "x86_64+sse2", "aarch64+neon"), resolve)]
+fn indirect() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[cfg(feature = "std")]
+#[multiversion(
+    targets("x86_64+avx", "x86_64+sse2", "aarch64+neon"),
+    dispatcher = "direct",
+    resolve
+)]
+pub fn direct<T: Copy>(x: T) -> T {
+    x
+}
+
+#[multiversion(
+    targets("x86_64+avx", "x86_64+sse2", "aarch64+neon"),
+    dispatcher = "static",
+    resolve
+)]
+fn statically_dispatched() {}
+
+#[test]
+fn resolve() {
+    indirect_resolve();
+    #[cfg(feature = "std")]
+    direct_resolve();
+    statically_dispatched_resolve();
+
+    let target = indirect();
+    if cfg!(target_arch = "x86_64") {
+        assert!(target.supports_feature_str("sse2"));
+    }
+    #[cfg(feature = "std")]
+    assert_eq!(direct(1), 1);
+    statically_dispatched();
+}
@@ -0,0 +1,50 @@
+use multiversion::multiversion;
+
+struct Scaler;
+
+impl Scaler {
+    const FACTOR: u32 = 3;
+
+    fn helper(x: u32) -> u32 {
+        x + 1
+    }
+
+    #[multiversion(targets("x86_64+avx2", "aarch64+neon"), self_type = "Scaler")]
+    fn scale(x: u32) -> u32 {
+        Self::helper(x * Self::FACTOR)
+    }
+
+    #[cfg(feature = "std")]
+    #[multiversion(
+        targets("x86_64+avx2", "aarch64+neon"),
+        self_type = "Scaler",
+        dispatcher = "indirect"
+    )]
+    fn scale_indirect(x: u32) -> Self {
+        assert_eq!(Self::helper(x * Self::FACTOR), 7);
+        Self
+    }
+}
+
+trait Offset {
+    const OFFSET: u32;
+
+    fn offset(x: u32) -> u32;
+}
+
+impl Offset for Scaler {
+    const OFFSET: u32 = 10;
+
+    #[multiversion(targets("x86_64+avx2", "aarch64+neon"), self_type = "Scaler")]
+    fn offset(x: u32) -> u32 {
+        x + <Self as Offset>::OFFSET
+    }
+}
+
+#[test]
+fn self_type() {
+    assert_eq!(Scaler::scale(2), 7);
+    #[cfg(feature = "std")]
+    let _: Scaler = Scaler::scale_indirect(2);
+    assert_eq!(Scaler::offset(2), 12);
+}
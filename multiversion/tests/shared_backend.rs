@@ -0,0 +1,34 @@
+use multiversion::{multiversion, target::selected_target};
+
+// Every instantiation of `sum` calls the same versions of `backend`.
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), share_generic_backend = true)]
+fn sum<T: AsRef<[f32]>>(x: T) -> f32 {
+    #[backend]
+    fn backend(x: &[f32]) -> f32 {
+        x.iter().sum()
+    }
+
+    backend(x.as_ref())
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "aarch64+neon"),
+    share_generic_backend = true,
+    dispatcher = "static"
+)]
+fn has_avx2<T>(_: T) -> bool {
+    #[backend]
+    fn backend() -> bool {
+        selected_target!().supports_feature_str("avx2")
+    }
+
+    backend()
+}
+
+#[test]
+fn shared_backend() {
+    assert_eq!(sum([1., 2.]), 3.);
+    assert_eq!(sum(vec![1., 2., 3.]), 6.);
+    assert_eq!(has_avx2(()), cfg!(target_feature = "avx2"));
+    assert_eq!(has_avx2("x"), has_avx2(1));
+}
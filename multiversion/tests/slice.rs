@@ -0,0 +1,45 @@
+#![cfg(feature = "slice")]
+
+use multiversion::slice;
+
+#[test]
+fn map() {
+    let input = (0..100).collect::<Vec<i32>>();
+    let mut output = vec![0.; 100];
+    slice::map(&input, &mut output, |x| x as f32 * 2.);
+    assert!(output.iter().enumerate().all(|(i, x)| *x == i as f32 * 2.));
+}
+
+#[test]
+fn zip_map() {
+    let a = (0..100).collect::<Vec<u32>>();
+    let b = (100..200).collect::<Vec<u32>>();
+    let mut output = vec![0; 100];
+    slice::zip_map(&a, &b, &mut output, |a, b| a + b);
+    assert!(output
+        .iter()
+        .enumerate()
+        .all(|(i, x)| *x == 2 * i as u32 + 100));
+}
+
+#[test]
+#[should_panic]
+fn zip_map_lengths() {
+    slice::zip_map(&[1], &[2, 3], &mut [0], |a: i32, b: i32| a + b);
+}
+
+#[test]
+fn reduce() {
+    let input = (1..=100).collect::<Vec<u64>>();
+    assert_eq!(slice::reduce(&input, 0, |a, b| a + b), 5050);
+    assert_eq!(slice::reduce(&input, u64::MAX, u64::min), 1);
+    assert_eq!(slice::reduce(&[], 0, |a: u64, b| a + b), 0);
+}
+
+#[test]
+fn dot() {
+    let a = (0..19).map(|x| x as f64).collect::<Vec<_>>();
+    let b = vec![2.; 19];
+    assert_eq!(slice::dot(&a, &b), 342.);
+    assert_eq!(slice::dot::<i32>(&[], &[]), 0);
+}
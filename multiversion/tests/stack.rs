@@ -0,0 +1,60 @@
+#![cfg(feature = "std")]
+
+use multiversion::multiversion;
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    min_stack = 524288
+)]
+fn square(x: &mut [f32]) {
+    for v in x {
+        *v *= *v
+    }
+}
+
+#[cfg(feature = "stack_usage")]
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"))]
+fn sum(x: &[f32]) -> f32 {
+    x.iter().sum()
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn remaining() {
+    let remaining = multiversion::stack::remaining().unwrap();
+    let nested = std::thread::Builder::new()
+        .stack_size(128 * 1024)
+        .spawn(|| multiversion::stack::remaining().unwrap())
+        .unwrap()
+        .join()
+        .unwrap();
+    assert!(remaining > 0);
+    assert!(nested > 0 && nested < 524288);
+}
+
+#[test]
+fn min_stack() {
+    let mut x = [1., 2., 3.];
+    square(&mut x);
+    assert_eq!(x, [1., 4., 9.]);
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn min_stack_exceeded() {
+    let result = std::thread::Builder::new()
+        .stack_size(128 * 1024)
+        .spawn(|| square(&mut [1., 2., 3.]))
+        .unwrap()
+        .join();
+    assert!(result.is_err());
+}
+
+#[test]
+#[cfg(feature = "stack_usage")]
+fn frame_sizes() {
+    assert_eq!(sum(&[1., 2., 3.]), 6.);
+    assert!(multiversion::stack::frame_sizes()
+        .iter()
+        .any(|frame| frame.function().ends_with("::sum") && frame.bytes() < 65536));
+}
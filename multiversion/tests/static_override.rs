@@ -0,0 +1,45 @@
+#![cfg(feature = "std")]
+
+use multiversion::{
+    multiversion,
+    target::{selected_target, with_static_target, Architecture, Target},
+};
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    dispatcher = "static"
+)]
+fn selected() -> Target {
+    selected_target!()
+}
+
+#[test]
+fn static_override() {
+    let static_target = selected();
+    let arch = Architecture::from_str(std::env::consts::ARCH);
+
+    // Without any features, the default version is selected.
+    with_static_target(Target::new(arch), || {
+        assert_eq!(selected(), multiversion::target_features::CURRENT_TARGET);
+    });
+
+    #[cfg(all(target_arch = "x86_64", not(target_feature = "sse4.2")))]
+    if std::is_x86_feature_detected!("sse4.2") {
+        let sse42 = Target::new(arch).with_feature_str("sse4.2");
+        with_static_target(sse42, || {
+            assert!(selected().supports_feature_str("sse4.2"));
+            assert!(!selected().supports_feature_str("avx2"));
+        });
+    }
+
+    #[cfg(all(target_arch = "x86_64", not(target_feature = "avx2")))]
+    if std::is_x86_feature_detected!("avx2") {
+        let avx2 = Target::new(arch).with_feature_str("avx2");
+        with_static_target(avx2, || assert!(selected().supports_feature_str("avx2")));
+    }
+
+    // The override is restored, even if the function panics.
+    let result = std::panic::catch_unwind(|| with_static_target(Target::new(arch), || panic!()));
+    assert!(result.is_err());
+    assert_eq!(selected(), static_target);
+}
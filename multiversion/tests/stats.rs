@@ -0,0 +1,44 @@
+#![cfg(feature = "stats")]
+
+use multiversion::{multiversion, stats};
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"))]
+fn double(x: u32) -> u32 {
+    x * 2
+}
+
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), dispatcher = "static")]
+fn triple(x: u32) -> u32 {
+    x * 3
+}
+
+#[test]
+fn call_counts() {
+    for i in 0..5 {
+        assert_eq!(double(i), i * 2);
+    }
+    assert_eq!(triple(1), 3);
+
+    let counts = stats::call_counts();
+    let calls = |function: &str| {
+        counts
+            .iter()
+            .filter(|count| count.function() == function)
+            .map(|count| count.calls())
+            .sum::<u64>()
+    };
+    assert_eq!(calls("stats::double"), 5);
+    assert_eq!(calls("stats::triple"), 1);
+    // Only one version of each function is called.
+    assert_eq!(counts.len(), 2);
+
+    stats::reset();
+    assert_eq!(double(1), 2);
+    let counts = stats::call_counts();
+    assert_eq!(counts.len(), 2);
+    assert_eq!(
+        counts.iter().map(|count| count.calls()).sum::<u64>(),
+        1,
+        "{counts:?}"
+    );
+}
@@ -0,0 +1,62 @@
+#![cfg(feature = "std")]
+
+use multiversion::{multiversion, table};
+
+struct Ops {
+    add: fn(f32, f32) -> f32,
+    sum: fn(&[f32]) -> f32,
+    first: unsafe fn(&[u8]) -> u8,
+}
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"), fn_ptr)]
+fn add(x: f32, y: f32) -> f32 {
+    x + y
+}
+
+mod kernels {
+    use multiversion::multiversion;
+
+    #[multiversion(targets("x86_64+avx2", "aarch64+neon"), fn_ptr)]
+    pub fn sum(x: &[f32]) -> f32 {
+        x.iter().sum()
+    }
+
+    #[multiversion(targets("x86_64+avx2", "aarch64+neon"), fn_ptr)]
+    pub unsafe fn first(x: &[u8]) -> u8 {
+        unsafe { *x.get_unchecked(0) }
+    }
+}
+
+table! {
+    static OPS: Ops = {
+        add,
+        sum: kernels::sum,
+        first: kernels::first,
+    };
+
+    #[allow(dead_code)]
+    static SUMS: Sums = { sum: kernels::sum };
+}
+
+struct Sums {
+    sum: fn(&[f32]) -> f32,
+}
+
+#[test]
+fn table() {
+    assert_eq!((OPS.add)(1., 2.), 3.);
+    assert_eq!((OPS.sum)(&[1., 2., 3.]), 6.);
+    assert_eq!(unsafe { (OPS.first)(&[4, 5]) }, 4);
+
+    // Every thread uses the same table.
+    let sums = std::thread::scope(|s| {
+        let handles = (0..4)
+            .map(|_| s.spawn(|| (SUMS.get().sum)(&[1., 2.])))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(sums, [3.; 4]);
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "std")]
+
+use multiversion::{multiversion, target::selected_target};
+
+#[multiversion(
+    targets(
+        /// Always compiled.
+        "x86_64+sse2",
+        #[cfg(any())]
+        "x86_64+sse4.2",
+        #[doc = "Compiled on AArch64."]
+        #[cfg(all())]
+        "aarch64+neon",
+    ),
+    force_target
+)]
+fn indirect() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[multiversion(
+    targets(#[cfg(any())] "x86_64+avx2", "x86_64+sse2", #[cfg(any())] "aarch64+neon"),
+    force_target,
+    dispatcher = "direct"
+)]
+fn direct<T>(x: T) -> T {
+    x
+}
+
+#[test]
+fn target_cfgs() {
+    // Targets that aren't compiled can't be forced.
+    assert!(!indirect_force_target(Some("x86_64+sse4.2")));
+    assert!(!direct_force_target(Some("x86_64+avx2")));
+    assert!(!direct_force_target(Some("aarch64+neon")));
+    if cfg!(target_arch = "x86_64") {
+        assert!(indirect_force_target(Some("x86_64+sse2")));
+        assert!(indirect().supports_feature_str("sse2"));
+        assert!(direct_force_target(Some("x86_64+sse2")));
+    }
+    assert_eq!(direct(1), 1);
+    assert!(indirect_force_target(None));
+    assert!(direct_force_target(None));
+}
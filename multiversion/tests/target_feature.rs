@@ -0,0 +1,22 @@
+use multiversion::{multiversion, target::selected_target};
+
+// The enabled features are added to every x86-64 target, and to the x86-64 baseline.
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"))]
+#[target_feature(enable = "popcnt")]
+unsafe fn count_ones(x: &[u64]) -> (u32, multiversion::target::Target) {
+    (x.iter().map(|x| x.count_ones()).sum(), selected_target!())
+}
+
+#[test]
+fn target_feature() {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if !std::arch::is_x86_feature_detected!("popcnt") {
+            return;
+        }
+    }
+    let (count, _target) = unsafe { count_ones(&[1, 3, u64::MAX]) };
+    assert_eq!(count, 67);
+    #[cfg(target_arch = "x86_64")]
+    assert!(_target.supports_feature_str("popcnt"));
+}
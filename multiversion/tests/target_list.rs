@@ -0,0 +1,28 @@
+use multiversion::{multiversion, target::selected_target};
+
+multiversion::target_list! {
+    /// Targets shared by the functions in this test.
+    macro_rules! shared_targets {
+        "x86_64+sse2",
+        "aarch64+neon",
+    }
+}
+
+#[multiversion(targets = shared_targets!())]
+fn listed() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[multiversion(targets = shared_targets!(), dispatcher = "static")]
+fn listed_with_options() -> multiversion::target::Target {
+    selected_target!()
+}
+
+#[test]
+fn target_list() {
+    assert_eq!(shared_targets!(), ["x86_64+sse2", "aarch64+neon"]);
+    if cfg!(target_arch = "x86_64") {
+        assert!(listed().supports_feature_str("sse2"));
+        assert!(listed_with_options().supports_feature_str("sse2"));
+    }
+}
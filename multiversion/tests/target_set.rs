@@ -0,0 +1,35 @@
+#![cfg(feature = "std")]
+
+use multiversion::target::TargetSet;
+
+#[test]
+fn set_operations() {
+    let a: TargetSet = r#""x86_64+avx2+fma", "x86_64+sse4.2", "aarch64+neon""#
+        .parse()
+        .unwrap();
+    let b: TargetSet = "x86_64+fma+avx2, x86_64+avx".parse().unwrap();
+
+    assert!(a.contains("x86_64+fma+avx2"));
+    assert!(!a.contains("x86_64+avx"));
+    assert_eq!(
+        a.union(&b).to_string(),
+        r#""x86_64+avx2+fma", "x86_64+sse4.2", "aarch64+neon", "x86_64+avx""#
+    );
+    assert_eq!(a.intersection(&b).to_string(), r#""x86_64+avx2+fma""#);
+    assert_eq!(
+        a.difference(&b).iter().collect::<Vec<_>>(),
+        ["x86_64+sse4.2", "aarch64+neon"]
+    );
+    assert_eq!(a.to_string().parse::<TargetSet>().unwrap(), a);
+}
+
+#[test]
+fn invalid_targets() {
+    let mut set = TargetSet::new();
+    assert!(set.insert("x86_64+avx").unwrap());
+    assert!(!set.insert("x86_64+avx").unwrap());
+    assert!(set.insert("x86_64+not-a-feature").is_err());
+    assert!(set.insert("not-an-arch+avx").is_err());
+    assert!("x86_64/not-a-cpu".parse::<TargetSet>().is_err());
+    assert_eq!(set.len(), 1);
+}
@@ -0,0 +1,49 @@
+#![cfg(feature = "std")]
+
+use multiversion::target::TargetSpec;
+use std::convert::TryFrom;
+
+#[test]
+fn parse() {
+    let target: TargetSpec = "x86_64/x86-64-v2+fma+avx2+fma~znver3".parse().unwrap();
+    assert_eq!(target.as_str(), "x86_64/x86-64-v2+avx2+fma~znver3");
+    assert_eq!(target.to_string(), target.as_str());
+    assert_eq!(target.arch(), "x86_64");
+    assert_eq!(target.tune(), Some("znver3"));
+    assert!(target.target().supports_feature_str("sse4.2"));
+    assert_eq!(TargetSpec::try_from(target.as_str()).unwrap(), target);
+}
+
+#[test]
+fn invalid() {
+    // The same errors as the `multiversion` macro.
+    for (target, reason) in [
+        ("x86_64+avx3", "unknown target feature: avx3"),
+        ("x86_64+", "feature string cannot be empty"),
+        ("x86-64+avx", "invalid architecture specifier"),
+        (
+            "aarch64+neon~znver3",
+            "tuning is only supported on x86 and x86-64",
+        ),
+        (
+            "x86_64+avx2~x86-64-v3",
+            "can't tune for generic CPU: x86-64-v3",
+        ),
+    ] {
+        let err = target.parse::<TargetSpec>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("invalid target specification string `{target}`: {reason}")
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde() {
+    let target: TargetSpec = "x86_64+fma+avx2".parse().unwrap();
+    let json = serde_json::to_string(&target).unwrap();
+    assert_eq!(json, r#""x86_64+avx2+fma""#);
+    assert_eq!(serde_json::from_str::<TargetSpec>(&json).unwrap(), target);
+    assert!(serde_json::from_str::<TargetSpec>(r#""x86_64+avx3""#).is_err());
+}
@@ -0,0 +1,42 @@
+#![cfg(feature = "std")]
+
+use multiversion::multiversion;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static SELECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    dispatcher = "tls"
+)]
+fn first(x: &[u8]) -> &u8 {
+    &x[0]
+}
+
+#[test]
+fn tls() {
+    multiversion::on_select(|function, _| {
+        if function == "tls::first" {
+            SELECTIONS.fetch_add(1, Ordering::Relaxed);
+        }
+    })
+    .unwrap();
+
+    let threads = (0..4)
+        .map(|_| {
+            std::thread::spawn(|| {
+                for _ in 0..3 {
+                    assert_eq!(*first(&[1, 2]), 1);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // Each thread selects the version once.
+    if cfg!(all(target_arch = "x86_64", not(target_feature = "avx2"))) {
+        assert_eq!(SELECTIONS.load(Ordering::Relaxed), 4);
+    }
+}
@@ -0,0 +1,34 @@
+#![cfg(feature = "track_selection")]
+
+use multiversion::multiversion;
+
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), dispatcher = "indirect")]
+fn indirect() {}
+
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), dispatcher = "direct")]
+fn direct() {}
+
+#[test]
+fn track_selection() {
+    indirect();
+    direct();
+    direct();
+
+    let report = multiversion::report::snapshot();
+    assert_eq!(report.arch(), std::env::consts::ARCH);
+
+    // Dispatch is elided on other architectures, or if AVX2 is already enabled
+    let expected = usize::from(cfg!(all(
+        target_arch = "x86_64",
+        not(target_feature = "avx2")
+    )));
+    for name in ["indirect", "direct"] {
+        let path = format!("track_selection::{name}");
+        let selections = report
+            .selections()
+            .iter()
+            .filter(|s| s.function() == path)
+            .count();
+        assert_eq!(selections, expected);
+    }
+}
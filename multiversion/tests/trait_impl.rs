@@ -0,0 +1,90 @@
+use multiversion::multiversion;
+
+struct Counter {
+    next: u32,
+    end: u32,
+}
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse4.1", "aarch64+neon"))]
+impl Iterator for Counter {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next < self.end {
+            self.next += 1;
+            Some(self.next - 1)
+        } else {
+            None
+        }
+    }
+
+    fn fold<B, F>(self, init: B, f: F) -> B
+    where
+        F: FnMut(B, Self::Item) -> B,
+    {
+        (self.next..self.end).fold(init, f)
+    }
+}
+
+trait Dot {
+    const ZERO: Self;
+
+    fn dot(&self, other: &Self) -> f32;
+}
+
+#[derive(Copy, Clone)]
+struct Vector([f32; 8]);
+
+#[multiversion(targets = "simd")]
+impl Dot for Vector {
+    const ZERO: Self = Vector([0.; 8]);
+
+    fn dot(&self, other: &Self) -> f32 {
+        if self.0 == Self::ZERO.0 {
+            return 0.;
+        }
+        self.0.iter().zip(other.0.iter()).map(|(x, y)| x * y).sum()
+    }
+}
+
+// The default bodies are multiversioned, and the trait can still be used as a trait object.
+#[multiversion(targets = "simd")]
+trait Kernel {
+    fn factor(&self) -> f32;
+
+    fn apply(&self, x: &mut [f32]) {
+        for v in x {
+            *v *= self.factor();
+        }
+    }
+}
+
+struct Scale(f32);
+
+impl Kernel for Scale {
+    fn factor(&self) -> f32 {
+        self.0
+    }
+}
+
+#[test]
+fn trait_impl() {
+    let counter = Counter { next: 0, end: 5 };
+    assert_eq!(counter.fold(0, |acc, x| acc * 10 + x), 1234);
+    let mut counter = Counter { next: 3, end: 5 };
+    assert_eq!(counter.next(), Some(3));
+    assert_eq!(counter.collect::<Vec<_>>(), vec![4]);
+
+    let x = Vector([1.; 8]);
+    assert_eq!(x.dot(&x), 8.);
+    assert_eq!(Vector::ZERO.dot(&x), 0.);
+
+    // Trait objects call the multiversioned methods.
+    let counter: Box<dyn Iterator<Item = u32>> = Box::new(Counter { next: 0, end: 3 });
+    assert_eq!(counter.sum::<u32>(), 3);
+
+    let kernel: Box<dyn Kernel> = Box::new(Scale(2.));
+    let mut x = [1., 2.];
+    kernel.apply(&mut x);
+    assert_eq!(x, [2., 4.]);
+}
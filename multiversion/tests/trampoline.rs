@@ -0,0 +1,36 @@
+#![allow(clippy::needless_lifetimes)]
+
+use multiversion::{multiversion, trampoline::Context};
+
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), trampoline)]
+fn sum(x: &[f32]) -> f32 {
+    x.iter().sum()
+}
+
+#[multiversion(targets("x86_64+avx2", "aarch64+neon"), trampoline)]
+pub unsafe fn scale<'a>(x: &'a mut [f32], factor: f32) {
+    for v in x {
+        *v *= factor;
+    }
+}
+
+#[test]
+fn trampoline() {
+    let callbacks = [sum_trampoline()];
+    let data = [1., 2., 3.];
+    let mut context: Context<(&[f32],), f32> = Context::new((&data[..],));
+    unsafe { callbacks[0].call(context.as_mut_ptr()) };
+    assert_eq!(context.into_output(), Some(6.));
+}
+
+#[test]
+fn table() {
+    let table = [scale_trampoline(), scale_trampoline()];
+    let mut data = [1., 2.];
+    for f in table {
+        let mut context: Context<(&mut [f32], f32), ()> = Context::new((&mut data[..], 2.));
+        unsafe { f.as_fn()(context.as_mut_ptr()) };
+        assert_eq!(context.into_output(), Some(()));
+    }
+    assert_eq!(data, [4., 8.]);
+}
@@ -0,0 +1,39 @@
+use multiversion::multiversion;
+
+#[multiversion(targets("x86_64+avx2~znver3", "x86_64+avx2~alderlake", "x86_64+avx2"))]
+fn tuned(x: &mut [f32]) {
+    for v in x {
+        *v *= *v;
+    }
+}
+
+#[test]
+fn tuned_clones() {
+    let mut x = [1., 2., 3.];
+    tuned(&mut x);
+    assert_eq!(x, [1., 4., 9.]);
+}
+
+#[cfg(all(feature = "track_selection", target_arch = "x86_64"))]
+#[test]
+fn select_by_vendor() {
+    #[multiversion(
+        targets("x86_64+sse2~znver3", "x86_64+sse2~alderlake", "x86_64+sse2"),
+        dispatcher = "direct"
+    )]
+    fn vendor() {}
+
+    vendor();
+    let report = multiversion::report::snapshot();
+    let selection = report
+        .selections()
+        .iter()
+        .find(|s| s.function() == "tune::vendor")
+        .unwrap();
+    let expected = match report.vendor() {
+        Some("AuthenticAMD") => "~znver3",
+        Some("GenuineIntel") => "~alderlake",
+        _ => "+sse2",
+    };
+    assert!(selection.target().ends_with(expected));
+}
@@ -0,0 +1,76 @@
+use multiversion::multiversion;
+
+fn double(x: f32) -> f32 {
+    x * 2.
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    variants(pub, module = "simd_impls")
+)]
+pub fn double_all(x: &mut [f32]) {
+    for v in x {
+        *v = double(*v);
+    }
+}
+
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    variants(pub(crate)),
+    dispatcher = "static"
+)]
+fn sum<T: Copy + core::iter::Sum<T>>(x: &[T]) -> T {
+    x.iter().copied().sum()
+}
+
+#[cfg(feature = "std")]
+#[multiversion(
+    targets("x86_64+avx2", "x86_64+sse4.2", "aarch64+neon"),
+    variants(),
+    fn_ptr
+)]
+fn product(x: &[f32]) -> f32 {
+    x.iter().product()
+}
+
+mod nested {
+    use multiversion::multiversion;
+
+    #[multiversion(targets("x86_64+avx2", "aarch64+neon"), variants(pub(super)))]
+    pub(super) fn negate(x: i32) -> i32 {
+        -x
+    }
+}
+
+#[test]
+fn variants() {
+    let mut x = [1., 2., 3.];
+    double_all(&mut x);
+    assert_eq!(x, [2., 4., 6.]);
+    simd_impls::double_all_default_version(&mut x);
+    assert_eq!(x, [4., 8., 12.]);
+
+    assert_eq!(sum(&[1, 2, 3]), 6);
+    assert_eq!(sum::sum_default_version(&[1., 2., 3.]), 6.);
+
+    #[cfg(feature = "std")]
+    {
+        assert_eq!(product(&[1., 2., 3.]), 6.);
+        assert_eq!(product_fn()(&[1., 2., 3.]), 6.);
+        assert_eq!(product::product_default_version(&[1., 2., 3.]), 6.);
+    }
+
+    assert_eq!(nested::negate(1), -1);
+    assert_eq!(nested::negate::negate_default_version(1), -1);
+}
+
+#[test]
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+fn call_variant() {
+    if std::is_x86_feature_detected!("sse4.2") {
+        let mut x = [1., 2., 3.];
+        // Safety: the features were detected.
+        unsafe { simd_impls::double_all_sse_sse2_sse3_sse41_sse42_ssse3_version(&mut x) };
+        assert_eq!(x, [2., 4., 6.]);
+    }
+}
@@ -0,0 +1,62 @@
+use multiversion::{
+    multiversion,
+    target::{selected_target, TargetExt},
+};
+
+#[multiversion(targets("x86_64+avx2", "x86_64+sse2", "aarch64+neon"))]
+fn vector_bytes() -> Option<usize> {
+    selected_target!().suggested_vector_bytes()
+}
+
+#[test]
+fn suggested_vector_bytes() {
+    let bytes = vector_bytes();
+    if cfg!(target_arch = "x86_64") || cfg!(target_arch = "aarch64") {
+        let bytes = bytes.unwrap();
+        assert!(bytes >= 16);
+        assert!(bytes.is_power_of_two());
+    }
+}
+
+#[cfg(feature = "std")]
+mod aligned_buffer {
+    use multiversion::target::{selected_target, AlignedBuffer};
+    use std::rc::Rc;
+
+    #[multiversion::multiversion(targets("x86_64+avx2", "x86_64+sse2", "aarch64+neon"))]
+    fn buffer(len: usize) -> AlignedBuffer<f64> {
+        AlignedBuffer::for_target(&selected_target!(), len)
+    }
+
+    #[test]
+    fn alignment() {
+        for len in [0, 1, 3, 100] {
+            let mut buffer = buffer(len);
+            assert_eq!(buffer.len(), len);
+            assert!(buffer.align() >= std::mem::align_of::<f64>());
+            assert_eq!(buffer.as_ptr() as usize % buffer.align(), 0);
+            assert!(buffer.iter().all(|x| *x == 0.));
+            buffer.iter_mut().for_each(|x| *x = 1.);
+            assert_eq!(buffer.iter().sum::<f64>(), len as f64);
+        }
+
+        let buffer = AlignedBuffer::<u8>::new(5, 64);
+        assert_eq!(buffer.align(), 64);
+        assert_eq!(buffer.as_ptr() as usize % 64, 0);
+
+        let buffer = AlignedBuffer::<()>::new(5, 32);
+        assert_eq!(buffer.as_ptr() as usize % 32, 0);
+    }
+
+    #[test]
+    fn drops_elements() {
+        let rc = Rc::new(());
+        let mut buffer = AlignedBuffer::<Option<Rc<()>>>::new(3, 32);
+        for x in buffer.iter_mut() {
+            *x = Some(rc.clone());
+        }
+        assert_eq!(Rc::strong_count(&rc), 4);
+        drop(buffer);
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
@@ -0,0 +1,52 @@
+use multiversion::multiversion;
+
+#[multiversion(
+    targets(
+        "x86_64+avx2+fma",
+        "x86_64+sse4.2",
+        "x86_64+avx2~znver3",
+        "aarch64+neon"
+    ),
+    variants(pub(crate), module = "kernels"),
+    version_names = "short",
+    baseline("x86_64+sse2")
+)]
+fn square(x: &mut [f32]) {
+    for v in x {
+        *v *= *v;
+    }
+}
+
+#[test]
+fn version_names() {
+    let mut x = [1., 2., 3.];
+    square(&mut x);
+    assert_eq!(x, [1., 4., 9.]);
+
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        kernels::square_default(&mut x);
+        assert_eq!(x, [1., 16., 81.]);
+    }
+}
+
+#[test]
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+fn call_short_variant() {
+    let mut x = [1., 2., 3.];
+    // Safety: the baseline features are always supported.
+    unsafe { kernels::square_x86_64_baseline(&mut x) };
+    assert_eq!(x, [1., 4., 9.]);
+    if std::is_x86_feature_detected!("sse4.2") {
+        // Safety: the features were detected.
+        unsafe { kernels::square_sse42(&mut x) };
+        assert_eq!(x, [1., 16., 81.]);
+    }
+    if std::is_x86_feature_detected!("avx2") && std::is_x86_feature_detected!("fma") {
+        // Safety: the features were detected.
+        unsafe {
+            kernels::square_avx2_fma(&mut x);
+            kernels::square_avx2_tune_znver3(&mut x);
+        }
+    }
+}